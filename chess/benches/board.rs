@@ -0,0 +1,96 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use chess::color::Color;
+use chess::movegen::{self, MoveList};
+use chess::prelude::*;
+use chess::square::Square;
+use chess::zobrist::Zobrist;
+
+/* 
+ * To run the benches of the board:
+ * $ cargo bench -p chess --bench board
+ *
+ * To compare a change against a baseline:
+ * $ cargo bench -p chess --bench board -- --save-baseline before
+ * $ cargo bench -p chess --bench board -- --baseline before
+ */
+
+/// The positions the board is measured on: the start position, a middlegame full of tactics, and an endgame.
+const FENS: &[(&str, &str)] = &[
+    ("startpos", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+];
+
+/// Returns the board of the given fen, and its legal moves.
+fn position(fen: &str) -> (Board, MoveList) {
+    let board = Board::new(fen).unwrap();
+    let mut list = MoveList::new();
+    movegen::legals_into(&board, &mut list);
+    (board, list)
+}
+
+/// Does and undoes every legal move of each position.
+fn do_undo(c: &mut Criterion) {
+    let mut group = c.benchmark_group("do_undo");
+
+    for &(name, fen) in FENS {
+        let (mut board, list) = position(fen);
+
+        group.bench_function(name, |b| b.iter(|| {
+            for &mv in list.iter() {
+                board.do_move(black_box(mv));
+                board.undo_move(mv);
+            }
+        }));
+    }
+
+    group.finish();
+}
+
+/// Queries the attacks of every square of each position.
+fn attacks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attacks");
+
+    for &(name, fen) in FENS {
+        let (board, list) = position(fen);
+
+        group.bench_function(format!("{}/attacks_from", name), |b| b.iter(|| {
+            Square::iter().map(|sq| usize::from(board.attacks_from(black_box(sq)).count())).sum::<usize>()
+        }));
+        group.bench_function(format!("{}/attackers_of", name), |b| b.iter(|| {
+            Square::iter().map(|sq| usize::from(board.attackers_of(black_box(sq)).count())).sum::<usize>()
+        }));
+        group.bench_function(format!("{}/is_attacked", name), |b| b.iter(|| {
+            Square::iter().filter(|&sq| board.is_attacked(black_box(sq), Color::Black)).count()
+        }));
+        group.bench_function(format!("{}/gives_check", name), |b| b.iter(|| {
+            list.iter().filter(|&&mv| board.gives_check(black_box(mv))).count()
+        }));
+    }
+
+    group.finish();
+}
+
+/// Updates a zobrist key with the pieces moved by every legal move of each position,
+/// as done incrementally when moves are played.
+fn zobrist(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zobrist");
+
+    for &(name, fen) in FENS {
+        let (board, list) = position(fen);
+        let us = board.get_side_to_move();
+
+        group.bench_function(name, |b| b.iter(|| {
+            list.iter().fold(board.get_zobrist(), |zobrist, &mv| {
+                let piece = board.get_piece_unchecked(mv.from());
+                zobrist ^ Zobrist::from((us, piece, black_box(mv.from()))) ^ Zobrist::from((us, piece, mv.to()))
+            })
+        }));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, do_undo, attacks, zobrist);
+criterion_main!(benches);
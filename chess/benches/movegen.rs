@@ -0,0 +1,83 @@
+use std::thread;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use chess::movegen::{self, LegalMoves, MoveList};
+use chess::prelude::*;
+
+/* 
+ * To run the benches of the move generator:
+ * $ cargo bench -p chess --bench movegen
+ *
+ * To compare a change against a baseline:
+ * $ cargo bench -p chess --bench movegen -- --save-baseline before
+ * $ cargo bench -p chess --bench movegen -- --baseline before
+ */
+
+/// The positions the move generator is measured on: the start position, a middlegame full of tactics,
+/// a position with many promotions, and an endgame.
+const FENS: &[(&str, &str)] = &[
+    ("startpos", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("promotions", "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1"),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+];
+
+/// Returns the board of the given fen.
+fn board(fen: &str) -> Board {
+    Board::new(fen).unwrap()
+}
+
+/// Generates the legal moves of each position, into a list or lazily.
+fn legals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("legals");
+
+    for &(name, fen) in FENS {
+        let board = board(fen);
+        let mut list = MoveList::new();
+
+        group.bench_function(format!("{}/list", name), |b| b.iter(|| {
+            list.clear();
+            movegen::legals_into(black_box(&board), &mut list);
+            list.len()
+        }));
+        group.bench_function(format!("{}/lazy", name), |b| b.iter(|| LegalMoves::new(black_box(&board)).count()));
+        group.bench_function(format!("{}/has_legals", name), |b| b.iter(|| movegen::has_legals(black_box(&board))));
+    }
+
+    group.finish();
+}
+
+/// Counts the leaf nodes of each position at a small depth, reporting the node rate.
+fn perft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("perft");
+    group.sample_size(10);
+
+    for &(name, fen) in FENS {
+        let mut board = board(fen);
+        group.throughput(Throughput::Elements(movegen::perft(&mut board, 4)));
+        group.bench_function(name, |b| b.iter(|| movegen::perft(black_box(&mut board), 4)));
+    }
+
+    group.finish();
+}
+
+/// Counts the leaf nodes of the middlegame position on all the available cores, with and without tables.
+fn perft_parallel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("perft_parallel");
+    group.sample_size(10);
+
+    let board = board(FENS[1].1);
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let nodes: u64 = movegen::divide_parallel(&board, 4, 1, 0).values().sum();
+    group.throughput(Throughput::Elements(nodes));
+
+    group.bench_function("kiwipete", |b| b.iter(|| movegen::divide_parallel(black_box(&board), 4, threads, 0)));
+    // The tables are allocated by each run, as they would be by the perft binary.
+    group.bench_function("kiwipete/hashed", |b| b.iter(|| movegen::divide_parallel(black_box(&board), 4, threads, 16 << 20)));
+
+    group.finish();
+}
+
+criterion_group!(benches, legals, perft, perft_parallel);
+criterion_main!(benches);
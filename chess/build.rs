@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Generates the attack patterns of the sliders, which are too many to be computed
+// in a const context without slowing down the compilation of the chess lib a lot.
+// They are written as array literals, included by src/attacks.rs.
+//
+// Two backends are available for the lookup of those patterns: pext and pdep with bmi2, or
+// fancy magic bitboards. Magics are used when the target has no bmi2, or when the magic feature
+// is enabled, as pext and pdep are microcoded and very slow on AMD CPUs before Zen 3.
+// The magic cfg is then set for the chess lib.
+
+/// For use with the 0x88 trick, as in src/attacks.rs.
+type Dirs = [(i32, i32); 4];
+const BISHOP_DIR: Dirs = [
+    (-9, -17), (-7, -15), (7, 15), (9, 17),
+];
+const ROOK_DIR: Dirs = [
+    (-8, -16), (-1, -1), (1, 1), (8, 16),
+];
+
+/// Returns the attacks of a slider on square sq moving in the given dirs, with the given occupancy.
+/// If edges is false, the last square of each ray is left out, which gives the relevant occupancy.
+fn slider_attacks(sq: i32, dirs: &Dirs, occ: u64, edges: bool) -> u64 {
+    let sq88 = sq + (sq & !7);
+    let mut bb = 0;
+
+    for dir in dirs {
+        let mut d = 1;
+        while (sq88 + d * dir.1) & 0x88 == 0 {
+            if !edges && (sq88 + (d+1) * dir.1) & 0x88 != 0 {
+                break;
+            }
+
+            let bb2 = 1 << (sq + d * dir.0);
+            bb |= bb2;
+            if occ & bb2 != 0 {
+                break;
+            }
+            d += 1;
+        }
+    }
+
+    bb
+}
+
+/// Performs a parallel bits extract (pext).
+fn pext(bits: u64, mask: u64) -> u64 {
+    (0..64).filter(|i| mask & 1 << i != 0)
+        .enumerate()
+        .fold(0, |res, (j, i)| res | (bits >> i & 1) << j)
+}
+
+/// Performs a parallel bits deposit (pdep).
+fn pdep(bits: u64, mask: u64) -> u64 {
+    (0..64).filter(|i| mask & 1 << i != 0)
+        .enumerate()
+        .fold(0, |res, (j, i)| res | (bits >> j & 1) << i)
+}
+
+/// The xorshift algorithm for 64 bits numbers, used to search for magics.
+fn xorshift(seed: &mut u64) -> u64 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    *seed
+}
+
+/// Pushes the attack patterns of a slider moving in the given dirs, for every square and
+/// every occupancy of the relevant squares, ordered by the pext of that occupancy.
+/// The patterns are compressed with a pext over the attacks on an empty board.
+fn gen_patterns(dirs: &Dirs, patterns: &mut Vec<u16>) {
+    for sq in 0..64 {
+        let mask1 = slider_attacks(sq, dirs, 0, false);
+        let mask2 = slider_attacks(sq, dirs, 0, true);
+
+        for i in 0..1 << mask1.count_ones() {
+            let attacks = slider_attacks(sq, dirs, pdep(i, mask1), true);
+            patterns.push(pext(attacks, mask2) as u16);
+        }
+    }
+}
+
+/// Searches for the magics of a slider moving in the given dirs, for every square, and pushes
+/// its attacks for every occupancy of the relevant squares, indexed by the magic of the square.
+/// Returns the magics.
+fn gen_magics(dirs: &Dirs, seed: &mut u64, attacks: &mut Vec<u64>) -> Vec<u64> {
+    (0..64).map(|sq| {
+        let mask = slider_attacks(sq, dirs, 0, false);
+        let bits = mask.count_ones();
+
+        let occupancies: Vec<_> = (0..1 << bits).map(|i| {
+            let occ = pdep(i, mask);
+            (occ, slider_attacks(sq, dirs, occ, true))
+        }).collect();
+
+        // The table of the square, and the attempt at which each entry was last written.
+        let mut table = vec![0; 1 << bits];
+        let mut written = vec![0; 1 << bits];
+
+        for attempt in 1.. {
+            // Magics with few bits set are better candidates.
+            let magic = xorshift(seed) & xorshift(seed) & xorshift(seed);
+            if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+                continue;
+            }
+
+            // The magic is good if no two occupancies with different attacks share an index.
+            let good = occupancies.iter().all(|&(occ, bb)| {
+                let i = (occ.wrapping_mul(magic) >> (64 - bits)) as usize;
+                if written[i] != attempt {
+                    written[i] = attempt;
+                    table[i] = bb;
+                    true
+                } else {
+                    table[i] == bb
+                }
+            });
+
+            if good {
+                attacks.extend(table);
+                return magic;
+            }
+        }
+
+        unreachable!()
+    }).collect()
+}
+
+/// Writes the array literal of the given values to a file of the output directory.
+fn write_array<T: ToString>(name: &str, values: &[T]) {
+    let literal = values.iter().map(T::to_string).collect::<Vec<_>>().join(", ");
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join(name);
+    fs::write(path, format!("[{}]", literal)).unwrap();
+}
+
+fn main() {
+    let bmi2 = env::var("CARGO_CFG_TARGET_ARCH").is_ok_and(|arch| arch == "x86_64")
+        && env::var("CARGO_CFG_TARGET_FEATURE").is_ok_and(|features| features.split(',').any(|f| f == "bmi2"));
+
+    if bmi2 && env::var_os("CARGO_FEATURE_MAGIC").is_none() {
+        let mut patterns = Vec::new();
+        gen_patterns(&BISHOP_DIR, &mut patterns);
+        gen_patterns(&ROOK_DIR, &mut patterns);
+
+        write_array("slider_attacks.rs", &patterns);
+    } else {
+        // Fixed seed, so that the magics are the same for every build.
+        let mut seed = 0x2545F4914F6CDD1D;
+        let mut attacks = Vec::new();
+        let bishop_magics = gen_magics(&BISHOP_DIR, &mut seed, &mut attacks);
+        let rook_magics = gen_magics(&ROOK_DIR, &mut seed, &mut attacks);
+
+        write_array("bishop_magics.rs", &bishop_magics);
+        write_array("rook_magics.rs", &rook_magics);
+        write_array("magic_attacks.rs", &attacks);
+
+        println!("cargo:rustc-cfg=magic");
+    }
+
+    println!("cargo:rustc-check-cfg=cfg(magic)");
+    println!("cargo:rerun-if-changed=build.rs");
+}
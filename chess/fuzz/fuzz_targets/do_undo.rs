@@ -0,0 +1,42 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use chess::board::Board;
+use chess::color::Color;
+use chess::movegen;
+use chess::piece::Piece;
+use chess::random;
+use chess::square::Square;
+use chess::zobrist::Zobrist;
+
+// What must be restored when a move is undone: the fen, the zobrist key and the mailbox.
+fn snapshot(board: &Board) -> (String, Zobrist, Vec<Option<(Color, Piece)>>) {
+    (board.to_string(), board.get_zobrist(), Square::iter().map(|sq| board.get_piece(sq)).collect())
+}
+
+// Along a random game, every legal move of every position must be undone exactly,
+// and the zobrist key kept up to date by the moves must be the one of the position.
+fuzz_target!(|input: (u64, u8)| {
+    let (seed, plies) = input;
+    let (mut board, moves) = random::random_game(seed, usize::from(plies));
+
+    for &mv in moves.iter().rev() {
+        let before = snapshot(&board);
+        assert_eq!(before.1, Board::new(&before.0).unwrap().get_zobrist());
+
+        let mut legals = Vec::new();
+        movegen::legals(&board, &mut legals);
+        assert_eq!(legals.len(), board.legals_count());
+        assert_eq!(legals.is_empty(), !board.has_legal_move());
+
+        for mv in legals {
+            board.do_move(mv);
+            assert_eq!(board.get_zobrist(), Board::new(&board.to_string()).unwrap().get_zobrist());
+            assert_eq!(board.undo_last(), Some(mv));
+            assert_eq!(snapshot(&board), before);
+        }
+
+        assert_eq!(board.undo_last(), Some(mv));
+    }
+});
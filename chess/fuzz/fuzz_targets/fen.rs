@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use chess::board::Board;
+
+// Parsing an arbitrary string must never panic, and the fen of a parsed
+// board must parse back to the same board, with the same zobrist key.
+fuzz_target!(|fen: &str| {
+    if let Ok(board) = Board::new(fen) {
+        let formatted = board.to_string();
+        let parsed = Board::new(&formatted).expect("The fen of a board must be valid.");
+
+        assert_eq!(parsed.to_string(), formatted);
+        assert_eq!(parsed.get_zobrist(), board.get_zobrist());
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use chess::movegen;
+use chess::random;
+
+// Parsing an arbitrary string as a move, in a position of a random game, must never
+// panic, and must only ever give legal moves.
+fuzz_target!(|input: (u64, u8, &str)| {
+    let (seed, plies, s) = input;
+    let (board, _) = random::random_game(seed, usize::from(plies));
+
+    let mut legals = Vec::new();
+    movegen::legals(&board, &mut legals);
+
+    for parsed in [board.parse_move(s), board.parse_san(s)] {
+        if let Ok(mv) = parsed {
+            assert!(legals.contains(&mv), "{} parsed to an illegal move", s);
+        }
+    }
+});
@@ -1,5 +1,8 @@
+use core::ops::Range;
+
 use crate::bitboard::BitBoard;
 use crate::color::Color;
+use crate::piece::Piece;
 use crate::square::Square;
 
 //#################################################################################################
@@ -8,33 +11,6 @@ use crate::square::Square;
 //
 //#################################################################################################
 
-/// A struct containing the informations necessary for a bmi2 lookup.
-#[derive(Debug)]
-struct Bmi2Info {
-    offset: usize,
-    mask1: BitBoard,
-    mask2: BitBoard,
-}
-
-impl Bmi2Info {
-    /// A default value for that particular struct.
-    const ZERO: Bmi2Info = Bmi2Info {
-        offset: 0, 
-        mask1: BitBoard::EMPTY, 
-        mask2: BitBoard::EMPTY
-    };
-}
-
-/// An array of 64 bmi2 infos, one for each square.
-type Bmi2Array = [Bmi2Info; 64];
-
-/// The bmi2 infos associated with bishops and rooks, for every square on the board.
-static mut BISHOP_BMI2: Bmi2Array = [Bmi2Info::ZERO; 64];
-static mut ROOK_BMI2  : Bmi2Array = [Bmi2Info::ZERO; 64];
-
-/// The array that contains every attack pattern, indexed through bmi2 infos with pext and pdep.
-static mut SLIDER_ATTACKS: [u16; 107648] = [0; 107648];
-
 /// For use with the 0x88 trick.
 type Dirs = [(i32, i32); 4];
 const BISHOP_DIR: Dirs = [
@@ -44,67 +20,203 @@ const ROOK_DIR: Dirs = [
     (-8, -16), (-1, -1), (1, 1), (8, 16),
 ];
 
-/// Generates the bmi2 infos for a certain piece, with given dirs.
-/// Uses some space in the SLIDER_ATTACKS array and return the index of the next
-/// available spot.
-#[cold]
-unsafe fn init_bmi2(info: &mut Bmi2Array, dirs: &Dirs, mut idx: usize) -> usize {
-    let mut squares = Vec::new();
+/// The number of attack patterns of bishops and rooks, for every square on the board.
+const SLIDER_ATTACKS_LEN: usize = 107648;
 
-    for sq in 0..64 {
-        info[sq as usize].offset = idx as usize;
+/// Returns the attacks of a slider on square sq moving in the given dirs, with the given occupancy.
+/// If edges is false, the last square of each ray is left out, which gives the relevant occupancy.
+const fn slider_attacks(sq: i32, dirs: &Dirs, occ: u64, edges: bool) -> u64 {
+    let sq88 = sq + (sq & !7);
+    let mut bb = 0;
 
-        let sq88 = sq + (sq & !7);
-        let mut bb = BitBoard::EMPTY;
-        for dir in dirs {
-            if (sq88 + dir.1) & 0x88 != 0 {
-                continue;
+    let mut i = 0;
+    while i < dirs.len() {
+        let dir = dirs[i];
+
+        let mut d = 1;
+        while (sq88 + d * dir.1) & 0x88 == 0 {
+            if !edges && (sq88 + (d+1) * dir.1) & 0x88 != 0 {
+                break;
             }
 
-            let mut d = 2;
-            while (sq88 + d * dir.1) & 0x88 == 0 {
-                bb |= Square::from((sq + (d-1) * dir.0) as i8).into();
-                d += 1;
+            let bb2 = 1 << (sq + d * dir.0);
+            bb |= bb2;
+            if occ & bb2 != 0 {
+                break;
             }
+            d += 1;
         }
-        info[sq as usize].mask1 = bb;
 
-        squares.clear();
-        for sq in bb.iter_squares() {
-            squares.push(sq);
+        i += 1;
+    }
+
+    bb
+}
+
+// The backend used for the lookups of the attacks of the sliders, chosen by the build script.
+#[cfg(not(magic))]
+use self::bmi2 as sliders;
+#[cfg(magic)]
+use self::magic as sliders;
+
+/// Lookups of the attacks of the sliders with pext and pdep, fast on CPUs with a native bmi2.
+#[cfg(not(magic))]
+mod bmi2 {
+    use super::*;
+
+    /// A struct containing the informations necessary for a bmi2 lookup.
+    #[derive(Clone, Copy, Debug)]
+    struct Bmi2Info {
+        offset: usize,
+        mask1: BitBoard,
+        mask2: BitBoard,
+    }
+
+    impl Bmi2Info {
+        /// A default value for that particular struct.
+        const ZERO: Bmi2Info = Bmi2Info {
+            offset: 0, 
+            mask1: BitBoard::EMPTY, 
+            mask2: BitBoard::EMPTY
+        };
+    }
+
+    /// An array of 64 bmi2 infos, one for each square.
+    type Bmi2Array = [Bmi2Info; 64];
+
+    /// The bmi2 infos associated with bishops and rooks, for every square on the board.
+    /// The attack patterns of the rooks are stored after those of the bishops.
+    static BISHOP_BMI2: Bmi2Array = gen_bmi2(&BISHOP_DIR, 0);
+    static ROOK_BMI2  : Bmi2Array = gen_bmi2(&ROOK_DIR, BISHOP_BMI2[63].offset + (1 << BISHOP_BMI2[63].mask1.0.count_ones()));
+
+    /// The array that contains every attack pattern, indexed through bmi2 infos with pext and pdep.
+    /// It is generated by the build script, in the order of the bmi2 infos.
+    static SLIDER_ATTACKS: [u16; SLIDER_ATTACKS_LEN] = include!(concat!(env!("OUT_DIR"), "/slider_attacks.rs"));
+
+    /// Generates the bmi2 infos for a certain piece, with given dirs, the attack
+    /// patterns starting at the given offset of the SLIDER_ATTACKS array.
+    const fn gen_bmi2(dirs: &Dirs, mut offset: usize) -> Bmi2Array {
+        let mut info = [Bmi2Info::ZERO; 64];
+
+        let mut sq = 0;
+        while sq < 64 {
+            let mask1 = slider_attacks(sq as i32, dirs, 0, false);
+
+            info[sq] = Bmi2Info {
+                offset,
+                mask1: BitBoard(mask1),
+                mask2: BitBoard(slider_attacks(sq as i32, dirs, 0, true)),
+            };
+
+            offset += 1 << mask1.count_ones();
+            sq += 1;
         }
 
-        for i in 0..(1 << squares.len()) {
-            bb = BitBoard::EMPTY;
+        info
+    }
 
-            for (j, &square) in squares.iter().enumerate() {
-                if i & (1 << j) != 0 {
-                    bb |= square.into();
-                }
-            }
+    /// Returns the attacks BitBoard of a slider, with the bmi2 infos of its square.
+    #[inline]
+    fn lookup(info: &Bmi2Info, occ: BitBoard) -> BitBoard {
+        let mask = SLIDER_ATTACKS[info.offset + occ.pext(info.mask1).0 as usize];
+        BitBoard(mask as u64).pdep(info.mask2)
+    }
 
-            let mut bb2 = BitBoard::EMPTY;
-            for dir in dirs {
-                let mut d = 1;
-                while (sq88 + d * dir.1) & 0x88 == 0 {
-                    let bb3 = Square::from((sq + d * dir.0) as i8).into();
-                    bb2 |= bb3;
-                    if (bb & bb3).not_empty() {
-                        break;
-                    }
-                    d += 1;
-                }
-            }
+    /// Returns the attacks BitBoard of a Rook located on square sq, with Board occupancy occ.
+    #[inline]
+    pub(super) fn rook(sq: Square, occ: BitBoard) -> BitBoard {
+        lookup(&ROOK_BMI2[usize::from(sq)], occ)
+    }
 
-            if i == 0 {
-                info[sq as usize].mask2 = bb2;
-            }
-            SLIDER_ATTACKS[idx] = (bb2.pext(info[sq as usize].mask2).0 & 0xFFFF) as u16;
-            idx += 1;
+    /// Returns the attacks BitBoard of a Bishop located on square sq, with Board occupancy occ.
+    #[inline]
+    pub(super) fn bishop(sq: Square, occ: BitBoard) -> BitBoard {
+        lookup(&BISHOP_BMI2[usize::from(sq)], occ)
+    }
+}
+
+/// Lookups of the attacks of the sliders with fancy magic bitboards, for CPUs without
+/// a native bmi2, on which pext and pdep are slow.
+#[cfg(magic)]
+mod magic {
+    use super::*;
+
+    /// A struct containing the informations necessary for a magic lookup.
+    #[derive(Clone, Copy, Debug)]
+    struct MagicInfo {
+        offset: usize,
+        mask: BitBoard,
+        magic: u64,
+        shift: u32,
+    }
+
+    impl MagicInfo {
+        /// A default value for that particular struct.
+        const ZERO: MagicInfo = MagicInfo {
+            offset: 0,
+            mask: BitBoard::EMPTY,
+            magic: 0,
+            shift: 0,
+        };
+    }
+
+    /// An array of 64 magic infos, one for each square.
+    type MagicArray = [MagicInfo; 64];
+
+    /// The magic numbers of bishops and rooks, for every square on the board, found by the build script.
+    static BISHOP_MAGICS: [u64; 64] = include!(concat!(env!("OUT_DIR"), "/bishop_magics.rs"));
+    static ROOK_MAGICS  : [u64; 64] = include!(concat!(env!("OUT_DIR"), "/rook_magics.rs"));
+
+    /// The magic infos associated with bishops and rooks, for every square on the board.
+    /// The attacks of the rooks are stored after those of the bishops.
+    static BISHOP_MAGIC: MagicArray = gen_magic(&BISHOP_DIR, &BISHOP_MAGICS, 0);
+    static ROOK_MAGIC  : MagicArray = gen_magic(&ROOK_DIR, &ROOK_MAGICS, BISHOP_MAGIC[63].offset + (1 << (64 - BISHOP_MAGIC[63].shift)));
+
+    /// The array that contains the attacks of the sliders, indexed through magic infos.
+    /// It is generated by the build script, in the order of the magic infos.
+    static SLIDER_ATTACKS: [u64; SLIDER_ATTACKS_LEN] = include!(concat!(env!("OUT_DIR"), "/magic_attacks.rs"));
+
+    /// Generates the magic infos for a certain piece, with given dirs and magics,
+    /// the attacks starting at the given offset of the SLIDER_ATTACKS array.
+    const fn gen_magic(dirs: &Dirs, magics: &[u64; 64], mut offset: usize) -> MagicArray {
+        let mut info = [MagicInfo::ZERO; 64];
+
+        let mut sq = 0;
+        while sq < 64 {
+            let mask = slider_attacks(sq as i32, dirs, 0, false);
+
+            info[sq] = MagicInfo {
+                offset,
+                mask: BitBoard(mask),
+                magic: magics[sq],
+                shift: 64 - mask.count_ones(),
+            };
+
+            offset += 1 << mask.count_ones();
+            sq += 1;
         }
+
+        info
     }
 
-    idx
+    /// Returns the attacks BitBoard of a slider, with the magic infos of its square.
+    #[inline]
+    fn lookup(info: &MagicInfo, occ: BitBoard) -> BitBoard {
+        let i = (occ & info.mask).0.wrapping_mul(info.magic) >> info.shift;
+        BitBoard(SLIDER_ATTACKS[info.offset + i as usize])
+    }
+
+    /// Returns the attacks BitBoard of a Rook located on square sq, with Board occupancy occ.
+    #[inline]
+    pub(super) fn rook(sq: Square, occ: BitBoard) -> BitBoard {
+        lookup(&ROOK_MAGIC[usize::from(sq)], occ)
+    }
+
+    /// Returns the attacks BitBoard of a Bishop located on square sq, with Board occupancy occ.
+    #[inline]
+    pub(super) fn bishop(sq: Square, occ: BitBoard) -> BitBoard {
+        lookup(&BISHOP_MAGIC[usize::from(sq)], occ)
+    }
 }
 
 //#################################################################################################
@@ -114,75 +226,64 @@ unsafe fn init_bmi2(info: &mut Bmi2Array, dirs: &Dirs, mut idx: usize) -> usize
 //#################################################################################################
 
 /// King attacks.
-static mut KING_ATTACKS: [BitBoard; 64] = [BitBoard::EMPTY; 64];
+static KING_ATTACKS: [BitBoard; 64] = gen_jumper_attacks(&[
+    (1, 1), (1, 0), (1, -1), (0, -1),
+    (-1, -1), (-1, 0), (-1, 1), (0, 1),
+]);
 
 /// Knight attacks.
-static mut KNIGHT_ATTACKS: [BitBoard; 64] = [BitBoard::EMPTY; 64];
+static KNIGHT_ATTACKS: [BitBoard; 64] = gen_jumper_attacks(&[
+    (1, 2), (2, 1), (2, -1), (1, -2),
+    (-1, -2), (-2, -1), (-2, 1), (-1, 2),
+]);
 
 /// Pawn attacks.
-static mut WHITE_PAWN_ATTACKS: [BitBoard; 64] = [BitBoard::EMPTY; 64];
-static mut BLACK_PAWN_ATTACKS: [BitBoard; 64] = [BitBoard::EMPTY; 64];
+static WHITE_PAWN_ATTACKS: [BitBoard; 64] = gen_jumper_attacks(&[(1, 1), (-1, 1)]);
+static BLACK_PAWN_ATTACKS: [BitBoard; 64] = gen_jumper_attacks(&[(1, -1), (-1, -1)]);
 
 /// Pawn pushes.
-static mut WHITE_PAWN_PUSHES: [Option<Square>; 64] = [None; 64];
-static mut BLACK_PAWN_PUSHES: [Option<Square>; 64] = [None; 64];
+static WHITE_PAWN_PUSHES: [Option<Square>; 64] = gen_pushes((0, 1), 0..8);
+static BLACK_PAWN_PUSHES: [Option<Square>; 64] = gen_pushes((0, -1), 0..8);
 
 /// Pawn double pushes.
-static mut WHITE_PAWN_DOUBLE_PUSHES: [Option<Square>; 64] = [None; 64];
-static mut BLACK_PAWN_DOUBLE_PUSHES: [Option<Square>; 64] = [None; 64];
+static WHITE_PAWN_DOUBLE_PUSHES: [Option<Square>; 64] = gen_pushes((0, 2), 1..2);
+static BLACK_PAWN_DOUBLE_PUSHES: [Option<Square>; 64] = gen_pushes((0, -2), 6..7);
+
+/// Generates the attacks of a piece jumping in the given dirs, for every square.
+const fn gen_jumper_attacks(dirs: &[(i8, i8)]) -> [BitBoard; 64] {
+    let mut attacks = [BitBoard::EMPTY; 64];
+
+    let mut sq = 0;
+    while sq < 64 {
+        let mut i = 0;
+        while i < dirs.len() {
+            if let Some(to) = Square::SQUARES[sq].displace(dirs[i]) {
+                attacks[sq].0 |= 1 << to as u8;
+            }
+            i += 1;
+        }
 
-//#################################################################################################
-//
-//                                          init
-//
-//#################################################################################################
+        sq += 1;
+    }
 
-/// Turns an Option<Square> into a bitboard, an empty one if the Option is None.
-#[cold]
-fn to_bitboard(sq: Option<Square>) -> BitBoard {
-    sq.map_or(BitBoard::EMPTY, |sq| sq.into())
+    attacks
 }
 
-/// Initializes the sliders attacks and their bmi infos, then initialize the 
-/// jumpers attacks.
-#[cold]
-pub(crate) unsafe fn init() {
-    // Slider attacks
-    let idx = init_bmi2(&mut BISHOP_BMI2, &BISHOP_DIR, 0);
-    init_bmi2(&mut ROOK_BMI2, &ROOK_DIR, idx);
-
-    for sq in Square::SQUARES {
-        // Kings attacks
-        for dir in [
-            (1, 1), (1, 0), (1, -1), (0, -1),
-            (-1, -1), (-1, 0), (-1, 1), (0, 1),
-        ] {
-            KING_ATTACKS[usize::from(sq)] |= to_bitboard(sq.displace(dir));
-        }
+/// Generates the pushes of a pawn in the given dir, for every square whose rank is in ranks.
+const fn gen_pushes(dir: (i8, i8), ranks: Range<i8>) -> [Option<Square>; 64] {
+    let mut pushes = [None; 64];
 
-        // Knights attacks
-        for dir in [
-            (1, 2), (2, 1), (2, -1), (1, -2),
-            (-1, -2), (-2, -1), (-2, 1), (-1, 2),
-        ] {
-            KNIGHT_ATTACKS[usize::from(sq)] |= to_bitboard(sq.displace(dir));
+    let mut sq = 0;
+    while sq < 64 {
+        let y = Square::SQUARES[sq].y();
+        if ranks.start <= y && y < ranks.end {
+            pushes[sq] = Square::SQUARES[sq].displace(dir);
         }
 
-        // Pawns attacks
-        WHITE_PAWN_ATTACKS[usize::from(sq)] = to_bitboard(sq.displace((1, 1)))  | to_bitboard(sq.displace((-1, 1)));
-        BLACK_PAWN_ATTACKS[usize::from(sq)] = to_bitboard(sq.displace((1, -1))) | to_bitboard(sq.displace((-1, -1)));
-
-        // Pawn pushes
-        WHITE_PAWN_PUSHES[usize::from(sq)] = sq.displace((0,  1));
-        BLACK_PAWN_PUSHES[usize::from(sq)] = sq.displace((0, -1));
-
-        // Pawn double pushes
-        match sq.y() {
-            1 => WHITE_PAWN_DOUBLE_PUSHES[usize::from(sq)] = sq.displace((0,  2)),
-            6 => BLACK_PAWN_DOUBLE_PUSHES[usize::from(sq)] = sq.displace((0, -2)),
-            _ => (),
-        }
+        sq += 1;
     }
+
+    pushes
 }
 
 //#################################################################################################
@@ -194,12 +295,9 @@ pub(crate) unsafe fn init() {
 /// Returns the attacks BitBoard of a Pawn of Color color located on square sq with Board occupancy occ.
 #[inline]
 pub(crate) fn pawn(color: Color, sq: Square) -> BitBoard {
-    // SAFE: arrays are initialized at startup
-    unsafe {
-        match color {
-            Color::White => WHITE_PAWN_ATTACKS[usize::from(sq)],
-            Color::Black => BLACK_PAWN_ATTACKS[usize::from(sq)],
-        }
+    match color {
+        Color::White => WHITE_PAWN_ATTACKS[usize::from(sq)],
+        Color::Black => BLACK_PAWN_ATTACKS[usize::from(sq)],
     }
 }
 
@@ -207,12 +305,9 @@ pub(crate) fn pawn(color: Color, sq: Square) -> BitBoard {
 /// would occupy if it was pushed.
 #[inline]
 pub(crate) fn pawn_push(color: Color, sq: Square) -> Option<Square> {
-    // SAFE: arrays are initialized at startup
-    unsafe {
-        match color {
-            Color::White => WHITE_PAWN_PUSHES[usize::from(sq)],
-            Color::Black => BLACK_PAWN_PUSHES[usize::from(sq)],
-        }
+    match color {
+        Color::White => WHITE_PAWN_PUSHES[usize::from(sq)],
+        Color::Black => BLACK_PAWN_PUSHES[usize::from(sq)],
     }
 }
 
@@ -220,42 +315,28 @@ pub(crate) fn pawn_push(color: Color, sq: Square) -> Option<Square> {
 /// would occupy if it was double pushed.
 #[inline]
 pub(crate) fn pawn_double_push(color: Color, sq: Square) -> Option<Square> {
-    // SAFE: arrays are initialized at startup
-    unsafe {
-        match color {
-            Color::White => WHITE_PAWN_DOUBLE_PUSHES[usize::from(sq)],
-            Color::Black => BLACK_PAWN_DOUBLE_PUSHES[usize::from(sq)],
-        }
+    match color {
+        Color::White => WHITE_PAWN_DOUBLE_PUSHES[usize::from(sq)],
+        Color::Black => BLACK_PAWN_DOUBLE_PUSHES[usize::from(sq)],
     }
 }
 
 /// Returns the attacks BitBoard of a Rook located on square sq, with Board occupancy occ.
 #[inline]
 pub(crate) fn rook(sq: Square, occ: BitBoard) -> BitBoard {
-    // SAFE: arrays are initialized at startup
-    unsafe {
-        let info = &ROOK_BMI2[usize::from(sq)];
-        let mask = SLIDER_ATTACKS[info.offset + occ.pext(info.mask1).0 as usize];
-        BitBoard(mask as u64).pdep(info.mask2)
-    }
+    sliders::rook(sq, occ)
 }
 
 /// Returns the attacks BitBoard of a Knight located on square sq.
 #[inline]
 pub(crate) fn knight(sq: Square) -> BitBoard {
-    // SAFE: array is initialized at startup
-    unsafe {KNIGHT_ATTACKS[usize::from(sq)]}
+    KNIGHT_ATTACKS[usize::from(sq)]
 }
 
 /// Returns the attacks BitBoard of a Bishop located on square sq, with Board occupancy occ.
 #[inline]
 pub(crate) fn bishop(sq: Square, occ: BitBoard) -> BitBoard {
-    // SAFE: arrays are initialized at startup
-    unsafe {
-        let info = &BISHOP_BMI2[usize::from(sq)];
-        let mask = SLIDER_ATTACKS[info.offset + occ.pext(info.mask1).0 as usize];
-        BitBoard(mask as u64).pdep(info.mask2)
-    }
+    sliders::bishop(sq, occ)
 }
 
 /// Returns the attacks BitBoard of a Queen located on square sq, with Board occupancy occ.
@@ -267,6 +348,130 @@ pub(crate) fn queen(sq: Square, occ: BitBoard) -> BitBoard {
 /// Returns the attacks BitBoard of a King located on square sq.
 #[inline]
 pub(crate) fn king(sq: Square) -> BitBoard {
-    // SAFE: array is initialized at startup
-    unsafe {KING_ATTACKS[usize::from(sq)]}
-}
\ No newline at end of file
+    KING_ATTACKS[usize::from(sq)]
+}
+
+/// Returns the attacks BitBoard of a piece located on square sq, on an empty board.
+/// Pawns are not handled, as their attacks depend on their color.
+#[inline]
+pub(crate) const fn empty_board(piece: Piece, sq: Square) -> BitBoard {
+    let bishop = slider_attacks(sq as i32, &BISHOP_DIR, 0, true);
+    let rook = slider_attacks(sq as i32, &ROOK_DIR, 0, true);
+
+    match piece {
+        Piece::Knight => KNIGHT_ATTACKS[sq as usize],
+        Piece::Bishop => BitBoard(bishop),
+        Piece::Rook => BitBoard(rook),
+        Piece::Queen => BitBoard(bishop | rook),
+        Piece::King => KING_ATTACKS[sq as usize],
+        Piece::Pawn => BitBoard::EMPTY,
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+// The tables are computed at compile time, by const fns and by the build script:
+// they are checked here against a plain walk of the board.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the squares reached from sq by the given displacements.
+    fn jumps(sq: Square, dirs: &[(i8, i8)]) -> BitBoard {
+        dirs.iter().filter_map(|&dir| sq.displace(dir)).fold(BitBoard::EMPTY, |bb, to| bb | BitBoard::from(to))
+    }
+
+    /// Returns the squares attacked from sq along the given directions, stopping at the first occupied square.
+    fn rays(sq: Square, dirs: &[(i8, i8)], occ: BitBoard) -> BitBoard {
+        let mut bb = BitBoard::EMPTY;
+
+        for &(dx, dy) in dirs {
+            let mut to = sq.displace((dx, dy));
+            while let Some(sq) = to {
+                bb |= BitBoard::from(sq);
+                if occ.contains(sq) {
+                    break;
+                }
+                to = sq.displace((dx, dy));
+            }
+        }
+
+        bb
+    }
+
+    const KING_DIRS: [(i8, i8); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+    const KNIGHT_DIRS: [(i8, i8); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+    const BISHOP_DIRS: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+    const ROOK_DIRS: [(i8, i8); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+    #[test]
+    fn jumper_tables() {
+        for sq in Square::iter() {
+            assert_eq!(king(sq), jumps(sq, &KING_DIRS));
+            assert_eq!(knight(sq), jumps(sq, &KNIGHT_DIRS));
+            assert_eq!(pawn(Color::White, sq), jumps(sq, &[(-1, 1), (1, 1)]));
+            assert_eq!(pawn(Color::Black, sq), jumps(sq, &[(-1, -1), (1, -1)]));
+        }
+    }
+
+    #[test]
+    fn pawn_pushes() {
+        for sq in Square::iter() {
+            assert_eq!(pawn_push(Color::White, sq), sq.displace((0, 1)));
+            assert_eq!(pawn_push(Color::Black, sq), sq.displace((0, -1)));
+            assert_eq!(pawn_double_push(Color::White, sq), sq.displace((0, 2)).filter(|_| sq.y() == 1));
+            assert_eq!(pawn_double_push(Color::Black, sq), sq.displace((0, -2)).filter(|_| sq.y() == 6));
+        }
+    }
+
+    #[test]
+    fn slider_tables() {
+        let mut state = 0x2545F491u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for sq in Square::iter() {
+            for _ in 0..64 {
+                // Sparse occupancies give longer rays.
+                let occ = BitBoard(next() & next());
+
+                assert_eq!(bishop(sq, occ), rays(sq, &BISHOP_DIRS, occ));
+                assert_eq!(rook(sq, occ), rays(sq, &ROOK_DIRS, occ));
+                assert_eq!(queen(sq, occ), rays(sq, &BISHOP_DIRS, occ) | rays(sq, &ROOK_DIRS, occ));
+            }
+
+            assert_eq!(empty_board(Piece::Queen, sq), rays(sq, &KING_DIRS, BitBoard::EMPTY));
+            assert_eq!(empty_board(Piece::Knight, sq), knight(sq));
+        }
+    }
+
+    #[test]
+    fn between_tables() {
+        for from in Square::iter() {
+            for to in Square::iter() {
+                let (dx, dy) = (to.x() - from.x(), to.y() - from.y());
+                let straight = (dx == 0) != (dy == 0);
+                let diagonal = dx != 0 && dx.abs() == dy.abs();
+
+                let between = if straight || diagonal {
+                    let dir = (dx.signum(), dy.signum());
+                    rays(from, &[dir], BitBoard::from(to)) & !BitBoard::from(to)
+                } else {
+                    BitBoard::EMPTY
+                };
+
+                assert_eq!(BitBoard::between(from, to), between);
+                assert_eq!(BitBoard::between_straight(from, to), if straight {between} else {BitBoard::EMPTY});
+                assert_eq!(BitBoard::between_diagonal(from, to), if diagonal {between} else {BitBoard::EMPTY});
+            }
+        }
+    }
+}
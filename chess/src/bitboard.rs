@@ -1,7 +1,8 @@
-use std::fmt;
-use std::num::NonZeroU64;
-use std::ops;
+use core::fmt;
+use core::num::NonZeroU64;
+use core::ops;
 
+use crate::attacks;
 use crate::color::Color;
 use crate::square::Square;
 
@@ -13,76 +14,77 @@ use crate::square::Square;
 
 /// An array whose ith element is 1 << i, precalculated as lookup
 /// is slightly faster than calculating them.
-static mut SHIFTS: [BitBoard; 64] = [BitBoard::EMPTY; 64];
+static SHIFTS: [BitBoard; 64] = gen_shifts();
 
 // These arrays contain bitboards indexed by two squares, from and to. They contain respectively:
 // - the squares between from and to if they are aligned horizontally or vertically.
 // - the squares between from and to if they are aligned diagonally.
 // - the xor of the two former arrays.
 // - same as the previous array but goes past to until the end of the board.
-static mut SQUARES_BETWEEN_STRAIGHT: [[BitBoard; 64]; 64] = [[BitBoard::EMPTY; 64]; 64];
-static mut SQUARES_BETWEEN_DIAGNOAL: [[BitBoard; 64]; 64] = [[BitBoard::EMPTY; 64]; 64];
-static mut SQUARES_BETWEEN: [[BitBoard; 64]; 64] = [[BitBoard::EMPTY; 64]; 64];
-static mut SQUARES_RAY_MASK: [[BitBoard; 64]; 64] = [[BitBoard::EMPTY; 64]; 64];
-
-/// Initializes the arrays above and the shifts table.
-#[cold]
-pub(crate) unsafe fn init() {
-    for (i, shift) in SHIFTS.iter_mut().enumerate() {
-        *shift = BitBoard(1 << i);
-    }
-
-    const SIGN: fn(i8) -> i8 = |i| match i {
-        0 => 0,
-        j if j > 0 => 1,
-        _ => -1,
-    };
-
-    for sq1 in Square::SQUARES {
-        for sq2 in Square::SQUARES {
-            if sq1 == sq2 {
-                continue;
-            }
+static SQUARES_BETWEEN_STRAIGHT: [[BitBoard; 64]; 64] = gen_between(true, false, false);
+static SQUARES_BETWEEN_DIAGNOAL: [[BitBoard; 64]; 64] = gen_between(false, true, false);
+static SQUARES_BETWEEN: [[BitBoard; 64]; 64] = gen_between(true, true, false);
+static SQUARES_RAY_MASK: [[BitBoard; 64]; 64] = gen_between(true, true, true);
+
+/// Generates the shifts table, at compile time.
+const fn gen_shifts() -> [BitBoard; 64] {
+    let mut shifts = [BitBoard::EMPTY; 64];
 
-            let dx = sq2.x() - sq1.x();
-            let dy = sq2.y() - sq1.y();
+    let mut i = 0;
+    while i < 64 {
+        shifts[i] = BitBoard(1 << i);
+        i += 1;
+    }
 
-            let dir = (SIGN(dx), SIGN(dy));
+    shifts
+}
 
-            let between;
-            if dx == 0 || dy == 0 {
-                between = &mut SQUARES_BETWEEN_STRAIGHT
-            } else if dx.abs() == dy.abs() {
-                between = &mut SQUARES_BETWEEN_DIAGNOAL
+/// Generates one of the arrays above, at compile time, for the squares aligned straight and/or
+/// diagonally. If ray is true, the squares from to until the end of the board are added as well.
+const fn gen_between(straight: bool, diagonal: bool, ray: bool) -> [[BitBoard; 64]; 64] {
+    let mut between = [[BitBoard::EMPTY; 64]; 64];
+
+    let mut i = 0;
+    while i < 64 {
+        let mut j = 0;
+        while j < 64 {
+            let (x1, y1) = ((i % 8) as i8, (i / 8) as i8);
+            let (x2, y2) = ((j % 8) as i8, (j / 8) as i8);
+            let (dx, dy) = (x2 - x1, y2 - y1);
+
+            let aligned = if dx == 0 || dy == 0 {
+                straight
             } else {
-                continue;
-            }
+                diagonal && dx.abs() == dy.abs()
+            };
 
-            let i = usize::from(sq1);
-            let j = usize::from(sq2);
+            if i != j && aligned {
+                let (mut x, mut y) = (x1 + dx.signum(), y1 + dy.signum());
+                let mut bb = 0;
 
-            let mut sq = sq1;
-            loop {
-                sq = sq.displace(dir).unwrap();
-                if sq == sq2 {
-                    break;
+                while x != x2 || y != y2 {
+                    bb |= 1 << (x + 8*y);
+                    x += dx.signum();
+                    y += dy.signum();
                 }
-                between[i][j] |= sq.into();
-            }
 
-            SQUARES_BETWEEN[i][j] = between[i][j];
-            SQUARES_RAY_MASK[i][j] = SQUARES_BETWEEN[i][j];
-            
-            loop {
-                SQUARES_RAY_MASK[i][j] |= sq.into();
-                if let Some(s) = sq.displace(dir) {
-                    sq = s;
-                } else {
-                    break;
+                if ray {
+                    while 0 <= x && x < 8 && 0 <= y && y < 8 {
+                        bb |= 1 << (x + 8*y);
+                        x += dx.signum();
+                        y += dy.signum();
+                    }
                 }
+
+                between[i][j] = BitBoard(bb);
             }
+
+            j += 1;
         }
+        i += 1;
     }
+
+    between
 }
 
 //#################################################################################################
@@ -182,24 +184,21 @@ impl BitBoard {
     /// from and to are aligned horizontally or vertically. Returns an empty bitboard if they are not.
     #[inline]
     pub fn between_straight(from: Square, to: Square) -> BitBoard {
-        // SAFE: array is initialized at startup
-        unsafe {SQUARES_BETWEEN_STRAIGHT[usize::from(from)][usize::from(to)]}
+        SQUARES_BETWEEN_STRAIGHT[usize::from(from)][usize::from(to)]
     }
 
     /// Returns a bitboard of the squares between from and to (exclusive) if 
     /// from and to are aligned diagonally. Returns an empty bitboard if they are not.
     #[inline]
     pub fn between_diagonal(from: Square, to: Square) -> BitBoard {
-        // SAFE: array is initialized at startup
-        unsafe {SQUARES_BETWEEN_DIAGNOAL[usize::from(from)][usize::from(to)]}
+        SQUARES_BETWEEN_DIAGNOAL[usize::from(from)][usize::from(to)]
     }
 
     /// Returns a bitboard of the squares between from and to (exclusive).
     /// if they are aligned. Returns an empty bitboard if they are not.
     #[inline]
     pub fn between(from: Square, to: Square) -> BitBoard {
-        // SAFE: array is initialized at startup
-        unsafe {SQUARES_BETWEEN[usize::from(from)][usize::from(to)]}
+        SQUARES_BETWEEN[usize::from(from)][usize::from(to)]
     }
 
     /// Returns a bitboard of the squares on the ray from-to, with
@@ -207,8 +206,7 @@ impl BitBoard {
     /// Returns an empty bitboard if they are not.
     #[inline]
     pub fn ray_mask(from: Square, to: Square) -> BitBoard {
-        // SAFE: array is initialized at startup
-        unsafe {SQUARES_RAY_MASK[usize::from(from)][usize::from(to)]}
+        SQUARES_RAY_MASK[usize::from(from)][usize::from(to)]
     }
 
     /// Pops the least significant bit. Returns 0 if self is empty.
@@ -234,65 +232,152 @@ impl BitBoard {
     pub fn is_two(self) -> bool {
         self.pop_lsb().is_one()
     }
-}
 
-// ================================ pub(crate) impl
+    /// Shifts the bitboard one rank up, towards black's side.
+    #[inline]
+    pub fn north(self) -> BitBoard {
+        self << 8
+    }
 
-impl BitBoard {
-    /// Performs a parallel bits extract (pext) using the intrinsic (fast).
-    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    /// Shifts the bitboard one rank down, towards white's side.
     #[inline]
-    pub(crate) fn pext(self, mask: BitBoard) -> BitBoard {
-        // SAFE: arch and cpu flags checked
-        BitBoard(unsafe {
-            std::arch::x86_64::_pext_u64(self.0, mask.0)
-        })
+    pub fn south(self) -> BitBoard {
+        self >> 8
     }
 
-    /// Performs a parallel bits extract (pext) without the intrinsic (slow).
-    #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+    /// Shifts the bitboard one file right, towards the h-file.
     #[inline]
-    pub(crate) fn pext(self, mut mask: BitBoard) -> BitBoard {
-        let (mut i, mut res) = (0, 0);
+    pub fn east(self) -> BitBoard {
+        (self << 1) & !BitBoard::FILE_A
+    }
 
-        while mask.0 != 0 {
-            let tmp = mask.0;
-            mask.0 &= mask.0 - 1;
-            if (mask.0 ^ tmp) & self.0 != 0 {
-                res |= 1 << i;
-            }
-            i += 1;
+    /// Shifts the bitboard one file left, towards the a-file.
+    #[inline]
+    pub fn west(self) -> BitBoard {
+        (self >> 1) & !BitBoard::FILE_H
+    }
+
+    /// Shifts the bitboard one square diagonally, up and right.
+    #[inline]
+    pub fn north_east(self) -> BitBoard {
+        self.north().east()
+    }
+
+    /// Shifts the bitboard one square diagonally, up and left.
+    #[inline]
+    pub fn north_west(self) -> BitBoard {
+        self.north().west()
+    }
+
+    /// Shifts the bitboard one square diagonally, down and right.
+    #[inline]
+    pub fn south_east(self) -> BitBoard {
+        self.south().east()
+    }
+
+    /// Shifts the bitboard one square diagonally, down and left.
+    #[inline]
+    pub fn south_west(self) -> BitBoard {
+        self.south().west()
+    }
+
+    /// Shifts the bitboard one rank forward, from the point of view of the given color.
+    #[inline]
+    pub fn forward(self, color: Color) -> BitBoard {
+        match color {
+            Color::White => self.north(),
+            Color::Black => self.south(),
         }
+    }
 
-        BitBoard(res)
+    /// Returns the bitboard with every square above a set square set too.
+    #[inline]
+    pub fn north_fill(self) -> BitBoard {
+        let mut fill = self;
+        fill |= fill << 8;
+        fill |= fill << 16;
+        fill | fill << 32
     }
 
-    /// Performs a parallel bits deposit (pdep) using the intrinsic (fast).
-    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    /// Returns the bitboard with every square below a set square set too.
     #[inline]
-    pub(crate) fn pdep(self, mask: BitBoard) -> BitBoard {
+    pub fn south_fill(self) -> BitBoard {
+        let mut fill = self;
+        fill |= fill >> 8;
+        fill |= fill >> 16;
+        fill | fill >> 32
+    }
+
+    /// Returns the bitboard with every square in front of a set square set too,
+    /// from the point of view of the given color.
+    #[inline]
+    pub fn forward_fill(self, color: Color) -> BitBoard {
+        match color {
+            Color::White => self.north_fill(),
+            Color::Black => self.south_fill(),
+        }
+    }
+
+    /// Returns the bitboard of the files containing at least one set square.
+    #[inline]
+    pub fn file_fill(self) -> BitBoard {
+        self.north_fill() | self.south_fill()
+    }
+
+    /// Returns the bitboard of the ranks containing at least one set square.
+    #[inline]
+    pub fn rank_fill(self) -> BitBoard {
+        (0..8).filter(|rank| (self.0 >> (8 * rank)) & 0xFF != 0)
+            .fold(BitBoard::EMPTY, |fill, rank| fill | BitBoard::RANK_1 << (8 * rank))
+    }
+
+    /// Returns the squares in front of the given square on its file, exclusive, from the point
+    /// of view of the given color. These are the squares a pawn must cross to promote.
+    #[inline]
+    pub fn front_span(color: Color, sq: Square) -> BitBoard {
+        BitBoard::from(sq).forward(color).forward_fill(color)
+    }
+
+    /// Returns the squares where no enemy pawn may be for a pawn of the given color on the
+    /// given square to be passed: the squares in front of it, on its file and the adjacent ones.
+    #[inline]
+    pub fn passed_pawn_mask(color: Color, sq: Square) -> BitBoard {
+        let span = BitBoard::front_span(color, sq);
+        span | span.east() | span.west()
+    }
+
+    /// Returns the king safety zone of a king of the given color on the given square: its square
+    /// and its neighbours, plus the three squares two ranks in front of it.
+    #[inline]
+    pub fn king_zone(color: Color, sq: Square) -> BitBoard {
+        let around = attacks::king(sq) | BitBoard::from(sq);
+        around | around.forward(color)
+    }
+}
+
+// ================================ pub(crate) impl
+
+impl BitBoard {
+    /// Performs a parallel bits extract (pext) using the intrinsic. Without the magic cfg,
+    /// the build script has checked that the target is a x86_64 cpu with bmi2.
+    #[cfg(not(magic))]
+    #[inline]
+    pub(crate) fn pext(self, mask: BitBoard) -> BitBoard {
         // SAFE: arch and cpu flags checked
         BitBoard(unsafe {
-            std::arch::x86_64::_pdep_u64(self.0, mask.0)
+            core::arch::x86_64::_pext_u64(self.0, mask.0)
         })
     }
 
-    /// Performs a parallel bits deposit (pdep) without the intrinsic (slow).
-    #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+    /// Performs a parallel bits deposit (pdep) using the intrinsic. Without the magic cfg,
+    /// the build script has checked that the target is a x86_64 cpu with bmi2.
+    #[cfg(not(magic))]
     #[inline]
-    pub(crate) fn pdep(self, mut mask: BitBoard) -> BitBoard {
-        let (mut i, mut res) = (0, 0);
-
-        while mask.0 != 0 {
-            let tmp = mask.0;
-            mask.0 &= mask.0 - 1;
-            if self.0 & (1 << i) != 0 {
-                res |= mask.0 ^ tmp;
-            }
-            i += 1;
-        }
-
-        BitBoard(res)
+    pub(crate) fn pdep(self, mask: BitBoard) -> BitBoard {
+        // SAFE: arch and cpu flags checked
+        BitBoard(unsafe {
+            core::arch::x86_64::_pdep_u64(self.0, mask.0)
+        })
     }
 }
 
@@ -319,8 +404,7 @@ impl From<Square> for BitBoard {
     /// Returns the bitboard containing only that square.
     #[inline]
     fn from(sq: Square) -> BitBoard {
-        // SAFE: array is initialized at startup
-        unsafe {SHIFTS[usize::from(sq)]}
+        SHIFTS[usize::from(sq)]
     }
 }
 
@@ -468,4 +552,78 @@ impl ops::ShrAssign<BitBoard> for BitBoard {
     fn shr_assign(&mut self, rhs: BitBoard) {
         self.0.shr_assign(rhs.0)
     }
-}
\ No newline at end of file
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the bitboard with the given squares set.
+    fn squares(squares: &[Square]) -> BitBoard {
+        squares.iter().fold(BitBoard::EMPTY, |bb, &sq| bb | BitBoard::from(sq))
+    }
+
+    #[test]
+    fn shifts() {
+        let e4 = BitBoard::from(Square::E4);
+        assert_eq!(e4.north(), BitBoard::from(Square::E5));
+        assert_eq!(e4.south(), BitBoard::from(Square::E3));
+        assert_eq!(e4.east(), BitBoard::from(Square::F4));
+        assert_eq!(e4.west(), BitBoard::from(Square::D4));
+        assert_eq!(e4.north_east(), BitBoard::from(Square::F5));
+        assert_eq!(e4.north_west(), BitBoard::from(Square::D5));
+        assert_eq!(e4.south_east(), BitBoard::from(Square::F3));
+        assert_eq!(e4.south_west(), BitBoard::from(Square::D3));
+        assert_eq!(e4.forward(Color::White), BitBoard::from(Square::E5));
+        assert_eq!(e4.forward(Color::Black), BitBoard::from(Square::E3));
+
+        // Squares shifted off the board are lost, they do not wrap around to the other side.
+        assert_eq!(BitBoard::FILE_A.east(), BitBoard::FILE_B);
+        assert_eq!(BitBoard::FILE_H.east(), BitBoard::EMPTY);
+        assert_eq!(BitBoard::FILE_A.west(), BitBoard::EMPTY);
+        assert_eq!(BitBoard::RANK_8.north(), BitBoard::EMPTY);
+        assert_eq!(BitBoard::RANK_1.south(), BitBoard::EMPTY);
+        assert_eq!(squares(&[Square::A1, Square::H4, Square::H8]).north_east(), BitBoard::from(Square::B2));
+        assert_eq!(squares(&[Square::A5, Square::H8, Square::A1]).south_west(), BitBoard::from(Square::G7));
+    }
+
+    #[test]
+    fn fills() {
+        let d4 = BitBoard::from(Square::D4);
+        assert_eq!(d4.north_fill(), BitBoard(0x0808_0808_0800_0000));
+        assert_eq!(d4.south_fill(), BitBoard(0x0000_0000_0808_0808));
+        assert_eq!(d4.forward_fill(Color::White), d4.north_fill());
+        assert_eq!(d4.forward_fill(Color::Black), d4.south_fill());
+
+        let b2_g7 = squares(&[Square::B2, Square::G7]);
+        assert_eq!(b2_g7.file_fill(), BitBoard::FILE_B | BitBoard::FILE_G);
+        assert_eq!(b2_g7.rank_fill(), BitBoard::RANK_2 | BitBoard::RANK_7);
+        assert_eq!(BitBoard::EMPTY.file_fill(), BitBoard::EMPTY);
+        assert_eq!(BitBoard::EMPTY.rank_fill(), BitBoard::EMPTY);
+    }
+
+    #[test]
+    fn pawn_masks() {
+        assert_eq!(BitBoard::front_span(Color::White, Square::E4), BitBoard(0x1010_1010_0000_0000));
+        assert_eq!(BitBoard::front_span(Color::Black, Square::E4), BitBoard(0x0000_0000_0010_1010));
+        assert_eq!(BitBoard::front_span(Color::White, Square::E8), BitBoard::EMPTY);
+
+        assert_eq!(BitBoard::passed_pawn_mask(Color::White, Square::A6), squares(&[Square::A7, Square::A8, Square::B7, Square::B8]));
+        assert_eq!(BitBoard::passed_pawn_mask(Color::Black, Square::E4), BitBoard(0x0000_0000_0038_3838));
+        assert_eq!(BitBoard::passed_pawn_mask(Color::Black, Square::H2), squares(&[Square::G1, Square::H1]));
+    }
+
+    #[test]
+    fn king_zone() {
+        assert_eq!(BitBoard::king_zone(Color::White, Square::G1), BitBoard(0x0000_0000_00E0_E0E0));
+        assert_eq!(BitBoard::king_zone(Color::Black, Square::G8), BitBoard(0xE0E0_E000_0000_0000));
+        assert_eq!(BitBoard::king_zone(Color::White, Square::E4), BitBoard(0x0000_3838_3838_0000));
+        assert_eq!(BitBoard::king_zone(Color::Black, Square::A1), squares(&[Square::A1, Square::A2, Square::B1, Square::B2]));
+    }
+}
@@ -1,10 +1,15 @@
-use std::fmt;
-use std::str::FromStr;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
 
-use anyhow::{Error, Result};
+use crate::error::{Error, Result};
 
 use crate::attacks;
 use crate::bitboard::BitBoard;
+use crate::book;
 use crate::castle_rights::CastleMask;
 use crate::castle_rights::CastleRights;
 use crate::color::Color;
@@ -14,6 +19,7 @@ use crate::movegen;
 use crate::moves::Move;
 use crate::piece::Piece;
 use crate::square::Square;
+use crate::variant::Variant;
 use crate::zobrist::Zobrist;
 
 //#################################################################################################
@@ -23,7 +29,7 @@ use crate::zobrist::Zobrist;
 //#################################################################################################
 
 /// An enum representing the status of a game.
-#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Status {
     Playing,
     Draw,
@@ -39,13 +45,26 @@ impl Status {
     }
 }
 
+/// The reasons a position may be drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+    /// The side to move has no legal move, and is not in check.
+    Stalemate,
+    /// Fifty moves were played by each side without a capture or a pawn move.
+    FiftyMoves,
+    /// The position occurred three times.
+    Repetition,
+    /// Neither side has enough material left to checkmate.
+    InsufficientMaterial,
+}
+
 //#################################################################################################
 //
 //                                    struct StateInfo
 //
 //#################################################################################################
 
-/// The state of the board at a given turn.
+/// The state of the board at a given turn, with what is needed to undo the move that led to it.
 #[derive(Clone, Default, Debug)]
 pub(crate) struct StateInfo {
     side_to_move: Color,
@@ -55,6 +74,19 @@ pub(crate) struct StateInfo {
     castle_rights: CastleRights,
     ep_square: EnPassantSquare,
     zobrist: Zobrist,
+    // The move that led to this state, None after a null move or for the first state.
+    last_move: Option<Move>,
+    // The piece captured by that move, with the square it stood on.
+    captured: Option<(Square, Piece)>,
+    // The squares the rook was moved from and to, if that move was castling.
+    castled_rook: Option<(Square, Square)>,
+    // The number of checks given by each color, only counted at three-check.
+    checks: [u8; 2],
+    // The material of each color, in total and without pawns, counted as with Piece::material().
+    material: [u16; 2],
+    non_pawn_material: [u16; 2],
+    // The sum of the weights of the pieces on the board, counted as with Piece::phase().
+    phase: u16,
 }
 
 //#################################################################################################
@@ -110,16 +142,34 @@ pub struct Board {
 
     state: StateInfo,
     prev_states: Vec<StateInfo>,
+
+    variant: Variant,
 }
 
 // ================================ pub impl
 
 impl Board {
+    /// The size in bytes of the binary representation of boards.
+    pub const ENCODED_SIZE: usize = 32;
+
+    /// The phase of the game when all pieces are on the board.
+    pub const MAX_PHASE: u8 = 24;
+
+    /// The number of planes of the representation of boards given by Board::to_planes().
+    pub const PLANES: usize = 21;
+
     /// Tries to parse the fen string into a board.
     pub fn new(fen: &str) -> Result<Board> {
         Board::from_str(fen)
     }
 
+    /// Tries to parse the fen string into a board, played with the rules of the given variant.
+    pub fn with_variant(fen: &str, variant: Variant) -> Result<Board> {
+        let mut board = Board::from_str(fen)?;
+        board.variant = variant;
+        Ok(board)
+    }
+
     // ================================ Accessers
 
     /// Returns the color of the side to move.
@@ -134,6 +184,38 @@ impl Board {
         self.state.side_to_move.invert()
     }
 
+    /// Returns the variant whose rules the board is played with.
+    #[inline]
+    pub fn get_variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Returns the number of checks given by the given color. They are only counted at three-check.
+    #[inline]
+    pub fn get_checks(&self, color: Color) -> u8 {
+        self.state.checks[usize::from(color)]
+    }
+
+    /// Returns the material of the given color, in pawns, its pieces being counted as with Piece::material().
+    #[inline]
+    pub fn material(&self, color: Color) -> u16 {
+        self.state.material[usize::from(color)]
+    }
+
+    /// Returns the material of the given color without its pawns, in pawns, as with material().
+    #[inline]
+    pub fn non_pawn_material(&self, color: Color) -> u16 {
+        self.state.non_pawn_material[usize::from(color)]
+    }
+
+    /// Returns the phase of the game, from Board::MAX_PHASE when all pieces are on the board, down to 0
+    /// when only kings and pawns are left, the pieces being weighted as with Piece::phase(). Positions
+    /// with more pieces, after promotions, are at Board::MAX_PHASE too.
+    #[inline]
+    pub fn phase(&self) -> u8 {
+        self.state.phase.min(u16::from(Board::MAX_PHASE)) as u8
+    }
+
     /// Returns the bitboard containing the checkers in the current position.
     #[inline]
     pub fn get_checkers(&self) -> BitBoard {
@@ -164,10 +246,23 @@ impl Board {
         self.state.castle_rights
     }
 
-    /// Returns the en passant square of the current position.
+    /// Returns the en passant square of the current position: the square of the pawn that was just
+    /// pushed two squares, if any, even if no pawn can capture it.
+    #[inline]
+    pub fn get_ep_square(&self) -> Option<Square> {
+        self.state.ep_square.into()
+    }
+
+    /// Returns the square a pawn capturing en passant would move to, the one the pawn that was just
+    /// pushed two squares went over, if any, even if no pawn can capture it.
     #[inline]
-    pub fn get_ep_square(&self) -> EnPassantSquare {
-        self.state.ep_square
+    pub fn get_ep_target(&self) -> Option<Square> {
+        let sq = self.get_ep_square()?;
+        let behind = match self.get_side_to_move() {
+            Color::White => (0, 1),
+            Color::Black => (0, -1),
+        };
+        sq.displace(behind)
     }
 
     /// Gets the bitboard corresponding to that color and piece type.
@@ -194,10 +289,20 @@ impl Board {
         self.state.zobrist
     }
 
+    /// The key of the current board in polyglot books, which differs from its zobrist hash. Unlike the
+    /// zobrist hash, it is computed from scratch, and is meant for probing books rather than for searching.
+    #[inline]
+    pub fn get_polyglot_key(&self) -> u64 {
+        book::polyglot_hash(self)
+    }
+
     /// Clears the history of the board, making it impossible to 
     /// undo the previous moves but freeing a bit of memory.
     #[inline]
     pub fn clear_history(&mut self) {
+        self.state.last_move = None;
+        self.state.captured = None;
+        self.state.castled_rook = None;
         self.prev_states.clear()
     }
 
@@ -218,59 +323,53 @@ impl Board {
         unsafe {king_bb.as_square_unchecked()}
     }
 
-    /// Returns the status of the current game. Must be called every turn to be accurate.
-    pub fn status(&self) -> Status {
-        let halfmoves = self.get_halfmove();
-
-        // 50 moves rule and threefold repetition.
-        if halfmoves >= 50 {
-            return Status::Draw;
-        } else if halfmoves >= 3 {
-            let repetitions = self.prev_states.iter().rev()
-                .take(usize::from(self.get_halfmove()))
-                .filter(|state| state.zobrist == self.state.zobrist)
-                .count();
+    /// Returns the bitboard of the squares attacked by the piece on the given square, or an
+    /// empty bitboard if there is none. Pawns attack diagonally whether the squares are occupied or not.
+    pub fn attacks_from(&self, sq: Square) -> BitBoard {
+        let occ = self.get_occupancy().all();
 
-            if repetitions >= 3 {
-                return Status::Draw;
-            }
+        match self.get_piece(sq) {
+            Some((color, Piece::Pawn)) => attacks::pawn(color, sq),
+            Some((_, Piece::Rook)) => attacks::rook(sq, occ),
+            Some((_, Piece::Knight)) => attacks::knight(sq),
+            Some((_, Piece::Bishop)) => attacks::bishop(sq, occ),
+            Some((_, Piece::Queen)) => attacks::queen(sq, occ),
+            Some((_, Piece::King)) => attacks::king(sq),
+            None => BitBoard::EMPTY,
         }
+    }
 
-        // Draw by insufficient material.
+    /// Returns the bitboard of the pieces of both colors attacking the given square.
+    /// Does not take en passant into account.
+    pub fn attackers_of(&self, sq: Square) -> BitBoard {
         let occ = self.get_occupancy().all();
-        match occ.count() {
-            // King versus King
-            2 => return Status::Draw,
-            3 => {
-                // King + Knight versus King or King + Bishop versus King.
-                let other = occ ^ self.get_bitboard(Color::White, Piece::King) ^ self.get_bitboard(Color::Black, Piece::King);
-                // SAFE: cardinality of other is one
-                match self.get_piece_unchecked(unsafe {other.as_square_unchecked()}) {
-                    Piece::Knight | Piece::Bishop => return Status::Draw,
-                    _ => (),
-                }
-            },
-            4 => {
-                // King + Bishop versus King + Bishop where the bishops have the same parity.
-                let others = occ ^ self.get_bitboard(Color::White, Piece::King) ^ self.get_bitboard(Color::Black, Piece::King);
-                // SAFE: cardinality of other is two
-                let sq1 = unsafe {others.as_square_unchecked()};
-                let sq2 = unsafe {others.pop_lsb().as_square_unchecked()};
-                if sq1.parity() == sq2.parity() {
-                    let (color1, piece1) = self.get_piece(sq1).unwrap();
-                    let (color2, piece2) = self.get_piece(sq2).unwrap();
-                    if color1 != color2 && piece1 == Piece::Bishop && piece2 == Piece::Bishop {
-                        return Status::Draw;
-                    }
-                }
-            },
-            _ => (),
+        self.attackers_by(sq, Color::White, occ) | self.attackers_by(sq, Color::Black, occ)
+    }
+
+    /// Returns true if the given square is attacked by a piece of the given color.
+    /// Does not take en passant into account.
+    pub fn is_attacked(&self, sq: Square, by: Color) -> bool {
+        self.attackers_by(sq, by, self.get_occupancy().all()).not_empty()
+    }
+
+    /// Returns the bitboard of the squares attacked by the pieces of the given color, as with attacks_from().
+    pub fn attacks_by(&self, color: Color) -> BitBoard {
+        self.get_occupancy().colored(color).iter_squares().fold(BitBoard::EMPTY, |attacks, sq| attacks | self.attacks_from(sq))
+    }
+
+    /// Returns the status of the current game. Must be called every turn to be accurate.
+    pub fn status(&self) -> Status {
+        // The rules of the variant may end the game first.
+        if let Some(winner) = self.variant.winner(self) {
+            return Status::Win(winner);
+        }
+
+        if self.rule_draw().is_some() {
+            return Status::Draw;
         }
 
         // Stalemate, or checkmate.
-        let mut legals = Vec::new();
-        movegen::legals(self, &mut legals);
-        if legals.len() == 0 {
+        if !self.has_legal_move() {
             if self.get_checkers().empty() {
                 return Status::Draw;
             } else {
@@ -281,6 +380,30 @@ impl Board {
         Status::Playing
     }
 
+    /// Returns the reason the current game is drawn, or None if the status of the game is not Status::Draw.
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        if self.variant.winner(self).is_some() {
+            return None;
+        }
+
+        self.rule_draw().or_else(|| {
+            (self.get_checkers().empty() && !self.has_legal_move()).then_some(DrawReason::Stalemate)
+        })
+    }
+
+    /// Returns true if the side to move has at least one legal move. Cheaper than generating
+    /// the legal moves, as it stops at the first one found, and does not allocate.
+    #[inline]
+    pub fn has_legal_move(&self) -> bool {
+        movegen::has_legals(self)
+    }
+
+    /// Returns the number of legal moves of the side to move, without allocating.
+    #[inline]
+    pub fn legals_count(&self) -> usize {
+        movegen::legals_count(self)
+    }
+
     /// Returns true if that pseudo-legal move is legal.
     /// In particular, checks whether or not the move does not violate pin
     /// (or double pin for en passant moves), or, if it is a castling move,
@@ -338,6 +461,60 @@ impl Board {
         !self.get_pinned().contains(from) || BitBoard::ray_mask(self.king_sq(self.get_side_to_move()), from).contains(to)
     }
 
+    /// Returns true if that legal move gives check to the opponent, without doing it.
+    /// Both direct checks from the moved piece and discovered checks are detected.
+    pub fn gives_check(&self, mv: Move) -> bool {
+        let us = self.get_side_to_move();
+        let (from, to) = mv.squares();
+        let king_sq = self.king_sq(self.get_other_side());
+
+        // The occupancy after the move, ignoring captured pieces and castling rooks.
+        let occ = (self.get_occupancy().all() ^ BitBoard::from(from)) | BitBoard::from(to);
+
+        // Direct check from the moved piece.
+        let piece = if mv.is_promote() {mv.get_promote()} else {self.get_piece_unchecked(from)};
+        let attacks = match piece {
+            Piece::Pawn => attacks::pawn(us, to),
+            Piece::Knight => attacks::knight(to),
+            Piece::Bishop => attacks::bishop(to, occ),
+            Piece::Rook => attacks::rook(to, occ),
+            Piece::Queen => attacks::queen(to, occ),
+            Piece::King => BitBoard::EMPTY,
+        };
+
+        if attacks.contains(king_sq) {
+            return true;
+        }
+
+        // Discovered check, by moving a piece out of the line between one of our sliders and the king.
+        if self.discoverers().contains(from) && !BitBoard::ray_mask(king_sq, from).contains(to) {
+            return true;
+        }
+
+        let mut rooks = self.get_bitboard(us, Piece::Rook) | self.get_bitboard(us, Piece::Queen);
+        let bishops = self.get_bitboard(us, Piece::Bishop) | self.get_bitboard(us, Piece::Queen);
+
+        let occ = if mv.is_castle() {
+            // The rook may give check after castling.
+            let (rook_from, rook_to) = match to {
+                Square::G1 => (Square::H1, Square::F1),
+                Square::G8 => (Square::H8, Square::F8),
+                Square::C1 => (Square::A1, Square::D1),
+                Square::C8 => (Square::A8, Square::D8),
+                _ => unreachable!(),
+            };
+            rooks ^= BitBoard::from(rook_from) | BitBoard::from(rook_to);
+            occ ^ BitBoard::from(rook_from) | BitBoard::from(rook_to)
+        } else if mv.is_en_passant() {
+            // Removing the captured pawn may discover a check.
+            occ ^ BitBoard::from(self.get_ep_square().unwrap())
+        } else {
+            return false;
+        };
+
+        (attacks::rook(king_sq, occ) & rooks | attacks::bishop(king_sq, occ) & bishops).not_empty()
+    }
+
     /// Returns true if that random move is pseudo-legal. Only assumes that the
     /// move was created through one of the Move type's metods.
     pub fn is_pseudo_legal(&self, mv: Move) -> bool {
@@ -420,14 +597,16 @@ impl Board {
                     // It is a valid pawn attack too.
                     return attacks::pawn(color, from).contains(to);
                 } else {
-                    // If the move is a promotion, it must go to the first or last rank.
-                    verify!(BitBoard::promote_rank(self.get_side_to_move()).contains(from) || !mv.is_promote());
+                    // The move is a promotion if and only if it goes to the first or last rank.
+                    verify!(BitBoard::promote_rank(self.get_side_to_move()).contains(from) == mv.is_promote());
 
                     // Verify that the move is legal for a pawn.
                     if mv.is_capture() {
                         return attacks::pawn(color, from).contains(to);
                     } else {
                         return if mv.is_double_push() {
+                            // The square jumped over must be empty too.
+                            verify!((BitBoard::between(from, to) & self.get_occupancy().all()).empty());
                             attacks::pawn_double_push(color, from)
                         } else {
                             attacks::pawn_push(color, from)
@@ -479,22 +658,32 @@ impl Board {
         // Determine if the move is reversible or not.
         let reversible = mv.is_quiet() && piece != Piece::Pawn;
 
+        // Record the move, and what it captures or castles below, so that it may be undone.
+        self.state.last_move = Some(mv);
+        self.state.captured = None;
+        self.state.castled_rook = None;
+
         if mv.is_castle() {
             // If the move is castling, move the rook as well.
-            match to {
-                Square::G1 => self.displace_piece::<true>(Square::H1, Square::F1),
-                Square::G8 => self.displace_piece::<true>(Square::H8, Square::F8),
-                Square::C1 => self.displace_piece::<true>(Square::A1, Square::D1),
-                Square::C8 => self.displace_piece::<true>(Square::A8, Square::D8),
+            let rook = match to {
+                Square::G1 => (Square::H1, Square::F1),
+                Square::G8 => (Square::H8, Square::F8),
+                Square::C1 => (Square::A1, Square::D1),
+                Square::C8 => (Square::A8, Square::D8),
                 _ => unreachable!(),
             };
+            self.displace_piece::<true>(rook.0, rook.1);
+            self.state.castled_rook = Some(rook);
         } else if mv.is_en_passant() {
             // If the move is en passant, remove the pawn at the en passant square.
-            self.remove_piece::<true>(self.get_ep_square().unwrap());
+            let sq = self.get_ep_square().unwrap();
+            let (_, pawn) = self.remove_piece::<true>(sq);
+            self.state.captured = Some((sq, pawn));
         } else {
             // If the move is a capture, remove the enemy piece from the destination square.
             if mv.is_capture() {
-                self.remove_piece::<true>(to);
+                let (_, captured) = self.remove_piece::<true>(to);
+                self.state.captured = Some((to, captured));
             }
     
             // If the move is a promotion, set the piece to be the promotion.
@@ -510,6 +699,13 @@ impl Board {
         self.state.checkers = self.checkers();
         self.state.pinned = self.pinned();
 
+        // At three-check, count the checks given, up to the number that wins.
+        if self.variant == Variant::ThreeCheck && self.state.checkers.not_empty() && self.state.checks[usize::from(color)] < Variant::CHECKS_TO_WIN {
+            let checks = &mut self.state.checks[usize::from(color)];
+            self.state.zobrist ^= Zobrist::checks(color, *checks) ^ Zobrist::checks(color, *checks + 1);
+            *checks += 1;
+        }
+
         // Update castling rights.
         self.state.castle_rights.update(from, to);
         self.state.zobrist ^= Zobrist::from(self.state.castle_rights);
@@ -535,11 +731,32 @@ impl Board {
     }
 
     /// Undoes the move, reverting the board to it's previous state.
+    /// The move must be the last move played on the board.
     #[inline]
     pub fn undo_move(&mut self, mv: Move) {
+        debug_assert_eq!(self.state.last_move, Some(mv), "Undoing a move that was not the last played.");
+        self.undo_last();
+    }
+
+    /// Returns the last move played on the board, or None if no move was played
+    /// or if the last one was a null move.
+    #[inline]
+    pub fn last_move(&self) -> Option<Move> {
+        self.state.last_move
+    }
+
+    /// Undoes the last move played on the board and returns it, reverting the board to it's previous state.
+    /// Returns None and leaves the board untouched if no move was played, or if the last one was a null move,
+    /// which must be undone with undo_null.
+    pub fn undo_last(&mut self) -> Option<Move> {
         // Them color.
         let them = self.get_side_to_move();
 
+        // Take what is needed to undo the move from the current state, before it is dropped.
+        let mv = self.state.last_move?;
+        let captured = self.state.captured;
+        let castled_rook = self.state.castled_rook;
+
         // Restore the previous state and decrement the fullmove counter.
         self.state = self.prev_states.pop().unwrap();
         self.ply -= 1;
@@ -548,31 +765,24 @@ impl Board {
         let (from, to) = mv.squares();
         let (color, mut piece) = self.remove_piece::<false>(to);
 
-        if mv.is_castle() {
-            // If the move was castling, move the rook back as well.
-            match to {
-                Square::G1 => self.displace_piece::<true>(Square::F1, Square::H1),
-                Square::G8 => self.displace_piece::<true>(Square::F8, Square::H8),
-                Square::C1 => self.displace_piece::<true>(Square::D1, Square::A1),
-                Square::C8 => self.displace_piece::<true>(Square::D8, Square::A8),
-                _ => unreachable!(),
-            };
-        } else if mv.is_en_passant() {
-            // If the move was en passant, place the enemy pawn back as well.
-            self.place_piece::<false>(them, Piece::Pawn, self.get_ep_square().unwrap());
-        } else {
-            // If the move was a capture, replace the taken enemy piece in it's place.
-            if mv.is_capture() {
-                self.place_piece::<false>(them, mv.get_capture(), to);
-            }
-    
-            // If the move was a promotion, the original piece was a pawn.
-            if mv.is_promote() {
-                piece = Piece::Pawn;
-            }
+        // If the move was castling, move the rook back as well.
+        if let Some((rook_from, rook_to)) = castled_rook {
+            self.displace_piece::<false>(rook_to, rook_from);
+        }
+
+        // If the move was a promotion, the original piece was a pawn.
+        if mv.is_promote() {
+            piece = Piece::Pawn;
         }
 
         self.place_piece::<false>(color, piece, from);
+
+        // If the move was a capture, en passant or not, replace the taken enemy piece in it's place.
+        if let Some((sq, captured)) = captured {
+            self.place_piece::<false>(them, captured, sq);
+        }
+
+        Some(mv)
     }
 
     /// Performs a null move (pass). Intended only for the engine to perform null-move pruning.
@@ -592,6 +802,11 @@ impl Board {
         // Invert the side to move.
         self.state.side_to_move = self.get_other_side();
 
+        // A null move has nothing to undo but the state.
+        self.state.last_move = None;
+        self.state.captured = None;
+        self.state.castled_rook = None;
+
         // Determine checkers and pinned bitboard.
         self.state.checkers = self.checkers();
         self.state.pinned = self.pinned();
@@ -613,6 +828,16 @@ impl Board {
         self.ply -= 1;
     }
 
+    /// Returns the zobrist key of the position the given number of plies ago,
+    /// if the moves leading from it to the current position were played on this board.
+    #[inline]
+    pub fn get_previous_zobrist(&self, plies: usize) -> Option<Zobrist> {
+        match plies {
+            0 => Some(self.state.zobrist),
+            _ => self.prev_states.len().checked_sub(plies).map(|i| self.prev_states[i].zobrist),
+        }
+    }
+
     /// Efficiently tests for an upcoming repetition on the line,
     /// using cuckoo hashing.
     pub fn test_upcoming_repetition(&self) -> bool {
@@ -645,8 +870,32 @@ impl Board {
         false
     }
 
+    /// Returns true if the position is a repetition, considering that the last plies
+    /// moves were played during a search: it is enough for the position to have occurred once
+    /// within those moves, but it must have occurred twice before them, as in the threefold repetition rule.
+    pub fn is_repetition(&self, plies: usize) -> bool {
+        let n = usize::from(self.get_halfmove()).min(self.prev_states.len());
+        let mut count = 0;
+
+        for d in (4..=n).step_by(2) {
+            if self.prev_states[self.prev_states.len() - d].zobrist == self.state.zobrist {
+                count += 1;
+                if d <= plies || count == 2 {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
     /// Tries to build a move from the given parameters. Returns an error if the move is illegal.
     pub fn make_move(&self, from: Square, to: Square, maybe_promote: Option<Piece>) -> Result<Move> {
+        // No move may be played once the rules of the variant ended the game.
+        if self.variant.winner(self).is_some() {
+            return Err(Error::GameOver);
+        }
+
         let mv = if let Some(promote) = maybe_promote {
             if let Some((_, capture)) = self.get_piece(to) {
                 Move::promote_capture(from, to, capture, promote)
@@ -690,27 +939,21 @@ impl Board {
         if self.is_pseudo_legal(mv) && self.is_legal(mv) {
             Ok(mv)
         } else {
-            Err(Error::msg("Move is invalid in this context."))
+            Err(Error::IllegalMove)
         }
     }
 
     /// Parses the move, checking the legality of the move.
     pub fn parse_move(&self, s: &str) -> Result<Move> {
         if s.len() != 4 && s.len() != 5 {
-            return Err(Error::msg("Invalid length for move literal."))
+            return Err(Error::ParseMove {reason: "it should be 4 or 5 characters long"})
         }
 
-        let from = Square::from_str(&s[0..2])?;
-        let to = Square::from_str(&s[2..4])?;
+        let from = Square::from_str(s.get(0..2).ok_or(Error::ParseSquare)?)?;
+        let to = Square::from_str(s.get(2..4).ok_or(Error::ParseSquare)?)?;
 
         if s.len() == 5 {
-            let promote = match s.chars().nth(4).unwrap() {
-                'r' => Piece::Rook,
-                'n' => Piece::Knight,
-                'b' => Piece::Bishop,
-                'q' => Piece::Queen,
-                _ => return Err(Error::msg("Unrecognized promotion.")),
-            };
+            let promote = Piece::from_promotion_char(s.chars().nth(4).unwrap())?;
 
             self.make_move(from, to, Some(promote))
         } else {
@@ -718,6 +961,274 @@ impl Board {
         }
     }
 
+    /// Parses and plays a sequence of moves in coordinate notation, such as the one of the "position ... moves ..."
+    /// command of UCI. Each item may hold several moves separated by whitespace. If a move can't be parsed or
+    /// is illegal, the moves already played are undone and the error gives the index of the failing move.
+    pub fn apply_moves<'a, I: IntoIterator<Item = &'a str>>(&mut self, moves: I) -> Result<()> {
+        let moves = moves.into_iter().flat_map(str::split_whitespace);
+
+        for (index, s) in moves.enumerate() {
+            match self.parse_move(s) {
+                Ok(mv) => self.do_move(mv),
+                Err(err) => {
+                    for _ in 0..index {
+                        self.undo_last();
+                    }
+
+                    return Err(Error::InvalidMoveSequence {index, reason: format!("\"{}\", {}", s, err)});
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a move written in standard algebraic notation, checking the legality of the move.
+    /// Check and annotation symbols are ignored.
+    pub fn parse_san(&self, s: &str) -> Result<Move> {
+        let san = s.trim_end_matches(&['+', '#', '!', '?'][..]);
+
+        let mut legals = movegen::MoveList::new();
+        movegen::legals_into(self, &mut legals);
+
+        // Castling, identified by the file the king lands on.
+        if let Some(file) = match san {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        } {
+            return legals.iter().copied()
+                .find(|mv| mv.is_castle() && mv.to().file_index() == file)
+                .ok_or(Error::IllegalMove);
+        }
+
+        // The moved piece, pawns having no letter.
+        let (piece, san) = match san.chars().next() {
+            Some(c @ ('K' | 'Q' | 'R' | 'B' | 'N')) => (Piece::from_char(c)?.1, &san[1..]),
+            _ => (Piece::Pawn, san),
+        };
+
+        // The promotion, with or without the '=' sign.
+        let (san, promote) = match san.chars().last() {
+            Some(c @ ('Q' | 'R' | 'B' | 'N')) => (san[..san.len() - 1].trim_end_matches('='), Some(Piece::from_char(c)?.1)),
+            _ => (san, None),
+        };
+
+        // The destination square, preceded by the optional capture sign and disambiguation.
+        let san = san.replace('x', "");
+        if san.len() < 2 || !san.is_char_boundary(san.len() - 2) {
+            return Err(Error::ParseMove {reason: "it has no destination square"});
+        }
+        let (disambiguation, to) = san.split_at(san.len() - 2);
+        let to = Square::from_str(to)?;
+
+        let mut candidates = legals.iter().copied().filter(|&mv| {
+            mv.to() == to
+                && self.get_piece_unchecked(mv.from()) == piece
+                && (if mv.is_promote() {Some(mv.get_promote())} else {None}) == promote
+                && disambiguation.chars().all(|c| match c {
+                    'a'..='h' => mv.from().file_index() == c as u8 - b'a',
+                    '1'..='8' => mv.from().rank_index() == c as u8 - b'1',
+                    _ => false,
+                })
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(mv), None) => Ok(mv),
+            (Some(_), Some(_)) => Err(Error::AmbiguousMove),
+            (None, _) => Err(Error::IllegalMove),
+        }
+    }
+
+    /// Formats a legal move in standard algebraic notation, such as "Nbd7", "exd8=Q+" or "O-O#".
+    pub fn to_san(&self, mv: Move) -> String {
+        let mut san = String::new();
+
+        if mv.is_castle() {
+            san.push_str(if mv.to().file_index() == 6 {"O-O"} else {"O-O-O"});
+        } else {
+            let piece = self.get_piece_unchecked(mv.from());
+            let capture = mv.is_capture() || mv.is_en_passant();
+
+            if piece == Piece::Pawn {
+                if capture {
+                    san.push(char::from(b'a' + mv.from().file_index()));
+                }
+            } else {
+                san.push(piece.as_char(Color::White));
+
+                // The other pieces of the same kind that may go to the same square.
+                let mut legals = movegen::MoveList::new();
+                movegen::legals_into(self, &mut legals);
+                let others: Vec<Square> = legals.iter()
+                    .filter(|other| other.to() == mv.to() && other.from() != mv.from() && self.get_piece_unchecked(other.from()) == piece)
+                    .map(|other| other.from())
+                    .collect();
+
+                // Disambiguates by file if possible, then by rank, then by both.
+                if !others.is_empty() {
+                    if others.iter().all(|sq| sq.file_index() != mv.from().file_index()) {
+                        san.push(char::from(b'a' + mv.from().file_index()));
+                    } else if others.iter().all(|sq| sq.rank_index() != mv.from().rank_index()) {
+                        san.push(char::from(b'1' + mv.from().rank_index()));
+                    } else {
+                        san.push_str(&mv.from().to_string());
+                    }
+                }
+            }
+
+            if capture {
+                san.push('x');
+            }
+
+            san.push_str(&mv.to().to_string());
+
+            if mv.is_promote() {
+                san.push('=');
+                san.push(mv.get_promote().as_char(Color::White));
+            }
+        }
+
+        // Check and checkmate.
+        if self.gives_check(mv) {
+            let mut board = self.clone();
+            board.do_move(mv);
+            san.push(if board.has_legal_move() {'+'} else {'#'});
+        }
+
+        san
+    }
+
+    /// Returns the board as Board::PLANES planes of 64 squares, for feeding neural networks. Each plane holds
+    /// a 1 on the squares where its feature is present and a 0 elsewhere, in the order of the squares, from
+    /// a1 to h8. The planes are, in order:
+    /// - the 12 planes of the pieces, the white pawns, knights, bishops, rooks, queens and king, then the black ones.
+    /// - a plane full if white is to move, and empty otherwise.
+    /// - 4 planes full if the castling rights are available, to the king side for white, to the queen side for white,
+    ///   then for black, and empty otherwise.
+    /// - the en passant target square, as with get_ep_target().
+    /// - the pieces giving check.
+    /// - the squares attacked by white, then by black, as with attacks_by().
+    pub fn to_planes(&self) -> [u8; Board::PLANES * 64] {
+        let mut planes = [0; Board::PLANES * 64];
+        let mut set = |plane: usize, bb: BitBoard| {
+            for sq in bb.iter_squares() {
+                planes[plane * 64 + usize::from(sq)] = 1;
+            }
+        };
+        let full = |present: bool| if present {BitBoard::FULL} else {BitBoard::EMPTY};
+
+        for (i, &color) in Color::COLORS.iter().enumerate() {
+            for (j, &piece) in Piece::PIECES.iter().enumerate() {
+                set(6 * i + j, self.get_bitboard(color, piece));
+            }
+        }
+
+        let rights = self.get_castle_rights();
+        set(12, full(self.get_side_to_move() == Color::White));
+        set(13, full(rights.kingside(Color::White)));
+        set(14, full(rights.queenside(Color::White)));
+        set(15, full(rights.kingside(Color::Black)));
+        set(16, full(rights.queenside(Color::Black)));
+        set(17, self.get_ep_target().map_or(BitBoard::EMPTY, BitBoard::from));
+        set(18, self.get_checkers());
+        set(19, self.attacks_by(Color::White));
+        set(20, self.attacks_by(Color::Black));
+
+        planes
+    }
+
+    /// Encodes the board into a compact and canonical binary representation:
+    /// - the occupancy bitboard, as 8 little endian bytes.
+    /// - a nibble for each piece, in the order of their squares, holding their color and kind.
+    /// - the side to move and the castle rights packed in one byte.
+    /// - the en passant square, or 0xFF if there is none.
+    /// - the halfmove clock, then the ply as 2 little endian bytes.
+    /// - zeroes to fill the remaining bytes.
+    pub fn to_bytes(&self) -> [u8; Board::ENCODED_SIZE] {
+        let mut bytes = [0; Board::ENCODED_SIZE];
+
+        bytes[..8].copy_from_slice(&self.occ.all.0.to_le_bytes());
+
+        for (i, sq) in self.occ.all.iter_squares().enumerate() {
+            let (color, piece) = self.get_piece(sq).unwrap();
+            let nibble = (color as u8) << 3 | piece as u8;
+            bytes[8 + i / 2] |= nibble << (4 * (i & 1));
+        }
+
+        bytes[24] = self.get_side_to_move() as u8 | self.get_castle_rights().raw() << 1;
+        bytes[25] = match self.state.ep_square {
+            EnPassantSquare::Some(sq) => sq as u8,
+            EnPassantSquare::None => 0xFF,
+        };
+        bytes[26] = self.get_halfmove();
+        bytes[27..29].copy_from_slice(&self.get_ply().to_le_bytes());
+
+        bytes
+    }
+
+    /// Tries to decode a board from the binary representation given by to_bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Board> {
+        if bytes.len() != Board::ENCODED_SIZE {
+            return Err(Error::InvalidEncoding {reason: "invalid length"});
+        }
+
+        let mut occ = [0; 8];
+        occ.copy_from_slice(&bytes[..8]);
+        let occ = BitBoard(u64::from_le_bytes(occ));
+
+        if occ.count() > 32 {
+            return Err(Error::InvalidEncoding {reason: "too many pieces"});
+        }
+
+        // An empty board.
+        let mut board = Board::default();
+
+        // Decode the pieces.
+        for (i, sq) in occ.iter_squares().enumerate() {
+            let nibble = bytes[8 + i / 2] >> (4 * (i & 1)) & 0xF;
+            let piece = Piece::try_from(nibble & 0b111).map_err(|_| Error::InvalidEncoding {reason: "invalid piece"})?;
+            let color = Color::try_from(nibble >> 3).map_err(|_| Error::InvalidEncoding {reason: "invalid color"})?;
+            board.place_piece::<true>(color, piece, sq);
+        }
+
+        // Decode the state.
+        if bytes[24] >> 5 != 0 {
+            return Err(Error::InvalidEncoding {reason: "invalid flags"});
+        }
+        board.state.side_to_move = Color::COLORS[usize::from(bytes[24] & 1)];
+        board.state.castle_rights = CastleRights::from_raw(bytes[24] >> 1);
+        board.state.ep_square = match bytes[25] {
+            0xFF => EnPassantSquare::None,
+            i => EnPassantSquare::Some(Square::try_from(i).map_err(|_| Error::InvalidEncoding {reason: "invalid en passant square"})?),
+        };
+        board.state.halfmove = bytes[26];
+        board.ply = u16::from_le_bytes([bytes[27], bytes[28]]);
+
+        // The encoding is canonical: unused bits must be zero.
+        if board.to_bytes()[..] != bytes[..] {
+            return Err(Error::InvalidEncoding {reason: "the encoding is not canonical"});
+        }
+
+        board.init_state()?;
+        Ok(board)
+    }
+
+    /// Returns the board mirrored vertically with the colors swapped: the position seen by the other side,
+    /// with the pieces, the side to move, the castling rights and the en passant square all mirrored.
+    /// The positions are equivalent, which is useful to augment training data or test the symmetry of an
+    /// evaluation. As with a board parsed from a fen string, the history of the board is not kept.
+    pub fn mirror(&self) -> Board {
+        self.transformed(|sq| sq.mirror(), Color::invert, self.get_castle_rights().swap_colors())
+    }
+
+    /// Returns the board flipped horizontally, the a-file becoming the h-file. Castling rights are
+    /// lost, since the kings and rooks no longer stand on their castling squares. The position is
+    /// only equivalent when there are no castling rights. The history of the board is not kept.
+    pub fn flip_horizontal(&self) -> Board {
+        self.transformed(|sq| sq.flip_horizontal(), |color| color, CastleRights::from_raw(0))
+    }
+
     /// Pretty-prints the board into a terminal, with emojis for pieces and ansi colors for squares.
     pub fn pretty_print(&self) -> String {
         const RESET: &str = "\x1b[0m";
@@ -775,24 +1286,83 @@ impl Board {
     /// en passant into account.
     #[inline]
     pub(crate) fn attackers_to(&self, sq: Square, occ: BitBoard) -> BitBoard {
-        let us = self.get_side_to_move();
-        let them = self.get_other_side();
-
-        let queens = self.get_bitboard(them, Piece::Queen);
+        self.attackers_by(sq, self.get_other_side(), occ)
+    }
 
-        attacks::pawn(us, sq) & self.get_bitboard(them, Piece::Pawn) 
-        | attacks::rook(sq, occ) & (self.get_bitboard(them, Piece::Rook) | queens)
-        | attacks::knight(sq) & self.get_bitboard(them, Piece::Knight) 
-        | attacks::bishop(sq, occ) & (self.get_bitboard(them, Piece::Bishop) | queens)
-        | attacks::king(sq) & self.get_bitboard(them, Piece::King)
+    /// Returns the bitboard of the pieces of the given color attacking that square,
+    /// with the given occupancy. Does not take en passant into account.
+    #[inline]
+    pub(crate) fn attackers_by(&self, sq: Square, color: Color, occ: BitBoard) -> BitBoard {
+        let queens = self.get_bitboard(color, Piece::Queen);
+
+        attacks::pawn(color.invert(), sq) & self.get_bitboard(color, Piece::Pawn) 
+        | attacks::rook(sq, occ) & (self.get_bitboard(color, Piece::Rook) | queens)
+        | attacks::knight(sq) & self.get_bitboard(color, Piece::Knight) 
+        | attacks::bishop(sq, occ) & (self.get_bitboard(color, Piece::Bishop) | queens)
+        | attacks::king(sq) & self.get_bitboard(color, Piece::King)
     }
 }
 
 // ================================ impl
 
 impl Board {
+    /// Returns the reason the position is drawn by the rules of chess, that is by the fifty moves rule,
+    /// by threefold repetition or by insufficient material, or None if it is not. Stalemate is left to the caller.
+    fn rule_draw(&self) -> Option<DrawReason> {
+        let halfmoves = self.get_halfmove();
+
+        // 50 moves rule and threefold repetition.
+        if halfmoves >= 50 {
+            return Some(DrawReason::FiftyMoves);
+        } else if halfmoves >= 3 {
+            let repetitions = self.prev_states.iter().rev()
+                .take(usize::from(self.get_halfmove()))
+                .filter(|state| state.zobrist == self.state.zobrist)
+                .count();
+
+            if repetitions >= 3 {
+                return Some(DrawReason::Repetition);
+            }
+        }
+
+        // Draw by insufficient material.
+        if self.variant.has_insufficient_material() {
+            let occ = self.get_occupancy().all();
+            match occ.count() {
+                // King versus King
+                2 => return Some(DrawReason::InsufficientMaterial),
+                3 => {
+                    // King + Knight versus King or King + Bishop versus King.
+                    let other = occ ^ self.get_bitboard(Color::White, Piece::King) ^ self.get_bitboard(Color::Black, Piece::King);
+                    // SAFE: cardinality of other is one
+                    match self.get_piece_unchecked(unsafe {other.as_square_unchecked()}) {
+                        Piece::Knight | Piece::Bishop => return Some(DrawReason::InsufficientMaterial),
+                        _ => (),
+                    }
+                },
+                4 => {
+                    // King + Bishop versus King + Bishop where the bishops have the same parity.
+                    let others = occ ^ self.get_bitboard(Color::White, Piece::King) ^ self.get_bitboard(Color::Black, Piece::King);
+                    // SAFE: cardinality of other is two
+                    let sq1 = unsafe {others.as_square_unchecked()};
+                    let sq2 = unsafe {others.pop_lsb().as_square_unchecked()};
+                    if sq1.parity() == sq2.parity() {
+                        let (color1, piece1) = self.get_piece(sq1).unwrap();
+                        let (color2, piece2) = self.get_piece(sq2).unwrap();
+                        if color1 != color2 && piece1 == Piece::Bishop && piece2 == Piece::Bishop {
+                            return Some(DrawReason::InsufficientMaterial);
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        None
+    }
+
     /// Places a piece of the given color on the given square. If ZOBRIST is true, 
-    /// updates the zobrist key accordingly.
+    /// updates the zobrist key and the material counters accordingly.
     #[inline]
     fn place_piece<const ZOBRIST: bool>(&mut self, color: Color, piece: Piece, sq: Square) {
         self.mailbox[usize::from(sq)] = Some((color, piece));
@@ -804,11 +1374,16 @@ impl Board {
 
         if ZOBRIST {
             self.state.zobrist ^= Zobrist::from((color, piece, sq));
+            self.state.material[usize::from(color)] += piece.material();
+            if piece != Piece::Pawn {
+                self.state.non_pawn_material[usize::from(color)] += piece.material();
+            }
+            self.state.phase += piece.phase();
         }
     }
 
     /// Removes the piece on the given square. If ZOBRIST is true, updates the
-    /// zobrist key accordingly.
+    /// zobrist key and the material counters accordingly.
     #[inline]
     fn remove_piece<const ZOBRIST: bool>(&mut self, sq: Square) -> (Color, Piece) {
         let (color, piece) = self.mailbox[usize::from(sq)].unwrap();
@@ -821,6 +1396,11 @@ impl Board {
 
         if ZOBRIST {
             self.state.zobrist ^= Zobrist::from((color, piece, sq));
+            self.state.material[usize::from(color)] -= piece.material();
+            if piece != Piece::Pawn {
+                self.state.non_pawn_material[usize::from(color)] -= piece.material();
+            }
+            self.state.phase -= piece.phase();
         }
 
         (color, piece)
@@ -869,6 +1449,94 @@ impl Board {
 
         pinned
     }
+
+    /// Returns the board with its squares and colors transformed by the given functions, and the given
+    /// castling rights, built as a board parsed from a fen string is. The fullmove number is kept.
+    fn transformed(&self, square: impl Fn(Square) -> Square, color: impl Fn(Color) -> Color, castle_rights: CastleRights) -> Board {
+        let mut board = Board::default();
+
+        for sq in self.occ.all.iter_squares() {
+            let (c, piece) = self.get_piece(sq).unwrap();
+            board.place_piece::<true>(color(c), piece, square(sq));
+        }
+
+        board.state.side_to_move = color(self.get_side_to_move());
+        board.state.castle_rights = castle_rights;
+        board.state.ep_square = match self.state.ep_square {
+            EnPassantSquare::Some(sq) => EnPassantSquare::Some(square(sq)),
+            EnPassantSquare::None => EnPassantSquare::None,
+        };
+        board.state.halfmove = self.get_halfmove();
+        for c in Color::COLORS {
+            board.state.checks[usize::from(color(c))] = self.get_checks(c);
+        }
+        board.ply = self.get_ply() / 2 * 2 + u16::from(board.state.side_to_move == Color::Black);
+
+        board.variant = self.variant;
+
+        // SAFE: the transformed board has the same kings as that one.
+        board.init_state().unwrap();
+        board
+    }
+
+    /// Checks that a freshly parsed board is valid, and computes the parts of its state
+    /// that are derived from the position of the pieces.
+    fn init_state(&mut self) -> Result<()> {
+        // Check that both sides have only one king
+        for color in Color::COLORS {
+            if !self.get_bitboard(color, Piece::King).is_one() {
+                return Err(Error::InvalidPosition {reason: "each side must have exactly one king"});
+            }
+        }
+
+        // Compute the checkers of the board.
+        self.state.checkers = self.checkers();
+        // Compute the pinned pieces of the board.
+        self.state.pinned = self.pinned();
+
+        // Hash the en passant square, the castling rights and the side to move, as moves do,
+        // so that the zobrist key does not depend on how the position was reached.
+        self.state.zobrist ^= Zobrist::from(self.state.ep_square);
+        self.state.zobrist ^= Zobrist::from(self.state.castle_rights);
+        for color in Color::COLORS {
+            self.state.zobrist ^= Zobrist::checks(color, self.get_checks(color));
+        }
+        if self.state.side_to_move == Color::Black {
+            self.state.zobrist = !self.state.zobrist;
+        }
+
+        // TODO: further checks ?
+
+        Ok(())
+    }
+
+    /// The bitboard of the pieces of the side to move that are the only piece between
+    /// one of its sliders and the opponent's king: moving them may discover a check.
+    #[inline]
+    fn discoverers(&self) -> BitBoard {
+        let us = self.get_side_to_move();
+        let occ_us = self.occ.colored(us);
+        let queens = self.get_bitboard(us, Piece::Queen);
+        let king_sq = self.king_sq(self.get_other_side());
+
+        let mut discoverers = BitBoard::EMPTY;
+
+        for sq in (self.get_bitboard(us, Piece::Rook) | queens).iter_squares() {
+            let between = BitBoard::between_straight(king_sq, sq);
+            if (between & self.occ.all).is_one() {
+                discoverers |= between & occ_us;
+            }
+        }
+
+        for sq in (self.get_bitboard(us, Piece::Bishop) | queens).iter_squares() {
+            let between = BitBoard::between_diagonal(king_sq, sq);
+            if (between & self.occ.all).is_one() {
+                discoverers |= between & occ_us;
+            }
+        }
+
+        discoverers
+    }
 }
 
 // ================================ traits impl
@@ -885,6 +1553,8 @@ impl Default for Board {
         
             state: StateInfo::default(),
             prev_states: Vec::new(),
+
+            variant: Variant::Standard,
         }
     }
 }
@@ -920,14 +1590,22 @@ impl fmt::Display for Board {
             }
         }
 
-        write!(f, " {} {} {} {} {}", 
+        write!(f, " {} {} {}", 
             self.get_side_to_move(),
             self.get_castle_rights(),
-            self.get_ep_square(),
-            self.get_halfmove(),
-            1 + self.get_ply() / 2,
+            EnPassantSquare::from(self.get_ep_target()),
         )?;
 
+        // At three-check, the checks remaining to each side, as in "3+3", also written when they were parsed.
+        if self.variant == Variant::ThreeCheck || self.state.checks != [0, 0] {
+            write!(f, " {}+{}",
+                Variant::CHECKS_TO_WIN - self.get_checks(Color::White),
+                Variant::CHECKS_TO_WIN - self.get_checks(Color::Black),
+            )?;
+        }
+
+        write!(f, " {} {}", self.get_halfmove(), 1 + self.get_ply() / 2)?;
+
         Ok(())
     }
 }
@@ -940,26 +1618,51 @@ impl<'a> FromStr for Board {
         let mut split = s.split(' ');
 
         // Closure to get the next arg, or return an error if there is not.
-        let mut next_arg = || split.next().ok_or_else(|| Error::msg("not enough arguments in fen string"));
+        let mut next_arg = || split.next().ok_or(Error::InvalidFen {reason: "not enough fields"});
 
         // Parse the fen string later.
         let ranks: Vec<_> = next_arg()?.split('/').collect();
         if ranks.len() != 8 {
-            return Err(Error::msg("Invalid number of ranks in fen string."));
+            return Err(Error::InvalidFen {reason: "invalid number of ranks"});
         }
 
         // An empty board.
         let mut board = Board::default();
 
         // Parse the state arguments.
-        board.state.side_to_move = Color::from_str(next_arg()?)?;
-        board.state.castle_rights = CastleRights::from_str(next_arg()?)?;
-        board.state.ep_square = EnPassantSquare::from_str(next_arg()?)?;
-        board.state.halfmove = u8::from_str(next_arg()?)?;
-        board.ply = u16::from_str(next_arg()?)?;
+        board.state.side_to_move = Color::from_str(next_arg()?).map_err(|_| Error::InvalidFen {reason: "invalid side to move"})?;
+        board.state.castle_rights = CastleRights::from_str(next_arg()?).map_err(|_| Error::InvalidFen {reason: "invalid castling rights"})?;
+        // The fen gives the square the pawn went over, the board keeps the square of the pawn itself.
+        let ep_target = EnPassantSquare::from_str(next_arg()?).map_err(|_| Error::InvalidFen {reason: "invalid en passant square"})?;
+        if let EnPassantSquare::Some(target) = ep_target {
+            let rank = match board.state.side_to_move {
+                Color::White => 5,
+                Color::Black => 2,
+            };
+            if target.y() != rank {
+                return Err(Error::InvalidFen {reason: "invalid en passant square"});
+            }
+            board.state.ep_square = EnPassantSquare::from(attacks::pawn_push(board.state.side_to_move.invert(), target));
+        }
+        // At three-check, the checks remaining to each side may follow, as in "3+3".
+        let mut field = next_arg()?;
+        if let Some((white, black)) = field.split_once('+') {
+            for (color, remaining) in [(Color::White, white), (Color::Black, black)] {
+                let remaining = u8::from_str(remaining).ok().filter(|&remaining| remaining <= Variant::CHECKS_TO_WIN)
+                    .ok_or(Error::InvalidFen {reason: "invalid remaining checks"})?;
+                board.state.checks[usize::from(color)] = Variant::CHECKS_TO_WIN - remaining;
+            }
+            field = next_arg()?;
+        }
+        board.state.halfmove = u8::from_str(field).map_err(|_| Error::InvalidFen {reason: "invalid halfmove clock"})?;
+        // The ply is deduced from the fullmove number, which starts at 1 and is incremented after black's move.
+        let fullmove = u16::from_str(next_arg()?).map_err(|_| Error::InvalidFen {reason: "invalid fullmove number"})?;
+        board.ply = fullmove.saturating_sub(1).checked_mul(2)
+            .and_then(|ply| ply.checked_add(u16::from(board.state.side_to_move == Color::Black)))
+            .ok_or(Error::InvalidFen {reason: "invalid fullmove number"})?;
 
         if split.next().is_some() {
-            return Err(Error::msg("Too many arguments in fen string."));
+            return Err(Error::InvalidFen {reason: "too many fields"});
         }
 
         // Parse the fen board.
@@ -969,7 +1672,10 @@ impl<'a> FromStr for Board {
                 match c {
                     '1'..='8' => x += c.to_digit(10).unwrap(),
                     _ => {
-                        let (color, piece) = Piece::from_char(c)?;
+                        let (color, piece) = Piece::from_char(c).map_err(|_| Error::InvalidFen {reason: "invalid piece"})?;
+                        if x >= 8 {
+                            return Err(Error::InvalidFen {reason: "rank too large"});
+                        }
                         let sq = Square::from((x as i8, 7 - y as i8));
                         board.get_bitboard(Color::White, Piece::Pawn);
                         board.place_piece::<true>(color, piece, sq);
@@ -978,29 +1684,472 @@ impl<'a> FromStr for Board {
                 }
                 
                 if x > 8 {
-                    return Err(Error::msg("Rank too large in fen string."));
+                    return Err(Error::InvalidFen {reason: "rank too large"});
                 }
             }
 
             if x != 8 {
-                return Err(Error::msg("Rank too small in fen string."));
+                return Err(Error::InvalidFen {reason: "rank too small"});
             }
         }
 
-        // Check that both sides have only one king
-        for color in Color::COLORS {
-            if !board.get_bitboard(color, Piece::King).is_one() {
-                return Err(Error::msg("Invalid number of kings on the board."));
+        board.init_state()?;
+        Ok(board)
+    }
+}
+//#################################################################################################
+//
+//                                              tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use crate::bitboard::BitBoard;
+    use crate::board::{Board, DrawReason, Status};
+    use crate::color::Color;
+    use crate::error::Error;
+    use crate::movegen;
+    use crate::moves::Move;
+    use crate::piece::Piece;
+    use crate::square::Square;
+    use crate::variant::Variant;
+
+    const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// Positions with castling both ways, en passant and a capturing promotion among their legal moves.
+    const SPECIAL_MOVES_FENS: [&str; 3] = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+
+    /// Plays every sequence of legal moves of the given length from the board, visiting each position
+    /// right after the move leading to it was played, and undoing the moves afterwards.
+    fn walk(board: &mut Board, depth: usize, visit: &mut impl FnMut(&mut Board, Move)) {
+        if depth == 0 {
+            return;
+        }
+
+        let mut legals = Vec::new();
+        movegen::legals(board, &mut legals);
+
+        for mv in legals {
+            board.do_move(mv);
+            visit(board, mv);
+            walk(board, depth - 1, visit);
+            board.undo_move(mv);
+        }
+    }
+
+    #[test]
+    fn mirror() {
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mirrored = board.mirror();
+
+        assert_eq!(mirrored.to_string(), "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1");
+        assert_eq!(mirrored.get_zobrist(), Board::new(&mirrored.to_string()).unwrap().get_zobrist());
+        assert_eq!(mirrored.mirror().to_string(), board.to_string());
+        assert_eq!(movegen::perft(&mut mirrored.clone(), 3), movegen::perft(&mut board.clone(), 3));
+
+        // The en passant square is mirrored too.
+        let board = Board::new("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        assert_eq!(board.mirror().to_string(), "rnbqkbnr/pppp1ppp/8/8/3PpP2/8/PPP1P1PP/RNBQKBNR b KQkq f3 0 3");
+    }
+
+    #[test]
+    fn flip_horizontal() {
+        let board = Board::new("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        let flipped = board.flip_horizontal();
+
+        assert_eq!(flipped.to_string(), "8/5p2/4p3/r5PK/k1p3R1/8/1P1P4/8 w - - 0 1");
+        assert_eq!(flipped.flip_horizontal().to_string(), board.to_string());
+        assert_eq!(movegen::perft(&mut flipped.clone(), 4), movegen::perft(&mut board.clone(), 4));
+
+        // Castling rights are lost.
+        let board = Board::new("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.flip_horizontal().to_string(), "r2k3r/8/8/8/8/8/8/R2K3R w - - 0 1");
+    }
+
+    #[test]
+    fn undo_last() {
+        for fen in SPECIAL_MOVES_FENS {
+            let mut board = Board::new(fen).unwrap();
+            assert_eq!(board.last_move(), None);
+            assert_eq!(board.undo_last(), None);
+
+            walk(&mut board, 1, &mut |board, mv| {
+                assert_eq!(board.last_move(), Some(mv));
+                assert_eq!(board.undo_last(), Some(mv));
+                assert_eq!(board.to_string(), fen);
+                assert_eq!(board.get_zobrist(), Board::new(fen).unwrap().get_zobrist());
+                board.do_move(mv);
+            });
+        }
+
+        // An en passant capture, in a position reached by playing the moves.
+        let mut board = Board::new(DEFAULT_FEN).unwrap();
+        for mv in ["e2e4", "a7a6", "e4e5", "f7f5"] {
+            board.do_move(board.parse_move(mv).unwrap());
+        }
+        let (fen, zobrist) = (board.to_string(), board.get_zobrist());
+
+        let mv = board.parse_move("e5f6").unwrap();
+        assert!(mv.is_en_passant());
+        board.do_move(mv);
+        assert_eq!(board.undo_last(), Some(mv));
+        assert_eq!(board.to_string(), fen);
+        assert_eq!(board.get_zobrist(), zobrist);
+        assert_eq!(board.get_ep_square(), Some(Square::F5));
+    }
+
+    #[test]
+    fn material_counters() {
+        // Returns the material, non-pawn material and phase of the board, counted from its bitboards.
+        fn recount(board: &Board) -> ([u16; 2], [u16; 2], u8) {
+            let count = |color, piece: Piece| u16::from(board.get_bitboard(color, piece).count());
+            let material = |color, pieces: &[Piece]| pieces.iter().map(|&piece| count(color, piece) * piece.material()).sum();
+            let phase: u16 = Color::COLORS.iter().flat_map(|&color| Piece::PIECES.iter().map(move |&piece| count(color, piece) * piece.phase())).sum();
+
+            (
+                [material(Color::White, &Piece::PIECES), material(Color::Black, &Piece::PIECES)],
+                [material(Color::White, &Piece::PIECES[1..]), material(Color::Black, &Piece::PIECES[1..])],
+                phase.min(24) as u8,
+            )
+        }
+
+        let board = Board::new(DEFAULT_FEN).unwrap();
+        assert_eq!((board.material(Color::White), board.non_pawn_material(Color::Black), board.phase()), (39, 31, Board::MAX_PHASE));
+
+        // Captures, castling, en passant and promotions, two plies deep.
+        for fen in SPECIAL_MOVES_FENS {
+            walk(&mut Board::new(fen).unwrap(), 2, &mut |board, _| {
+                assert_eq!((board.state.material, board.state.non_pawn_material, board.phase()), recount(board));
+            });
+        }
+    }
+
+    #[test]
+    fn planes() {
+        let mut board = Board::new(DEFAULT_FEN).unwrap();
+        board.apply_moves(["e2e4 d7d5 e4e5 f7f5"]).unwrap();
+        let planes = board.to_planes();
+        let plane = |i: usize| planes[i * 64..(i + 1) * 64].to_vec();
+        let count = |i: usize| plane(i).iter().filter(|&&x| x == 1).count();
+
+        assert!(planes.iter().all(|&x| x <= 1));
+        assert_eq!((0..12).map(count).collect::<Vec<_>>(), [8, 2, 2, 2, 1, 1, 8, 2, 2, 2, 1, 1]);
+        assert_eq!(plane(0)[usize::from(Square::E5)], 1);
+        assert_eq!(plane(11)[usize::from(Square::E8)], 1);
+
+        // The en passant target, no checkers, and the squares attacked by each side.
+        assert_eq!((count(17), plane(17)[usize::from(Square::F6)]), (1, 1));
+        assert_eq!(count(18), 0);
+        assert_eq!(plane(19)[usize::from(Square::D6)], 1);
+        assert_eq!(plane(19)[usize::from(Square::E6)], 0);
+        assert_eq!(plane(20)[usize::from(Square::E4)], 1);
+        assert_eq!(count(19), board.attacks_by(Color::White).count() as usize);
+
+        // Black to move, with castling rights to the king side for white and to the queen side for black.
+        let planes = Board::new("r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1").unwrap().to_planes();
+        let count = |i: usize| planes[i * 64..(i + 1) * 64].iter().filter(|&&x| x == 1).count();
+        assert_eq!((12..17).map(count).collect::<Vec<_>>(), [0, 64, 0, 0, 64]);
+    }
+
+    #[test]
+    fn apply_moves() {
+        let mut board = Board::new(DEFAULT_FEN).unwrap();
+        board.apply_moves(["e2e4 e7e5", "g1f3"]).unwrap();
+        assert_eq!(board.to_string(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        // On error, the board is left untouched and the index of the failing move is given.
+        let fen = board.to_string();
+        match board.apply_moves(["b8c6  f1c4", "c6c4"]) {
+            Err(Error::InvalidMoveSequence {index, ..}) => assert_eq!(index, 2),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(board.to_string(), fen);
+        assert!(board.apply_moves(["b8c6 f1c"]).is_err());
+        assert_eq!(board.to_string(), fen);
+    }
+
+    #[test]
+    fn san() {
+        let board = Board::new("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.parse_san("O-O").unwrap(), board.parse_move("e1g1").unwrap());
+        assert_eq!(board.parse_san("bxa8=Q+").unwrap(), board.parse_move("b7a8q").unwrap());
+        assert_eq!(board.parse_san("b8N").unwrap(), board.parse_move("b7b8n").unwrap());
+        assert_eq!(board.parse_san("Rad1").unwrap(), board.parse_move("a1d1").unwrap());
+        assert!(matches!(board.parse_san("Rd2"), Err(Error::IllegalMove)));
+        assert_eq!(board.to_san(board.parse_move("b7a8q").unwrap()), "bxa8=Q+");
+        assert_eq!(board.to_san(board.parse_move("e1c1").unwrap()), "O-O-O");
+
+        let board = Board::new("6k1/5ppp/8/8/8/8/8/R3R1K1 w - - 0 1").unwrap();
+        assert_eq!(board.to_san(board.parse_move("a1a8").unwrap()), "Ra8#");
+        assert_eq!(board.to_san(board.parse_move("e1e2").unwrap()), "Re2");
+        assert_eq!(board.to_san(board.parse_move("a1d1").unwrap()), "Rad1");
+    }
+
+    #[test]
+    fn has_legal_move() {
+        let fens = [
+            DEFAULT_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            // Single and double check.
+            "7k/8/8/8/8/8/5PPq/6K1 w - - 0 1",
+            "4k3/8/8/8/1b6/8/8/r3K1N1 w - - 0 1",
+            // Stalemate and checkmate.
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            "6Qk/8/6K1/8/8/8/8/8 b - - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::new(fen).unwrap();
+            let mut legals = Vec::new();
+            movegen::legals(&board, &mut legals);
+
+            assert_eq!(board.legals_count(), legals.len(), "{}", fen);
+            assert_eq!(board.has_legal_move(), !legals.is_empty(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn pseudo_legality() {
+        let board = Board::new("4k3/P7/8/8/8/4n3/4P1P1/4K3 w - - 0 1").unwrap();
+
+        // Pawns may not jump over a piece, nor reach the last rank without promoting.
+        assert!(!board.is_pseudo_legal(Move::double_push(Square::E2, Square::E4)));
+        assert!(board.is_pseudo_legal(Move::double_push(Square::G2, Square::G4)));
+        assert!(!board.is_pseudo_legal(Move::quiet(Square::A7, Square::A8)));
+        assert!(board.is_pseudo_legal(Move::promote(Square::A7, Square::A8, Piece::Queen)));
+        assert!(!board.is_pseudo_legal(Move::promote(Square::G2, Square::G3, Piece::Queen)));
+
+        let mut legals = Vec::new();
+        movegen::legals(&board, &mut legals);
+        assert!(legals.iter().all(|&mv| board.is_pseudo_legal(mv)));
+    }
+
+    #[test]
+    fn draw_reasons() {
+        let cases = [
+            (DEFAULT_FEN, Status::Playing, None),
+            ("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", Status::Win(Color::White), None),
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Status::Draw, Some(DrawReason::Stalemate)),
+            ("4k3/8/8/8/8/8/4R3/4K3 w - - 100 80", Status::Draw, Some(DrawReason::FiftyMoves)),
+            ("4k3/8/8/8/8/8/8/4KB2 w - - 0 1", Status::Draw, Some(DrawReason::InsufficientMaterial)),
+        ];
+
+        for (fen, status, reason) in cases {
+            let board = Board::new(fen).unwrap();
+            assert_eq!(board.status(), status, "{}", fen);
+            assert_eq!(board.draw_reason(), reason, "{}", fen);
+        }
+
+        let mut board = Board::new(DEFAULT_FEN).unwrap();
+        board.apply_moves(["g1f3 g8f6 f3g1 f6g8"; 3]).unwrap();
+        assert_eq!(board.status(), Status::Draw);
+        assert_eq!(board.draw_reason(), Some(DrawReason::Repetition));
+    }
+
+    #[test]
+    fn zobrist_is_stable() {
+        // Zobrist keys are persisted, they must not change from one build to another.
+        let board = Board::new(DEFAULT_FEN).unwrap();
+        assert_eq!(board.get_zobrist().get_raw(), 0xa5130234e9133934);
+        assert_eq!(board.get_polyglot_key(), 0x463b96181691fc9c);
+
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.get_zobrist().get_raw(), 0xb3363984c18724e3);
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let fen = "4k3/8/8/8/8/4K3/8/8 w - - 0 1";
+        assert_eq!(Board::new(fen).unwrap().status(), Status::Draw);
+
+        // Lone kings may still win by reaching the hill, after which no move is legal.
+        let mut board = Board::with_variant(fen, Variant::KingOfTheHill).unwrap();
+        assert_eq!(board.status(), Status::Playing);
+
+        board.do_move(board.parse_move("e3e4").unwrap());
+        assert_eq!(board.status(), Status::Win(Color::White));
+        assert!(!board.has_legal_move());
+        assert_eq!(board.mirror().get_variant(), Variant::KingOfTheHill);
+    }
+
+    #[test]
+    fn three_check() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1";
+        let mut board = Board::with_variant(fen, Variant::ThreeCheck).unwrap();
+        assert_eq!(board.to_string(), fen);
+        assert_eq!(board.get_checks(Color::White), 2);
+
+        let mv = board.parse_move("a1a8").unwrap();
+        board.do_move(mv);
+        assert_eq!(board.to_string(), "R3k3/8/8/8/8/8/8/4K3 b - - 0+3 1 1");
+        assert_eq!(board.get_zobrist(), Board::with_variant(&board.to_string(), Variant::ThreeCheck).unwrap().get_zobrist());
+        assert_eq!(board.status(), Status::Win(Color::White));
+
+        board.undo_move(mv);
+        assert_eq!(board.get_checks(Color::White), 2);
+
+        // Once the game is won, no move is accepted, and a fourth check is not counted.
+        let mut board = Board::with_variant("4k3/8/8/8/8/8/8/R3K3 w - - 0+3 0 1", Variant::ThreeCheck).unwrap();
+        assert_eq!(board.status(), Status::Win(Color::White));
+        assert!(matches!(board.parse_move("a1a8"), Err(Error::GameOver)));
+        board.do_move(mv);
+        assert_eq!(board.get_checks(Color::White), 3);
+
+        // Checks are not counted at standard chess.
+        let mut board = Board::new("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        board.do_move(mv);
+        assert_eq!(board.get_checks(Color::White), 0);
+    }
+
+    #[test]
+    fn castle_rights_and_en_passant() {
+        let board = Board::new("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        let rights = board.get_castle_rights();
+        assert!(rights.kingside(Color::White) && !rights.queenside(Color::White));
+        assert!(!rights.kingside(Color::Black) && rights.queenside(Color::Black));
+        assert_eq!(board.get_ep_square(), None);
+
+        let mut board = Board::new(DEFAULT_FEN).unwrap();
+        board.do_move(board.parse_move("e2e4").unwrap());
+        assert_eq!(board.get_ep_square(), Some(Square::E4));
+        assert_eq!(board.get_ep_target(), Some(Square::E3));
+    }
+
+    #[test]
+    fn fen_en_passant() {
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let board = Board::new(fen).unwrap();
+        assert_eq!(board.get_ep_square(), Some(Square::F5));
+        assert_eq!(board.get_ep_target(), Some(Square::F6));
+        assert_eq!(board.to_string(), fen);
+
+        let mut legals = Vec::new();
+        movegen::legals(&board, &mut legals);
+        assert!(legals.contains(&board.parse_move("e5f6").unwrap()));
+        assert!(legals.iter().any(|mv| mv.is_en_passant() && mv.to() == Square::F6));
+
+        // The same position, reached by playing the moves.
+        let mut played = Board::new(DEFAULT_FEN).unwrap();
+        for mv in ["e2e4", "d7d5", "e4e5", "f7f5"] {
+            played.do_move(played.parse_move(mv).unwrap());
+        }
+        assert_eq!(played.to_string(), fen);
+        assert_eq!(played.get_zobrist(), board.get_zobrist());
+
+        let board = Board::new("rnbqkbnr/pppp1ppp/8/8/3PpP2/8/PPP1P1PP/RNBQKBNR b KQkq f3 0 3").unwrap();
+        assert_eq!(board.get_ep_square(), Some(Square::F4));
+
+        // The target must be on the third or sixth rank, depending on the side to move.
+        assert!(Board::new("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f5 0 3").is_err());
+        assert!(Board::new("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f3 0 3").is_err());
+    }
+
+    #[test]
+    fn gives_check() {
+        // Checks that each legal move gives check exactly when the opponent is in check once it is played.
+        fn check_all(board: &mut Board) {
+            let mut legals = Vec::new();
+            movegen::legals(board, &mut legals);
+
+            for mv in legals {
+                let gives_check = board.gives_check(mv);
+                board.do_move(mv);
+                assert_eq!(gives_check, board.get_checkers().not_empty(), "{} after {}", board, mv);
+                board.undo_move(mv);
             }
         }
 
-        // Compute the checkers of the board.
-        board.state.checkers = board.checkers();
-        // Compute the pinned pieces of the board.
-        board.state.pinned = board.pinned();
+        let fens = [
+            DEFAULT_FEN,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            // Discovered checks by a knight, a king and an en passant capture.
+            "4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1",
+            "7k/8/8/8/8/2K5/8/B7 w - - 0 1",
+            "8/8/8/1k1pP2R/8/8/8/4K3 w - d6 0 1",
+            // Checks by castling and by promoting.
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "3k4/1P6/8/8/8/8/8/4K3 w - - 0 1",
+        ];
 
-        // TODO: further checks ?
- 
-        Ok(board)
+        for fen in SPECIAL_MOVES_FENS.iter().chain(&fens) {
+            let mut board = Board::new(fen).unwrap();
+            check_all(&mut board);
+            walk(&mut board, 1, &mut |board, _| check_all(board));
+        }
+
+        let board = Board::new("8/8/8/1k1pP2R/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert!(board.gives_check(board.parse_move("e5d6").unwrap()));
+        assert!(!board.gives_check(board.parse_move("e5e6").unwrap()));
+        let board = Board::new("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(board.gives_check(board.parse_move("e1g1").unwrap()));
+        let board = Board::new("3k4/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(board.gives_check(board.parse_move("b7b8q").unwrap()));
+        assert!(!board.gives_check(board.parse_move("b7b8n").unwrap()));
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let fens = [
+            DEFAULT_FEN,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/pppp1ppp/8/8/3PpP2/8/PPP1P1PP/RNBQKBNR b KQkq f3 0 3",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 37 80",
+        ];
+
+        for fen in SPECIAL_MOVES_FENS.iter().chain(&fens) {
+            let board = Board::new(fen).unwrap();
+            let decoded = Board::from_bytes(&board.to_bytes()).unwrap();
+            assert_eq!(decoded.to_string(), *fen);
+            assert_eq!(decoded.get_zobrist(), board.get_zobrist());
+            assert_eq!(decoded.to_bytes(), board.to_bytes());
+        }
+
+        // Wrong lengths and encodings that are not canonical are rejected.
+        let bytes = Board::new(DEFAULT_FEN).unwrap().to_bytes();
+        assert!(Board::from_bytes(&bytes[1..]).is_err());
+        let mut padded = bytes;
+        padded[Board::ENCODED_SIZE - 1] = 1;
+        assert!(Board::from_bytes(&padded).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn attack_queries() {
+        let squares = |squares: &[Square]| squares.iter().fold(BitBoard::EMPTY, |bb, &sq| bb | BitBoard::from(sq));
+        let board = Board::new("4k3/8/2n5/3p4/4P3/8/1B6/4K2R w K - 0 1").unwrap();
+
+        // Pawns attack diagonally, sliders stop on the first piece they meet, of either color.
+        assert_eq!(board.attacks_from(Square::E4), squares(&[Square::D5, Square::F5]));
+        assert_eq!(board.attacks_from(Square::D5), squares(&[Square::C4, Square::E4]));
+        assert_eq!(board.attacks_from(Square::B2), squares(&[
+            Square::A1, Square::C1, Square::A3, Square::C3, Square::D4, Square::E5, Square::F6, Square::G7, Square::H8,
+        ]));
+        assert_eq!(board.attacks_from(Square::H1), squares(&[
+            Square::E1, Square::F1, Square::G1, Square::H2, Square::H3, Square::H4, Square::H5, Square::H6, Square::H7, Square::H8,
+        ]));
+        assert_eq!(board.attacks_from(Square::C6), squares(&[
+            Square::A5, Square::A7, Square::B4, Square::B8, Square::D4, Square::D8, Square::E5, Square::E7,
+        ]));
+        assert_eq!(board.attacks_from(Square::E1), squares(&[Square::D1, Square::D2, Square::E2, Square::F2, Square::F1]));
+        assert_eq!(board.attacks_from(Square::A4), BitBoard::EMPTY);
+
+        // The attackers of both colors are given.
+        assert_eq!(board.attackers_of(Square::E5), squares(&[Square::B2, Square::C6]));
+        assert_eq!(board.attackers_of(Square::D5), squares(&[Square::E4]));
+        assert_eq!(board.attackers_of(Square::E4), squares(&[Square::D5]));
+        assert_eq!(board.attackers_of(Square::F1), squares(&[Square::E1, Square::H1]));
+        assert_eq!(board.attackers_of(Square::D8), squares(&[Square::C6, Square::E8]));
+        assert_eq!(board.attackers_of(Square::A6), BitBoard::EMPTY);
+
+        assert!(board.is_attacked(Square::F1, Color::White));
+        assert!(!board.is_attacked(Square::F1, Color::Black));
+        assert!(board.is_attacked(Square::D4, Color::White) && board.is_attacked(Square::D4, Color::Black));
+        assert!(!board.is_attacked(Square::A3, Color::Black));
+        assert!(!board.is_attacked(Square::H8, Color::Black));
+    }
+}
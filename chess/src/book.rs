@@ -1,18 +1,26 @@
-use std::convert::TryInto;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+#[cfg(feature = "std")]
 use std::fs;
+#[cfg(feature = "std")]
 use std::path::Path;
 
-use anyhow::{Error, Result};
+use crate::error::{Error, Result};
 
 use crate::attacks;
 use crate::board::Board;
+use crate::books;
 use crate::color::Color;
 use crate::castle_rights::CastleMask;
-use crate::en_passant::EnPassantSquare;
 use crate::moves::Move;
 use crate::piece::Piece;
 use crate::square::Square;
 
+/// The position the lines of Book::from_lines() start from.
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 /// The zobrist keys used by polyglot. They are used in a bit of a strange way,
 /// and are thus kept separate of the standard zobrist hashing of the main Rush
 /// engine. Plus I really didn't want to change the way hashing was done.
@@ -217,7 +225,7 @@ const POLYGLOT_HASHING: [u64; 781] = [
 
 /// Compute the polyglot zobrist hash of the given position.
 #[inline]
-fn polyglot_hash(board: &Board) -> u64 {
+pub(crate) fn polyglot_hash(board: &Board) -> u64 {
     let mut hash = 0;
 
     // Pieces.
@@ -250,7 +258,7 @@ fn polyglot_hash(board: &Board) -> u64 {
     // En passant square.
     // Polyglot only hashes the en passant square if an en passant capture is possible.
     // That is quite awful.
-    if let EnPassantSquare::Some(sq) = board.get_ep_square() {
+    if let Some(sq) = board.get_ep_target() {
         let us = board.get_side_to_move();
         let them = board.get_other_side();
         
@@ -262,6 +270,21 @@ fn polyglot_hash(board: &Board) -> u64 {
     hash
 }
 
+/// Encodes a move the way polyglot does: castling is encoded as the king taking its own rook.
+#[inline]
+fn polyglot_move(mv: Move) -> u16 {
+    let to = match mv.to() {
+        Square::C1 if mv.is_castle() => Square::A1,
+        Square::G1 if mv.is_castle() => Square::H1,
+        Square::C8 if mv.is_castle() => Square::A8,
+        Square::G8 if mv.is_castle() => Square::H8,
+        to => to,
+    };
+    let promote = mv.promotion().map_or(0, |piece| piece as u16);
+
+    to as u16 | (mv.from() as u16) << 6 | promote << 12
+}
+
 //#################################################################################################
 //
 //                                         struct BookEntry
@@ -276,6 +299,9 @@ struct BookEntry {
     from: Square,
     to: Square,
     maybe_promote: Option<Piece>,
+    // The priority of the book the entry comes from, and its index among the merged books.
+    priority: u8,
+    source: u8,
 }
 
 // ================================ impl
@@ -287,7 +313,7 @@ impl BookEntry {
         let mut to = self.to;
 
         if matches!(self.from, Square::E1 | Square::E8) {
-            let (_, piece) = board.get_piece(self.from).ok_or(Error::msg("Empty from square"))?;
+            let (_, piece) = board.get_piece(self.from).ok_or(Error::InvalidBook {reason: "an entry moves from an empty square"})?;
             if piece == Piece::King {
                 match to {
                     Square::A1 => to = Square::C1,
@@ -303,6 +329,120 @@ impl BookEntry {
     }
 }
 
+//#################################################################################################
+//
+//                                         struct BookLayer
+//
+//#################################################################################################
+
+/// How the entries of a book are merged with those of other books, see Book::merge().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookLayer {
+    /// In a position, only the moves of the books of the highest priority having any are played, those
+    /// of the other books are ignored: a narrow repertoire book is given a higher priority than a broad one.
+    pub priority: u8,
+    /// The factor the weights of the entries of the book are multiplied by, to balance the books of the same priority.
+    pub scale: f32,
+}
+
+// ================================ traits impl
+
+impl Default for BookLayer {
+    /// The lowest priority, with the weights of the book left untouched.
+    fn default() -> BookLayer {
+        BookLayer {priority: 0, scale: 1.0}
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct BookCandidate
+//
+//#################################################################################################
+
+/// A move found in a position by the books of a merged book, the entries of all the books having it being
+/// aggregated. See Book::probe_all().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookCandidate {
+    /// The move.
+    pub mv: Move,
+    /// The sum of the scaled weights of the entries of the move.
+    pub weight: u32,
+    /// The highest priority of the books having the move.
+    pub priority: u8,
+    /// The number of books having the move.
+    pub books: u8,
+}
+
+//#################################################################################################
+//
+//                                         enum BookPolicy
+//
+//#################################################################################################
+
+/// The way a move is chosen among the entries of a book matching a position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookPolicy {
+    /// Always plays the entry with the highest weight, the first one in case of a tie.
+    BestWeight,
+    /// Picks an entry with a probability proportional to its weight raised to the power of 1 / temperature.
+    /// A temperature of 1 follows the weights of the book, lower ones favor the heaviest entries and
+    /// higher ones make all entries more equally likely. Entries of null weight are never picked,
+    /// unless all of them are null. This is the default, with a temperature of 1.
+    Weighted {temperature: f32},
+    /// Picks any of the entries with the same probability, whatever their weights.
+    Uniform,
+}
+
+// ================================ traits impl
+
+impl Default for BookPolicy {
+    /// Follows the weights of the book.
+    fn default() -> BookPolicy {
+        BookPolicy::Weighted {temperature: 1.0}
+    }
+}
+
+/// Raises a positive base to the given power. Without the standard library, it is approximated
+/// closely enough to weigh the entries of a book.
+#[inline]
+fn powf(base: f32, exp: f32) -> f32 {
+    #[cfg(feature = "std")]
+    return base.powf(exp);
+
+    #[cfg(not(feature = "std"))]
+    return approx_powf(base, exp);
+}
+
+/// Approximates a positive base raised to the given power, without the standard library.
+#[cfg(any(test, not(feature = "std")))]
+fn approx_powf(base: f32, exp: f32) -> f32 {
+    // The base is split into 2^k * m with m in [sqrt(2) / 2, sqrt(2)), and ln(m) = 2 * atanh((m - 1) / (m + 1)).
+    let bits = (base as f64).to_bits();
+    let mut k = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut m = f64::from_bits((bits & !(0x7ff << 52)) | (1023 << 52));
+    if m >= core::f64::consts::SQRT_2 {
+        m /= 2.0;
+        k += 1;
+    }
+    let t = (m - 1.0) / (m + 1.0);
+    let t2 = t * t;
+    let ln = k as f64 * core::f64::consts::LN_2 + 2.0 * t * (1.0 + t2 * (1.0 / 3.0 + t2 * (1.0 / 5.0 + t2 * (1.0 / 7.0 + t2 / 9.0))));
+
+    // Then e^x = 2^n * e^r with |r| <= ln(2) / 2, e^r being summed as a series.
+    let x = (ln * exp as f64).clamp(-700.0, 700.0);
+    let n = (x / core::f64::consts::LN_2 + 0.5) as i64 - i64::from(x < -core::f64::consts::LN_2 / 2.0);
+    let r = x - n as f64 * core::f64::consts::LN_2;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for i in 1..12 {
+        term *= r / i as f64;
+        sum += term;
+    }
+
+    (sum * f64::from_bits(((n + 1023) as u64) << 52)) as f32
+}
+
 //#################################################################################################
 //
 //                                           struct Book
@@ -324,17 +464,22 @@ impl Book {
     /// then convert the format into a more easily readable one.
     /// Preferably done at startup.
     #[cold]
+    #[cfg(feature = "std")]
     pub fn open(path: &Path) -> Result<Book> {
-        let bytes = fs::read(path)?;
+        Book::from_bytes(&fs::read(path)?)
+    }
 
-        if bytes.len() % 16 != 0 {
-            return Err(Error::msg("File has invalid size."))
+    /// Tries to read a new book from the bytes of a polyglot file.
+    #[cold]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book> {
+        if !bytes.len().is_multiple_of(16) {
+            return Err(Error::InvalidBook {reason: "its size is not a multiple of 16 bytes"})
         }
 
-        let entries = bytes.as_slice().chunks(16).map(|chunk| {
+        let entries = bytes.chunks(16).map(|chunk| {
             let mv = u16::from_be_bytes(chunk[8..10].try_into().unwrap());
 
-            BookEntry {
+            Ok(BookEntry {
                 key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
                 weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
                 from: Square::SQUARES[usize::from(mv.wrapping_shr(6) & 0x3f)],
@@ -345,36 +490,240 @@ impl Book {
                     2 => Some(Piece::Bishop),
                     3 => Some(Piece::Rook),
                     4 => Some(Piece::Queen),
-                    _ => panic!("Unexpected promotion code."),
+                    _ => return Err(Error::InvalidBook {reason: "an entry has an unknown promotion code"}),
                 },
-            }
-        }).collect();
+                priority: 0,
+                source: 0,
+            })
+        }).collect::<Result<_>>()?;
 
         Ok(Book {entries})
     }
 
-    /// Probes the book and returns the list of matches, or none if there is none.
+    /// Builds a book from opening lines, one per line of the text, each being a sequence of moves in standard
+    /// algebraic notation from the starting position. Move numbers are skipped, as well as empty lines and
+    /// lines starting with '#'. The weight of a move in a position is the number of lines playing it there.
+    #[cold]
+    pub fn from_lines(text: &str) -> Result<Book> {
+        let start = Board::new(STARTING_FEN)?;
+        // The weights of the entries, by key and polyglot move.
+        let mut weights: BTreeMap<(u64, u16), u16> = BTreeMap::new();
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut board = start.clone();
+
+            for san in line.split_whitespace().filter(|token| !token.ends_with('.')) {
+                let mv = board.parse_san(san).map_err(|_| Error::InvalidBook {reason: "a line has an illegal move"})?;
+                let weight = weights.entry((polyglot_hash(&board), polyglot_move(mv))).or_insert(0);
+                *weight = weight.saturating_add(1);
+                board.do_move(mv);
+            }
+        }
+
+        // The heaviest moves of a position come first, as in polyglot files.
+        let mut weights: Vec<_> = weights.into_iter().collect();
+        weights.sort_by_key(|&((key, mv), weight)| (key, u16::MAX - weight, mv));
+
+        let mut bytes = Vec::with_capacity(16 * weights.len());
+        for ((key, mv), weight) in weights {
+            bytes.extend_from_slice(&key.to_be_bytes());
+            bytes.extend_from_slice(&mv.to_be_bytes());
+            bytes.extend_from_slice(&weight.to_be_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+        }
+
+        Book::from_bytes(&bytes)
+    }
+
+    /// Returns the bytes of the book in polyglot's format, the learn fields being left at zero.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 * self.entries.len());
+
+        for entry in self.entries.iter() {
+            let promote = entry.maybe_promote.map_or(0, |piece| piece as u16);
+            let mv = entry.to as u16 | (entry.from as u16) << 6 | promote << 12;
+
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&mv.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+        }
+
+        bytes
+    }
+
+    /// Returns the opening book compiled into the library, see chess::books.
+    #[cold]
+    pub fn builtin() -> Book {
+        // SAFE: the built-in book is a valid polyglot file.
+        Book::from_bytes(books::BUILTIN).unwrap()
+    }
+
+    /// Merges several books into one, the entries of each being weighted and prioritized following its layer,
+    /// so that a broad general book may be combined with a narrow repertoire book. The books are merged in
+    /// the given order, which is that of the moves of a position when probing. The priorities and the
+    /// scaling of books that were already merged are replaced.
+    #[cold]
+    pub fn merge(books: impl IntoIterator<Item = (Book, BookLayer)>) -> Book {
+        let mut entries = Vec::new();
+
+        for (source, (book, layer)) in books.into_iter().enumerate() {
+            entries.extend(book.entries.into_vec().into_iter().map(|entry| BookEntry {
+                weight: (f32::from(entry.weight) * layer.scale.max(0.0) + 0.5).min(f32::from(u16::MAX)) as u16,
+                priority: layer.priority,
+                source: source.try_into().unwrap_or(u8::MAX),
+                ..entry
+            }));
+        }
+
+        // The sort is stable: the entries of a position stay in the order of their books.
+        entries.sort_by_key(|entry| entry.key);
+        Book {entries: entries.into_boxed_slice()}
+    }
+
+    /// Opens the books at the given paths and merges them, each following its layer. See Book::merge().
+    #[cold]
+    #[cfg(feature = "std")]
+    pub fn open_merged(books: &[(&Path, BookLayer)]) -> Result<Book> {
+        let books = books.iter().map(|&(path, layer)| Ok((Book::open(path)?, layer))).collect::<Result<Vec<_>>>()?;
+        Ok(Book::merge(books))
+    }
+
+    /// Probes the book and returns the list of matches, or none if there is none. When books were merged,
+    /// only the moves of the books of the highest priority having any are given, with the weights of the
+    /// entries of a move summed.
     /// Not the most efficient method, should not be used during search for example.
     pub fn probe(&self, board: &Board) -> Box<[(Move, u16)]> {
-        let hash = polyglot_hash(board);
+        let entries = self.matches(board);
+        let priority = entries.iter().map(|&(_, entry)| entry.priority).max();
+
+        let entries = entries.into_iter().filter(|&(_, entry)| Some(entry.priority) == priority);
+        Book::aggregate(entries).into_iter().map(|candidate| {
+            (candidate.mv, candidate.weight.min(u32::from(u16::MAX)) as u16)
+        }).collect()
+    }
+
+    /// Probes the book and returns the moves found by all the books that were merged into it, whatever their
+    /// priorities, with the entries of each move aggregated. The moves are in the order of their first entries.
+    pub fn probe_all(&self, board: &Board) -> Box<[BookCandidate]> {
+        Book::aggregate(self.matches(board)).into_boxed_slice()
+    }
+
+    /// Probes the book and picks one of the matches, each with a probability proportional to its weight,
+    /// from the given random number. Returns none if there is no match.
+    pub fn choose(&self, board: &Board, rand: u32) -> Option<Move> {
+        self.choose_with(board, BookPolicy::default(), rand)
+    }
+
+    /// Probes the book and picks one of the matches following the given policy, from the given
+    /// random number. Returns none if there is no match.
+    pub fn choose_with(&self, board: &Board, policy: BookPolicy, rand: u32) -> Option<Move> {
+        let results = self.probe(board);
+        let total_weight: u32 = results.iter().map(|&(_, weight)| u32::from(weight)).sum();
+
+        // When all weights are null, the moves are equally likely.
+        if total_weight == 0 || policy == BookPolicy::Uniform {
+            return results.get(rand as usize % results.len().max(1)).map(|&(mv, _)| mv);
+        }
+
+        match policy {
+            BookPolicy::BestWeight => {
+                // The first of the heaviest entries.
+                results.iter().rev().max_by_key(|&&(_, weight)| weight).map(|&(mv, _)| mv)
+            },
+            BookPolicy::Weighted {temperature: 1.0} => {
+                let mut rand = rand % total_weight;
+                for &(mv, weight) in results.iter() {
+                    if rand < u32::from(weight) {
+                        return Some(mv);
+                    }
+                    rand -= u32::from(weight);
+                }
+
+                unreachable!()
+            },
+            BookPolicy::Weighted {temperature} => {
+                // Weights are normalized by the heaviest one, so that the powers stay in [0, 1].
+                let max_weight = f32::from(results.iter().map(|&(_, weight)| weight).max().unwrap());
+                let exp = 1.0 / temperature.max(0.01);
+                let scaled: Box<[f32]> = results.iter().map(|&(_, weight)| {
+                    if weight == 0 {0.0} else {powf(f32::from(weight) / max_weight, exp)}
+                }).collect();
+
+                let mut rand = rand as f32 / u32::MAX as f32 * scaled.iter().sum::<f32>();
+                for (&(mv, _), &scaled) in results.iter().zip(scaled.iter()) {
+                    if rand < scaled {
+                        return Some(mv);
+                    }
+                    rand -= scaled;
+                }
 
-        let mut start = 0;
-        let mut end = 0;
+                // Rounding errors may leave some of the random number, the last move with a weight is kept.
+                results.iter().rev().find(|&&(_, weight)| weight != 0).map(|&(mv, _)| mv)
+            },
+            BookPolicy::Uniform => unreachable!(),
+        }
+    }
+
+    /// Lowers the weight of the entries of the given move in the given position by the given penalty,
+    /// without going below zero, so that it is chosen less often. This is book learning: the moves that
+    /// led to lost games are penalized. Returns false if the book has no entry for that move.
+    pub fn learn(&mut self, board: &Board, mv: Move, penalty: u16) -> bool {
+        let hash = polyglot_hash(board);
 
-        // Get all matches.
-        if let Some(idx) = self.entries.binary_search_by_key(&hash, |entry| entry.key).ok() {
-            start = idx;
-            while start != 0 && self.entries[start - 1].key == hash {
-                start -= 1;
+        let start = self.entries.partition_point(|entry| entry.key < hash);
+        let mut found = false;
+        for entry in self.entries[start..].iter_mut().take_while(|entry| entry.key == hash) {
+            if entry.mv(board).ok() == Some(mv) {
+                entry.weight = entry.weight.saturating_sub(penalty);
+                found = true;
             }
-    
-            end = idx;
-            while end != self.entries.len() && self.entries[end].key == hash {
-                end += 1;
+        }
+
+        found
+    }
+}
+
+// ================================ impl
+
+impl Book {
+    /// Returns the entries of the given position, with their moves, skipping the invalid ones.
+    fn matches(&self, board: &Board) -> Vec<(Move, &BookEntry)> {
+        let hash = polyglot_hash(board);
+        let start = self.entries.partition_point(|entry| entry.key < hash);
+
+        self.entries[start..].iter()
+            .take_while(|entry| entry.key == hash)
+            .filter_map(|entry| entry.mv(board).ok().map(|mv| (mv, entry)))
+            .collect()
+    }
+
+    /// Aggregates the given entries by move, in the order of their first entries. The entries of
+    /// a position are in the order of their books, so the entries of a book for a move follow each other.
+    fn aggregate<'a>(entries: impl IntoIterator<Item = (Move, &'a BookEntry)>) -> Vec<BookCandidate> {
+        let mut candidates: Vec<BookCandidate> = Vec::new();
+        // The last book each candidate was found in.
+        let mut sources: Vec<u8> = Vec::new();
+
+        for (mv, entry) in entries {
+            match candidates.iter().position(|candidate| candidate.mv == mv) {
+                Some(i) => {
+                    let candidate = &mut candidates[i];
+                    candidate.weight += u32::from(entry.weight);
+                    candidate.priority = candidate.priority.max(entry.priority);
+                    if sources[i] != entry.source {
+                        candidate.books += 1;
+                        sources[i] = entry.source;
+                    }
+                },
+                None => {
+                    candidates.push(BookCandidate {mv, weight: u32::from(entry.weight), priority: entry.priority, books: 1});
+                    sources.push(entry.source);
+                },
             }
         }
 
-        (&self.entries[start..end]).iter().filter_map(|entry| entry.mv(board).ok().map(|mv| (mv, entry.weight))).collect()
+        candidates
     }
 }
 
@@ -387,6 +736,10 @@ impl Book {
 #[cfg(test)]
 mod tests {
     use crate::board::Board;
+    use crate::error::Error;
+    use crate::square::Square;
+
+    use super::BookPolicy;
 
     const FEN_HASHES: &[(&str, u64)] = &[
         ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
@@ -402,10 +755,139 @@ mod tests {
 
     #[test]
     fn polyglot_hash() {
-        crate::init();
-
         for &(fen, hash) in FEN_HASHES {
             assert_eq!(super::polyglot_hash(&Board::new(fen).unwrap()), hash, "mismatch on fen: {:?}", fen);
         }
     }
+
+    #[test]
+    fn builtin_book() {
+        let book = super::Book::builtin();
+
+        let board = Board::new(FEN_HASHES[0].0).unwrap();
+        assert!(book.probe(&board).len() > 1);
+        assert!(book.choose(&board, 0).is_some());
+
+        // Castling is encoded as the king taking its own rook in polyglot books.
+        let board = Board::new("r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 2 5").unwrap();
+        assert!(book.probe(&board).iter().any(|(mv, _)| mv.to_string() == "e1g1"));
+    }
+
+    #[test]
+    fn builtin_lines() {
+        // The built-in book is up to date with its lines.
+        let book = super::Book::from_lines(include_str!("../books/builtin.txt")).unwrap();
+        assert!(book.to_bytes() == crate::books::BUILTIN, "the built-in book must be rebuilt with book-gen");
+        assert_eq!(super::Book::from_bytes(&book.to_bytes()).unwrap().to_bytes(), book.to_bytes());
+
+        // Castling is encoded as the king taking its own rook, and the weight counts the lines.
+        let book = super::Book::from_lines("# Two lines.\n1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O\n\n1. e4 c5").unwrap();
+        let board = Board::new(FEN_HASHES[0].0).unwrap();
+        assert_eq!(book.probe(&board).as_ref(), [(board.parse_move("e2e4").unwrap(), 2)]);
+        assert!(book.to_bytes().chunks(16).any(|entry| entry[8..10] == (Square::H1 as u16 | (Square::E1 as u16) << 6).to_be_bytes()));
+        assert!(super::Book::from_lines("1. e4 e4").is_err());
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        assert!(matches!(super::Book::from_bytes(&[0; 17]), Err(Error::InvalidBook {..})));
+
+        // The promotion code of the move of an entry is one of its 4 highest bits.
+        let mut bytes = [0; 16];
+        bytes[8..10].copy_from_slice(&(5 << 12 | 11 << 6 | 27u16).to_be_bytes());
+        assert!(matches!(super::Book::from_bytes(&bytes), Err(Error::InvalidBook {reason: "an entry has an unknown promotion code"})));
+        bytes[8] = 0x40;
+        assert!(super::Book::from_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn approx_powf() {
+        // The weights relative to the heaviest one, raised to 1 / temperature for temperatures from 100 down to 0.01.
+        for exp in [0.0, 0.01, 0.5, 1.0, 1.5, 2.0, 10.0, 100.0] {
+            for base in [1.0 / 65535.0, 0.001, 0.1, 0.25, 0.5, 0.7, 0.75, 0.999, 1.0] {
+                let (approx, exact) = (super::approx_powf(base, exp), f32::powf(base, exp));
+                assert!((approx - exact).abs() <= 1e-6 * exact, "{}^{}: {} instead of {}", base, exp, approx, exact);
+            }
+        }
+
+        assert_eq!(super::approx_powf(0.3, 0.0), 1.0);
+        assert_eq!(super::approx_powf(0.3, 1.0), 0.3);
+    }
+
+    #[test]
+    fn policies() {
+        let book = super::Book::builtin();
+        let board = Board::new(FEN_HASHES[0].0).unwrap();
+        let entries = book.probe(&board);
+
+        // The heaviest entry is always played.
+        let best = entries.iter().max_by_key(|&&(_, weight)| weight).unwrap().0;
+        for rand in [0, 12345, u32::MAX] {
+            assert_eq!(book.choose_with(&board, BookPolicy::BestWeight, rand), Some(best));
+        }
+
+        // Every entry may be played uniformly, and the cold weighting favors the heaviest one.
+        for (i, &(mv, _)) in entries.iter().enumerate() {
+            assert_eq!(book.choose_with(&board, BookPolicy::Uniform, i as u32), Some(mv));
+        }
+        assert_eq!(book.choose_with(&board, BookPolicy::Weighted {temperature: 0.01}, u32::MAX / 2), Some(best));
+        assert!(book.choose_with(&board, BookPolicy::Weighted {temperature: 2.0}, 0).is_some());
+    }
+
+    #[test]
+    fn merging() {
+        use super::{Book, BookLayer};
+
+        // A repertoire book playing d2d4, then e2e4 with a lower weight, from the start position.
+        let entry = |from: u16, to: u16, weight: u16| {
+            let mut bytes = FEN_HASHES[0].1.to_be_bytes().to_vec();
+            bytes.extend((from << 6 | to).to_be_bytes());
+            bytes.extend(weight.to_be_bytes());
+            bytes.extend([0; 4]);
+            bytes
+        };
+        let repertoire = || Book::from_bytes(&[entry(11, 27, 10), entry(12, 28, 5)].concat()).unwrap();
+        let board = Board::new(FEN_HASHES[0].0).unwrap();
+        let builtin = Book::builtin().probe(&board);
+        let e4 = builtin.iter().find(|(mv, _)| mv.to_string() == "e2e4").unwrap().1;
+
+        // The book of higher priority hides the other one, which probe_all() still gives.
+        let book = Book::merge([
+            (Book::builtin(), BookLayer::default()),
+            (repertoire(), BookLayer {priority: 1, scale: 1.0}),
+        ]);
+        let moves: Vec<_> = book.probe(&board).iter().map(|(mv, weight)| (mv.to_string(), *weight)).collect();
+        assert_eq!(moves, [("d2d4".to_string(), 10), ("e2e4".to_string(), 5)]);
+        assert_eq!(book.probe_all(&board).len(), builtin.len());
+        let candidate = book.probe_all(&board).iter().find(|candidate| candidate.mv.to_string() == "e2e4").copied().unwrap();
+        assert_eq!((candidate.weight, candidate.priority, candidate.books), (u32::from(e4) + 5, 1, 2));
+
+        // Books of the same priority are summed, once scaled.
+        let book = Book::merge([
+            (Book::builtin(), BookLayer {priority: 0, scale: 2.0}),
+            (repertoire(), BookLayer::default()),
+        ]);
+        let probed = book.probe(&board);
+        assert_eq!(probed.len(), builtin.len());
+        assert!(probed.contains(&(candidate.mv, 2 * e4 + 5)));
+
+        // A position out of the repertoire is played from the general book.
+        let board = Board::new(FEN_HASHES[1].0).unwrap();
+        assert_eq!(book.probe(&board).len(), Book::builtin().probe(&board).len());
+    }
+
+    #[test]
+    fn learning() {
+        let mut book = super::Book::builtin();
+        let board = Board::new(FEN_HASHES[0].0).unwrap();
+        let (mv, weight) = book.probe(&board)[0];
+
+        assert!(book.learn(&board, mv, 1));
+        assert_eq!(book.probe(&board)[0], (mv, weight - 1));
+        assert!(book.learn(&board, mv, u16::MAX));
+        assert_eq!(book.probe(&board)[0], (mv, 0));
+
+        let board = Board::new("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(!book.learn(&board, mv, 1));
+    }
 }
\ No newline at end of file
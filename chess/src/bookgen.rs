@@ -0,0 +1,38 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use clap::{App, Arg};
+
+use chess::book::Book;
+
+/* 
+ * To rebuild the built-in book after editing its lines:
+ * $ cargo run -p chess --bin book-gen -- chess/books/builtin.txt chess/books/builtin.bin
+ */
+
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine book generator")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("Benjamin Lefebvre")
+        .about("Builds a polyglot book (.bin) from opening lines, one per line in standard algebraic notation, the weight of a move being the number of lines playing it.")
+        .arg(Arg::with_name("lines")
+            .index(1)
+            .value_name("LINES")
+            .help("The path to the text file holding the lines.")
+            .required(true))
+        .arg(Arg::with_name("output")
+            .index(2)
+            .value_name("OUTPUT")
+            .help("The path of the book to write.")
+            .required(true))
+        .get_matches();
+
+    let book = Book::from_lines(&fs::read_to_string(Path::new(args.value_of("lines").unwrap()))?)?;
+    let bytes = book.to_bytes();
+    fs::write(Path::new(args.value_of("output").unwrap()), &bytes)?;
+
+    println!("Wrote {} entries.", bytes.len() / 16);
+    Ok(())
+}
@@ -0,0 +1,5 @@
+/// The bytes of the opening book compiled into the library, in polyglot's format.
+/// It holds a few thousand positions of the main lines of the most common openings, the
+/// weight of a move being the number of these lines playing it. It is built from the lines of
+/// books/builtin.txt by the book-gen binary, see Book::from_lines. See Book::builtin.
+pub const BUILTIN: &[u8] = include_bytes!("../books/builtin.bin");
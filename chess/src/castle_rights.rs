@@ -1,8 +1,9 @@
-use std::fmt;
-use std::str::FromStr;
+use core::fmt;
+use core::str::FromStr;
 
-use anyhow::{Error, Result};
+use crate::error::{Error, Result};
 
+use crate::color::Color;
 use crate::square::Square;
 
 //#################################################################################################
@@ -40,9 +41,50 @@ impl CastleRights {
         (self.0 & mask as u8) != 0
     }
 
+    /// Returns true if the given color may still castle kingside, should the path be clear and safe.
+    #[inline]
+    pub fn kingside(self, color: Color) -> bool {
+        self.has(match color {
+            Color::White => CastleMask::WhiteOO,
+            Color::Black => CastleMask::BlackOO,
+        })
+    }
+
+    /// Returns true if the given color may still castle queenside, should the path be clear and safe.
+    #[inline]
+    pub fn queenside(self, color: Color) -> bool {
+        self.has(match color {
+            Color::White => CastleMask::WhiteOOO,
+            Color::Black => CastleMask::BlackOOO,
+        })
+    }
+
+    /// Returns the castling rights as a raw integer.
+    #[inline]
+    pub fn raw(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the rights with the colors swapped: the rights of white become those of black.
+    #[inline]
+    pub fn swap_colors(self) -> CastleRights {
+        CastleRights((self.0 & 0b0011) << 2 | self.0 >> 2)
+    }
+}
+
+// ================================ pub(crate) impl
+
+impl CastleRights {
+    /// Creates castling rights from a raw integer, which must be in 0..16.
+    #[inline]
+    pub(crate) fn from_raw(raw: u8) -> CastleRights {
+        debug_assert!(raw < 16);
+        CastleRights(raw)
+    }
+
     /// Updates the rights with the given from and to squares of the move.
     #[inline]
-    pub fn update(&mut self, from: Square, to: Square) {
+    pub(crate) fn update(&mut self, from: Square, to: Square) {
         match from {
             Square::A1 => self.remove(CastleMask::WhiteOOO),
             Square::E1 => {
@@ -67,12 +109,6 @@ impl CastleRights {
             _ => (),
         }
     }
-
-    /// Returns the castling rights as a raw integer.
-    #[inline]
-    pub fn raw(self) -> u8 {
-        self.0
-    }
 }
 
 // ================================ impl
@@ -141,7 +177,7 @@ impl<'a> FromStr for CastleRights {
             "Kkq"  => 0b1101,
             "Qkq"  => 0b1110,
             "KQkq" => 0b1111,
-            _ => return Err(Error::msg("Invalid castle rights format")),
+            _ => return Err(Error::ParseCastleRights),
         }))
     }
 }
\ No newline at end of file
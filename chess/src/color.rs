@@ -1,7 +1,8 @@
-use std::fmt;
-use std::str::FromStr;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
 
-use anyhow::{Error, Result};
+use crate::error::{Error, Result};
 
 //#################################################################################################
 //
@@ -12,6 +13,7 @@ use anyhow::{Error, Result};
 /// Represents the color of a player.
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     White = 0,
     Black = 1,
@@ -25,6 +27,12 @@ impl Color {
         Color::White, Color::Black,
     ];
 
+    /// Returns an iterator over all colors, ordered by their values.
+    #[inline]
+    pub fn iter() -> impl Iterator<Item = Color> {
+        Color::COLORS.iter().copied()
+    }
+
     /// Gives the opposite color of self.
     #[inline]
     pub const fn invert(self) -> Color {
@@ -62,7 +70,7 @@ impl<'a> FromStr for Color {
         match s {
             "w" => Ok(Color::White),
             "b" => Ok(Color::Black),
-            _ => Err(Error::msg("invalid color litteral")),
+            _ => Err(Error::ParseColor),
         }
     }
 }
@@ -73,4 +81,32 @@ impl From<Color> for usize {
     fn from(color: Color) -> usize {
         color as usize
     }
-}
\ No newline at end of file
+}
+
+impl TryFrom<u8> for Color {
+    type Error = Error;
+
+    /// Tries to get the color of the given value.
+    fn try_from(i: u8) -> Result<Color, Error> {
+        Color::COLORS.get(usize::from(i)).copied().ok_or(Error::ParseColor)
+    }
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn helpers() {
+        assert_eq!(Color::iter().collect::<Vec<_>>(), [Color::White, Color::Black]);
+        assert_eq!(Color::try_from(0).unwrap(), Color::White);
+        assert_eq!(Color::try_from(1).unwrap(), Color::Black);
+        assert!(Color::try_from(2).is_err());
+    }
+}
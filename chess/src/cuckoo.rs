@@ -11,64 +11,69 @@ use crate::zobrist::Zobrist;
 //
 //#################################################################################################
 
-// The cuckoo tables.
-static mut CUCKOO: [Zobrist; 8192] = [Zobrist::ZERO; 8192];
-static mut SQUARES: [Option<(Square, Square)>; 8192] = [None; 8192];
-
-/// Returns true if the move is valid on an empty board.
-/// Pawn moves are never reversible so we don't take them into account.
-#[cold]
-unsafe fn is_valid(piece: Piece, from: Square, to: Square) -> bool {
-    let occ = from.into();
-
-    match piece {
-        Piece::Rook => attacks::rook(from, occ),
-        Piece::Bishop => attacks::bishop(from, occ),
-        Piece::Knight => attacks::knight(from),
-        Piece::Queen => attacks::queen(from, occ),
-        Piece::King => attacks::king(from),
-        _ => unreachable!(),
-    }.contains(to)
+/// The cuckoo tables: the hashes of the reversible moves, and their squares.
+struct Cuckoo {
+    hashes: [Zobrist; 8192],
+    squares: [Option<(Square, Square)>; 8192],
 }
 
-/// Inserts into the cuckoo table, only if the move is valid.
-#[cold]
-unsafe fn insert(color: Color, piece: Piece, from: Square, to: Square) {
-    if !is_valid(piece, from, to) {
-        return;
-    }
-    
-    let mut zobrist = !(Zobrist::from((color, piece, from)) ^ Zobrist::from((color, piece, to)));
-    let mut i = zobrist.h1();
-    let mut squares = Some((from, to));
+/// The cuckoo tables, generated at compile time.
+static CUCKOO: Cuckoo = Cuckoo::new();
 
-    loop {
-        // Take that spot.
-        std::mem::swap(&mut CUCKOO[i], &mut zobrist);
-        std::mem::swap(&mut SQUARES[i], &mut squares);
+impl Cuckoo {
+    /// Generates the cuckoo tables.
+    const fn new() -> Cuckoo {
+        let mut cuckoo = Cuckoo {
+            hashes: [Zobrist::ZERO; 8192],
+            squares: [None; 8192],
+        };
 
-        // The spot was empty, we are done.
-        if zobrist == Zobrist::ZERO {
-            break;
+        let mut color = 0;
+        while color < 2 {
+            let mut piece = 1;
+            while piece < 6 {
+                let mut from = 0;
+                while from < 64 {
+                    let mut to = from + 1;
+                    while to < 64 {
+                        cuckoo.insert(Color::COLORS[color], Piece::PIECES[piece], Square::SQUARES[from], Square::SQUARES[to]);
+                        to += 1;
+                    }
+                    from += 1;
+                }
+                piece += 1;
+            }
+            color += 1;
         }
 
-        if i == zobrist.h1() {
-            i = zobrist.h2();
-        } else {
-            i = zobrist.h1();
-        }
+        cuckoo
     }
-}
 
-/// Initializes the cuckoo tables.
-#[cold]
-pub(crate) unsafe fn init() {
-    for color in Color::COLORS {
-        for &piece in &Piece::PIECES[1..] {
-            for from in Square::SQUARES {
-                for &to in &Square::SQUARES[usize::from(from)+1..] {
-                    insert(color, piece, from, to);
-                }
+    /// Inserts into the cuckoo tables, only if the move is valid on an empty board.
+    /// Pawn moves are never reversible so we don't take them into account.
+    const fn insert(&mut self, color: Color, piece: Piece, from: Square, to: Square) {
+        if attacks::empty_board(piece, from).0 & 1 << to as u8 == 0 {
+            return;
+        }
+
+        let mut zobrist = Zobrist(!(Zobrist::piece(color, piece, from).0 ^ Zobrist::piece(color, piece, to).0));
+        let mut i = zobrist.h1();
+        let mut squares = Some((from, to));
+
+        loop {
+            // Take that spot.
+            core::mem::swap(&mut self.hashes[i], &mut zobrist);
+            core::mem::swap(&mut self.squares[i], &mut squares);
+
+            // The spot was empty, we are done.
+            if zobrist.0 == 0 {
+                break;
+            }
+
+            if i == zobrist.h1() {
+                i = zobrist.h2();
+            } else {
+                i = zobrist.h1();
             }
         }
     }
@@ -84,18 +89,15 @@ pub(crate) unsafe fn init() {
 /// Uses cuckoo hashing to reduce the memory footprint of the hash table.
 #[inline]
 pub(crate) fn is_hash_of_legal_move(board: &Board, diff: Zobrist) -> bool {
-    // SAFETY: arrays are initialized at startup
-    unsafe {
-        let mut i = diff.h1();
+    let mut i = diff.h1();
 
-        if CUCKOO[i] != diff {
-            i = diff.h2();
-            if CUCKOO[i] != diff {
-                return false;
-            }
+    if CUCKOO.hashes[i] != diff {
+        i = diff.h2();
+        if CUCKOO.hashes[i] != diff {
+            return false;
         }
-
-        let (from, to) = SQUARES[i].unwrap();
-        board.is_path_clear(from, to)
     }
+
+    let (from, to) = CUCKOO.squares[i].unwrap();
+    board.is_path_clear(from, to)
 }
\ No newline at end of file
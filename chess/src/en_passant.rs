@@ -1,7 +1,7 @@
-use std::fmt;
-use std::str::FromStr;
+use core::fmt;
+use core::str::FromStr;
 
-use anyhow::{Error, Result};
+use crate::error::{Error, Result};
 
 use crate::square::Square;
 
@@ -40,6 +40,28 @@ impl EnPassantSquare {
 
 // ================================ traits impl
 
+impl From<EnPassantSquare> for Option<Square> {
+    /// Converts the en passant square to an option.
+    #[inline]
+    fn from(ep_square: EnPassantSquare) -> Option<Square> {
+        match ep_square {
+            EnPassantSquare::Some(sq) => Some(sq),
+            EnPassantSquare::None => None,
+        }
+    }
+}
+
+impl From<Option<Square>> for EnPassantSquare {
+    /// Converts an option to an en passant square.
+    #[inline]
+    fn from(sq: Option<Square>) -> EnPassantSquare {
+        match sq {
+            Some(sq) => EnPassantSquare::Some(sq),
+            None => EnPassantSquare::None,
+        }
+    }
+}
+
 impl Default for EnPassantSquare {
     /// Returns EnPassantSquare::None.
     fn default() -> EnPassantSquare {
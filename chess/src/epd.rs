@@ -0,0 +1,207 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+use crate::board::Board;
+use crate::moves::Move;
+
+/// Splits the operations part of an EPD line into its operations, each made of an opcode
+/// and its operands. Operations are terminated by semicolons, and operands may be quoted strings.
+fn split_operations(s: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let mut operations = Vec::new();
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => {
+                let mut tokens = core::mem::take(&mut tokens).into_iter();
+                if let Some(opcode) = tokens.next() {
+                    operations.push((opcode, tokens.collect()));
+                }
+            },
+            '"' => {
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => token.push(c),
+                        None => return Err(Error::InvalidEpd {reason: "unterminated string in operations".to_string()}),
+                    }
+                }
+                tokens.push(token);
+            },
+            c if c.is_ascii_whitespace() => (),
+            c => {
+                let mut token = c.to_string();
+                while let Some(&c) = chars.peek().filter(|&&c| !c.is_ascii_whitespace() && c != ';') {
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            },
+        }
+    }
+
+    if !tokens.is_empty() {
+        return Err(Error::InvalidEpd {reason: "unterminated operation, missing a semicolon".to_string()});
+    }
+
+    Ok(operations)
+}
+
+//#################################################################################################
+//
+//                                           struct Epd
+//
+//#################################################################################################
+
+/// A position in the extended position description format, as used by test suites such as WAC or STS:
+/// the first four fields of a fen string, followed by operations such as "bm Qg6; id \"WAC.001\";".
+/// Moves of the "bm" (best moves) and "am" (avoid moves) operations may be written in standard
+/// algebraic notation or in pure coordinate notation.
+#[derive(Clone, Debug)]
+pub struct Epd {
+    board: Board,
+    id: Option<String>,
+    best_moves: Vec<Move>,
+    avoid_moves: Vec<Move>,
+    operations: Vec<(String, Vec<String>)>,
+}
+
+// ================================ pub impl
+
+impl Epd {
+    /// Reads all positions of an EPD file, one per line. Empty lines are ignored.
+    #[cold]
+    #[cfg(feature = "std")]
+    pub fn open(path: &Path) -> Result<Vec<Epd>> {
+        fs::read_to_string(path)?.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Epd::from_str)
+            .collect()
+    }
+
+    /// Returns the position.
+    #[inline]
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the identifier of the position, given by the "id" operation.
+    #[inline]
+    pub fn get_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Returns the best moves of the position, given by the "bm" operation.
+    #[inline]
+    pub fn get_best_moves(&self) -> &[Move] {
+        &self.best_moves
+    }
+
+    /// Returns the moves to avoid in the position, given by the "am" operation.
+    #[inline]
+    pub fn get_avoid_moves(&self) -> &[Move] {
+        &self.avoid_moves
+    }
+
+    /// Returns the operands of the operation with the given opcode, if it is present.
+    pub fn get_operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Returns true if the move is a solution of the position: it is one of the best moves,
+    /// if any are given, and is not one of the moves to avoid.
+    pub fn is_solution(&self, mv: Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&mv)) && !self.avoid_moves.contains(&mv)
+    }
+}
+
+// ================================ traits impl
+
+impl FromStr for Epd {
+    type Err = Error;
+
+    /// Parses a position from an EPD line.
+    fn from_str(s: &str) -> Result<Epd> {
+        let s = s.trim();
+
+        // The four fields of the position, and the operations.
+        let mut fields = Vec::new();
+        let mut rest = s;
+        for _ in 0..4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err(Error::InvalidEpd {reason: "less than four fields".to_string()});
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+
+        let operations = split_operations(rest)?;
+        let operand = |opcode: &str| operations.iter().find(|(op, _)| op == opcode).and_then(|(_, operands)| operands.first());
+
+        // The move counters are not part of the position, but may be given as operations.
+        let halfmove = operand("hmvc").map_or("0", String::as_str);
+        let fullmove = operand("fmvn").map_or("1", String::as_str);
+        let board = Board::from_str(&format!("{} {} {}", fields.join(" "), halfmove, fullmove))?;
+
+        let parse_moves = |opcode: &str| -> Result<Vec<Move>> {
+            operations.iter().filter(|(op, _)| op == opcode).flat_map(|(_, operands)| operands).map(|mv| {
+                board.parse_san(mv).or_else(|_| board.parse_move(mv))
+                    .map_err(|_| Error::InvalidEpd {reason: format!("invalid move in operation \"{}\"", mv)})
+            }).collect()
+        };
+
+        Ok(Epd {
+            id: operand("id").cloned(),
+            best_moves: parse_moves("bm")?,
+            avoid_moves: parse_moves("am")?,
+            board,
+            operations,
+        })
+    }
+}
+
+//#################################################################################################
+//
+//                                              tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Epd;
+    use crate::error::Error;
+
+    #[test]
+    fn epd_parsing() {
+        let epd = Epd::from_str("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";").unwrap();
+        assert_eq!(epd.get_id(), Some("WAC.001"));
+        assert_eq!(epd.get_best_moves(), &[epd.get_board().parse_move("g3g6").unwrap()]);
+        assert!(epd.get_avoid_moves().is_empty());
+
+        let epd = Epd::from_str("r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - am Nxc6 Nb5; hmvc 3; fmvn 7;").unwrap();
+        let board = epd.get_board();
+        assert_eq!(epd.get_avoid_moves(), &[board.parse_move("d4c6").unwrap(), board.parse_move("d4b5").unwrap()]);
+        assert_eq!(board.get_halfmove(), 3);
+        assert_eq!(epd.get_operation("fmvn"), Some(&["7".to_string()][..]));
+        assert!(!epd.is_solution(board.parse_move("d4c6").unwrap()));
+        assert!(epd.is_solution(board.parse_move("f1e2").unwrap()));
+
+        let epd = Epd::from_str("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - bm O-O-O;").unwrap();
+        assert_eq!(epd.get_best_moves(), &[epd.get_board().parse_move("e1c1").unwrap()]);
+
+        assert!(matches!(Epd::from_str("8/8/8/8 w"), Err(Error::InvalidEpd {..})));
+    }
+}
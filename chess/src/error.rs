@@ -0,0 +1,115 @@
+use alloc::string::String;
+use core::fmt;
+
+//#################################################################################################
+//
+//                                           enum Error
+//
+//#################################################################################################
+
+/// An error of the chess lib. The errors of parsing carry the reason of the failure,
+/// so that they may be displayed, while the others can be matched upon.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A fen string could not be parsed.
+    InvalidFen {reason: &'static str},
+    /// A board encoded by Board::to_bytes() could not be decoded.
+    InvalidEncoding {reason: &'static str},
+    /// A position is invalid, for example because a side does not have exactly one king.
+    InvalidPosition {reason: &'static str},
+    /// A string could not be parsed as a move.
+    ParseMove {reason: &'static str},
+    /// A move is not legal in the position it is played in.
+    IllegalMove,
+    /// A move of a sequence could not be parsed or played, the index being its position in the sequence.
+    InvalidMoveSequence {index: usize, reason: String},
+    /// A move in standard algebraic notation matches several legal moves.
+    AmbiguousMove,
+    /// A string or an index could not be parsed as a square.
+    ParseSquare,
+    /// A character or an index could not be parsed as a piece.
+    ParsePiece,
+    /// A string or an index could not be parsed as a color.
+    ParseColor,
+    /// A string could not be parsed as castling rights.
+    ParseCastleRights,
+    /// A string could not be parsed as a variant.
+    ParseVariant,
+    /// A game in portable game notation could not be parsed.
+    InvalidPgn {reason: String},
+    /// A line in extended position description could not be parsed.
+    InvalidEpd {reason: String},
+    /// A polyglot book could not be read.
+    InvalidBook {reason: &'static str},
+    /// The game is over, and can't go on.
+    GameOver,
+    /// There is no move to undo in the game.
+    NothingToUndo,
+    /// There is no move to redo in the game.
+    NothingToRedo,
+    /// There is no draw offer to answer in the game.
+    NoDrawOffer,
+    /// A player tried to answer their own draw offer.
+    OwnDrawOffer,
+    /// The player to move ran out of time, and lost the game.
+    OutOfTime,
+    /// A file could not be read or written.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+/// A result whose error is, by default, an error of the chess lib.
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+// ================================ traits impl
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    /// Converts the error of reading or writing a file.
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for Error {
+    /// Displays a message describing the error.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidFen {reason} => write!(f, "Invalid fen string: {}.", reason),
+            Error::InvalidEncoding {reason} => write!(f, "Invalid encoded board: {}.", reason),
+            Error::InvalidPosition {reason} => write!(f, "Invalid position: {}.", reason),
+            Error::ParseMove {reason} => write!(f, "Invalid move literal: {}.", reason),
+            Error::IllegalMove => f.write_str("Move is invalid in this context."),
+            Error::InvalidMoveSequence {index, reason} => write!(f, "Invalid move at index {} of the sequence: {}", index, reason),
+            Error::AmbiguousMove => f.write_str("Move is ambiguous in this context."),
+            Error::ParseSquare => f.write_str("Invalid square, it should be a letter between a and h followed by a digit between 1 and 8."),
+            Error::ParsePiece => f.write_str("Invalid piece."),
+            Error::ParseColor => f.write_str("Invalid color."),
+            Error::ParseCastleRights => f.write_str("Invalid castle rights format."),
+            Error::ParseVariant => f.write_str("Invalid variant, it should be standard, kingofthehill or threecheck."),
+            Error::InvalidPgn {reason} => write!(f, "Invalid PGN: {}.", reason),
+            Error::InvalidEpd {reason} => write!(f, "Invalid EPD: {}.", reason),
+            Error::InvalidBook {reason} => write!(f, "Invalid book: {}.", reason),
+            Error::GameOver => f.write_str("The game is over."),
+            Error::NothingToUndo => f.write_str("There is no move to undo."),
+            Error::NothingToRedo => f.write_str("There is no move to redo."),
+            Error::NoDrawOffer => f.write_str("There is no draw offer."),
+            Error::OwnDrawOffer => f.write_str("Cannot answer your own draw offer."),
+            Error::OutOfTime => f.write_str("The player ran out of time."),
+            #[cfg(feature = "std")]
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    /// Returns the error of the file operation that failed, if any.
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
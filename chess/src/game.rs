@@ -0,0 +1,465 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::error::{Error, Result};
+
+use crate::board::{Board, Status};
+use crate::color::Color;
+use crate::moves::Move;
+use crate::pgn::Pgn;
+
+//#################################################################################################
+//
+//                                      struct TimeControl
+//
+//#################################################################################################
+
+/// A time control: a base time for each player, and an increment added
+/// to a player's clock after each of their moves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+//#################################################################################################
+//
+//                                        enum Termination
+//
+//#################################################################################################
+
+/// The ways a game may end.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Termination {
+    /// The side to move is checkmated.
+    Checkmate,
+    /// The position is drawn: stalemate, threefold repetition, fifty moves rule or insufficient material.
+    DrawnPosition,
+    /// A player resigned.
+    Resignation,
+    /// The players agreed to a draw.
+    Agreement,
+    /// A player ran out of time.
+    Time,
+    /// A player won by the rules of the variant, such as bringing their king to the hill.
+    VariantWin,
+}
+
+/// The outcome of a finished game: the color of the winner, if any, and the way the game ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Outcome {
+    pub winner: Option<Color>,
+    pub termination: Termination,
+}
+
+// ================================ pub impl
+
+impl Outcome {
+    /// Returns the result of the game as a status of the board.
+    pub fn status(self) -> Status {
+        match self.winner {
+            Some(color) => Status::Win(color),
+            None => Status::Draw,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                          struct Game
+//
+//#################################################################################################
+
+/// A game of chess: the board, the moves played from the starting position along with their
+/// standard algebraic notation, the clocks of the players if the game is timed, and the draw
+/// offers, resignations and flags that may end the game. Undone moves are kept, and may be redone
+/// until another move is played.
+#[derive(Clone, Debug)]
+pub struct Game {
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    sans: Vec<String>,
+    cursor: usize,
+    time_control: Option<TimeControl>,
+    clocks: Vec<[Duration; 2]>,
+    draw_offer: Option<Color>,
+    ended: Option<Outcome>,
+    // The status of the board, cached as it is asked for much more often than the board changes.
+    status: Status,
+}
+
+// ================================ pub impl
+
+impl Game {
+    /// Creates a new untimed game, starting from the given position.
+    pub fn new(start: Board) -> Game {
+        Game {
+            status: start.status(),
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+            sans: Vec::new(),
+            cursor: 0,
+            time_control: None,
+            clocks: Vec::new(),
+            draw_offer: None,
+            ended: None,
+        }
+    }
+
+    /// Returns the starting position of the game.
+    #[inline]
+    pub fn get_start(&self) -> &Board {
+        &self.start
+    }
+
+    /// Returns the current position of the game.
+    #[inline]
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the status of the current position, as Board::status() does, without computing it again:
+    /// it is computed once per move played, undone or redone. It ignores the ways players may end the game.
+    #[inline]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Returns the moves played, without the undone ones.
+    #[inline]
+    pub fn get_moves(&self) -> &[Move] {
+        &self.moves[..self.cursor]
+    }
+
+    /// Returns the moves played in standard algebraic notation, without the undone ones.
+    #[inline]
+    pub fn get_sans(&self) -> &[String] {
+        &self.sans[..self.cursor]
+    }
+
+    /// Returns the moves that were undone and may be redone, the next one first.
+    #[inline]
+    pub fn get_undone(&self) -> &[Move] {
+        &self.moves[self.cursor..]
+    }
+
+    /// Returns the time control of the game, if it is timed.
+    #[inline]
+    pub fn get_time_control(&self) -> Option<TimeControl> {
+        self.time_control
+    }
+
+    /// Sets the time control of the game, or removes the clocks. The clocks of both
+    /// players are reset to the base time, including for the moves already played.
+    pub fn set_time_control(&mut self, time_control: Option<TimeControl>) {
+        self.time_control = time_control;
+        self.clocks = match time_control {
+            Some(time_control) => vec![[time_control.base; 2]; self.moves.len() + 1],
+            None => Vec::new(),
+        };
+    }
+
+    /// Returns the remaining time of the player of the given color, if the game is timed.
+    #[inline]
+    pub fn remaining(&self, color: Color) -> Option<Duration> {
+        self.clocks.get(self.cursor).map(|clocks| clocks[color as usize])
+    }
+
+    /// Plays the given move, without any time spent on it. See play_timed().
+    pub fn play(&mut self, mv: Move) -> Result<()> {
+        self.play_timed(mv, Duration::ZERO)
+    }
+
+    /// Plays the given move, the side to move having spent the given time on it. The move must be
+    /// legal and the game must not be over. In a timed game, the increment is added to the clock of
+    /// the player after their move, and a player that spent more time than they had loses on time,
+    /// without their move being played. A draw offer stands until the opponent of the offerer moves.
+    pub fn play_timed(&mut self, mv: Move, elapsed: Duration) -> Result<()> {
+        if self.is_over() {
+            return Err(Error::GameOver);
+        }
+
+        if !self.board.is_pseudo_legal(mv) || !self.board.is_legal(mv) {
+            return Err(Error::IllegalMove);
+        }
+
+        let mover = self.board.get_side_to_move();
+
+        let clocks = match (self.time_control, self.clocks.get(self.cursor)) {
+            (Some(time_control), Some(&clocks)) => {
+                let mut clocks = clocks;
+                clocks[mover as usize] = match clocks[mover as usize].checked_sub(elapsed) {
+                    Some(remaining) if remaining > Duration::ZERO => remaining + time_control.increment,
+                    _ => return self.flag(mover).and(Err(Error::OutOfTime)),
+                };
+                Some(clocks)
+            },
+            _ => None,
+        };
+
+        // Playing the next undone move redoes it, playing another one forgets all undone moves.
+        if self.moves.get(self.cursor) != Some(&mv) {
+            self.moves.truncate(self.cursor);
+            self.sans.truncate(self.cursor);
+            self.clocks.truncate(self.cursor + 1);
+            self.moves.push(mv);
+            self.sans.push(self.board.to_san(mv));
+        }
+
+        // The clocks after the move, there is one more of them than there are moves.
+        if let Some(clocks) = clocks {
+            match self.clocks.get_mut(self.cursor + 1) {
+                Some(old) => *old = clocks,
+                None => self.clocks.push(clocks),
+            }
+        }
+
+        self.board.do_move(mv);
+        self.status = self.board.status();
+        self.cursor += 1;
+
+        if self.draw_offer.is_some_and(|color| color != mover) {
+            self.draw_offer = None;
+        }
+
+        Ok(())
+    }
+
+    /// Undoes the last move played, which also takes back the end of the game and the draw offer, if any.
+    /// The clocks are those of the previous position.
+    pub fn undo(&mut self) -> Result<Move> {
+        if self.cursor == 0 {
+            return Err(Error::NothingToUndo);
+        }
+
+        self.cursor -= 1;
+        let mv = self.moves[self.cursor];
+        self.board.undo_move(mv);
+        self.status = self.board.status();
+        self.draw_offer = None;
+        self.ended = None;
+
+        Ok(mv)
+    }
+
+    /// Redoes the last undone move, which also takes back the end of the game and the draw offer, if any.
+    /// The clocks are those saved when the move was first played.
+    pub fn redo(&mut self) -> Result<Move> {
+        let mv = *self.moves.get(self.cursor).ok_or(Error::NothingToRedo)?;
+
+        self.board.do_move(mv);
+        self.status = self.board.status();
+        self.cursor += 1;
+        self.draw_offer = None;
+        self.ended = None;
+
+        Ok(mv)
+    }
+
+    /// Returns the color of the player offering a draw, if any.
+    #[inline]
+    pub fn get_draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    /// The player of the given color offers a draw to their opponent.
+    pub fn offer_draw(&mut self, color: Color) -> Result<()> {
+        if self.is_over() {
+            return Err(Error::GameOver);
+        }
+
+        self.draw_offer = Some(color);
+        Ok(())
+    }
+
+    /// The player of the given color accepts the draw offered by their opponent, ending the game.
+    pub fn accept_draw(&mut self, color: Color) -> Result<()> {
+        self.answer_draw_offer(color)?;
+        self.end(None, Termination::Agreement);
+        Ok(())
+    }
+
+    /// The player of the given color declines the draw offered by their opponent.
+    pub fn decline_draw(&mut self, color: Color) -> Result<()> {
+        self.answer_draw_offer(color)
+    }
+
+    /// The player of the given color resigns, ending the game.
+    pub fn resign(&mut self, color: Color) -> Result<()> {
+        if self.is_over() {
+            return Err(Error::GameOver);
+        }
+
+        self.end(Some(color.invert()), Termination::Resignation);
+        Ok(())
+    }
+
+    /// The player of the given color ran out of time, ending the game. Used when the clocks
+    /// are kept outside of the game, as it happens by itself with play_timed() otherwise.
+    pub fn flag(&mut self, color: Color) -> Result<()> {
+        if self.is_over() {
+            return Err(Error::GameOver);
+        }
+
+        if let Some(clocks) = self.clocks.get_mut(self.cursor) {
+            clocks[color as usize] = Duration::ZERO;
+        }
+
+        self.end(Some(color.invert()), Termination::Time);
+        Ok(())
+    }
+
+    /// Returns the outcome of the game, or None if it is not over.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.ended.or_else(|| match self.status {
+            Status::Playing => None,
+            Status::Draw => Some(Outcome {winner: None, termination: Termination::DrawnPosition}),
+            Status::Win(color) if self.board.get_variant().winner(&self.board).is_some() => {
+                Some(Outcome {winner: Some(color), termination: Termination::VariantWin})
+            },
+            Status::Win(color) => Some(Outcome {winner: Some(color), termination: Termination::Checkmate}),
+        })
+    }
+
+    /// Returns true if the game is over, on the board or because of the players.
+    #[inline]
+    pub fn is_over(&self) -> bool {
+        self.outcome().is_some()
+    }
+
+    /// Returns the game in portable game notation, with its result.
+    pub fn to_pgn(&self) -> Pgn {
+        let mut pgn = Pgn::new(self.start.clone());
+
+        for &mv in self.get_moves() {
+            pgn.push(mv);
+        }
+        pgn.set_result(self.outcome().map_or(Status::Playing, Outcome::status));
+
+        pgn
+    }
+}
+
+// ================================ impl
+
+impl Game {
+    /// Checks that the player of the given color may answer a draw offer, and withdraws it.
+    fn answer_draw_offer(&mut self, color: Color) -> Result<()> {
+        match self.draw_offer {
+            Some(offerer) if offerer != color => {
+                self.draw_offer = None;
+                Ok(())
+            },
+            Some(_) => Err(Error::OwnDrawOffer),
+            None => Err(Error::NoDrawOffer),
+        }
+    }
+
+    /// Ends the game with the given winner, if any.
+    fn end(&mut self, winner: Option<Color>, termination: Termination) {
+        self.ended = Some(Outcome {winner, termination});
+        self.draw_offer = None;
+    }
+}
+
+//#################################################################################################
+//
+//                                              tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::{Game, Outcome, Termination, TimeControl};
+    use crate::board::{Board, Status};
+    use crate::color::Color;
+    use crate::error::Error;
+
+    /// Plays the given moves, written in pure coordinate notation.
+    fn play(game: &mut Game, moves: &[&str]) {
+        for s in moves {
+            let mv = game.get_board().parse_move(s).unwrap();
+            game.play(mv).unwrap();
+        }
+    }
+
+    #[test]
+    fn game_history() {
+        let mut game = Game::new(Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap());
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+
+        assert_eq!(game.get_sans(), ["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(game.status(), Status::Win(Color::Black));
+        assert_eq!(game.outcome(), Some(Outcome {winner: Some(Color::Black), termination: Termination::Checkmate}));
+        assert!(matches!(game.play(game.get_moves()[0]), Err(Error::GameOver)));
+        assert!(game.to_pgn().to_string().ends_with("1. f3 e5 2. g4 Qh4# 0-1\n"));
+
+        // Undoing takes back the end of the game, and the undone moves may be redone.
+        let mate = game.undo().unwrap();
+        game.undo().unwrap();
+        assert!(!game.is_over());
+        assert_eq!(game.status(), Status::Playing);
+        assert_eq!(game.get_undone().len(), 2);
+        game.redo().unwrap();
+        game.play(mate).unwrap();
+        assert_eq!(game.get_sans().len(), 4);
+
+        // Playing another move forgets the undone ones.
+        game.undo().unwrap();
+        play(&mut game, &["d8g5"]);
+        assert_eq!(game.get_sans()[3], "Qg5");
+        assert!(game.get_undone().is_empty());
+        assert!(matches!(game.redo(), Err(Error::NothingToRedo)));
+    }
+
+    #[test]
+    fn game_endings() {
+        let mut game = Game::new(Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap());
+
+        // A draw offer stands until the opponent of the offerer moves.
+        game.offer_draw(Color::White).unwrap();
+        assert!(matches!(game.accept_draw(Color::White), Err(Error::OwnDrawOffer)));
+        play(&mut game, &["e2e4"]);
+        assert_eq!(game.get_draw_offer(), Some(Color::White));
+        play(&mut game, &["e7e5"]);
+        assert_eq!(game.get_draw_offer(), None);
+
+        game.offer_draw(Color::White).unwrap();
+        game.accept_draw(Color::Black).unwrap();
+        assert_eq!(game.outcome().map(Outcome::status), Some(Status::Draw));
+        assert!(matches!(game.resign(Color::Black), Err(Error::GameOver)));
+
+        game.undo().unwrap();
+        game.resign(Color::White).unwrap();
+        assert_eq!(game.outcome(), Some(Outcome {winner: Some(Color::Black), termination: Termination::Resignation}));
+        assert_eq!(game.status(), Status::Playing);
+    }
+
+    #[test]
+    fn game_clocks() {
+        let mut game = Game::new(Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap());
+        game.set_time_control(Some(TimeControl {base: Duration::from_secs(60), increment: Duration::from_secs(1)}));
+
+        let mv = game.get_board().parse_move("e2e4").unwrap();
+        game.play_timed(mv, Duration::from_secs(10)).unwrap();
+        assert_eq!(game.remaining(Color::White), Some(Duration::from_secs(51)));
+        assert_eq!(game.remaining(Color::Black), Some(Duration::from_secs(60)));
+
+        game.undo().unwrap();
+        assert_eq!(game.remaining(Color::White), Some(Duration::from_secs(60)));
+        game.redo().unwrap();
+        assert_eq!(game.remaining(Color::White), Some(Duration::from_secs(51)));
+
+        let mv = game.get_board().parse_move("e7e5").unwrap();
+        assert!(matches!(game.play_timed(mv, Duration::from_secs(60)), Err(Error::OutOfTime)));
+        assert_eq!(game.outcome(), Some(Outcome {winner: Some(Color::White), termination: Termination::Time}));
+        assert_eq!(game.get_moves().len(), 1);
+    }
+}
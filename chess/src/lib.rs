@@ -1,3 +1,11 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+// Errors.
+pub mod error;
+pub use self::error::Error;
+
 // Primitive types.
 pub mod bitboard;
 pub mod color;
@@ -15,30 +23,20 @@ mod cuckoo;
 // Board type.
 pub mod board;
 pub mod movegen;
+pub mod variant;
 
 // Utils.
 pub mod book;
+pub mod books;
+pub mod epd;
+pub mod game;
+pub mod motifs;
+pub mod pgn;
+pub mod random;
 
 pub mod prelude {
-    pub use crate::board::{Board, Status}; 
+    pub use crate::board::{Board, DrawReason, Status}; 
     pub use crate::color::Color;
     pub use crate::moves::Move;
     pub use crate::movegen;
 }
-
-/// Initializes the components of the chess lib.
-/// Must be called before using the methods of the chess lib.
-#[cold]
-pub fn init() {
-    use std::sync::Once;
-
-    static INIT: Once = Once::new();
-
-    // SAFE: thread safe by the Once's lock.
-    INIT.call_once(|| unsafe {
-        bitboard::init();
-        zobrist::init();
-        attacks::init();
-        cuckoo::init();
-    });
-}
\ No newline at end of file
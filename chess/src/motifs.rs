@@ -0,0 +1,268 @@
+use alloc::vec::Vec;
+
+use crate::attacks;
+use crate::bitboard::BitBoard;
+use crate::board::Board;
+use crate::color::Color;
+use crate::piece::Piece;
+use crate::square::Square;
+
+/// A piece of the board, with the square it stands on.
+pub type Placed = (Square, Piece);
+
+// The conventional value of a piece, used to tell which pieces are worth attacking.
+// The king is worth more than everything else.
+fn value(piece: Piece) -> u8 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight | Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => u8::MAX,
+    }
+}
+
+// Returns the piece on the given square, which must be occupied.
+fn placed(board: &Board, sq: Square) -> Placed {
+    (sq, board.get_piece_unchecked(sq))
+}
+
+// Returns true if attacking the target with the attacker threatens to win material:
+// the target is the king, is worth more than the attacker, or is not defended.
+fn is_threat(board: &Board, attacker: Piece, target: Placed) -> bool {
+    let (sq, piece) = target;
+    let color = board.get_piece(sq).map(|(color, _)| color).unwrap();
+
+    piece == Piece::King
+    || value(piece) > value(attacker)
+    || board.attackers_by(sq, color, board.get_occupancy().all()).empty()
+}
+
+//#################################################################################################
+//
+//                                          enum Motif
+//
+//#################################################################################################
+
+/// A tactical motif found on a board, with the pieces involved. The motifs are
+/// detected statically, without searching: they hint at a tactic, but do not
+/// guarantee that it works.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Motif {
+    /// A piece that is attacked, and either not defended or attacked by a lesser piece.
+    Hanging {
+        target: Placed,
+    },
+    /// A piece threatening two or more pieces at once.
+    Fork {
+        attacker: Placed,
+        targets: Vec<Placed>,
+    },
+    /// A slider attacking a piece, that can't move away without exposing a more valuable one behind it.
+    Pin {
+        pinner: Placed,
+        pinned: Placed,
+        target: Placed,
+    },
+    /// A slider attacking a valuable piece, that exposes a lesser one behind it when it moves away.
+    Skewer {
+        attacker: Placed,
+        front: Placed,
+        behind: Placed,
+    },
+    /// A piece standing between one of its own sliders and an enemy piece, which is attacked when it moves.
+    DiscoveredAttack {
+        mover: Placed,
+        attacker: Placed,
+        target: Placed,
+    },
+}
+
+// ================================ pub impl
+
+impl Motif {
+    /// Returns the bitboard of all the squares involved in the motif.
+    pub fn squares(&self) -> BitBoard {
+        let mut bb = BitBoard::EMPTY;
+
+        match self {
+            Motif::Hanging {target} => bb |= BitBoard::from(target.0),
+            Motif::Fork {attacker, targets} => {
+                bb |= BitBoard::from(attacker.0);
+                for target in targets {
+                    bb |= BitBoard::from(target.0);
+                }
+            },
+            Motif::Pin {pinner: a, pinned: b, target: c}
+            | Motif::Skewer {attacker: a, front: b, behind: c}
+            | Motif::DiscoveredAttack {mover: b, attacker: a, target: c} => {
+                bb |= BitBoard::from(a.0) | BitBoard::from(b.0) | BitBoard::from(c.0);
+            },
+        }
+
+        bb
+    }
+}
+
+//#################################################################################################
+//
+//                                          detectors
+//
+//#################################################################################################
+
+/// Returns the pieces of the opponent of the given color that the given color could win:
+/// those it attacks, that are either not defended or attacked by a lesser piece. Kings are never hanging.
+pub fn hanging(board: &Board, by: Color) -> Vec<Motif> {
+    let occ = board.get_occupancy().all();
+    let targets = board.get_occupancy().colored(by.invert()) & !board.get_bitboard(by.invert(), Piece::King);
+
+    targets.iter_squares().filter_map(|sq| {
+        let target = placed(board, sq);
+        let threatened = board.attackers_by(sq, by, occ).iter_squares().any(|from| {
+            is_threat(board, board.get_piece_unchecked(from), target)
+        });
+
+        threatened.then_some(Motif::Hanging {target})
+    }).collect()
+}
+
+/// Returns the pieces of the given color threatening two or more pieces of the opponent at once.
+pub fn forks(board: &Board, by: Color) -> Vec<Motif> {
+    let enemies = board.get_occupancy().colored(by.invert());
+
+    board.get_occupancy().colored(by).iter_squares().filter_map(|from| {
+        let attacker = placed(board, from);
+        let targets: Vec<_> = (board.attacks_from(from) & enemies).iter_squares()
+            .map(|sq| placed(board, sq))
+            .filter(|&target| is_threat(board, attacker.1, target))
+            .collect();
+
+        (targets.len() >= 2).then_some(Motif::Fork {attacker, targets})
+    }).collect()
+}
+
+/// Returns the pieces of the opponent pinned by the sliders of the given color, against the king or a more valuable piece.
+pub fn pins(board: &Board, by: Color) -> Vec<Motif> {
+    xrays(board, by).filter_map(|(pinner, pinned, target)| {
+        let enemy = board.get_piece(pinned.0).map(|(color, _)| color) == Some(by.invert());
+        (enemy && value(target.1) > value(pinned.1)).then_some(Motif::Pin {pinner, pinned, target})
+    }).collect()
+}
+
+/// Returns the skewers of the sliders of the given color: a valuable piece of the opponent
+/// attacked, with a lesser piece of the opponent behind it.
+pub fn skewers(board: &Board, by: Color) -> Vec<Motif> {
+    xrays(board, by).filter_map(|(attacker, front, behind)| {
+        let enemy = board.get_piece(front.0).map(|(color, _)| color) == Some(by.invert());
+        let worth = enemy && value(front.1) > value(behind.1) && is_threat(board, attacker.1, front);
+        worth.then_some(Motif::Skewer {attacker, front, behind})
+    }).collect()
+}
+
+/// Returns the discovered attacks of the given color: a piece of its own standing between
+/// one of its sliders and a piece of the opponent the slider would threaten.
+pub fn discovered_attacks(board: &Board, by: Color) -> Vec<Motif> {
+    xrays(board, by).filter_map(|(attacker, mover, target)| {
+        let own = board.get_piece(mover.0).map(|(color, _)| color) == Some(by);
+        (own && is_threat(board, attacker.1, target)).then_some(Motif::DiscoveredAttack {mover, attacker, target})
+    }).collect()
+}
+
+/// Returns all the motifs the given color could exploit on the board.
+pub fn detect(board: &Board, by: Color) -> Vec<Motif> {
+    let mut motifs = hanging(board, by);
+    motifs.extend(forks(board, by));
+    motifs.extend(pins(board, by));
+    motifs.extend(skewers(board, by));
+    motifs.extend(discovered_attacks(board, by));
+    motifs
+}
+
+// Returns an iterator over the lines of the sliders of the given color, going through exactly one piece
+// of any color before reaching a piece of the opponent: (slider, piece in between, piece of the opponent).
+fn xrays(board: &Board, by: Color) -> impl Iterator<Item = (Placed, Placed, Placed)> + '_ {
+    let occ = board.get_occupancy().all();
+    let enemies = board.get_occupancy().colored(by.invert());
+    let sliders = board.get_bitboard(by, Piece::Bishop)
+        | board.get_bitboard(by, Piece::Rook)
+        | board.get_bitboard(by, Piece::Queen);
+
+    sliders.iter_squares().flat_map(move |from| {
+        let slider = placed(board, from);
+        let lines = attacks::empty_board(slider.1, from) & enemies;
+
+        lines.iter_squares().filter_map(move |to| {
+            let between = BitBoard::between(from, to) & occ;
+            // SAFE: the bitboard has exactly one square
+            between.is_one().then(|| (slider, placed(board, unsafe {between.as_square_unchecked()}), placed(board, to)))
+        })
+    })
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::color::Color;
+    use crate::motifs::{self, Motif};
+    use crate::piece::Piece;
+    use crate::square::Square;
+
+    #[test]
+    fn hanging() {
+        let board = Board::new("4k3/8/2n5/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(motifs::hanging(&board, Color::White), [Motif::Hanging {target: (Square::C6, Piece::Knight)}]);
+
+        // A defended knight is not hanging when attacked by a rook.
+        let board = Board::new("4k3/3p4/2n5/8/8/8/8/2R1K3 w - - 0 1").unwrap();
+        assert_eq!(motifs::hanging(&board, Color::White), []);
+    }
+
+    #[test]
+    fn fork() {
+        let board = Board::new("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(motifs::forks(&board, Color::White), [Motif::Fork {
+            attacker: (Square::C7, Piece::Knight),
+            targets: vec![(Square::A8, Piece::Rook), (Square::E8, Piece::King)],
+        }]);
+    }
+
+    #[test]
+    fn pin() {
+        let board = Board::new("4k3/8/2n5/8/B7/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(motifs::pins(&board, Color::White), [Motif::Pin {
+            pinner: (Square::A4, Piece::Bishop),
+            pinned: (Square::C6, Piece::Knight),
+            target: (Square::E8, Piece::King),
+        }]);
+        assert_eq!(motifs::skewers(&board, Color::White), []);
+    }
+
+    #[test]
+    fn skewer() {
+        let board = Board::new("3r4/8/8/3k4/8/8/8/K2R4 b - - 0 1").unwrap();
+        assert_eq!(motifs::skewers(&board, Color::White), [Motif::Skewer {
+            attacker: (Square::D1, Piece::Rook),
+            front: (Square::D5, Piece::King),
+            behind: (Square::D8, Piece::Rook),
+        }]);
+        assert_eq!(motifs::pins(&board, Color::White), []);
+    }
+
+    #[test]
+    fn discovered_attack() {
+        let board = Board::new("3qk3/8/8/8/3N4/8/8/3RK3 w - - 0 1").unwrap();
+        let motifs = motifs::discovered_attacks(&board, Color::White);
+        assert_eq!(motifs, [Motif::DiscoveredAttack {
+            mover: (Square::D4, Piece::Knight),
+            attacker: (Square::D1, Piece::Rook),
+            target: (Square::D8, Piece::Queen),
+        }]);
+        assert!(motifs[0].squares().contains(Square::D4));
+    }
+}
@@ -1,12 +1,27 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ops::Deref;
+use core::slice;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::thread;
+
 use crate::attacks;
 use crate::bitboard::BitBoard;
 use crate::board::Board;
 use crate::castle_rights::CastleMask;
 use crate::color::Color;
-use crate::en_passant::EnPassantSquare;
 use crate::moves::Move;
 use crate::piece::Piece;
 use crate::square::Square;
+use crate::zobrist::Zobrist;
 
 //#################################################################################################
 //
@@ -55,7 +70,7 @@ pub fn gen_en_passant(board: &Board, mut gen: impl FnMut(Move)) {
     let us = board.get_side_to_move();
     let them = board.get_other_side();
 
-    if let EnPassantSquare::Some(sq) = board.get_ep_square() {
+    if let Some(sq) = board.get_ep_square() {
         let to = attacks::pawn_push(us, sq).unwrap();
         for from in (attacks::pawn(them, to) & board.get_bitboard(us, Piece::Pawn)).iter_squares() {
             gen(Move::en_passant(from, to));
@@ -225,6 +240,133 @@ pub fn gen_quiets(board: &Board, mut gen: impl FnMut(Piece, Move)) {
     }
 }
 
+// ================================ captures on a square
+
+/// Gives all pseudo-legal captures of the piece on the given square, from the least valuable attacker to the
+/// most valuable one: pawns, knights, bishops, rooks, queens and the king, as needed by exchange evaluation.
+/// A pawn capturing on the last rank gives its promotions to the given pieces, in order. En passant, which
+/// does not land on the square of the captured pawn, is not given. Gives nothing if the square holds no enemy piece.
+/// The provided closure is called for all generated moves, and given the piece that moved.
+#[inline]
+pub fn gen_captures_to(board: &Board, sq: Square, promotes: &[Piece], mut gen: impl FnMut(Piece, Move)) {
+    let us = board.get_side_to_move();
+    let them = board.get_other_side();
+
+    let captured = match board.get_piece(sq) {
+        Some((color, piece)) if color == them => piece,
+        _ => return,
+    };
+
+    let occ = board.get_occupancy().all();
+    let ours = |piece| board.get_bitboard(us, piece);
+
+    for from in (attacks::pawn(them, sq) & ours(Piece::Pawn)).iter_squares() {
+        if BitBoard::promote_rank(us).contains(from) {
+            for &promote in promotes {
+                gen(Piece::Pawn, Move::promote_capture(from, sq, captured, promote));
+            }
+        } else {
+            gen(Piece::Pawn, Move::capture(from, sq, captured));
+        }
+    }
+
+    let attackers = [
+        (Piece::Knight, attacks::knight(sq)),
+        (Piece::Bishop, attacks::bishop(sq, occ)),
+        (Piece::Rook, attacks::rook(sq, occ)),
+        (Piece::Queen, attacks::queen(sq, occ)),
+        (Piece::King, attacks::king(sq)),
+    ];
+
+    for (piece, targets) in attackers {
+        for from in (targets & ours(piece)).iter_squares() {
+            gen(piece, Move::capture(from, sq, captured));
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct MoveList
+//
+//#################################################################################################
+
+/// A list of moves stored on the stack, with enough capacity to
+/// hold all legal moves of any position.
+pub struct MoveList {
+    moves: [MaybeUninit<Move>; MoveList::CAPACITY],
+    len: usize,
+}
+
+// ================================ pub impl
+
+impl MoveList {
+    /// The maximum number of moves a list can hold. No position has more legal moves than that.
+    pub const CAPACITY: usize = 256;
+
+    /// Creates a new empty list.
+    #[inline]
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [MaybeUninit::uninit(); MoveList::CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Pushes a move at the end of the list.
+    /// Panics if the list is full.
+    #[inline]
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = MaybeUninit::new(mv);
+        self.len += 1;
+    }
+
+    /// Removes all the moves of the list.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+// ================================ traits impl
+
+impl Default for MoveList {
+    /// Creates a new empty list.
+    fn default() -> MoveList {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    /// Gives access to the slice of the moves of the list, and
+    /// thus to their iterator and number.
+    #[inline]
+    fn deref(&self) -> &[Move] {
+        // SAFE: the first len moves have been initialized by push.
+        unsafe {slice::from_raw_parts(self.moves.as_ptr() as *const Move, self.len)}
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = slice::Iter<'a, Move>;
+
+    /// Iterates over the moves of the list.
+    #[inline]
+    fn into_iter(self) -> slice::Iter<'a, Move> {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for MoveList {
+    /// Displays the moves of the list.
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_list().entries(self.iter()).finish()
+    }
+}
+
 //#################################################################################################
 //
 //                                         fn legals()
@@ -234,52 +376,315 @@ pub fn gen_quiets(board: &Board, mut gen: impl FnMut(Piece, Move)) {
 /// Generates all legal moves for the current position, and pushes them at the end of the buffer, 
 /// in no particular order.
 pub fn legals(board: &Board, buffer: &mut Vec<Move>) {
-    // Generates all non-king moves with the given consumer.
-    pub fn gen_non_king(board: &Board, mut gen: impl FnMut(Move)) {
-        gen_promote_captures(board, &Piece::PROMOTES, |mv| gen(mv));
-        gen_en_passant(board, |mv| gen(mv));
-        gen_pawn_captures(board, |mv| gen(mv));
-        gen_promotes(board, &Piece::PROMOTES, |mv| gen(mv));
-        gen_pushes(board, |mv| gen(mv));
-        gen_captures(board, |_, mv| gen(mv));
-        gen_quiets(board, |_, mv| gen(mv));
+    gen_legals(board, |mv| buffer.push(mv));
+}
+
+/// Generates all legal moves for the current position, and pushes them at the end of the list, 
+/// in no particular order. Does not allocate.
+pub fn legals_into(board: &Board, list: &mut MoveList) {
+    gen_legals(board, |mv| list.push(mv));
+}
+
+/// Returns true if the side to move has at least one legal move. Stops at the first legal move
+/// found, trying the moves most likely to be legal first, and does not allocate.
+pub fn has_legals(board: &Board) -> bool {
+    if board.get_variant().winner(board).is_some() {
+        return false;
     }
 
-    let checkers = board.get_checkers();
+    let legality = Legality::new(board);
+    let legal = |mv: Move| legality.is_legal(board, mv);
 
-    let mut gen = |mv| if board.is_legal(mv) {buffer.push(mv)};
+    // Castling needs not be tried: when it is legal, so is the king's step towards the rook.
+    let mut found = false;
+    gen_king_quiets(board, |mv| found = found || legal(mv));
+    gen_king_captures(board, |mv| found = found || legal(mv));
+    if found || legality.mask.empty() {
+        return found;
+    }
 
-    if checkers.empty() {
-        // No checkers.
+    gen_quiets(board, |_, mv| found = found || legal(mv));
+    if found {
+        return true;
+    }
+    gen_pushes(board, |mv| found = found || legal(mv));
+    if found {
+        return true;
+    }
+    gen_captures(board, |_, mv| found = found || legal(mv));
+    if found {
+        return true;
+    }
+    gen_pawn_captures(board, |mv| found = found || legal(mv));
+    gen_en_passant(board, |mv| found = found || legal(mv));
+    gen_promotes(board, &Piece::PROMOTES, |mv| found = found || legal(mv));
+    gen_promote_captures(board, &Piece::PROMOTES, |mv| found = found || legal(mv));
+    found
+}
 
-        // Generate all castling and king moves. 
-        gen_castles(board, |mv| gen(mv));
-        gen_king_captures(board, |mv| gen(mv));
-        gen_king_quiets(board, |mv| gen(mv));
+/// Returns the number of legal moves of the side to move, without allocating.
+pub fn legals_count(board: &Board) -> usize {
+    let mut count = 0;
+    gen_legals(board, |_| count += 1);
+    count
+}
+
+/// Gives all legal moves of the side to move when it is in check: the moves of the king, and, if there is
+/// a single checker, the moves capturing it or blocking its attack. Gives nothing when not in check.
+/// The provided closure is called for all generated moves.
+pub fn gen_evasions(board: &Board, mut gen: impl FnMut(Move)) {
+    if board.get_checkers().empty() {
+        return;
+    }
 
-        // Generates all other moves.
+    let legality = Legality::new(board);
+    let mut gen = |mv| if legality.is_legal(board, mv) {gen(mv)};
+
+    // Generate all king moves.
+    gen_king_captures(board, &mut gen);
+    gen_king_quiets(board, &mut gen);
+
+    // With two checkers, only the king may move.
+    if legality.mask.not_empty() {
         gen_non_king(board, gen);
-    } else if checkers.is_one() {
-        // One checker.
+    }
+}
 
-        // Generate all king moves.
-        gen_king_captures(board, |mv| gen(mv));
-        gen_king_quiets(board, |mv| gen(mv));
+/// Gives all legal quiet moves giving check, castling included. Neither captures nor promotions are given.
+/// The provided closure is called for all generated moves.
+pub fn gen_quiet_checks(board: &Board, mut gen: impl FnMut(Move)) {
+    let legality = Legality::new(board);
+    let mut gen = |mv| if legality.is_legal(board, mv) && board.gives_check(mv) {gen(mv)};
 
-        // Check that the move is either capturing the checker or blocking it.
-        // SAFE: there is always a king on the board.
-        let checker = unsafe {checkers.as_square_unchecked()};
-        let mask = BitBoard::between(board.king_sq(board.get_side_to_move()), checker) | checkers;
-        let gen = |mv: Move| if mask.contains(mv.to()) && board.is_legal(mv) {buffer.push(mv)};
+    if board.get_checkers().empty() {
+        gen_castles(board, &mut gen);
+    }
+    gen_king_quiets(board, &mut gen);
+
+    if legality.mask.not_empty() {
+        gen_pushes(board, &mut gen);
+        gen_quiets(board, |_, mv| gen(mv));
+    }
+}
+
+/// Generates all legal moves for the current position, calling the provided closure for each of them.
+fn gen_legals(board: &Board, mut push: impl FnMut(Move)) {
+    // No move is legal once the variant ended the game.
+    if board.get_variant().winner(board).is_some() {
+        return;
+    }
 
-        // Generate.
+    let legality = Legality::new(board);
+    let mut gen = |mv| if legality.is_legal(board, mv) {push(mv)};
+
+    // Generate all castling and king moves. Castling is only possible when not in check.
+    if board.get_checkers().empty() {
+        gen_castles(board, &mut gen);
+    }
+    gen_king_captures(board, &mut gen);
+    gen_king_quiets(board, &mut gen);
+
+    // Generates all other moves, unless only the king may move.
+    if legality.mask.not_empty() {
         gen_non_king(board, gen);
+    }
+}
+
+/// Generates all pseudo-legal non-king moves with the given consumer.
+fn gen_non_king(board: &Board, mut gen: impl FnMut(Move)) {
+    gen_promote_captures(board, &Piece::PROMOTES, &mut gen);
+    gen_en_passant(board, &mut gen);
+    gen_pawn_captures(board, &mut gen);
+    gen_promotes(board, &Piece::PROMOTES, &mut gen);
+    gen_pushes(board, &mut gen);
+    gen_captures(board, |_, mv| gen(mv));
+    gen_quiets(board, |_, mv| gen(mv));
+}
+
+/// Returns the mask of the destination squares of the moves of pieces other than the king
+/// that may address the checks of the position: all squares when not in check, the checker
+/// and the squares between it and the king when in check once, and none when in double check.
+fn evasion_mask(board: &Board) -> BitBoard {
+    let checkers = board.get_checkers();
+
+    if checkers.empty() {
+        BitBoard::FULL
+    } else if checkers.is_one() {
+        // SAFE: there is always a king on the board.
+        let checker = unsafe {checkers.as_square_unchecked()};
+        BitBoard::between(board.king_sq(board.get_side_to_move()), checker) | checkers
     } else {
-        // Two checkers.
+        // Only the king may move.
+        BitBoard::EMPTY
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct Legality
+//
+//#################################################################################################
+
+/// What is needed to tell the legal moves of a position among its pseudo-legal ones, computed once
+/// per position rather than once per move. Most moves, those of the pieces other than the king that
+/// are not pinned, are legal as soon as they address the checks: only the moves of the king, of the
+/// pinned pieces and en passant need more work.
+#[derive(Clone, Copy, Debug)]
+pub struct Legality {
+    king_sq: Square,
+    pinned: BitBoard,
+    // The squares pieces other than the king may move to, to address checks.
+    mask: BitBoard,
+}
+
+// ================================ pub impl
+
+impl Legality {
+    /// Computes the legality information of the given position.
+    #[inline]
+    pub fn new(board: &Board) -> Legality {
+        Legality {
+            king_sq: board.king_sq(board.get_side_to_move()),
+            pinned: board.get_pinned(),
+            mask: evasion_mask(board),
+        }
+    }
+
+    /// Returns true if that move, given by one of the generation primitives for the position the legality
+    /// was computed for, is legal. Unlike Board::is_legal(), the moves of the pieces other than the king
+    /// that do not address the checks of the position are rejected.
+    #[inline]
+    pub fn is_legal(&self, board: &Board, mv: Move) -> bool {
+        let (from, to) = mv.squares();
 
-        // Only generate king moves.
-        gen_king_captures(board, |mv| gen(mv));
-        gen_king_quiets(board, |mv| gen(mv));
+        if from == self.king_sq {
+            board.is_legal(mv)
+        } else if !self.evades(board, mv) {
+            false
+        } else if mv.is_en_passant() {
+            board.is_legal(mv)
+        } else {
+            !self.pinned.contains(from) || BitBoard::ray_mask(self.king_sq, from).contains(to)
+        }
+    }
+}
+
+// ================================ impl
+
+impl Legality {
+    /// Returns true if that move of a piece other than the king addresses the checks of the position.
+    /// A pawn taken en passant is not on the destination square of the move, and may be the checker.
+    #[inline]
+    fn evades(&self, board: &Board, mv: Move) -> bool {
+        self.mask.contains(mv.to()) || mv.is_en_passant() && board.get_ep_square().is_some_and(|sq| self.mask.contains(sq))
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct LegalMoves
+//
+//#################################################################################################
+
+/// The stages of the legal moves generation.
+#[derive(Clone, Copy, Debug)]
+enum Stage {
+    Captures,
+    Quiets,
+    Done,
+}
+
+/// An iterator over the legal moves of a position, generating them lazily, stage by stage:
+/// first the captures, then the quiet moves. Cheaper than legals() when only the first
+/// few moves are needed.
+#[derive(Debug)]
+pub struct LegalMoves<'a> {
+    board: &'a Board,
+    stage: Stage,
+    legality: Legality,
+    moves: MoveList,
+    index: usize,
+}
+
+// ================================ pub impl
+
+impl<'a> LegalMoves<'a> {
+    /// Creates a new iterator over the legal moves of the position.
+    pub fn new(board: &'a Board) -> LegalMoves<'a> {
+        LegalMoves {
+            board,
+            stage: Stage::Captures,
+            legality: Legality::new(board),
+            moves: MoveList::new(),
+            index: 0,
+        }
+    }
+}
+
+// ================================ impl
+
+impl LegalMoves<'_> {
+    /// Generates the legal moves of the next stage into the list.
+    fn gen_stage(&mut self) {
+        let (board, legality) = (self.board, self.legality);
+        let moves = &mut self.moves;
+        moves.clear();
+        self.index = 0;
+
+        let mut gen = |mv| if legality.is_legal(board, mv) {moves.push(mv)};
+
+        match self.stage {
+            Stage::Captures => {
+                gen_king_captures(board, &mut gen);
+
+                if legality.mask.not_empty() {
+                    gen_promote_captures(board, &Piece::PROMOTES, &mut gen);
+                    gen_en_passant(board, &mut gen);
+                    gen_pawn_captures(board, &mut gen);
+                    gen_captures(board, |_, mv| gen(mv));
+                }
+
+                self.stage = Stage::Quiets;
+            },
+            Stage::Quiets => {
+                // Castling is only possible when not in check.
+                if board.get_checkers().empty() {
+                    gen_castles(board, &mut gen);
+                }
+                gen_king_quiets(board, &mut gen);
+
+                if legality.mask.not_empty() {
+                    gen_promotes(board, &Piece::PROMOTES, &mut gen);
+                    gen_pushes(board, &mut gen);
+                    gen_quiets(board, |_, mv| gen(mv));
+                }
+
+                self.stage = Stage::Done;
+            },
+            Stage::Done => (),
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl Iterator for LegalMoves<'_> {
+    type Item = Move;
+
+    /// Returns the next legal move, generating the next stages as needed.
+    fn next(&mut self) -> Option<Move> {
+        loop {
+            if let Some(&mv) = self.moves.get(self.index) {
+                self.index += 1;
+                return Some(mv);
+            }
+
+            if let Stage::Done = self.stage {
+                return None;
+            }
+
+            self.gen_stage();
+        }
     }
 }
 
@@ -324,4 +729,296 @@ pub fn perft(board: &mut Board, depth: usize) -> u64 {
     } else {
         internal_perft(board, &mut Vec::new(), depth)
     }
-}
\ No newline at end of file
+}
+
+/// Counts the number of leaf nodes of a given position and a given game tree depth,
+/// storing the counts of the subtrees in a transposition table of roughly table_size
+/// bytes, so that transposed positions are only counted once.
+pub fn perft_hashed(board: &mut Board, depth: usize, table_size: usize) -> u64 {
+    PerftTable::new(table_size).perft(board, depth)
+}
+
+/// Counts the number of leaf nodes of each subtree of the root moves of a given
+/// position and a given game tree depth, which must be at least 1.
+#[cfg(feature = "std")]
+pub fn divide(board: &mut Board, depth: usize) -> HashMap<Move, u64> {
+    let mut list = MoveList::new();
+    legals_into(board, &mut list);
+
+    list.iter().map(|&mv| {
+        board.do_move(mv);
+        let count = perft(board, depth - 1);
+        board.undo_move(mv);
+        (mv, count)
+    }).collect()
+}
+
+/// Same as divide(), but the root moves are shared between the given number of threads.
+/// If table_size is not zero, each thread uses its own transposition table of roughly
+/// table_size bytes, as in perft_hashed().
+#[cfg(feature = "std")]
+pub fn divide_parallel(board: &Board, depth: usize, threads: usize, table_size: usize) -> HashMap<Move, u64> {
+    let mut list = MoveList::new();
+    legals_into(board, &mut list);
+
+    let next = AtomicUsize::new(0);
+    let counts = Mutex::new(HashMap::new());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                let mut board = board.clone();
+                let mut table = (table_size != 0).then(|| PerftTable::new(table_size));
+
+                // Take the next root move that no thread has counted yet.
+                while let Some(&mv) = list.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    board.do_move(mv);
+                    let count = match table.as_mut() {
+                        Some(table) => table.perft(&mut board, depth - 1),
+                        None => perft(&mut board, depth - 1),
+                    };
+                    board.undo_move(mv);
+
+                    counts.lock().unwrap().insert(mv, count);
+                }
+            });
+        }
+    });
+
+    counts.into_inner().unwrap()
+}
+
+//#################################################################################################
+//
+//                                       struct PerftTable
+//
+//#################################################################################################
+
+/// An entry of the perft table: the number of leaf nodes below a position, at a given depth.
+#[derive(Clone, Copy, Default, Debug)]
+struct PerftEntry {
+    zobrist: Zobrist,
+    depth: usize,
+    count: u64,
+}
+
+/// A transposition table used to speed up perft, by remembering the counts of the subtrees.
+#[derive(Debug)]
+struct PerftTable(Vec<PerftEntry>);
+
+// ================================ impl
+
+impl PerftTable {
+    /// Creates a new table, of roughly the given size in bytes.
+    fn new(size: usize) -> PerftTable {
+        PerftTable(vec![PerftEntry::default(); (size / mem::size_of::<PerftEntry>()).max(1)])
+    }
+
+    /// Counts the number of leaf nodes of a given position and a given game tree depth,
+    /// using and filling the table.
+    fn perft(&mut self, board: &mut Board, depth: usize) -> u64 {
+        if depth <= 1 {
+            return perft(board, depth);
+        }
+
+        let zobrist = board.get_zobrist();
+        let index = (zobrist.get_raw() % self.0.len() as u64) as usize;
+
+        let entry = self.0[index];
+        if entry.zobrist == zobrist && entry.depth == depth {
+            return entry.count;
+        }
+
+        let mut list = MoveList::new();
+        legals_into(board, &mut list);
+
+        let mut count = 0;
+        for &mv in list.iter() {
+            board.do_move(mv);
+            count += self.perft(board, depth - 1);
+            board.undo_move(mv);
+        }
+
+        self.0[index] = PerftEntry {zobrist, depth, count};
+        count
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions with checks of every kind: by a pawn that may be taken en passant, by sliders
+    /// that may be blocked, double checks, as well as pins and castling.
+    const FENS: [&str; 7] = [
+        "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+        "8/8/8/8/k2Pp2Q/8/8/4K3 b - d3 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "4k3/8/8/8/1b6/8/3n4/4K2R w K - 0 1",
+        "5k2/8/8/3p4/8/2B5/8/R3K2R w KQ - 0 1",
+    ];
+
+    /// Returns the legal moves of the position, sorted, found by playing every pseudo-legal move
+    /// and keeping those that do not leave the king of the side that played them in check.
+    fn reference_legals(board: &Board) -> Vec<Move> {
+        let mut pseudo = Vec::new();
+        if board.get_checkers().empty() {
+            gen_castles(board, |mv| if board.is_legal(mv) {pseudo.push(mv)});
+        }
+        gen_king_captures(board, |mv| pseudo.push(mv));
+        gen_king_quiets(board, |mv| pseudo.push(mv));
+        gen_non_king(board, |mv| pseudo.push(mv));
+
+        let us = board.get_side_to_move();
+        pseudo.retain(|&mv| {
+            let mut board = board.clone();
+            board.do_move(mv);
+            board.attackers_by(board.king_sq(us), us.invert(), board.get_occupancy().all()).empty()
+        });
+
+        sorted(pseudo)
+    }
+
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|mv| mv.get_raw());
+        moves
+    }
+
+    #[test]
+    fn legality() {
+        for fen in FENS {
+            let board = Board::new(fen).unwrap();
+            let legality = Legality::new(&board);
+
+            let mut legals = Vec::new();
+            super::legals(&board, &mut legals);
+            assert_eq!(sorted(legals), reference_legals(&board), "{}", fen);
+
+            let mut pseudo = Vec::new();
+            gen_non_king(&board, |mv| pseudo.push(mv));
+            for mv in pseudo {
+                assert_eq!(legality.is_legal(&board, mv), reference_legals(&board).contains(&mv), "{} {}", fen, mv);
+            }
+        }
+
+        // The pawn giving check is taken en passant, with the en passant square set by playing the move.
+        let mut board = Board::new("8/8/8/2k5/4p3/8/3P4/4K3 w - - 0 1").unwrap();
+        board.do_move(board.parse_move("d2d4").unwrap());
+        let mut legals = Vec::new();
+        super::legals(&board, &mut legals);
+        assert!(legals.contains(&Move::en_passant(Square::E4, Square::D3)));
+        assert!(has_legals(&board));
+    }
+
+    #[test]
+    fn evasions_and_quiet_checks() {
+        for fen in FENS {
+            let board = Board::new(fen).unwrap();
+            let legals = reference_legals(&board);
+
+            // Out of check, there is no evasion.
+            let mut evasions = Vec::new();
+            gen_evasions(&board, |mv| evasions.push(mv));
+            let expected = if board.get_checkers().empty() {Vec::new()} else {legals.clone()};
+            assert_eq!(sorted(evasions), expected, "{}", fen);
+
+            let gives_check = |mv| {
+                let mut board = board.clone();
+                board.do_move(mv);
+                board.get_checkers().not_empty()
+            };
+            let mut checks = Vec::new();
+            gen_quiet_checks(&board, |mv| checks.push(mv));
+            let expected: Vec<_> = legals.into_iter()
+                .filter(|&mv| !mv.is_capture() && !mv.is_en_passant() && !mv.is_promote() && gives_check(mv))
+                .collect();
+            assert_eq!(sorted(checks), expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn move_list() {
+        let moves = (0..MoveList::CAPACITY).map(|i| Move::quiet(Square::SQUARES[i % 64], Square::SQUARES[(i % 64 + i / 64 + 1) % 64])).collect::<Vec<_>>();
+        let mut list = MoveList::new();
+        assert!(list.is_empty());
+
+        // The list holds as many moves as its capacity, in the order they were pushed.
+        for &mv in &moves {
+            list.push(mv);
+        }
+        assert_eq!(list.len(), MoveList::CAPACITY);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), moves);
+        assert_eq!((&list).into_iter().nth(70), Some(&Move::quiet(Square::G1, Square::A2)));
+
+        list.clear();
+        assert!(list.is_empty());
+        list.push(moves[3]);
+        assert_eq!(&list[..], &moves[3..4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn move_list_overflow() {
+        let mut list = MoveList::new();
+        for _ in 0..=MoveList::CAPACITY {
+            list.push(Move::quiet(Square::A1, Square::A2));
+        }
+    }
+
+    #[test]
+    fn legal_moves() {
+        for fen in FENS {
+            let board = Board::new(fen).unwrap();
+            let moves = LegalMoves::new(&board).collect::<Vec<_>>();
+            assert_eq!(sorted(moves.clone()), reference_legals(&board), "{}", fen);
+
+            // The captures, including en passant, all come before the quiet moves.
+            let is_capture = |mv: &Move| mv.is_capture() || mv.is_en_passant();
+            let captures = moves.iter().take_while(|mv| is_capture(mv)).count();
+            assert!(moves[captures..].iter().all(|mv| !is_capture(mv)), "{}", fen);
+        }
+
+        // The en passant capture and the capturing promotions are in the first stage.
+        let board = Board::new("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        let moves = LegalMoves::new(&board).collect::<Vec<_>>();
+        let first_quiet = moves.iter().position(|mv| !mv.is_capture()).unwrap();
+        assert!(moves[..first_quiet].contains(&board.parse_move("d7c8q").unwrap()));
+        assert!(moves[first_quiet..].contains(&board.parse_move("e1g1").unwrap()));
+
+        let board = Board::new("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        let moves = LegalMoves::new(&board).collect::<Vec<_>>();
+        assert_eq!(moves.iter().take_while(|mv| mv.is_capture() || mv.is_en_passant()).count(), 2);
+        assert!(moves[..2].contains(&Move::en_passant(Square::E4, Square::D3)));
+    }
+
+    #[test]
+    fn captures_to() {
+        // The knight on d5 is attacked by a pawn, a knight, a bishop, a rook, a queen and the king,
+        // and the rook on h8 by a pawn promoting.
+        let board = Board::new("7r/6Pk/2K5/Q2n4/4PN2/8/B7/3R4 w - - 0 1").unwrap();
+        let captures = |sq, promotes: &[Piece]| {
+            let mut captures = Vec::new();
+            gen_captures_to(&board, sq, promotes, |piece, mv| captures.push((piece, mv.to_string())));
+            captures
+        };
+
+        let pieces: Vec<_> = captures(Square::D5, &[]).into_iter().map(|(piece, _)| piece).collect();
+        assert_eq!(pieces, [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]);
+        assert!(captures(Square::D5, &[]).iter().all(|(_, mv)| mv.ends_with("d5")));
+
+        let promotes: Vec<_> = captures(Square::H8, &Piece::PROMOTES).into_iter().map(|(_, mv)| mv).collect();
+        assert_eq!(promotes, ["g7h8q", "g7h8r", "g7h8b", "g7h8n"]);
+
+        // Nothing is given for empty squares and for the pieces of the side to move.
+        assert!(captures(Square::D4, &[]).is_empty());
+        assert!(captures(Square::C4, &[]).is_empty());
+    }
+}
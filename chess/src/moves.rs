@@ -1,8 +1,9 @@
-use std::fmt;
-use std::num::NonZeroU32;
-use std::sync::atomic::AtomicU32;
-use std::sync::atomic::Ordering;
+use core::fmt;
+use core::num::{NonZeroU16, NonZeroU32};
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
 
+use crate::board::Board;
 use crate::piece::Piece;
 use crate::prelude::Color;
 use crate::square::Square;
@@ -25,7 +26,7 @@ fn base(flags: u32, from: Square, to: Square) -> NonZeroU32 {
 /// pppcccttttttffffffmmmmm, where m is the type of the move, 
 /// f is the from square, t is the to square, c is the captured piece
 /// and p is the promote piece.
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct Move(NonZeroU32);
 
 // ================================ pub impl
@@ -126,23 +127,51 @@ impl Move {
         (self.from(), self.to())
     }
 
-    /// Returns the capture piece of the move.
+    /// Returns the capture piece of the move. The move must be a capture, or the
+    /// result is meaningless: see captured() for a checked version.
     #[inline]
     pub fn get_capture(self) -> Piece {
         Piece::PIECES[(u32::from(self.0) >> 17 & 0x7) as usize]
     }
 
-    /// Returns the promote piece of the move.
+    /// Returns the promote piece of the move. The move must be a promotion, or the
+    /// result is meaningless: see promotion() for a checked version.
     #[inline]
     pub fn get_promote(self) -> Piece {
         Piece::PIECES[(u32::from(self.0) >> 20 & 0x7) as usize]
     }
 
+    /// Returns the piece captured by the move, if any. En passant moves capture a pawn.
+    #[inline]
+    pub fn captured(self) -> Option<Piece> {
+        if self.is_capture() {
+            Some(self.get_capture())
+        } else if self.is_en_passant() {
+            Some(Piece::Pawn)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the piece the move promotes to, if it is a promotion.
+    #[inline]
+    pub fn promotion(self) -> Option<Piece> {
+        self.is_promote().then(|| self.get_promote())
+    }
+
     /// Returns the raw value of the move.
     #[inline]
     pub fn get_raw(self) -> u32 {
         u32::from(self.0)
     }
+
+    /// Creates a move from its raw value, as returned by get_raw(), or returns None if
+    /// the value does not encode a move. The move may still be illegal in any position.
+    #[inline]
+    pub fn from_raw(raw: u32) -> Option<Move> {
+        let valid = raw >> 23 == 0 && (raw >> 17 & 0x7) < 6 && (raw >> 20 & 0x7) < 6 && (raw >> 5 & 0x3F) != (raw >> 11 & 0x3F);
+        NonZeroU32::new(raw).filter(|_| valid).map(Move)
+    }
 }
 
 // ================================ impl
@@ -186,6 +215,100 @@ impl fmt::Debug for Move {
     }
 }
 
+//#################################################################################################
+//
+//                                       struct PackedMove
+//
+//#################################################################################################
+
+/// A move packed in 16 bits, half the size of a Move, for when moves are stored in bulk.
+/// Only the squares and the promotion are kept, encoded as 0ppptttttt ffffff in big endian,
+/// where p is the promote piece, or 0 if there is none: the rest of the move is
+/// recovered from the position it is played in, with unpack(). Moves are packed with into().
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
+pub struct PackedMove(NonZeroU16);
+
+// ================================ pub impl
+
+impl PackedMove {
+    /// Returns the from square of the move.
+    #[inline]
+    pub fn from_square(self) -> Square {
+        Square::SQUARES[(u16::from(self.0) & 0x3F) as usize]
+    }
+
+    /// Returns the to square of the move.
+    #[inline]
+    pub fn to_square(self) -> Square {
+        Square::SQUARES[(u16::from(self.0) >> 6 & 0x3F) as usize]
+    }
+
+    /// Returns the piece the move promotes to, if it is a promotion.
+    #[inline]
+    pub fn promotion(self) -> Option<Piece> {
+        match u16::from(self.0) >> 12 {
+            0 => None,
+            piece => Some(Piece::PIECES[piece as usize]),
+        }
+    }
+
+    /// Recovers the move in the position it is played in, or returns None if
+    /// it is not pseudo-legal there.
+    pub fn unpack(self, board: &Board) -> Option<Move> {
+        let (from, to) = (self.from_square(), self.to_square());
+        let (_, piece) = board.get_piece(from)?;
+        let capture = board.get_piece(to).map(|(_, capture)| capture);
+
+        let mv = match (piece, capture, self.promotion()) {
+            (Piece::Pawn, None, None) if from.file_index() != to.file_index() => Move::en_passant(from, to),
+            (Piece::Pawn, None, None) if from.rank_index().abs_diff(to.rank_index()) == 2 => Move::double_push(from, to),
+            (Piece::King, None, None) if from.file_index().abs_diff(to.file_index()) == 2 => Move::castle(from, to),
+            (_, None, None) => Move::quiet(from, to),
+            (_, Some(capture), None) => Move::capture(from, to, capture),
+            (_, None, Some(promote)) => Move::promote(from, to, promote),
+            (_, Some(capture), Some(promote)) => Move::promote_capture(from, to, capture, promote),
+        };
+
+        board.is_pseudo_legal(mv).then_some(mv)
+    }
+
+    /// Returns the raw value of the packed move.
+    #[inline]
+    pub fn get_raw(self) -> u16 {
+        u16::from(self.0)
+    }
+
+    /// Creates a packed move from its raw value, as returned by get_raw(), or returns None
+    /// if the value does not encode a move.
+    #[inline]
+    pub fn from_raw(raw: u16) -> Option<PackedMove> {
+        let valid = raw >> 12 <= Piece::Queen as u16 && (raw & 0x3F) != (raw >> 6 & 0x3F);
+        NonZeroU16::new(raw).filter(|_| valid).map(PackedMove)
+    }
+}
+
+// ================================ traits impl
+
+impl From<Move> for PackedMove {
+    #[inline]
+    fn from(mv: Move) -> PackedMove {
+        let promote = mv.promotion().map_or(0, |piece| piece as u16);
+        let raw = mv.from() as u16 | (mv.to() as u16) << 6 | promote << 12;
+        // SAFE: from and to are not equal, at least one of them is non zero.
+        PackedMove(unsafe {NonZeroU16::new_unchecked(raw)})
+    }
+}
+
+impl fmt::Display for PackedMove {
+    /// Displays a packed move using pure algebraic coordinate notation.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.promotion() {
+            Some(piece) => write!(fmt, "{}{}{}", self.from_square(), self.to_square(), piece.as_char(Color::Black)),
+            None => write!(fmt, "{}{}", self.from_square(), self.to_square()),
+        }
+    }
+}
+
 //#################################################################################################
 //
 //                                    struct AtomicMove
@@ -217,4 +340,69 @@ impl AtomicMove {
     pub fn store(&self, mv: Move) {
         self.0.store(u32::from(mv.0), Ordering::Release);
     }
-}
\ No newline at end of file
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::movegen;
+    use crate::moves::{Move, PackedMove};
+    use crate::piece::Piece;
+    use crate::square::Square;
+
+    #[test]
+    fn checked_accessors() {
+        assert_eq!(Move::quiet(Square::G1, Square::F3).captured(), None);
+        assert_eq!(Move::quiet(Square::G1, Square::F3).promotion(), None);
+        assert_eq!(Move::en_passant(Square::E5, Square::D6).captured(), Some(Piece::Pawn));
+
+        let mv = Move::promote_capture(Square::B7, Square::A8, Piece::Rook, Piece::Knight);
+        assert_eq!(mv.captured(), Some(Piece::Rook));
+        assert_eq!(mv.promotion(), Some(Piece::Knight));
+    }
+
+    #[test]
+    fn packed_roundtrip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ];
+
+        for fen in fens {
+            let board = Board::new(fen).unwrap();
+            let mut legals = Vec::new();
+            movegen::legals(&board, &mut legals);
+
+            // The positions have castling, en passant and promotions, with and without captures.
+            for mv in legals {
+                let packed = PackedMove::from(mv);
+                assert_eq!((packed.from_square(), packed.to_square(), packed.promotion()), (mv.from(), mv.to(), mv.promotion()));
+                assert_eq!(packed.to_string(), mv.to_string());
+                assert_eq!(PackedMove::from_raw(packed.get_raw()), Some(packed));
+                assert_eq!(packed.unpack(&board), Some(mv));
+            }
+        }
+
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let castle = PackedMove::from(Move::castle(Square::E1, Square::C1));
+        assert_eq!(castle.unpack(&board), Some(Move::castle(Square::E1, Square::C1)));
+
+        let board = Board::new("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        let promotion = PackedMove::from(Move::promote_capture(Square::D7, Square::C8, Piece::Bishop, Piece::Knight));
+        assert_eq!(promotion.to_string(), "d7c8n");
+        assert_eq!(promotion.unpack(&board), Some(Move::promote_capture(Square::D7, Square::C8, Piece::Bishop, Piece::Knight)));
+
+        // A move that is not pseudo-legal in the position is not unpacked.
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let packed: PackedMove = Move::quiet(Square::E2, Square::E5).into();
+        assert_eq!(packed.unpack(&board), None);
+        assert_eq!(PackedMove::from_raw(0x5000 | Square::E2 as u16 | (Square::E4 as u16) << 6), None);
+    }
+}
@@ -1,6 +1,7 @@
 use std::env;
 use std::str::FromStr;
 use std::thread;
+use std::time::Instant;
 
 use anyhow::{Error, Result};
 use clap::App;
@@ -22,6 +23,14 @@ use clap::Arg;
  * For a quick and dirty benchmark:
  * $ cargo build --bin perft --release 
  * $ time target/release/perft 6 "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+ *
+ * For measuring the node rate of the move generator, on a single thread and without any table:
+ * $ cargo build --bin perft --release 
+ * $ target/release/perft 6 "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1" --bench
+ *
+ * For deep perft runs, with a 256MB transposition table per thread:
+ * $ cargo build --bin perft --release 
+ * $ target/release/perft 7 "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1" --hash 256
  */
 
 fn main() -> Result<()> {
@@ -44,65 +53,70 @@ fn main() -> Result<()> {
             .index(3)
             .value_name("MOVES")
             .help("A space seperated serie of moves to perform before beginning game tree expansion."))
+        .arg(Arg::with_name("threads")
+            .short("t")
+            .long("threads")
+            .value_name("THREADS")
+            .help("The number of threads counting nodes in parallel. Defaults to the number of available cores.")
+            .takes_value(true))
+        .arg(Arg::with_name("hash")
+            .short("H")
+            .long("hash")
+            .value_name("MEGABYTES")
+            .default_value("0")
+            .help("The size of the transposition table of each thread, in megabytes. Disabled when 0.")
+            .takes_value(true))
+        .arg(Arg::with_name("bench")
+            .short("b")
+            .long("bench")
+            .help("Only counts the total number of nodes, on a single thread and without table, and prints the node rate."))
         .get_matches();
 
     // Parse depth.
     let depth = usize::from_str(args.value_of("depth").unwrap()).map_err(|_| Error::msg("Unable to parse depth."))?;
-    if !(0..=12).contains(&depth) {
+    if !(1..=12).contains(&depth) {
         return Err(Error::msg("Invalid depth, depth must be between 1 and 12."));
     }
-    
-    // Initialize the chess library.
-    chess::init();
 
+    // Parse the number of threads and the size of the tables.
+    let threads = match args.value_of("threads") {
+        Some(arg) => usize::from_str(arg).map_err(|_| Error::msg("Unable to parse threads."))?,
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let hash = usize::from_str(args.value_of("hash").unwrap()).map_err(|_| Error::msg("Unable to parse hash."))?;
+    
     // Parse the board.
     let mut board = Board::from_str(args.value_of("fen").unwrap())?;
 
     // Parse and do the moves to apply.
     if let Some(arg) = args.value_of("moves") {
-        for s in arg.split(' ') {
-            let mv = board.parse_move(s)?;
-            board.do_move(mv);
-        }
+        board.apply_moves([arg])?;
     }
 
-    // Compute the legal moves of the starting position.
-    let mut list = Vec::new();
-    movegen::legals(&board, &mut list);
+    // Measure the node rate of the move generator.
+    if args.is_present("bench") {
+        let start = Instant::now();
+        let total = movegen::perft(&mut board, depth);
+        let elapsed = start.elapsed().as_secs_f64();
 
-    // The total number of nodes.
-    let mut total = 0;
-    
-    if depth == 1 {
-        // Special case if depth is only one.
-        for &mv in list.iter() {
-            println!("{} 1", mv);
-        }
-
-        // Bulk-count the number of nodes.
-        total = list.len() as u64;
-    } else {
-        // Launch a thread for each move.
-        let mut handles = Vec::new();
+        println!("{} nodes in {:.3}s: {:.0} nodes/s", total, elapsed, total as f64 / elapsed);
+        return Ok(());
+    }
 
-        // For each thread, assign it a move to perform before perft.
-        for &mv in list.iter() {
-            let mut board = board.clone();
+    // Count the nodes below each root move, sharing the root moves between the threads.
+    let counts = movegen::divide_parallel(&board, depth, threads, hash << 20);
 
-            handles.push(thread::spawn(move || {
-                board.do_move(mv);
-                movegen::perft(&mut board, depth - 1)
-            }));
-        }
+    // Print the counts in the order the moves are generated.
+    let mut list = movegen::MoveList::new();
+    movegen::legals_into(&board, &mut list);
 
-        // Join all thread handles and get results.
-        for (handle, mv) in handles.into_iter().zip(list) {
-            let count = handle.join().unwrap();
-            println!("{} {}", mv, count);
-            total += count;
-        }
+    for mv in list.iter() {
+        println!("{} {}", mv, counts[mv]);
     }
 
+    // The total number of nodes.
+    let total: u64 = counts.values().sum();
+
     // Print the total after an empty line.
     println!("\n{}", total);
 
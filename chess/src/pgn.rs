@@ -0,0 +1,481 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+use crate::error::{Error, Result};
+
+use crate::board::{Board, Status};
+use crate::color::Color;
+use crate::moves::Move;
+
+/// The fen of the standard starting position. Games starting from another
+/// position have it recorded in their "FEN" tag.
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The maximum length of the lines of the movetext, as recommended by the PGN standard.
+const MAX_LINE_LENGTH: usize = 80;
+
+/// Returns the PGN literal of a game result.
+pub fn result_str(result: Status) -> &'static str {
+    match result {
+        Status::Playing => "*",
+        Status::Draw => "1/2-1/2",
+        Status::Win(Color::White) => "1-0",
+        Status::Win(Color::Black) => "0-1",
+    }
+}
+
+/// Parses a PGN game result literal.
+fn parse_result(s: &str) -> Option<Status> {
+    match s {
+        "*" => Some(Status::Playing),
+        "1/2-1/2" => Some(Status::Draw),
+        "1-0" => Some(Status::Win(Color::White)),
+        "0-1" => Some(Status::Win(Color::Black)),
+        _ => None,
+    }
+}
+
+/// Parses a tag pair, of the form [Name "Value"], escaped characters being preceded by backslashes.
+fn parse_tag(line: &str) -> Result<(String, String)> {
+    let inner = line.strip_prefix('[').and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| Error::InvalidPgn {reason: "tag pair is not enclosed in brackets".to_string()})?;
+    let (name, value) = inner.split_once(char::is_whitespace)
+        .ok_or_else(|| Error::InvalidPgn {reason: "tag pair has no value".to_string()})?;
+    let value = value.trim().strip_prefix('"').and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| Error::InvalidPgn {reason: "tag value is not a string".to_string()})?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' {chars.next().unwrap_or(c)} else {c});
+    }
+
+    Ok((name.to_string(), unescaped))
+}
+
+/// Returns the date of the proleptic gregorian calendar that is the given number of days after
+/// the 1st of january 1970, as its year, month and day.
+#[cfg(feature = "std")]
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Counts from the 1st of march of year 0, so that leap days end the years, in eras of 400 years.
+    let days = days + 719468;
+    let (era, day_of_era) = (days / 146097, days % 146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 {month + 3} else {month - 9};
+
+    (era * 400 + year_of_era + u64::from(month <= 2), month, day)
+}
+
+/// Splits a movetext into its move and result tokens, dropping move numbers, comments,
+/// variations and numeric annotation glyphs.
+fn movetext_tokens(s: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => if !chars.by_ref().any(|c| c == '}') {
+                return Err(Error::InvalidPgn {reason: "unterminated comment in movetext".to_string()});
+            },
+            ';' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some('{') => if !chars.by_ref().any(|c| c == '}') {
+                            return Err(Error::InvalidPgn {reason: "unterminated comment in movetext".to_string()});
+                        },
+                        Some(_) => (),
+                        None => return Err(Error::InvalidPgn {reason: "unterminated variation in movetext".to_string()}),
+                    }
+                }
+            },
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token = c.to_string();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && !"{;(".contains(c)) {
+                    token.push(c);
+                }
+
+                // Move numbers may be attached to the move that follows them. Only digits followed by
+                // a dot are a move number, castling may be written "0-0".
+                let number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                if number.starts_with('.') {
+                    token = number.trim_start_matches('.').to_string();
+                }
+
+                if !token.is_empty() && !token.starts_with('$') {
+                    tokens.push(token);
+                }
+            },
+        }
+    }
+
+    Ok(tokens)
+}
+
+//#################################################################################################
+//
+//                                           struct Pgn
+//
+//#################################################################################################
+
+/// A game in portable game notation: its tag pairs, its starting position,
+/// the moves played, the comments following them and its result. Comments and variations
+/// are not kept when parsing.
+#[derive(Clone, Debug)]
+pub struct Pgn {
+    tags: Vec<(String, String)>,
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    comments: Vec<Option<String>>,
+    result: Status,
+}
+
+// ================================ pub impl
+
+impl Pgn {
+    /// Creates a new game starting from the given position, with the seven tags
+    /// required by the standard set to unknown values.
+    pub fn new(start: Board) -> Pgn {
+        let tags = ["Event", "Site", "Date", "Round", "White", "Black"].iter()
+            .zip(["?", "?", "????.??.??", "?", "?", "?"])
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Pgn {
+            tags,
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+            comments: Vec::new(),
+            result: Status::Playing,
+        }
+    }
+
+    /// Reads all the games of a PGN file.
+    #[cold]
+    #[cfg(feature = "std")]
+    pub fn open(path: &Path) -> Result<Vec<Pgn>> {
+        let text = fs::read_to_string(path)?;
+        let mut games = Vec::new();
+
+        // A new game starts with the first tag pair following a movetext.
+        let mut game = String::new();
+        let mut in_movetext = false;
+        for line in text.lines() {
+            let tag = line.trim_start().starts_with('[');
+            if tag && in_movetext {
+                games.push(Pgn::from_str(&game)?);
+                game.clear();
+                in_movetext = false;
+            } else if !tag && !line.trim().is_empty() {
+                in_movetext = true;
+            }
+
+            game.push_str(line);
+            game.push('\n');
+        }
+
+        if !game.trim().is_empty() {
+            games.push(Pgn::from_str(&game)?);
+        }
+
+        Ok(games)
+    }
+
+    /// Writes the game to a file.
+    #[cold]
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Returns the value of the tag with the given name, if it is present.
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of the tag with the given name. The "Result", "SetUp" and "FEN"
+    /// tags are derived from the game and are ignored.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        if matches!(name, "Result" | "SetUp" | "FEN") {
+            return;
+        }
+
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Sets the "Date" tag to the current date, in UTC.
+    #[cfg(feature = "std")]
+    pub fn set_date_today(&mut self) {
+        let days = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() / 86400);
+        let (year, month, day) = civil_date(days);
+        self.set_tag("Date", &format!("{:04}.{:02}.{:02}", year, month, day));
+    }
+
+    /// Returns the starting position of the game.
+    #[inline]
+    pub fn get_start(&self) -> &Board {
+        &self.start
+    }
+
+    /// Returns the position reached at the end of the game.
+    #[inline]
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the moves of the game.
+    #[inline]
+    pub fn get_moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Returns the result of the game, Status::Playing if it is unknown or still ongoing.
+    #[inline]
+    pub fn get_result(&self) -> Status {
+        self.result
+    }
+
+    /// Sets the result of the game, for example when a player resigns.
+    #[inline]
+    pub fn set_result(&mut self, result: Status) {
+        self.result = result;
+    }
+
+    /// Plays the given move, which must be legal, and updates the result of the game
+    /// if the move ends it.
+    pub fn push(&mut self, mv: Move) {
+        self.board.do_move(mv);
+        self.moves.push(mv);
+        self.comments.push(None);
+        self.result = self.board.status();
+    }
+
+    /// Returns the comment following the move of the given index, if it has one.
+    #[inline]
+    pub fn get_comment(&self, index: usize) -> Option<&str> {
+        self.comments.get(index)?.as_deref()
+    }
+
+    /// Sets the comment following the last move played, replacing any previous one.
+    /// Does nothing if no move was played.
+    pub fn set_comment(&mut self, comment: &str) {
+        if let Some(last) = self.comments.last_mut() {
+            // A comment ends at the first closing brace.
+            *last = Some(comment.replace('}', ")"));
+        }
+    }
+
+    /// Sets the comment following the move of the given index, replacing any previous one.
+    /// Does nothing if there is no such move.
+    pub fn set_comment_at(&mut self, index: usize, comment: &str) {
+        if let Some(old) = self.comments.get_mut(index) {
+            *old = Some(comment.replace('}', ")"));
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl FromStr for Pgn {
+    type Err = Error;
+
+    /// Parses a single game, moves being written in standard algebraic notation
+    /// or in pure coordinate notation.
+    fn from_str(s: &str) -> Result<Pgn> {
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+
+        // Blank lines, such as the ones around the tags, separate nothing in the movetext.
+        for line in s.lines() {
+            let line = line.trim();
+            if line.starts_with('[') && movetext.is_empty() {
+                tags.push(parse_tag(line)?);
+            } else if !line.is_empty() && !line.starts_with('%') {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+
+        let tag = |name: &str| tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str());
+        let start = Board::from_str(tag("FEN").unwrap_or(STARTING_FEN))?;
+
+        let mut pgn = Pgn::new(start);
+        for (name, value) in &tags {
+            pgn.set_tag(name, value);
+        }
+        pgn.result = tag("Result").and_then(parse_result).unwrap_or(Status::Playing);
+
+        for token in movetext_tokens(&movetext)? {
+            if let Some(result) = parse_result(&token) {
+                pgn.result = result;
+                break;
+            }
+
+            let mv = pgn.board.parse_san(&token).or_else(|_| pgn.board.parse_move(&token))
+                .map_err(|_| Error::InvalidPgn {reason: format!("invalid move in movetext \"{}\"", token)})?;
+            pgn.board.do_move(mv);
+            pgn.moves.push(mv);
+            pgn.comments.push(None);
+        }
+
+        Ok(pgn)
+    }
+}
+
+impl fmt::Display for Pgn {
+    /// Formats the game in the export format of the PGN standard.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tags = self.tags.iter().map(|(name, value)| (name.as_str(), value.clone())).collect::<Vec<_>>();
+        tags.insert(tags.len().min(6), ("Result", result_str(self.result).to_string()));
+
+        let fen = self.start.to_string();
+        if fen != STARTING_FEN {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", fen));
+        }
+
+        for (name, value) in tags {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        writeln!(f)?;
+
+        // The movetext, wrapped to a maximum line length.
+        let mut board = self.start.clone();
+        let mut tokens = Vec::new();
+        for (i, &mv) in self.moves.iter().enumerate() {
+            let number = board.get_ply() / 2 + 1;
+            match board.get_side_to_move() {
+                Color::White => tokens.push(format!("{}.", number)),
+                Color::Black if i == 0 => tokens.push(format!("{}...", number)),
+                Color::Black => (),
+            }
+
+            tokens.push(board.to_san(mv));
+            board.do_move(mv);
+
+            if let Some(comment) = &self.comments[i] {
+                tokens.push(format!("{{{}}}", comment));
+            }
+        }
+        tokens.push(result_str(self.result).to_string());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length != 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            } else if line_length != 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+
+            write!(f, "{}", token)?;
+            line_length += token.len();
+        }
+
+        writeln!(f)
+    }
+}
+
+//#################################################################################################
+//
+//                                              tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{civil_date, Pgn};
+    use crate::board::{Board, Status};
+    use crate::color::Color;
+
+    #[test]
+    fn pgn_round_trip() {
+        let text = r#"[Event "Casual \"game\""]
+[Site "?"]
+[Date "2021.05.01"]
+[Round "-"]
+[White "Alice"]
+[Black "Bob"]
+[Result "0-1"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 3.Bc4 $1 Nd4 4. Nxe5 Qg5 5. Nxf7 Qxg2
+6. Rf1 Qxe4+ 7. Be2 Nf3# 0-1
+"#;
+
+        let pgn = Pgn::from_str(text).unwrap();
+        assert_eq!(pgn.get_tag("Event"), Some("Casual \"game\""));
+        assert_eq!(pgn.get_tag("White"), Some("Alice"));
+        assert_eq!(pgn.get_moves().len(), 14);
+        assert_eq!(pgn.get_result(), Status::Win(Color::Black));
+        assert_eq!(pgn.get_board().status(), Status::Win(Color::Black));
+
+        let again = Pgn::from_str(&pgn.to_string()).unwrap();
+        assert_eq!(again.get_moves(), pgn.get_moves());
+        assert_eq!(again.get_tag("Event"), pgn.get_tag("Event"));
+        assert!(pgn.to_string().contains("[Black \"Bob\"]\n[Result \"0-1\"]\n"));
+
+        let mut pgn = Pgn::new(Board::from_str("4k3/8/8/8/8/8/8/4K2R b K - 3 20").unwrap());
+        pgn.push(pgn.get_board().parse_move("e8d7").unwrap());
+        pgn.set_comment("the {only} move");
+        pgn.push(pgn.get_board().parse_move("e1g1").unwrap());
+        let text = pgn.to_string();
+        assert!(text.contains("[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 3 20\"]"));
+        assert!(text.ends_with("20... Kd7 {the {only) move} 21. O-O *\n"));
+        assert_eq!(pgn.get_comment(0), Some("the {only) move"));
+        assert_eq!(Pgn::from_str(&text).unwrap().get_moves(), pgn.get_moves());
+    }
+
+    #[test]
+    fn blank_lines() {
+        let pgn = Pgn::from_str("\n\n[White \"Alice\"]\n\n[Black \"Bob\"]\n\n1. e4 e5\n\n2. Nf3 *\n\n").unwrap();
+        assert_eq!(pgn.get_tag("White"), Some("Alice"));
+        assert_eq!(pgn.get_tag("Black"), Some("Bob"));
+        assert_eq!(pgn.get_moves().len(), 3);
+    }
+
+    #[test]
+    fn castling_with_zeroes() {
+        let pgn = Pgn::from_str("1. e4 e5 2. Nf3 Nc6 3. Bc4 d6 4.0-0 Be6 5. d3 Qd7 6. Nc3 0-0-0 7. a3 Kb8 1/2-1/2").unwrap();
+        assert_eq!(pgn.get_moves().len(), 14);
+        assert!(pgn.get_moves()[6].is_castle() && pgn.get_moves()[11].is_castle());
+        assert_eq!(pgn.get_result(), Status::Draw);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(59), (1970, 3, 1));
+        assert_eq!(civil_date(11016), (2000, 2, 29));
+        assert_eq!(civil_date(11017), (2000, 3, 1));
+        assert_eq!(civil_date(20818), (2026, 12, 31));
+
+        let mut pgn = Pgn::new(Board::default());
+        pgn.set_date_today();
+        assert_ne!(pgn.get_tag("Date"), Some("????.??.??"));
+    }
+}
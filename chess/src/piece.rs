@@ -1,4 +1,6 @@
-use anyhow::{Error, Result};
+use core::convert::TryFrom;
+
+use crate::error::{Error, Result};
 
 use crate::color::Color;
 
@@ -11,6 +13,7 @@ use crate::color::Color;
 /// Represents a piece.
 #[repr(u8)]
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Piece {
     Pawn = 0,
     Knight = 1,
@@ -34,6 +37,24 @@ impl Piece {
         Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight,
     ];
 
+    /// Returns an iterator over all pieces, in order.
+    #[inline]
+    pub fn iter() -> impl Iterator<Item = Piece> {
+        Piece::PIECES.iter().copied()
+    }
+
+    /// Tries to parse the piece a pawn promotes to, from the lower case char
+    /// used in pure algebraic coordinates notation.
+    pub fn from_promotion_char(c: char) -> Result<Piece, Error> {
+        match c {
+            'r' => Ok(Piece::Rook),
+            'n' => Ok(Piece::Knight),
+            'b' => Ok(Piece::Bishop),
+            'q' => Ok(Piece::Queen),
+            _ => Err(Error::ParsePiece),
+        }
+    }
+
     /// Tries to parse a piece from a single char.
     pub fn from_char(c: char) -> Result<(Color, Piece), Error> {
         match c {
@@ -49,7 +70,7 @@ impl Piece {
             'b' => Ok((Color::Black, Piece::Bishop)),
             'q' => Ok((Color::Black, Piece::Queen)),
             'k' => Ok((Color::Black, Piece::King)),
-            _ => Err(Error::msg("unrecognized piece literal")),
+            _ => Err(Error::ParsePiece),
         }
     }
 
@@ -71,6 +92,31 @@ impl Piece {
             (Color::Black, Piece::King)   => 'k',
         }
     }
+
+    /// Returns the conventional material value of the piece, in pawns: 1 for a pawn, 3 for a knight
+    /// or a bishop, 5 for a rook and 9 for a queen. Kings are never captured, they are worth nothing.
+    #[inline]
+    pub const fn material(self) -> u16 {
+        match self {
+            Piece::Pawn => 1,
+            Piece::Knight | Piece::Bishop => 3,
+            Piece::Rook => 5,
+            Piece::Queen => 9,
+            Piece::King => 0,
+        }
+    }
+
+    /// Returns the weight of the piece in the phase of the game: 1 for a knight or a bishop,
+    /// 2 for a rook and 4 for a queen. Pawns and kings do not count.
+    #[inline]
+    pub const fn phase(self) -> u16 {
+        match self {
+            Piece::Knight | Piece::Bishop => 1,
+            Piece::Rook => 2,
+            Piece::Queen => 4,
+            Piece::Pawn | Piece::King => 0,
+        }
+    }
 }
 
 // ================================ traits impl
@@ -81,4 +127,38 @@ impl From<Piece> for usize {
     fn from(piece: Piece) -> usize {
         piece as usize
     }
-}
\ No newline at end of file
+}
+
+impl TryFrom<u8> for Piece {
+    type Error = Error;
+
+    /// Tries to get the piece of the given value.
+    fn try_from(i: u8) -> Result<Piece, Error> {
+        Piece::PIECES.get(usize::from(i)).copied().ok_or(Error::ParsePiece)
+    }
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn helpers() {
+        assert_eq!(Piece::iter().collect::<Vec<_>>(), [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]);
+        assert_eq!(Piece::try_from(1).unwrap(), Piece::Knight);
+        assert_eq!(Piece::try_from(5).unwrap(), Piece::King);
+        assert!(Piece::try_from(6).is_err());
+
+        assert_eq!(Piece::from_promotion_char('q').unwrap(), Piece::Queen);
+        assert_eq!(Piece::from_promotion_char('n').unwrap(), Piece::Knight);
+        assert!(Piece::from_promotion_char('k').is_err());
+        assert!(Piece::from_promotion_char('p').is_err());
+        assert!(Piece::from_promotion_char('Q').is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+
+use crate::board::Board;
+use crate::movegen::{self, MoveList};
+use crate::moves::Move;
+
+/// The fen of the starting position, from which random games are played.
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// The xorshift* algorithm, producing 64 bits non-cryptographic numbers. The seed must not be zero.
+fn xorshift64(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545F4914F6CDD1D)
+}
+
+/// Plays a random legal game from the starting position, choosing each move uniformly among the
+/// legal ones, until the game is over or max_plies moves were played. The same seed always gives
+/// the same game. Returns the final board, whose history holds the game, and the moves played.
+pub fn random_game(seed: u64, max_plies: usize) -> (Board, Vec<Move>) {
+    let mut board = Board::new(STARTING_FEN).unwrap();
+    let mut moves = Vec::new();
+    // The state of xorshift must not be zero.
+    let mut state = seed | 1;
+    let mut legals = MoveList::new();
+
+    while moves.len() < max_plies && board.status().is_playing() {
+        legals.clear();
+        movegen::legals_into(&board, &mut legals);
+
+        let mv = legals[(xorshift64(&mut state) % legals.len() as u64) as usize];
+        board.do_move(mv);
+        moves.push(mv);
+    }
+
+    (board, moves)
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::random;
+
+    #[test]
+    fn random_game() {
+        for seed in 0..32 {
+            let (mut board, moves) = random::random_game(seed, 200);
+            assert_eq!(random::random_game(seed, 200).1, moves);
+            assert!(moves.len() == 200 || !board.status().is_playing());
+
+            // The board may be taken back to the starting position, and the
+            // incremental zobrist key matches the one computed from scratch.
+            for &mv in moves.iter().rev() {
+                assert_eq!(board.get_zobrist(), Board::new(&board.to_string()).unwrap().get_zobrist());
+                assert_eq!(board.undo_last(), Some(mv));
+            }
+            assert_eq!(board.to_string(), random::STARTING_FEN);
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use std::fmt;
-use std::str::FromStr;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
 
-use anyhow::{Error, Result};
+use crate::error::{Error, Result};
 
 use crate::bitboard::BitBoard;
 use crate::color::Color;
@@ -15,6 +16,7 @@ use crate::color::Color;
 /// Represents a Square of the board.
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Square {
     A1 =  0, B1 =  1, C1 =  2, D1 =  3, E1 =  4, F1 =  5, G1 =  6, H1 =  7,
     A2 =  8, B2 =  9, C2 = 10, D2 = 11, E2 = 12, F2 = 13, G2 = 14, H2 = 15, 
@@ -41,6 +43,12 @@ impl Square {
         Square::A8, Square::B8, Square::C8, Square::D8, Square::E8, Square::F8, Square::G8, Square::H8,
     ];
 
+    /// Returns an iterator over all squares in order: ranks first then files, starting from A1.
+    #[inline]
+    pub fn iter() -> impl Iterator<Item = Square> {
+        Square::SQUARES.iter().copied()
+    }
+
     /// Returns the x coodinate of that square.
     #[inline]
     pub const fn x(self) -> i8 {
@@ -69,6 +77,45 @@ impl Square {
         }
     }
 
+    /// Get the file the square stands on.
+    #[inline]
+    pub fn file(self) -> BitBoard {
+        BitBoard::FILE_A << self.x() as u32
+    }
+
+    /// Returns the index of the file of that square, from 0 for the a-file to 7 for the h-file.
+    #[inline]
+    pub const fn file_index(self) -> u8 {
+        self.x() as u8
+    }
+
+    /// Returns the index of the rank of that square, from 0 for the first rank to 7 for the eighth.
+    #[inline]
+    pub const fn rank_index(self) -> u8 {
+        self.y() as u8
+    }
+
+    /// Returns the square symmetric to that one relatively to the middle
+    /// of the board, on the same file: A1 becomes A8, E2 becomes E7.
+    #[inline]
+    pub const fn mirror(self) -> Square {
+        Square::SQUARES[self as usize ^ 56]
+    }
+
+    /// Returns the square symmetric to that one relatively to the middle
+    /// of the board, on the same rank: A1 becomes H1, E2 becomes D2.
+    #[inline]
+    pub const fn flip_horizontal(self) -> Square {
+        Square::SQUARES[self as usize ^ 7]
+    }
+
+    /// Returns the square at the same place on a board seen from the other side,
+    /// as when the board is flipped: A1 becomes H8, E2 becomes D7.
+    #[inline]
+    pub const fn rotate(self) -> Square {
+        Square::SQUARES[self as usize ^ 63]
+    }
+
     /// Returns the color of that square on the board.
     #[inline]
     pub fn parity(self) -> Color {
@@ -80,12 +127,12 @@ impl Square {
     }
 
     /// Displaces the square by dx, dy, return None if the square is off the board.
-    pub fn displace(self, (dx, dy): (i8, i8)) -> Option<Square> {
-        let x = self.x() as i8 + dx;
-        let y = self.y() as i8 + dy;
+    pub const fn displace(self, (dx, dy): (i8, i8)) -> Option<Square> {
+        let x = self.x() + dx;
+        let y = self.y() + dy;
 
-        if (0..8).contains(&x) && (0..8).contains(&y) {
-            Some(Square::from((x, y)))
+        if 0 <= x && x < 8 && 0 <= y && y < 8 {
+            Some(Square::SQUARES[(x + 8*y) as usize])
         } else {
             None
         }
@@ -141,29 +188,60 @@ impl From<Square> for usize {
     }
 }
 
+impl TryFrom<u8> for Square {
+    type Error = Error;
+
+    /// Tries to create a square from a number in 0..64.
+    fn try_from(i: u8) -> Result<Square, Error> {
+        Square::SQUARES.get(usize::from(i)).copied().ok_or(Error::ParseSquare)
+    }
+}
+
 impl FromStr for Square {
     type Err = Error;
 
     /// Tries to construct a square from a pure algebraic coordinates notation.
     fn from_str(s: &str) -> Result<Square, Error> {
-        if s.len() == 2 {
-            let mut chars = s.chars();
-
-            let file = chars.next().unwrap();
-            let rank = chars.next().unwrap();
-
-            Ok(Square::from((
-                match file {
-                    'a'..='h' => file as i8 - 'a' as i8,
-                    _ => return Err(Error::msg("first character of a square should be a letter between a and h")),
-                },
-                match rank {
-                    '1'..='8' => rank as i8 - '1' as i8,
-                    _ => return Err(Error::msg("second character of a square should be a digit between 1 and 8")),
-                },
-            )))
-        } else {
-            Err(Error::msg("a square should be exactly 2 characters long"))
+        let mut chars = s.chars();
+
+        // Matching on the chars rather than on the length, as a single non-ascii char may be two bytes long.
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(file @ 'a'..='h'), Some(rank @ '1'..='8'), None) => {
+                Ok(Square::from((file as i8 - 'a' as i8, rank as i8 - '1' as i8)))
+            },
+            _ => Err(Error::ParseSquare),
         }
     }
-}
\ No newline at end of file
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn helpers() {
+        let squares = Square::iter().collect::<Vec<_>>();
+        assert_eq!(squares.len(), 64);
+        assert_eq!((squares[0], squares[1], squares[8], squares[63]), (Square::A1, Square::B1, Square::A2, Square::H8));
+
+        assert_eq!((Square::A1.file_index(), Square::A1.rank_index()), (0, 0));
+        assert_eq!((Square::G3.file_index(), Square::G3.rank_index()), (6, 2));
+        assert_eq!(Square::C5.file(), BitBoard::FILE_C);
+        assert_eq!(Square::H2.file(), BitBoard::FILE_H);
+
+        assert_eq!(Square::A1.mirror(), Square::A8);
+        assert_eq!(Square::E2.mirror(), Square::E7);
+        assert_eq!(Square::H5.mirror(), Square::H4);
+
+        assert_eq!(Square::try_from(0u8).unwrap(), Square::A1);
+        assert_eq!(Square::try_from(28u8).unwrap(), Square::E4);
+        assert_eq!(Square::try_from(63u8).unwrap(), Square::H8);
+        assert!(Square::try_from(64u8).is_err());
+    }
+}
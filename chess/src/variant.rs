@@ -0,0 +1,94 @@
+use core::fmt;
+use core::str::FromStr;
+
+use crate::bitboard::BitBoard;
+use crate::board::Board;
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::piece::Piece;
+
+//#################################################################################################
+//
+//                                        enum Variant
+//
+//#################################################################################################
+
+/// The rules a board is played with. Variants keep the moves of standard chess, but may end the game
+/// in other ways: the board asks its variant for a winner before applying the usual rules, and no move
+/// is legal once the variant ended the game.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// The rules of standard chess.
+    #[default]
+    Standard,
+    /// A player also wins by bringing their king to one of the four central squares, the hill.
+    KingOfTheHill,
+    /// A player also wins by giving check three times.
+    ThreeCheck,
+}
+
+// ================================ pub impl
+
+impl Variant {
+    /// List of variants.
+    pub const VARIANTS: [Variant; 3] = [
+        Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck,
+    ];
+
+    /// The central squares a king must reach to win at king of the hill: d4, e4, d5 and e5.
+    pub const HILL: BitBoard = BitBoard(0x0000001818000000);
+
+    /// The number of checks a player must give to win at three-check.
+    pub const CHECKS_TO_WIN: u8 = 3;
+
+    /// Returns the winner of the game on the given board by the rules specific to the variant, if any.
+    /// The usual rules, such as checkmate, are not taken into account.
+    #[inline]
+    pub fn winner(self, board: &Board) -> Option<Color> {
+        match self {
+            Variant::Standard => None,
+            Variant::KingOfTheHill => Color::iter().find(|&color| (board.get_bitboard(color, Piece::King) & Variant::HILL).not_empty()),
+            Variant::ThreeCheck => Color::iter().find(|&color| board.get_checks(color) >= Variant::CHECKS_TO_WIN),
+        }
+    }
+
+    /// Returns true if the game is drawn when neither side has enough material to checkmate.
+    #[inline]
+    pub fn has_insufficient_material(self) -> bool {
+        match self {
+            Variant::Standard => true,
+            // A lone king may still walk to the hill.
+            Variant::KingOfTheHill => false,
+            // Checks may still be given.
+            Variant::ThreeCheck => false,
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl fmt::Display for Variant {
+    /// The name of the variant, as used by lichess.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Variant::Standard => "standard",
+            Variant::KingOfTheHill => "kingOfTheHill",
+            Variant::ThreeCheck => "threeCheck",
+        })
+    }
+}
+
+impl FromStr for Variant {
+    type Err = Error;
+
+    /// From the name of the variant, case insensitively, "koth" and "3check" being accepted as well.
+    fn from_str(s: &str) -> Result<Variant> {
+        match s.to_ascii_lowercase().as_str() {
+            "standard" | "chess" => Ok(Variant::Standard),
+            "kingofthehill" | "koth" => Ok(Variant::KingOfTheHill),
+            "threecheck" | "3check" => Ok(Variant::ThreeCheck),
+            _ => Err(Error::ParseVariant),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{BitXor, BitXorAssign, Not};
+use core::ops::{BitXor, BitXorAssign, Not};
 
 use crate::castle_rights::CastleRights;
 use crate::color::Color;
@@ -12,40 +12,73 @@ use crate::square::Square;
 //
 //#################################################################################################
 
-/// The zobrist keys for pieces.
-static mut PIECES: [[[Zobrist; 2]; 6]; 64] = [[[Zobrist::ZERO; 2]; 6]; 64];
-static mut EP_FILES: [Zobrist; 8] = [Zobrist::ZERO; 8];
-static mut CASTLING_RIGHTS: [Zobrist; 16] = [Zobrist::ZERO; 16];
+/// The zobrist keys for pieces, en passant files, castling rights and the checks given at three-check.
+struct Keys {
+    pieces: [[[Zobrist; 2]; 6]; 64],
+    ep_files: [Zobrist; 8],
+    castling_rights: [Zobrist; 16],
+    checks: [[Zobrist; 2]; 3],
+}
+
+/// The zobrist keys, generated at compile time from a fixed seed. They are thus the same across runs,
+/// builds and platforms, so that keys may be persisted, as the transposition table does. Changing the
+/// seed or the generator changes every key: see the zobrist_is_stable test of the board.
+static KEYS: Keys = Keys::new();
 
 /// The xorshift* algorithm for 64 bits numbers, producing
 /// good enough pseudo-random numbers for initializing zobrist keys.
-#[cold]
-fn xorshift(seed: &mut u64) -> Zobrist {
+const fn xorshift(seed: &mut u64) -> Zobrist {
     *seed ^= seed.wrapping_shl(13);
     *seed ^= seed.wrapping_shr(7);
     *seed ^= seed.wrapping_shl(17);
     Zobrist(seed.wrapping_mul(0x2545F4914F6CDD1D))
 }
 
-/// Initializes the zobrist keys at the beginning of the program.
-#[cold]
-pub(crate) unsafe fn init() {
-    // Warning: changing the seed may make the cuckoo init() function non terminating.
-    let mut seed = 0x0C3B301A1AF7EE42;
+impl Keys {
+    /// Generates the zobrist keys.
+    const fn new() -> Keys {
+        // Warning: changing the seed may make the generation of the cuckoo tables non terminating.
+        let mut seed = 0x0C3B301A1AF7EE42;
+
+        let mut keys = Keys {
+            pieces: [[[Zobrist::ZERO; 2]; 6]; 64],
+            ep_files: [Zobrist::ZERO; 8],
+            castling_rights: [Zobrist::ZERO; 16],
+            checks: [[Zobrist::ZERO; 2]; 3],
+        };
+
+        let mut i = 0;
+        while i < 64 {
+            let mut j = 0;
+            while j < 6 {
+                keys.pieces[i][j][0] = xorshift(&mut seed);
+                keys.pieces[i][j][1] = xorshift(&mut seed);
+                j += 1;
+            }
+            i += 1;
+        }
 
-    for i in 0..64 {
-        for j in 0..6 {
-            PIECES[i][j][0] = xorshift(&mut seed);
-            PIECES[i][j][1] = xorshift(&mut seed);
+        let mut i = 0;
+        while i < 8 {
+            keys.ep_files[i] = xorshift(&mut seed);
+            i += 1;
         }
-    }
 
-    for i in 0..8 {
-        EP_FILES[i] = xorshift(&mut seed);
-    }
+        let mut i = 0;
+        while i < 16 {
+            keys.castling_rights[i] = xorshift(&mut seed);
+            i += 1;
+        }
 
-    for i in 0..16 {
-        CASTLING_RIGHTS[i] = xorshift(&mut seed);
+        // Generated last, so that the keys above are the same as before they were added.
+        let mut i = 0;
+        while i < 3 {
+            keys.checks[i][0] = xorshift(&mut seed);
+            keys.checks[i][1] = xorshift(&mut seed);
+            i += 1;
+        }
+
+        keys
     }
 }
 
@@ -58,7 +91,7 @@ pub(crate) unsafe fn init() {
 /// A zobrist key, that may be used for hashing.
 #[repr(transparent)]
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-pub struct Zobrist(u64);
+pub struct Zobrist(pub(crate) u64);
 
 // ================================ pub impl
 
@@ -68,13 +101,13 @@ impl Zobrist {
 
     /// First hash function for indexing the cuckoo table.
     #[inline]
-    pub fn h1(self) -> usize {
+    pub const fn h1(self) -> usize {
         (self.0.wrapping_shr(32) & 0x1FFF) as usize
     }
 
     /// Second hash function for indexing the cuckoo table.
     #[inline]
-    pub fn h2(self) -> usize {
+    pub const fn h2(self) -> usize {
         (self.0.wrapping_shr(48) & 0x1FFF) as usize
     }
 
@@ -90,6 +123,32 @@ impl Zobrist {
     pub fn get_raw(self) -> u64 {
         self.0
     }
+
+    /// Creates a zobrist from its raw value, as returned by get_raw().
+    #[inline]
+    pub fn from_raw(raw: u64) -> Zobrist {
+        Zobrist(raw)
+    }
+}
+
+// ================================ pub(crate) impl
+
+impl Zobrist {
+    /// Hashes a color, piece, square triplet, in a const context.
+    #[inline]
+    pub(crate) const fn piece(color: Color, piece: Piece, sq: Square) -> Zobrist {
+        KEYS.pieces[sq as usize][piece as usize][color as usize]
+    }
+
+    /// Hashes the number of checks given by a color at three-check, which is at most 3.
+    /// No check given hashes to zero, so that the keys of other variants are left untouched.
+    #[inline]
+    pub(crate) fn checks(color: Color, count: u8) -> Zobrist {
+        match count {
+            0 => Zobrist::ZERO,
+            _ => KEYS.checks[usize::from(count - 1)][usize::from(color)],
+        }
+    }
 }
 
 // ================================ traits impl
@@ -98,8 +157,7 @@ impl From<(Color, Piece, Square)> for Zobrist {
     /// Hashes a color, piece, square triplet.
     #[inline]
     fn from((color, piece, sq): (Color, Piece, Square)) -> Zobrist {
-        // SAFE: array initialization is done at startup
-        unsafe {PIECES[usize::from(sq)][usize::from(piece)][usize::from(color)]}
+        Zobrist::piece(color, piece, sq)
     }
 }
 
@@ -108,8 +166,7 @@ impl From<EnPassantSquare> for Zobrist {
     #[inline]
     fn from(ep_square: EnPassantSquare) -> Zobrist {
         match ep_square {
-            // SAFE: array initialization is done at startup
-            EnPassantSquare::Some(sq) => unsafe {EP_FILES[sq.x() as usize]}
+            EnPassantSquare::Some(sq) => KEYS.ep_files[sq.x() as usize],
             _ => Zobrist::ZERO,
         }
     }
@@ -119,8 +176,7 @@ impl From<CastleRights> for Zobrist {
     /// Hashes a castle rights object.
     #[inline]
     fn from(castle_rights: CastleRights) -> Zobrist {
-        // SAFE: array initialization is done at startup
-        unsafe {CASTLING_RIGHTS[usize::from(castle_rights.raw())]}
+        KEYS.castling_rights[usize::from(castle_rights.raw())]
     }
 }
 
@@ -133,10 +133,17 @@ const FENS: [(&'static str, u64); 127] = [
 
 #[test]
 fn auto_perft() {
-    chess::init();
-
     for &(fen, res) in &FENS {
         let mut board = Board::new(fen).unwrap();
         assert_eq!(movegen::perft(&mut board, 4), res, "Error at {:?}.", fen);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn auto_perft_hashed() {
+    for &(fen, res) in &FENS {
+        let mut board = Board::new(fen).unwrap();
+        assert_eq!(movegen::perft_hashed(&mut board, 4, 1 << 20), res, "Error at {:?}.", fen);
+        assert_eq!(movegen::divide(&mut board, 4).values().sum::<u64>(), res, "Error at {:?}.", fen);
+    }
+}
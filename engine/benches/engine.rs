@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use chess::board::Board;
+use chess::movegen;
+use chess::moves::Move;
+use chess::piece::Piece;
+use engine::{BenchEval, BenchTable, Net, BENCH_FENS};
+
+/* 
+ * To run the benches of the evaluation and of the transposition table:
+ * $ cargo bench -p engine --bench engine
+ *
+ * To compare a change against a baseline:
+ * $ cargo bench -p engine --bench engine -- --save-baseline before
+ * $ cargo bench -p engine --bench engine -- --baseline before
+ */
+
+/// The size of the transposition table, in bytes.
+const TABLE_SIZE: usize = 16 << 20;
+
+/// Loads the network shipped with the engine.
+fn net() -> Arc<Net> {
+    Net::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("nets/nnue.bin")).unwrap()
+}
+
+/// Returns the positions of the bench of the engine.
+fn boards() -> Vec<Board> {
+    BENCH_FENS.iter().map(|fen| Board::new(fen).unwrap()).collect()
+}
+
+/// Returns the positions of the bench of the engine, along with the positions reached after each of their legal moves,
+/// and the move leading to each.
+fn children() -> Vec<(Board, Move)> {
+    let mut children = Vec::new();
+
+    for mut board in boards() {
+        let mut moves = Vec::new();
+        movegen::legals(&board, &mut moves);
+
+        for mv in moves {
+            board.do_move(mv);
+            children.push((board.clone(), mv));
+            board.undo_move(mv);
+        }
+    }
+
+    children
+}
+
+/// Runs the network on each position, computes its first layer from scratch, and updates it incrementally.
+fn eval(c: &mut Criterion) {
+    let mut group = c.benchmark_group("eval");
+    let net = net();
+    let boards = boards();
+    group.throughput(Throughput::Elements(boards.len() as u64));
+
+    let evals: Vec<_> = boards.iter().map(|board| BenchEval::new(net.clone(), board)).collect();
+    group.bench_function("forward", |b| b.iter(|| {
+        boards.iter().zip(&evals).map(|(board, eval)| eval.evaluate(black_box(board))).sum::<f32>()
+    }));
+
+    let mut eval = BenchEval::new(net.clone(), &boards[0]);
+    group.bench_function("reset", |b| b.iter(|| {
+        for board in &boards {
+            eval.reset(black_box(board));
+        }
+    }));
+
+    // Every legal move of every position is done and undone.
+    let mut positions: Vec<_> = boards.iter().map(|board| {
+        let mut moves = Vec::new();
+        movegen::legals(board, &mut moves);
+        (board.clone(), BenchEval::new(net.clone(), board), moves)
+    }).collect();
+    let moves: usize = positions.iter().map(|(_, _, moves)| moves.len()).sum();
+    group.throughput(Throughput::Elements(moves as u64));
+
+    group.bench_function("do_undo", |b| b.iter(|| {
+        for (board, eval, moves) in positions.iter_mut() {
+            for &mv in moves.iter() {
+                eval.do_move(board, black_box(mv));
+                eval.undo_move(board, mv);
+            }
+        }
+    }));
+
+    // Only the king moves, which refresh the accumulator of their side.
+    let mut king_positions: Vec<_> = positions.into_iter().map(|(board, eval, mut moves)| {
+        moves.retain(|&mv| board.get_piece(mv.from()).is_some_and(|(_, piece)| piece == Piece::King));
+        (board, eval, moves)
+    }).collect();
+    let king_moves: usize = king_positions.iter().map(|(_, _, moves)| moves.len()).sum();
+    group.throughput(Throughput::Elements(king_moves as u64));
+
+    group.bench_function("king_moves", |b| b.iter(|| {
+        for (board, eval, moves) in king_positions.iter_mut() {
+            for &mv in moves.iter() {
+                eval.do_move(board, black_box(mv));
+                eval.undo_move(board, mv);
+            }
+        }
+    }));
+
+    group.finish();
+}
+
+/// Inserts the positions in the transposition table, and probes them.
+fn table(c: &mut Criterion) {
+    let mut group = c.benchmark_group("table");
+    let children = children();
+    group.throughput(Throughput::Elements(children.len() as u64));
+
+    let table = BenchTable::new(TABLE_SIZE);
+    group.bench_function("insert", |b| b.iter(|| {
+        for (board, mv) in &children {
+            table.insert(black_box(board), *mv, 0.0, 8);
+        }
+    }));
+
+    // The table is left full of the positions by the inserts above.
+    group.bench_function("probe", |b| b.iter(|| {
+        children.iter().filter(|(board, _)| table.probe(black_box(board), 0).is_some()).count()
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, eval, table);
+criterion_main!(benches);
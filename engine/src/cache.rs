@@ -0,0 +1,72 @@
+use chess::zobrist::Zobrist;
+
+use crate::params;
+
+//#################################################################################################
+//
+//                                        struct EvalCache
+//
+//#################################################################################################
+
+/// The type of a bucket in the cache.
+type Bucket = Option<(Zobrist, f32)>;
+
+/// The size in buckets of the cache. It is a power of two for
+/// faster indexing.
+const NUM_BUCKETS: usize = (params::EVAL_CACHE_SIZE / std::mem::size_of::<Bucket>()).next_power_of_two();
+
+/// A per-thread cache of the evaluations of the network, keyed by the zobrist key of the position,
+/// so that positions reached by transposition are not evaluated twice. Entries are always replaced.
+#[derive(Debug)]
+pub(crate) struct EvalCache {
+    buckets: Box<[Bucket]>,
+    probes: u64,
+    hits: u64,
+}
+
+// ================================ pub(crate) impl
+
+impl EvalCache {
+    /// Creates a new, empty cache.
+    pub(crate) fn new() -> EvalCache {
+        EvalCache {
+            buckets: vec![None; NUM_BUCKETS].into_boxed_slice(),
+            probes: 0,
+            hits: 0,
+        }
+    }
+
+    /// Returns the evaluation stored for that position, if any.
+    #[inline]
+    pub(crate) fn probe(&mut self, zobrist: Zobrist) -> Option<f32> {
+        self.probes += 1;
+
+        match self.buckets[zobrist.idx::<NUM_BUCKETS>() as usize] {
+            Some((key, eval)) if key == zobrist => {
+                self.hits += 1;
+                Some(eval)
+            },
+            _ => None,
+        }
+    }
+
+    /// Stores the evaluation of that position, replacing any previous entry in its bucket.
+    #[inline]
+    pub(crate) fn insert(&mut self, zobrist: Zobrist, eval: f32) {
+        self.buckets[zobrist.idx::<NUM_BUCKETS>() as usize] = Some((zobrist, eval));
+    }
+
+    /// Empties the cache.
+    pub(crate) fn clear(&mut self) {
+        self.buckets.fill(None);
+    }
+
+    /// Returns the number of probes and hits since the last call, and resets them.
+    #[inline]
+    pub(crate) fn take_stats(&mut self) -> (u64, u64) {
+        let stats = (self.probes, self.hits);
+        self.probes = 0;
+        self.hits = 0;
+        stats
+    }
+}
@@ -0,0 +1,140 @@
+use chess::board::Board;
+use chess::color::Color;
+use chess::piece::Piece;
+use chess::square::Square;
+
+use crate::eval::value_of;
+
+// The piece-square tables, in centipawns, seen from white's side with the eighth rank first.
+// Kings have one table for the middlegame and one for the endgame, blended by the phase of the game.
+
+const PAWN_TABLE: [i8; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const KNIGHT_TABLE: [i8; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const BISHOP_TABLE: [i8; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const ROOK_TABLE: [i8; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+const QUEEN_TABLE: [i8; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+const KING_MIDDLEGAME_TABLE: [i8; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+const KING_ENDGAME_TABLE: [i8; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// Returns the value of the given piece-square table for a piece of the given color, in centipawns.
+#[inline]
+fn table_value(table: &[i8; 64], color: Color, sq: Square) -> i32 {
+    // The tables start with the eighth rank, from white's side.
+    let idx = match color {
+        Color::White => usize::from(sq) ^ 56,
+        Color::Black => usize::from(sq),
+    };
+
+    i32::from(table[idx])
+}
+
+/// Returns the material balance of the position, kings excepted, in pawns and from the point of view of the side to move.
+pub(crate) fn material(board: &Board) -> f32 {
+    let stm = board.get_side_to_move();
+
+    Piece::PIECES[..5].iter().map(|&piece| {
+        let balance = i32::from(board.get_bitboard(stm, piece).count()) - i32::from(board.get_bitboard(stm.invert(), piece).count());
+        balance as f32 * value_of(piece)
+    }).sum()
+}
+
+/// Evaluates the position with the material balance and piece-square tables, in pawns
+/// and from the point of view of the side to move. Much weaker than the network, but needs
+/// neither it nor any incremental update.
+pub(crate) fn evaluate(board: &Board) -> f32 {
+    let (phase, max_phase) = (i32::from(board.phase()), i32::from(Board::MAX_PHASE));
+    let mut score = 0;
+
+    for color in [Color::White, Color::Black] {
+        let sign = if color == Color::White {1} else {-1};
+
+        for &piece in &Piece::PIECES {
+            for sq in board.get_bitboard(color, piece).iter_squares() {
+                score += sign * match piece {
+                    Piece::Pawn => table_value(&PAWN_TABLE, color, sq),
+                    Piece::Knight => table_value(&KNIGHT_TABLE, color, sq),
+                    Piece::Bishop => table_value(&BISHOP_TABLE, color, sq),
+                    Piece::Rook => table_value(&ROOK_TABLE, color, sq),
+                    Piece::Queen => table_value(&QUEEN_TABLE, color, sq),
+                    Piece::King => {
+                        let middlegame = table_value(&KING_MIDDLEGAME_TABLE, color, sq);
+                        let endgame = table_value(&KING_ENDGAME_TABLE, color, sq);
+                        (middlegame * phase + endgame * (max_phase - phase)) / max_phase
+                    },
+                };
+            }
+        }
+    }
+
+    let positional = if board.get_side_to_move() == Color::White {score} else {-score};
+    material(board) + positional as f32 / 100.0
+}
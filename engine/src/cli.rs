@@ -1,13 +1,17 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{io, thread};
 use std::io::Write;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{Error, Result};
 use clap::{App, Arg};
 
+use chess::book::Book;
+use chess::pgn::Pgn;
 use chess::prelude::*;
-use engine::Engine;
+use engine::{BenchResult, Engine, EvalMode, Experience, Line, SmpMode, BENCH_DEPTH, BENCH_FENS, MAX_MATE_MOVES};
 
 /// The default fen used, the starting position.
 const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -17,19 +21,33 @@ const MAX_HISTORY: usize = 24;
 
 /// The text displayed when the user types "help".
 const HELP: &str = r#"Available commands:
-  help            : prints this message.
-  play <move>     : plays the given <move>, encoded in pure algebraic coordinate notation.
-  reset           : resets the game to it's original state.
-  think <seconds> : starts the engine for <seconds> seconds.
-  do              : plays the engine's preferred move.
-  auto <seconds>  : plays the engine against itself, with <seconds> seconds to think for each move.
-  exit            : exits the cli."#;
+  help              : prints this message.
+  play <move>       : plays the given <move>, encoded in pure algebraic coordinate notation.
+  back              : reverts the last move.
+  redo              : plays again the last reverted move.
+  reset             : resets the game to it's original state.
+  save <file>       : saves the game to the given PGN <file>, the moves of the engine commented with its searches.
+  load <file>       : loads the first game of the given PGN <file>.
+  think <seconds>   : starts the engine for <seconds> seconds.
+  do                : plays the engine's preferred move.
+  auto <seconds>    : plays the engine against itself, with <seconds> seconds to think for each move.
+  analyse <seconds> : lets the engine think for <seconds> seconds, printing its lines at each depth.
+  report            : prints the moves played since the last back, load or reset, with the searches of the engine.
+  mate <moves>      : searches for a forced mate of the side to move within <moves> moves.
+  eval              : prints the static evaluation of the position.
+  legal             : lists the legal moves in the position.
+  perft <depth>     : counts the leaf nodes of the game tree at the given <depth>.
+  bench [depth]     : searches a fixed set of positions to the given [depth] and reports the nodes searched and their signature.
+  smpbench [depth]  : searches the bench positions to the given [depth] with each way of sharing work between threads.
+  exit              : exits the cli."#;
 
 /// The global state of the cli.
 struct State {
     engine: Engine,
+    net_path: String,
     buffer: String,
     history: Vec<Move>,
+    redo: Vec<Move>,
 }
 
 // ================================ Utils
@@ -105,21 +123,58 @@ impl State {
 
     /// Makes the engine think for duration seconds.
     fn think_for(&mut self, duration: Duration) {
-        if self.engine.start() {
-            thread::sleep(duration);
-            self.engine.stop();
-        }
+        self.engine.search_movetime(duration);
+    }
+
+    /// Formats a line of the engine, its moves being written in standard algebraic notation.
+    fn format_line(&self, line: &Line) -> String {
+        let mut board = self.engine.read_board().clone();
+        let pv = line.pv.iter().map(|&mv| {
+            let san = board.to_san(mv);
+            board.do_move(mv);
+            san
+        }).collect::<Vec<_>>().join(" ");
+
+        format!("{:+.2} {}", line.score, pv)
+    }
+
+    /// Prints the report of the game: the moves played, those of the engine commented with their
+    /// score, depth and search time, followed by a summary of the searches.
+    fn print_report(&self) {
+        let report = self.engine.report();
+        let Some(pgn) = report.to_pgn() else {
+            println!("No move played yet.");
+            return;
+        };
+
+        print!("{}", pgn);
+
+        let searched = report.get_moves().iter().filter(|report| report.depth.is_some()).collect::<Vec<_>>();
+        let book = report.get_moves().iter().filter(|report| report.book).count();
+        let nodes: u64 = searched.iter().map(|report| report.nodes).sum();
+        let time: Duration = searched.iter().map(|report| report.time).sum();
+        let depth = searched.iter().map(|report| u64::from(report.depth.unwrap())).sum::<u64>() as f64 / searched.len().max(1) as f64;
+
+        println!("Engine moves: {} searched, {} from the book.", searched.len(), book);
+        println!("Searches: {} nodes in {:.2}s, average depth {:.1}.", nodes, time.as_secs_f64(), depth);
     }
 
     /// Plays the given move.
     fn play_move(&mut self, mv: Move) {
-        let mut board = self.engine.write_board();
-
         // Sanity check.
+        let board = self.engine.read_board();
         assert!(board.is_pseudo_legal(mv) && board.is_legal(mv), "Tried to play illegal move");
+        drop(board);
+
+        // Playing the last reverted move keeps the other reverted moves available for redo.
+        if self.redo.last() == Some(&mv) {
+            self.redo.pop();
+        } else {
+            self.redo.clear();
+        }
 
         self.history.push(mv);
-        board.do_move(mv);
+        self.engine.advance(mv);
     }
 }
 
@@ -144,8 +199,57 @@ impl State {
         }
 
         let mv = self.history.pop().unwrap();
-        let mut board = self.engine.write_board();
-        board.undo_move(mv);
+        self.engine.undo_move(mv);
+        self.redo.push(mv);
+
+        Ok(())
+    }
+
+    /// Plays again the last reverted move.
+    fn redo(&mut self) -> Result<()> {
+        let mv = *self.redo.last().ok_or(Error::msg("No move to redo."))?;
+        self.play_move(mv);
+
+        Ok(())
+    }
+
+    /// Saves the game to the PGN file whose path is parsed from the arguments, with its date and result.
+    fn save(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let path = args.next().ok_or(Error::msg("Cannot find <file> argument."))?;
+
+        // Retrieve the starting position by reverting the moves of the game.
+        let mut start = self.engine.read_board().clone();
+        for &mv in self.history.iter().rev() {
+            start.undo_move(mv);
+        }
+
+        let mut pgn = Pgn::new(start);
+        pgn.set_tag("Event", "Rush CLI game");
+        pgn.set_date_today();
+        for &mv in &self.history {
+            pgn.push(mv);
+        }
+
+        // The moves of the engine are commented with the searches that chose them.
+        self.engine.report().annotate(&mut pgn);
+
+        pgn.save(Path::new(&path))?;
+        Ok(())
+    }
+
+    /// Loads the first game of the PGN file whose path is parsed from the arguments,
+    /// replacing the current game.
+    fn load(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let path = args.next().ok_or(Error::msg("Cannot find <file> argument."))?;
+        let pgn = Pgn::open(Path::new(&path))?.into_iter().next().ok_or(Error::msg("No game found in file."))?;
+
+        *self.engine.write_board() = pgn.get_start().clone();
+        self.history.clear();
+        self.redo.clear();
+
+        for &mv in pgn.get_moves() {
+            self.play_move(mv);
+        }
 
         Ok(())
     }
@@ -188,6 +292,165 @@ impl State {
             self.play_move(mv);
         }
 
+        self.print_report();
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Lets the engine think for a given time, in seconds, parsed from the arguments,
+    /// printing the lines found each time a new depth is completed.
+    fn analyse(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        if !self.engine.read_board().status().is_playing() {
+            return Err(Error::msg("Game has ended. \"undo\" last move or \"reset\" the game."));
+        }
+
+        let seconds = args.next().ok_or(Error::msg("Cannot find <seconds> argument."))?;
+        let duration = Duration::from_secs_f64(f64::from_str(&seconds)?);
+
+        if self.engine.start() {
+            let start = Instant::now();
+            let mut depth = 0;
+
+            while start.elapsed() < duration {
+                thread::sleep(Duration::from_millis(10));
+
+                let analysis = self.engine.analysis();
+                if analysis.depth > depth {
+                    depth = analysis.depth;
+                    for line in &analysis.lines {
+                        println!("depth {:>2} seldepth {:>2} nodes {:>10}: {}", depth, analysis.seldepth, analysis.nodes, self.format_line(line));
+                    }
+                }
+            }
+
+            self.engine.stop();
+        }
+
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Prints the static evaluation of the position, and the contribution of each piece to it.
+    fn eval(&mut self) -> Result<()> {
+        let breakdown = self.engine.evaluate_detailed(&self.engine.read_board());
+
+        println!("Evaluation (white's point of view): {:+.2}", breakdown.total);
+        println!("  material   : {:+.2}", breakdown.material);
+        println!("  positional : {:+.2}", breakdown.positional);
+        println!("Contribution of the pieces (their owner's point of view):");
+        for piece in &breakdown.pieces {
+            println!("  {}{}: {:+.2}", piece.piece.as_char(piece.color), piece.square, piece.value);
+        }
+
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Searches for a forced mate within the number of moves parsed from the arguments, and prints it.
+    fn mate(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let moves = u8::from_str(&args.next().ok_or(Error::msg("Cannot find <moves> argument."))?)?;
+        if moves == 0 || moves > MAX_MATE_MOVES {
+            return Err(Error::msg(format!("The number of moves must be between 1 and {}.", MAX_MATE_MOVES)));
+        }
+
+        let start = Instant::now();
+        let result = self.engine.search_mate(moves);
+        println!("{} ({:.2}s)", result, start.elapsed().as_secs_f64());
+
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Prints the report of the game.
+    fn report(&mut self) -> Result<()> {
+        self.print_report();
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Lists the legal moves of the position.
+    fn legal(&mut self) -> Result<()> {
+        let board = self.engine.read_board().clone();
+
+        let mut legals = movegen::MoveList::new();
+        movegen::legals_into(&board, &mut legals);
+
+        let moves = legals.iter().map(|&mv| board.to_san(mv)).collect::<Vec<_>>();
+        println!("{} legal moves: {}", moves.len(), moves.join(", "));
+
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Counts the leaf nodes of the game tree at the depth parsed from the arguments,
+    /// for each legal move and in total.
+    fn perft(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let depth = usize::from_str(&args.next().ok_or(Error::msg("Cannot find <depth> argument."))?)?;
+        if depth == 0 {
+            return Err(Error::msg("The depth of perft must be at least 1."));
+        }
+
+        let mut board = self.engine.read_board().clone();
+        let start = Instant::now();
+
+        let mut divided = movegen::divide(&mut board, depth).into_iter().collect::<Vec<_>>();
+        divided.sort_by_key(|(mv, _)| mv.to_string());
+        for &(mv, nodes) in &divided {
+            println!("{}: {}", mv, nodes);
+        }
+
+        let nodes: u64 = divided.iter().map(|(_, nodes)| nodes).sum();
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("Perft {}: {} nodes in {:.2}s ({:.0} nodes/s).", depth, nodes, elapsed, nodes as f64 / elapsed);
+
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Searches the bench positions to a fixed depth, parsed from the arguments, with a single-threaded
+    /// engine and prints the number of nodes searched. Used to detect unintended changes of the search.
+    fn bench(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let depth = match args.next() {
+            Some(depth) => u8::from_str(&depth)?,
+            None => BENCH_DEPTH,
+        };
+
+        print_bench(&self.engine.bench(depth)?);
+
+        self.ask_ok();
+        Ok(())
+    }
+
+    /// Searches the bench positions to a fixed depth, parsed from the arguments, with a multi-threaded
+    /// engine in each of the ways its threads may share the work, and reports which one is the fastest.
+    fn smpbench(&mut self, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let depth = match args.next() {
+            Some(depth) => u8::from_str(&depth)?,
+            None => BENCH_DEPTH,
+        };
+
+        let mut results = Vec::new();
+
+        for mode in [SmpMode::DepthOffset, SmpMode::RootSplit] {
+            // A new engine for each mode, so that the transposition table starts empty.
+            let mut engine = Engine::new(Board::from_str(DEFAULT_FEN)?, None, &self.net_path)?;
+            engine.set_smp_mode(mode);
+            let start = Instant::now();
+
+            for fen in BENCH_FENS {
+                *engine.write_board() = Board::from_str(fen)?;
+                engine.search_depth(depth);
+            }
+
+            let elapsed = start.elapsed().as_secs_f64();
+            println!("{:?}: searched to depth {} in {:.2}s.", mode, depth, elapsed);
+            results.push((mode, elapsed));
+        }
+
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        let (fastest, _) = results.iter().min_by(|(_, time1), (_, time2)| time1.total_cmp(time2)).unwrap();
+        println!("With {} cores, the {:?} mode scales better.", cores, fastest);
+
         self.ask_ok();
         Ok(())
     }
@@ -200,17 +463,26 @@ impl State {
 
         // Reset the history.
         self.history.clear();
+        self.redo.clear();
 
         Ok(())
     }
 }
 
+/// Prints the results of a bench, ending with its signature.
+fn print_bench(result: &BenchResult) {
+    for position in &result.positions {
+        println!("{:>10} nodes, best move {}: {}", position.nodes, position.best_move, position.fen);
+    }
+
+    println!("Searched {} positions to depth {} in {:.2}s ({:.0} nodes/s).", result.positions.len(), result.depth, result.duration.as_secs_f64(), result.nps());
+    println!("Evaluation cache hit rate: {:.1}%.", 100.0 * result.eval_hit_rate);
+    println!("Bench signature: {}", result.signature());
+}
+
 /// The main function parses the programs arguments, initializes the chess library
 /// and the engine and then enter a REPL.
 fn main() -> Result<()> {
-    // Initializes the chess library.
-    chess::init();
-
     // Get the args to the program.
     let args = App::new("Rush chess engine CLI")
         .version(engine::VERSION)
@@ -232,25 +504,89 @@ fn main() -> Result<()> {
             .short("b")
             .long("book")
             .value_name("BOOK")
-            .help("Gives the path to a polyglot book (.bin), that the engine will use whenever it can.")
+            .help("Gives the path to a polyglot book (.bin), that the engine will use whenever it can, instead of the built-in one.")
+            .takes_value(true))
+        .arg(Arg::with_name("no_book")
+            .long("no-book")
+            .help("Disables the opening book, including the built-in one.")
+            .conflicts_with("book"))
+        .arg(Arg::with_name("contempt")
+            .long("contempt")
+            .value_name("CENTIPAWNS")
+            .help("Sets the contempt of the engine for draws: a draw is worth minus that many centipawns to it. Positive values avoid draws, negative ones seek them. 0 by default.")
+            .allow_hyphen_values(true)
+            .takes_value(true))
+        .arg(Arg::with_name("avoid_repetitions")
+            .long("avoid-repetitions")
+            .help("Makes the engine avoid repetitions when it is better and seek them when it is worse, biasing its contempt by its score rather than randomizing draw scores."))
+        .arg(Arg::with_name("eval")
+            .long("eval")
+            .value_name("MODE")
+            .help("Sets the way the engine evaluates positions: with its network, with a hand-crafted evaluation, or with the material balance alone. The last two are much weaker, but faster.")
+            .possible_values(&["nnue", "classical", "material"])
+            .default_value("nnue"))
+        .arg(Arg::with_name("experience")
+            .long("experience")
+            .value_name("EXPERIENCE")
+            .help("Gives the path to an experience file, created if it does not exist, where the engine records the moves it plays, and from which it learns in later games.")
             .takes_value(true))
+        .arg(Arg::with_name("affinity")
+            .long("affinity")
+            .help("Pins the search threads to physical cores, spreading the transposition table over the memory of their NUMA nodes. Only supported on Linux."))
+        .arg(Arg::with_name("bench")
+            .long("bench")
+            .value_name("DEPTH")
+            .help("Runs the bench to the given depth, or to the default one, prints its signature and exits.")
+            .min_values(0)
+            .max_values(1))
         .get_matches();
 
     // The fen string used for the position.
     let default_fen = args.value_of("fen").unwrap();
 
     // The book that may be used to lookup moves.
-    let book_path = args.value_of("book");
+    let book = if args.is_present("no_book") {
+        None
+    } else if let Some(book_path) = args.value_of("book") {
+        Some(Book::open(Path::new(book_path))?)
+    } else {
+        Some(Book::builtin())
+    };
 
     // The neural network used for evaluation.
     let net_path = args.value_of("net").unwrap();
 
+    // Run the bench without entering the REPL, so that its signature may be checked by scripts.
+    if args.is_present("bench") {
+        let depth = match args.value_of("bench") {
+            Some(depth) => u8::from_str(depth)?,
+            None => BENCH_DEPTH,
+        };
+
+        let engine = Engine::new(Board::from_str(default_fen)?, None, net_path)?;
+        print_bench(&engine.bench(depth)?);
+        return Ok(());
+    }
+
+    // Parse fen and create board, then engine, with its attitude towards draws.
+    let mut engine = Engine::new(Board::from_str(default_fen)?, book, net_path)?;
+    if let Some(contempt) = args.value_of("contempt") {
+        engine.set_contempt(i32::from_str(contempt).map_err(|_| Error::msg("Unable to parse contempt."))?);
+    }
+    engine.set_avoid_repetitions(args.is_present("avoid_repetitions"));
+    engine.set_eval_mode(EvalMode::from_str(args.value_of("eval").unwrap())?);
+    engine.set_affinity(args.is_present("affinity"));
+    if let Some(path) = args.value_of("experience") {
+        engine.set_experience(Some(Arc::new(Experience::open(Path::new(path))?)));
+    }
+
     // Construct the state.
     let mut state = State {
-        // Parse fen and create board, then engine.
-        engine: Engine::new(Board::from_str(default_fen)?, book_path, net_path)?,
+        engine,
+        net_path: net_path.to_string(),
         buffer: String::new(),
         history: Vec::new(),
+        redo: Vec::new(),
     };
 
     // The REPL.
@@ -272,9 +608,20 @@ fn main() -> Result<()> {
                 },
                 "play" => state.play(&mut args),
                 "back" => state.back(),
+                "redo" => state.redo(),
+                "save" => state.save(&mut args),
+                "load" => state.load(&mut args),
                 "think" => state.think(&mut args),
                 "do" => state.do_engine(),
                 "auto" => state.auto(&mut args),
+                "analyse" => state.analyse(&mut args),
+                "report" => state.report(),
+                "mate" => state.mate(&mut args),
+                "eval" => state.eval(),
+                "legal" => state.legal(),
+                "perft" => state.perft(&mut args),
+                "bench" => state.bench(&mut args),
+                "smpbench" => state.smpbench(&mut args),
                 "reset" => state.reset(&default_fen),
                 "exit" => {
                     println!("Goodbye.");
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use anyhow::Result;
+use clap::{App, Arg};
+
+use engine::{FeatureSet, Net};
+
+/*
+ * Converts a HalfKP network to the HalfKAv2 feature set, whose kings are bucketed and mirrored to the
+ * e to h files. The converted network evaluates positions as the original one did, the board being
+ * mirrored when the king is on the a to d files, and is meant as the starting point of a training
+ * in the new feature set: the weights of the plane of the kings are zero.
+ *
+ * $ cargo build --bin convert-net --release
+ * $ target/release/convert-net engine/nets/nnue.bin engine/nets/halfkav2.bin
+ */
+
+/// The main function parses the programs arguments, then converts the network.
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine network converter")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("Converts a HalfKP network to the HalfKAv2 feature set.")
+        .arg(Arg::with_name("input")
+            .index(1)
+            .value_name("INPUT")
+            .help("The path to the network file to convert.")
+            .required(true))
+        .arg(Arg::with_name("output")
+            .index(2)
+            .value_name("OUTPUT")
+            .help("The path to the network file to write.")
+            .required(true))
+        .get_matches();
+
+    let net = Net::load(Path::new(args.value_of("input").unwrap()))?;
+    let converted = net.convert(FeatureSet::HalfKaV2)?;
+    converted.save(Path::new(args.value_of("output").unwrap()))?;
+
+    println!("Converted the {} network to {}.", net.features(), converted.features());
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Error, Result};
+use clap::{App, Arg};
+
+use chess::prelude::*;
+use engine::{random_opening, LocalEngine, STARTING_FEN};
+
+/*
+ * Generates training data for the network, by playing the engine against itself.
+ * Each position searched is written to the output file as a record of 37 bytes:
+ * - the board, encoded with Board::to_bytes() (32 bytes).
+ * - the score of the search, in pawns and from white's point of view, as a little endian f32 (4 bytes).
+ * - the result of the game, from white's point of view: 1 for a win, 0 for a draw and -1 for a loss, as an i8 (1 byte).
+ * Positions in check, and positions whose best move is a capture or a promotion, are not recorded.
+ * With --planes, the board is written as its planes instead, with Board::to_planes() (1344 bytes),
+ * so that networks may be trained on them without decoding the boards.
+ *
+ * $ cargo build --bin datagen --release
+ * $ target/release/datagen engine/nets/nnue.bin data.bin --games 1000 --threads 8
+ */
+
+/// The number of games between two progress reports.
+const REPORT_INTERVAL: usize = 100;
+
+/// The rules used to play and adjudicate the games.
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    // The number of nodes searched per move.
+    nodes: u64,
+    // The number of random moves played at the start of each game.
+    random_plies: usize,
+    // The score above which a player is considered winning, in pawns.
+    win_score: f32,
+    // The number of consecutive plies a player must be winning for the game to be adjudicated.
+    win_plies: usize,
+    // The number of plies after which a game is adjudicated as a draw.
+    max_plies: usize,
+}
+
+/// Returns the size in bytes of a record, whose board is written as its planes or encoded.
+fn record_size(planes: bool) -> usize {
+    if planes {Board::PLANES * 64 + 5} else {Board::ENCODED_SIZE + 5}
+}
+
+/// Plays a game of the engine against itself, and returns the records of its positions,
+/// whose boards are written as their planes or encoded.
+fn play_game(engine: &mut LocalEngine, rules: Rules, planes: bool, seed: &mut u32) -> Result<Vec<u8>> {
+    *engine.write_board() = random_opening(rules.random_plies, seed);
+
+    // The positions and their scores, from white's point of view.
+    let mut positions = Vec::new();
+    let (mut plies, mut winning_plies) = (0, 0);
+
+    // The result of the game, from white's point of view.
+    let result: i8 = loop {
+        let board = engine.read_board().clone();
+
+        if plies >= rules.max_plies {
+            break 0;
+        }
+
+        let (mv, score) = match engine.think_nodes(rules.nodes) {
+            Some(found) => found,
+            // The game is over on the board.
+            None => break match board.status() {
+                Status::Win(Color::White) => 1,
+                Status::Win(Color::Black) => -1,
+                _ => 0,
+            },
+        };
+
+        let score = match board.get_side_to_move() {
+            Color::White => score,
+            Color::Black => -score,
+        };
+
+        if board.get_checkers().empty() && mv.is_quiet() {
+            positions.push((board, score));
+        }
+
+        // Adjudicates the game once a player has been winning for long enough.
+        if score.abs() >= rules.win_score {
+            winning_plies += 1;
+            if winning_plies >= rules.win_plies {
+                break if score > 0.0 {1} else {-1};
+            }
+        } else {
+            winning_plies = 0;
+        }
+
+        engine.advance(mv);
+        plies += 1;
+    };
+
+    let mut records = Vec::with_capacity(positions.len() * record_size(planes));
+    for (board, score) in positions {
+        if planes {
+            records.extend_from_slice(&board.to_planes());
+        } else {
+            records.extend_from_slice(&board.to_bytes());
+        }
+        records.extend_from_slice(&score.to_le_bytes());
+        records.push(result as u8);
+    }
+
+    Ok(records)
+}
+
+/// The main function parses the programs arguments, then spawns the threads
+/// playing the games and writes their records to the output file.
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine data generator")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("Generates training data for the network by playing the engine against itself.")
+        .arg(Arg::with_name("net")
+            .index(1)
+            .value_name("NET")
+            .help("The path to the network file to use for evaluation.")
+            .required(true))
+        .arg(Arg::with_name("output")
+            .index(2)
+            .value_name("OUTPUT")
+            .help("The path to the file the records are appended to.")
+            .required(true))
+        .arg(Arg::with_name("games")
+            .short("g")
+            .long("games")
+            .value_name("GAMES")
+            .default_value("100")
+            .help("The number of games to play.")
+            .takes_value(true))
+        .arg(Arg::with_name("threads")
+            .short("t")
+            .long("threads")
+            .value_name("THREADS")
+            .default_value("1")
+            .help("The number of threads playing games in parallel.")
+            .takes_value(true))
+        .arg(Arg::with_name("nodes")
+            .short("n")
+            .long("nodes")
+            .value_name("NODES")
+            .default_value("5000")
+            .help("The number of nodes searched per move, roughly.")
+            .takes_value(true))
+        .arg(Arg::with_name("random-plies")
+            .long("random-plies")
+            .value_name("PLIES")
+            .default_value("8")
+            .help("The number of random moves played at the start of each game.")
+            .takes_value(true))
+        .arg(Arg::with_name("win-score")
+            .long("win-score")
+            .value_name("PAWNS")
+            .default_value("10")
+            .help("The score, in pawns, above which a player is considered winning.")
+            .takes_value(true))
+        .arg(Arg::with_name("win-plies")
+            .long("win-plies")
+            .value_name("PLIES")
+            .default_value("8")
+            .help("The number of consecutive plies a player must be winning for the game to be adjudicated as a win.")
+            .takes_value(true))
+        .arg(Arg::with_name("max-plies")
+            .long("max-plies")
+            .value_name("PLIES")
+            .default_value("400")
+            .help("The number of plies after which a game is adjudicated as a draw.")
+            .takes_value(true))
+        .arg(Arg::with_name("planes")
+            .long("planes")
+            .help("Writes the boards as their feature planes, the pieces, the state and the attack maps, rather than encoded."))
+        .get_matches();
+
+    // Parses a numerical argument.
+    fn parse<T: FromStr>(args: &clap::ArgMatches, name: &str) -> Result<T> {
+        T::from_str(args.value_of(name).unwrap()).map_err(|_| Error::msg(format!("Unable to parse {}.", name)))
+    }
+
+    let games: usize = parse(&args, "games")?;
+    let threads: usize = parse(&args, "threads")?;
+    let planes = args.is_present("planes");
+    let rules = Rules {
+        nodes: parse(&args, "nodes")?,
+        random_plies: parse(&args, "random-plies")?,
+        win_score: parse(&args, "win-score")?,
+        win_plies: parse(&args, "win-plies")?,
+        max_plies: parse(&args, "max-plies")?,
+    };
+
+    if threads == 0 {
+        return Err(Error::msg("Invalid number of threads, there must be at least one."));
+    }
+
+    // The network is read once and shared by the threads.
+    let net = Arc::new(fs::read(args.value_of("net").unwrap())?);
+    let output = File::options().create(true).append(true).open(args.value_of("output").unwrap())?;
+    let mut output = BufWriter::new(output);
+
+    // The number of games started, shared by the threads.
+    let started = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+
+    let time_seed = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos();
+
+    // Launch the threads, each with its own engine.
+    let mut handles = Vec::new();
+    for i in 0..threads {
+        let (net, started, tx) = (net.clone(), started.clone(), tx.clone());
+        let mut seed = (time_seed ^ (i as u32).wrapping_mul(0x9E3779B9)) | 1;
+
+        handles.push(thread::spawn(move || -> Result<()> {
+            let mut engine = LocalEngine::new(Board::new(STARTING_FEN)?, &net)?;
+
+            while started.fetch_add(1, Ordering::Relaxed) < games {
+                let records = play_game(&mut engine, rules, planes, &mut seed)?;
+                if tx.send(records).is_err() {
+                    break;
+                }
+            }
+
+            Ok(())
+        }));
+    }
+    drop(tx);
+
+    // Write the records as games are completed.
+    let (mut finished, mut positions) = (0, 0);
+    for records in rx {
+        output.write_all(&records)?;
+        finished += 1;
+        positions += records.len() / record_size(planes);
+
+        if finished % REPORT_INTERVAL == 0 || finished == games {
+            println!("{}/{} games played, {} positions recorded.", finished, games, positions);
+        }
+    }
+    output.flush()?;
+
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    Ok(())
+}
@@ -1,20 +1,34 @@
 use std::fmt;
+use std::future::Future;
 use std::path::Path;
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
-use std::sync::{Arc, Barrier, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU16, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Barrier, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::task::{Context, Poll, Waker};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
 use anyhow::{Error, Result};
 
-use chess::board::Board;
-use chess::book::Book;
+use chess::board::{Board, Status};
+use chess::book::{Book, BookPolicy};
+use chess::color::Color;
+use chess::movegen;
 use chess::moves::{AtomicMove, Move};
+use chess::variant::Variant;
 
-use crate::eval::Net;
-use crate::{params, utils};
+use crate::bench::{self, BenchResult};
+use crate::eval::{Eval, EvalBreakdown, EvalMode, Net};
+use crate::experience::Experience;
+use crate::params::Tunables;
+use crate::{mate, params, utils};
+use crate::mate::MateResult;
+use crate::report::{GameReport, MoveReport};
 use crate::search::Search;
-use crate::table::TranspositionTable;
+use crate::split::{RootQueue, SmpMode};
+use crate::strength::Strength;
+use crate::table::{TableEntry, TableEntryFlag, TranspositionTable};
+use crate::utils::Deadline;
 
 //#################################################################################################
 //
@@ -27,12 +41,42 @@ use crate::table::TranspositionTable;
 pub(crate) struct GlobalInfo {
     barrier: Barrier,
     searching: AtomicBool,
+    limit_reached: AtomicBool,
     stop: AtomicBool,
+    running: AtomicU8,
+    completion: Mutex<Arc<Completion>>,
     
     table: TranspositionTable,
+    num_threads: u8,
     search_depth: AtomicU8,
+    seldepth: AtomicU8,
     search_id: AtomicU8,
+    smp_mode: AtomicU8,
+    eval_mode: AtomicU8,
+    root_queue: Mutex<RootQueue>,
     best_move: AtomicMove,
+    lines: RwLock<Vec<(Move, f32)>>,
+    multipv: AtomicU8,
+    root_moves: RwLock<Vec<Move>>,
+    depth_limit: AtomicU8,
+    nodes: AtomicU64,
+    node_limit: AtomicU64,
+    eval_probes: AtomicU64,
+    eval_hits: AtomicU64,
+    searches: AtomicU64,
+    total_nodes: AtomicU64,
+    search_micros: AtomicU64,
+    total_depth: AtomicU64,
+    deadline: RwLock<Option<Deadline>>,
+    strength: RwLock<Strength>,
+    variety: AtomicU8,
+    tunables: RwLock<Tunables>,
+    contempt: AtomicI32,
+    avoid_repetitions: AtomicBool,
+    seed: AtomicU32,
+    deterministic: AtomicBool,
+    generation: AtomicU16,
+    noise_key: AtomicU32,
 
     board: RwLock<Board>,
 }
@@ -40,6 +84,83 @@ pub(crate) struct GlobalInfo {
 // ================================ pub(crate) impl
 
 impl GlobalInfo {
+    /// Creates the info shared by num_threads search threads, working on the given board,
+    /// with a transposition table of roughly table_mb megabytes. If the threads are pinned to
+    /// cores, the memory of the table is left for them to touch first.
+    pub(crate) fn new(board: Board, num_threads: usize, table_mb: usize, pinned: bool) -> GlobalInfo {
+        GlobalInfo {
+            barrier: Barrier::new(num_threads + 1),
+            searching: AtomicBool::new(false),
+            limit_reached: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+            running: AtomicU8::new(0),
+            completion: Mutex::new(Arc::new(Completion::done())),
+            
+            table: if pinned {TranspositionTable::untouched(table_mb << 20)} else {TranspositionTable::new(table_mb << 20)},
+            num_threads: num_threads as u8,
+            search_depth: AtomicU8::new(0),
+            seldepth: AtomicU8::new(0),
+            search_id: AtomicU8::new(0),
+            smp_mode: AtomicU8::new(SmpMode::default() as u8),
+            eval_mode: AtomicU8::new(EvalMode::default() as u8),
+            root_queue: Mutex::new(RootQueue::default()),
+            best_move: AtomicMove::default(),
+            lines: RwLock::new(Vec::new()),
+            multipv: AtomicU8::new(1),
+            root_moves: RwLock::new(Vec::new()),
+            depth_limit: AtomicU8::new(params::MAX_DEPTH as u8),
+            nodes: AtomicU64::new(0),
+            node_limit: AtomicU64::new(u64::MAX),
+            eval_probes: AtomicU64::new(0),
+            eval_hits: AtomicU64::new(0),
+            searches: AtomicU64::new(0),
+            total_nodes: AtomicU64::new(0),
+            search_micros: AtomicU64::new(0),
+            total_depth: AtomicU64::new(0),
+            deadline: RwLock::new(None),
+            strength: RwLock::new(Strength::default()),
+            variety: AtomicU8::new(0),
+            tunables: RwLock::new(Tunables::default()),
+            contempt: AtomicI32::new(0),
+            avoid_repetitions: AtomicBool::new(false),
+            seed: AtomicU32::new(utils::seed()),
+            deterministic: AtomicBool::new(false),
+            generation: AtomicU16::new(0),
+            noise_key: AtomicU32::new(utils::seed()),
+
+            board: RwLock::new(board),
+        }
+    }
+
+    /// Creates the info shared by num_threads search threads, with an empty transposition table of roughly
+    /// table_mb megabytes as with new(), but with the same board and settings as this one. Must not be called while searching.
+    pub(crate) fn resized(&self, num_threads: usize, table_mb: usize, pinned: bool) -> GlobalInfo {
+        GlobalInfo {
+            smp_mode: AtomicU8::new(self.smp_mode.load(Ordering::Acquire)),
+            eval_mode: AtomicU8::new(self.eval_mode.load(Ordering::Acquire)),
+            multipv: AtomicU8::new(self.multipv.load(Ordering::Acquire)),
+            root_moves: RwLock::new(self.root_moves()),
+            depth_limit: AtomicU8::new(self.depth_limit.load(Ordering::Acquire)),
+            node_limit: AtomicU64::new(self.node_limit.load(Ordering::Acquire)),
+            eval_probes: AtomicU64::new(self.eval_probes.load(Ordering::Acquire)),
+            eval_hits: AtomicU64::new(self.eval_hits.load(Ordering::Acquire)),
+            searches: AtomicU64::new(self.searches.load(Ordering::Acquire)),
+            total_nodes: AtomicU64::new(self.total_nodes.load(Ordering::Acquire)),
+            search_micros: AtomicU64::new(self.search_micros.load(Ordering::Acquire)),
+            total_depth: AtomicU64::new(self.total_depth.load(Ordering::Acquire)),
+            strength: RwLock::new(self.strength()),
+            variety: AtomicU8::new(self.variety.load(Ordering::Acquire)),
+            tunables: RwLock::new(self.tunables()),
+            contempt: AtomicI32::new(self.contempt.load(Ordering::Acquire)),
+            avoid_repetitions: AtomicBool::new(self.avoids_repetitions()),
+            seed: AtomicU32::new(self.seed.load(Ordering::Acquire)),
+            deterministic: AtomicBool::new(self.is_deterministic()),
+            generation: AtomicU16::new(self.generation()),
+            noise_key: AtomicU32::new(self.noise_key.load(Ordering::Acquire)),
+            ..GlobalInfo::new(self.board(), num_threads, table_mb, pinned)
+        }
+    }
+
     /// Returns a reference to the TranspositionTable.
     #[inline]
     pub(crate) fn get_table(&self) -> &TranspositionTable {
@@ -58,12 +179,29 @@ impl GlobalInfo {
         self.searching.load(Ordering::Relaxed)
     }
 
+    /// Returns true if the search threads should keep on searching: the engine
+    /// is searching and no limit of the search was reached.
+    #[inline]
+    pub(crate) fn keep_searching(&self) -> bool {
+        self.is_searching() && !self.limit_reached.load(Ordering::Relaxed)
+    }
+
     /// Atomically looks for the stop signal.
     #[inline]
     pub(crate) fn should_stop(&self) -> bool {
         self.stop.load(Ordering::Acquire)
     }
 
+    /// Signals that a search thread is done searching the position, either because
+    /// it was told to stop or because a limit of the search was reached.
+    #[inline]
+    pub(crate) fn thread_done(&self) {
+        // The last thread to stop completes the search.
+        if self.running.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.completion.lock().unwrap().complete();
+        }
+    }
+
     /// Wait at the barrier for every other thread.
     #[inline]
     pub(crate) fn wait(&self) {
@@ -79,29 +217,59 @@ impl GlobalInfo {
     /// Returns the search depth a thread should search to next.
     /// This is computed as 1 + the current base depth + the id,
     /// where the id is a number such that at any given time,
-    /// one thread searches to log2(params::NUM_SEARCH_THREAD),
-    /// two at log2(params::NUM_SEARCH_THREAD)-1, four at 
-    /// log2(params::NUM_SEARCH_THREAD)-2, etc.
+    /// one thread searches to log2(num_threads),
+    /// two at log2(num_threads)-1, four at 
+    /// log2(num_threads)-2, etc.
     /// This allow for a flexible work distribution, and makes threads
     /// not all search at the same thing at the same time.
+    /// In deterministic mode, only one thread searches and it increases the depth one by one.
     #[inline]
     pub(crate) fn thread_search_depth(&self) -> u8 {
         let depth = self.search_depth();
 
+        if self.is_deterministic() {
+            return 1 + depth;
+        }
+
         let id = self.search_id.fetch_update(
             Ordering::SeqCst,
             Ordering::SeqCst,
-            |id| Some((id + 1) % params::NUM_SEARCH_THREAD as u8)
+            |id| Some((id + 1) % self.num_threads)
         ).unwrap();
 
         1 + depth + (id + 1).trailing_zeros() as u8 
     }
 
-    /// Report back a move, stores if it was searched at a deeper depth
-    /// than the current one, and subsequently increase the base search depth.
+    /// Returns the way the search threads share the work.
+    #[inline]
+    pub(crate) fn smp_mode(&self) -> SmpMode {
+        SmpMode::from_u8(self.smp_mode.load(Ordering::Relaxed))
+    }
+
+    /// Returns the way the positions are evaluated.
+    #[inline]
+    pub(crate) fn eval_mode(&self) -> EvalMode {
+        EvalMode::from_u8(self.eval_mode.load(Ordering::Relaxed))
+    }
+
+    /// Sets the way the positions are evaluated.
+    #[inline]
+    pub(crate) fn set_eval_mode(&self, mode: EvalMode) {
+        self.eval_mode.store(mode as u8, Ordering::Relaxed);
+    }
+
+    /// Returns a lock to the queue of root moves, used in the root splitting mode.
+    #[inline]
+    pub(crate) fn root_queue(&self) -> MutexGuard<'_, RootQueue> {
+        self.root_queue.lock().unwrap()
+    }
+
+    /// Report back a move and the best lines found at the root, stores them if they
+    /// were searched at a deeper depth than the current one, and subsequently increase
+    /// the base search depth.
     #[inline]
-    pub(crate) fn report_move(&self, mv: Move, depth: u8) {
-        self.search_depth.fetch_update(
+    pub(crate) fn report_move(&self, mv: Move, lines: &[(Move, f32)], depth: u8) {
+        let deeper = self.search_depth.fetch_update(
             Ordering::SeqCst,
             Ordering::SeqCst,
             |cur_depth| {
@@ -112,18 +280,324 @@ impl GlobalInfo {
                     Some(depth)
                 }
             }
-        ).ok();
+        ).is_ok();
+
+        if deeper {
+            *self.lines.write().unwrap() = lines.to_vec();
+        }
     }
-}
 
-// ================================ impl
+    /// Returns the number of lines to search at the root.
+    #[inline]
+    pub(crate) fn multipv(&self) -> usize {
+        usize::from(self.multipv.load(Ordering::Relaxed))
+    }
+
+    /// Returns the moves the search is restricted to at the root, or an empty list if all moves are searched.
+    #[inline]
+    pub(crate) fn root_moves(&self) -> Vec<Move> {
+        self.root_moves.read().unwrap().clone()
+    }
+
+    /// Restricts the search to the given moves at the root, ignoring the ones that are not legal
+    /// on the board. Lifts the restriction if none of them are.
+    pub(crate) fn set_root_moves(&self, moves: &[Move]) {
+        let board = self.read_board();
+        let legals: Vec<_> = moves.iter().copied().filter(|&mv| board.is_pseudo_legal(mv) && board.is_legal(mv)).collect();
+        *self.root_moves.write().unwrap() = legals;
+    }
+
+    /// Returns the maximum depth a thread may search to.
+    #[inline]
+    pub(crate) fn max_depth(&self) -> u8 {
+        self.depth_limit.load(Ordering::Relaxed).min(self.strength().max_depth)
+    }
+
+    /// Counts the nodes searched by a thread, and stops the search if the deadline
+    /// has passed or the node limit was reached, and a move was already found.
+    #[inline]
+    pub(crate) fn check_limits(&self, nodes: u64) {
+        self.total_nodes.fetch_add(nodes, Ordering::Relaxed);
+        let nodes = self.nodes.fetch_add(nodes, Ordering::Relaxed) + nodes;
+        let deadline_passed = self.deadline.read().unwrap().is_some_and(Deadline::has_passed);
+
+        let node_limit = self.node_limit.load(Ordering::Relaxed).min(self.strength().max_nodes);
+
+        if (deadline_passed || nodes >= node_limit) && self.get_best_move().is_some() {
+            self.limit_reached.store(true, Ordering::Release);
+        }
+    }
+
+    /// Returns the state of the analysis of the position. The principal variations are retrieved from the table.
+    pub(crate) fn analysis(&self) -> Analysis {
+        let depth = self.search_depth();
+
+        let lines = self.lines.read().unwrap().iter().map(|&(mv, score)| {
+            let mut board = self.board();
+            let mut pv = vec![mv];
+            let mut seen = vec![board.get_zobrist()];
+            board.do_move(mv);
+
+            // Follow the moves stored in the table, stopping on any cycle.
+            while pv.len() < usize::from(depth.max(1)) && !seen.contains(&board.get_zobrist()) {
+                match self.get_table().get_move(board.get_zobrist()) {
+                    Some(mv) if board.is_pseudo_legal(mv) && board.is_legal(mv) => {
+                        seen.push(board.get_zobrist());
+                        board.do_move(mv);
+                        pv.push(mv);
+                    },
+                    _ => break,
+                }
+            }
+
+            Line {score, pv}
+        }).collect();
+
+        Analysis {
+            depth,
+            seldepth: self.seldepth(),
+            nodes: self.nodes.load(Ordering::Relaxed),
+            lines,
+        }
+    }
+
+    /// Reports the selective depth reached by a search thread, the longest line it searched.
+    #[inline]
+    pub(crate) fn report_seldepth(&self, seldepth: u8) {
+        self.seldepth.fetch_max(seldepth, Ordering::Relaxed);
+    }
+
+    /// Returns the selective depth of the search, the longest line searched by any thread.
+    #[inline]
+    pub(crate) fn seldepth(&self) -> u8 {
+        self.seldepth.load(Ordering::Relaxed)
+    }
+
+    /// Adds the given number of probes and hits of the evaluation cache of a search thread to the statistics.
+    #[inline]
+    pub(crate) fn add_eval_stats(&self, probes: u64, hits: u64) {
+        self.eval_probes.fetch_add(probes, Ordering::Relaxed);
+        self.eval_hits.fetch_add(hits, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics of the engine since its creation.
+    #[inline]
+    pub(crate) fn stats(&self) -> Stats {
+        Stats {
+            eval_probes: self.eval_probes.load(Ordering::Relaxed),
+            eval_hits: self.eval_hits.load(Ordering::Relaxed),
+            searches: self.searches.load(Ordering::Relaxed),
+            nodes: self.total_nodes.load(Ordering::Relaxed),
+            search_time: Duration::from_micros(self.search_micros.load(Ordering::Relaxed)),
+            total_depth: self.total_depth.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Adds a search that lasted for the given duration and completed the given depth to the statistics.
+    #[inline]
+    pub(crate) fn add_search_stats(&self, duration: Duration, depth: u8) {
+        self.searches.fetch_add(1, Ordering::Relaxed);
+        self.search_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.total_depth.fetch_add(u64::from(depth), Ordering::Relaxed);
+    }
+
+    /// Returns the current strength of the engine.
+    #[inline]
+    pub(crate) fn strength(&self) -> Strength {
+        *self.strength.read().unwrap()
+    }
+
+    /// Sets the strength of the engine.
+    #[inline]
+    pub(crate) fn set_strength(&self, strength: Strength) {
+        *self.strength.write().unwrap() = strength;
+    }
+
+    /// Sets the variety level of the engine, clamped to MAX_VARIETY.
+    #[inline]
+    pub(crate) fn set_variety(&self, level: u8) {
+        self.variety.store(level.min(params::MAX_VARIETY), Ordering::Release);
+    }
+
+    /// Returns the current search parameters.
+    #[inline]
+    pub(crate) fn tunables(&self) -> Tunables {
+        *self.tunables.read().unwrap()
+    }
+
+    /// Sets the search parameters.
+    #[inline]
+    pub(crate) fn set_tunables(&self, tunables: Tunables) {
+        *self.tunables.write().unwrap() = tunables;
+    }
+
+    /// Returns the contempt of the engine for draws, in pawns.
+    #[inline]
+    pub(crate) fn contempt(&self) -> f32 {
+        self.contempt.load(Ordering::Relaxed) as f32 / 100.0
+    }
+
+    /// Sets the contempt of the engine for draws, in centipawns.
+    #[inline]
+    pub(crate) fn set_contempt(&self, cp: i32) {
+        self.contempt.store(cp, Ordering::Relaxed);
+    }
+
+    /// Returns true if the engine biases its contempt by its score to avoid or seek repetitions.
+    #[inline]
+    pub(crate) fn avoids_repetitions(&self) -> bool {
+        self.avoid_repetitions.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether the engine biases its contempt by its score to avoid or seek repetitions.
+    #[inline]
+    pub(crate) fn set_avoid_repetitions(&self, avoid: bool) {
+        self.avoid_repetitions.store(avoid, Ordering::Relaxed);
+    }
+
+    /// Sets the seed from which all pseudo-random numbers of the search are derived.
+    #[inline]
+    pub(crate) fn set_seed(&self, seed: u32) {
+        self.seed.store(seed, Ordering::Release);
+        self.draw_noise_key();
+    }
+
+    /// Returns the seed a search thread of the given id uses for the current search.
+    /// It only depends on the seed of the engine and on the number of searches since it was set.
+    #[inline]
+    pub(crate) fn thread_seed(&self, id: usize) -> u32 {
+        let mut seed = self.seed.load(Ordering::Acquire) ^ (id as u32 + 1).wrapping_mul(0x9E3779B9);
+        utils::xorshift32(&mut seed)
+    }
+
+    /// Returns true if the engine is in deterministic mode, where a single thread searches.
+    #[inline]
+    pub(crate) fn is_deterministic(&self) -> bool {
+        self.deterministic.load(Ordering::Relaxed)
+    }
+
+    /// Sets or unsets the deterministic mode.
+    #[inline]
+    pub(crate) fn set_deterministic(&self, deterministic: bool) {
+        self.deterministic.store(deterministic, Ordering::Release);
+    }
+
+    /// Returns the random offset added to the score of a root move, searched to the given depth, to weaken
+    /// the engine or to vary its play. It is the same for all threads, and stays the same until the search is reset.
+    #[inline]
+    pub(crate) fn root_noise(&self, mv: Move, search_depth: u8) -> f32 {
+        let variety = f32::from(self.variety.load(Ordering::Relaxed)) * params::VARIETY_NOISE
+            * f32::from(params::VARIETY_FULL_DEPTH) / f32::from(search_depth.max(params::VARIETY_FULL_DEPTH));
+
+        utils::noise(self.noise_key.load(Ordering::Relaxed) ^ mv.get_raw(), self.strength().noise + variety)
+    }
+
+    /// Returns the move to play: the best move found, or sometimes a random
+    /// legal move when the strength of the engine allows for blunders.
+    pub(crate) fn final_move(&self, seed: &mut u32) -> Option<Move> {
+        let best_move = self.get_best_move()?;
+
+        if utils::xorshift32(seed) % 100 < self.strength().blunder_chance {
+            let mut legals = movegen::MoveList::new();
+            let root_moves = self.root_moves();
+            if root_moves.is_empty() {
+                movegen::legals_into(&self.read_board(), &mut legals);
+            } else {
+                root_moves.into_iter().for_each(|mv| legals.push(mv));
+            }
+
+            if !legals.is_empty() {
+                return Some(legals[utils::xorshift32(seed) as usize % legals.len()]);
+            }
+        }
+
+        Some(best_move)
+    }
+
+    /// Sets the searching flag, and clears the limit reached flag.
+    #[inline]
+    pub(crate) fn set_searching(&self, searching: bool) {
+        self.limit_reached.store(false, Ordering::Release);
+        self.searching.store(searching, Ordering::Release);
+    }
+
+    /// Sets the deadline after which the search will stop by itself, if any.
+    #[inline]
+    pub(crate) fn set_deadline(&self, deadline: Option<Deadline>) {
+        *self.deadline.write().unwrap() = deadline;
+    }
+
+    /// Resets the search informations, before searching a new position.
+    #[inline]
+    pub(crate) fn reset_search(&self) {
+        self.search_depth.store(0, Ordering::Release);
+        self.seldepth.store(0, Ordering::Release);
+        self.search_id.store(0, Ordering::Release);
+        self.root_queue().clear();
+        self.best_move.reset();
+        self.lines.write().unwrap().clear();
+        self.nodes.store(0, Ordering::Release);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.draw_noise_key();
+    }
+
+    /// Returns the generation of the current search, incremented each time the search is reset.
+    /// It is the age of the entries of the transposition table, older entries being replaced first.
+    #[inline]
+    pub(crate) fn generation(&self) -> u16 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Sets the generation of the current search, when restoring the entries of the transposition table.
+    #[inline]
+    pub(crate) fn set_generation(&self, generation: u16) {
+        self.generation.store(generation, Ordering::Release);
+    }
+
+    /// Draws the key of the noise of the next search, advancing the seed.
+    #[inline]
+    pub(crate) fn draw_noise_key(&self) {
+        let mut seed = self.seed.load(Ordering::Acquire);
+        self.noise_key.store(utils::xorshift32(&mut seed), Ordering::Release);
+        self.seed.store(seed, Ordering::Release);
+    }
 
-impl GlobalInfo {
     /// Loads the best move found as of now.
     #[inline]
-    fn get_best_move(&self) -> Option<Move> {
+    pub(crate) fn get_best_move(&self) -> Option<Move> {
         self.best_move.load()
     }
+
+    /// Returns the score of the best line found as of now, from the point of view of the side to move.
+    #[inline]
+    pub(crate) fn get_best_score(&self) -> Option<f32> {
+        self.lines.read().unwrap().first().map(|&(_, score)| score)
+    }
+
+    /// Sets the maximum number of nodes searched, roughly, or removes the limit.
+    #[inline]
+    pub(crate) fn set_node_limit(&self, nodes: Option<u64>) {
+        self.node_limit.store(nodes.unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// Sets the maximum depth searched, or removes the limit.
+    #[inline]
+    pub(crate) fn set_depth_limit(&self, depth: Option<u8>) {
+        let depth = depth.map_or(params::MAX_DEPTH as u8, |depth| depth.min(params::MAX_DEPTH as u8));
+        self.depth_limit.store(depth, Ordering::Release);
+    }
+
+    /// Returns a read lock to the board.
+    #[inline]
+    pub(crate) fn read_board(&self) -> RwLockReadGuard<'_, Board> {
+        self.board.read().unwrap()
+    }
+
+    /// Returns a write lock to the board.
+    #[inline]
+    pub(crate) fn write_board(&self) -> RwLockWriteGuard<'_, Board> {
+        self.board.write().unwrap()
+    }
 }
 
 //#################################################################################################
@@ -145,7 +619,10 @@ pub enum EngineStatus {
     Preferred {
         mv: Move,
         depth: u8,
-    }
+        seldepth: u8,
+    },
+    /// When the engine was asked to think in a position where the game is over.
+    GameOver(Status),
 }
 
 // ================================ pub impl
@@ -174,11 +651,226 @@ impl fmt::Display for EngineStatus {
             EngineStatus::Idling => write!(f, "Engine has had no time to think yet."),
             EngineStatus::Thinking => write!(f, "Engine is currently thinking..."),
             EngineStatus::BookMove(mv) => write!(f, "Engine has found a book move, {}.", mv),
-            EngineStatus::Preferred {mv, depth} => write!(f, "Engine's preferred move is {}, found after searching up to depth: {}.", mv, depth),
+            EngineStatus::Preferred {mv, depth, seldepth} => write!(f, "Engine's preferred move is {}, found after searching up to depth: {} (selective depth: {}).", mv, depth, seldepth),
+            EngineStatus::GameOver(Status::Win(Color::White)) => write!(f, "The game is over, white won."),
+            EngineStatus::GameOver(Status::Win(Color::Black)) => write!(f, "The game is over, black won."),
+            EngineStatus::GameOver(_) => write!(f, "The game is over, it is a draw."),
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct Completion
+//
+//#################################################################################################
+
+/// The completion of a search, shared between the search threads and the futures and threads awaiting it.
+#[derive(Debug)]
+struct Completion {
+    state: Mutex<(bool, Option<Waker>)>,
+    // Notified when the search ends, for threads blocking on it.
+    ended: Condvar,
+}
+
+// ================================ impl
+
+impl Completion {
+    /// Creates the completion of a search that has not ended yet.
+    fn pending() -> Completion {
+        Completion {
+            state: Mutex::new((false, None)),
+            ended: Condvar::new(),
+        }
+    }
+
+    /// Creates the completion of a search that has already ended.
+    fn done() -> Completion {
+        Completion {
+            state: Mutex::new((true, None)),
+            ended: Condvar::new(),
+        }
+    }
+
+    /// Marks the search as ended, and wakes the task and the threads awaiting it, if any.
+    fn complete(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.0 = true;
+        self.ended.notify_all();
+
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    }
+
+    /// Blocks the current thread until the search has ended.
+    fn wait(&self) {
+        let state = self.state.lock().unwrap();
+        drop(self.ended.wait_while(state, |state| !state.0).unwrap());
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct SearchDone
+//
+//#################################################################################################
+
+/// A future resolving when the search started by Engine::think() ends, whatever the reason:
+/// its duration has passed, another limit of the search was reached, or it was stopped.
+/// The engine must then be stopped and polled to get the results of the search.
+#[derive(Debug)]
+pub struct SearchDone {
+    completion: Arc<Completion>,
+}
+
+// ================================ traits impl
+
+impl Future for SearchDone {
+    type Output = ();
+
+    /// Resolves once the search threads have all stopped searching.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.completion.state.lock().unwrap();
+
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct Analysis
+//
+//#################################################################################################
+
+/// A line found by the engine: a principal variation and its score.
+#[derive(Clone, Debug)]
+pub struct Line {
+    /// The score of the line, in pawns, from the point of view of the side to move.
+    pub score: f32,
+    /// The principal variation, starting with the root move.
+    pub pv: Vec<Move>,
+}
+
+/// The state of the engine's analysis of the position.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    /// The depth of the last completed iteration.
+    pub depth: u8,
+    /// The selective depth, the number of plies of the longest line searched, extensions and captures included.
+    pub seldepth: u8,
+    /// The number of nodes searched, roughly.
+    pub nodes: u64,
+    /// The best lines found, best first.
+    pub lines: Vec<Line>,
+}
+
+//#################################################################################################
+//
+//                                         struct Stats
+//
+//#################################################################################################
+
+/// Statistics about the searches of the engine, since its creation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// The number of positions looked up in the evaluation caches of the search threads.
+    pub eval_probes: u64,
+    /// The number of those positions that were found, and were not evaluated again.
+    pub eval_hits: u64,
+    /// The number of searches, a search resumed after being stopped counting again.
+    pub searches: u64,
+    /// The number of nodes searched, roughly.
+    pub nodes: u64,
+    /// The time spent searching.
+    pub search_time: Duration,
+    /// The sum of the depths completed by the searches.
+    pub total_depth: u64,
+}
+
+// ================================ pub impl
+
+impl Stats {
+    /// Returns the proportion of evaluations found in the evaluation caches, between 0 and 1.
+    pub fn eval_hit_rate(&self) -> f64 {
+        if self.eval_probes == 0 {
+            0.0
+        } else {
+            self.eval_hits as f64 / self.eval_probes as f64
+        }
+    }
+
+    /// Returns the number of nodes searched per second, on average.
+    pub fn nodes_per_second(&self) -> f64 {
+        let seconds = self.search_time.as_secs_f64();
+
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.nodes as f64 / seconds
+        }
+    }
+
+    /// Returns the depth completed by the searches, on average.
+    pub fn average_depth(&self) -> f64 {
+        if self.searches == 0 {
+            0.0
+        } else {
+            self.total_depth as f64 / self.searches as f64
         }
     }
 }
 
+//#################################################################################################
+//
+//                                      struct EngineConfig
+//
+//#################################################################################################
+
+/// The resources used by an engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// The number of search threads, between 1 and 128.
+    pub threads: usize,
+    /// The size of the transposition table, in megabytes. It is rounded up to a power of two.
+    pub hash_mb: usize,
+}
+
+// ================================ traits impl
+
+impl Default for EngineConfig {
+    /// The default configuration: 8 search threads, and a transposition table of 32 megabytes.
+    fn default() -> EngineConfig {
+        EngineConfig {
+            threads: params::NUM_SEARCH_THREAD,
+            hash_mb: params::TABLE_SIZE_MB,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct BookOptions
+//
+//#################################################################################################
+
+/// The way an engine plays the moves of its book.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BookOptions {
+    /// The way a move is chosen among the entries of the book, following their weights by default.
+    pub policy: BookPolicy,
+    /// The ply of the game from which the book is no longer probed, if any.
+    pub max_ply: Option<u16>,
+    /// Whether the book moves played by the losing side of a game lose some of their weight,
+    /// when the result is given to the engine. Off by default.
+    pub learning: bool,
+}
+
 //#################################################################################################
 //
 //                                        struct Engine
@@ -190,62 +882,68 @@ impl fmt::Display for EngineStatus {
 pub struct Engine {
     info: Arc<GlobalInfo>,
     handles: Vec<JoinHandle<()>>,
+    config: EngineConfig,
+    // The logical cpus the search threads are pinned to, empty if they are not.
+    cores: Vec<usize>,
     book: Option<Book>,
+    book_options: BookOptions,
+    book_moves: Vec<(Board, Move)>,
+    experience: Option<Arc<Experience>>,
+    net: Arc<Net>,
+    // The network to use from the next search, when it was set during a search.
+    pending_net: Option<Arc<Net>>,
     status: EngineStatus,
     seed: u32,
+    // The report of the moves played, and the statistics of the searches when the last one was.
+    report: GameReport,
+    report_stats: Stats,
 }
 
 // ================================ pub impl
 
 impl Engine {
     /// Initializes a new chess engine, working on a board.
-    pub fn new(board: Board, book_path: Option<&str>, net_path: &str) -> Result<Engine> {
-        // The book that may be used to lookup moves.
-        let book = if let Some(book_path) = book_path {
-            Some(Book::open(Path::new(book_path))?)
-        } else {
-            None
+    /// The engine plays the moves of the given book whenever it can.
+    pub fn new(board: Board, book: Option<Book>, net_path: &str) -> Result<Engine> {
+        Engine::with_config(board, book, net_path, EngineConfig::default())
+    }
+
+    /// Initializes a new chess engine, as with new(), using the given resources.
+    pub fn with_config(board: Board, book: Option<Book>, net_path: &str, config: EngineConfig) -> Result<Engine> {
+        let config = EngineConfig {
+            threads: config.threads.clamp(1, params::MAX_SEARCH_THREAD),
+            hash_mb: config.hash_mb.max(1),
         };
 
         // The neural network used for evaluation.
         let net = Net::load(Path::new(net_path))?;
 
         // Construct the initial info object.
-        let info = Arc::new(GlobalInfo {
-            barrier: Barrier::new(params::NUM_SEARCH_THREAD + 1),
-            searching: AtomicBool::new(false),
-            stop: AtomicBool::new(false),
-            
-            table: TranspositionTable::new(),
-            search_depth: AtomicU8::new(0),
-            search_id: AtomicU8::new(0),
-            best_move: AtomicMove::default(),
+        let info = Arc::new(GlobalInfo::new(board, config.threads, config.hash_mb, false));
 
-            board: RwLock::new(board),
-        });
-
-        // The seed used for all pseudo-random number generation.
-        let mut seed = utils::seed();
-
-        // Initializes the thread pool.
-        let handles = (0..params::NUM_SEARCH_THREAD).map(|_| {
-            let thread_seed = utils::xorshift32(&mut seed).wrapping_mul(0x98FF2E9E);
-            let info = info.clone();
-            let net = net.clone();
+        // The seed used for the pseudo-random choices of the engine, such as book moves.
+        let seed = utils::seed();
 
-            thread::spawn(move || {
-                let mut search = Search::new(thread_seed, info, net);
-                search.thread_main();
-            })
-        }).collect();
-
-        Ok(Engine {
+        let mut engine = Engine {
             info,
-            handles,
+            handles: Vec::new(),
+            config,
+            cores: Vec::new(),
             book,
+            book_options: BookOptions::default(),
+            book_moves: Vec::new(),
+            experience: None,
+            net,
+            pending_net: None,
             status: EngineStatus::Idling,
             seed,
-        })
+            report: GameReport::default(),
+            report_stats: Stats::default(),
+        };
+
+        // Initializes the thread pool.
+        engine.spawn_threads();
+        Ok(engine)
     }
 
     /// Returns the current best move.
@@ -253,15 +951,263 @@ impl Engine {
         &self.status
     }
 
+    /// Returns the opening book used by the engine, if it was given one.
+    pub fn get_book(&self) -> Option<&Book> {
+        self.book.as_ref()
+    }
+
+    /// Replaces the book of the engine, None meaning that it plays without a book.
+    /// Takes effect at the next call to start(). The book moves played are forgotten.
+    pub fn set_book(&mut self, book: Option<Book>) {
+        self.book = book;
+        self.book_moves.clear();
+    }
+
+    /// Replaces the network used for evaluation. If the engine is thinking, the search goes on with the
+    /// previous network, and the new one is used from the next call to start(). The transposition table
+    /// is cleared, as the scores it holds were given by the previous network.
+    pub fn set_net(&mut self, net: Arc<Net>) {
+        self.pending_net = Some(net);
+
+        if !self.info.is_searching() {
+            self.swap_net();
+        }
+    }
+
+    /// Returns the experience of the engine, if it was given one.
+    pub fn get_experience(&self) -> Option<&Arc<Experience>> {
+        self.experience.as_ref()
+    }
+
+    /// Gives the engine an experience, or none. The moves the engine chooses with deep enough searches,
+    /// of 8 plies at least, are recorded in it as they are played with advance(). In turn,
+    /// the move recorded for the position to search is searched first, its score being a lower bound of the
+    /// position's, and the book moves leading to positions the engine found bad for it are avoided.
+    /// Takes effect at the next call to start().
+    pub fn set_experience(&mut self, experience: Option<Arc<Experience>>) {
+        self.experience = experience;
+    }
+
+    /// Returns the way the engine plays the moves of its book.
+    pub fn get_book_options(&self) -> BookOptions {
+        self.book_options
+    }
+
+    /// Sets the way the engine plays the moves of its book. Takes effect at the next call to start().
+    pub fn set_book_options(&mut self, options: BookOptions) {
+        self.book_options = options;
+    }
+
+    /// Gives the engine the result of the game it played, None being a draw. When book learning is on,
+    /// the book moves played by the losing side since the last call lose some of their weight, so
+    /// that they are chosen less often. The book moves played are then forgotten.
+    pub fn learn_result(&mut self, winner: Option<Color>) {
+        let book_moves = std::mem::take(&mut self.book_moves);
+
+        if let (Some(book), Some(winner), true) = (&mut self.book, winner, self.book_options.learning) {
+            for (board, mv) in book_moves.iter().filter(|(board, _)| board.get_side_to_move() != winner) {
+                book.learn(board, *mv, params::BOOK_LEARNING_PENALTY);
+            }
+        }
+    }
+
     /// Returns a read lock to the board.
     pub fn read_board(&self) -> RwLockReadGuard<'_, Board> {
-        self.info.board.read().unwrap()
+        self.info.read_board()
+    }
+
+    /// Sets the strength level of the engine, between 0 and MAX_STRENGTH.
+    /// At lower levels, the search is limited in depth and nodes, the scores
+    /// of moves are made noisy, and the engine may even blunder.
+    /// Takes effect at the next call to start().
+    pub fn set_strength(&mut self, level: u8) {
+        self.info.set_strength(Strength::new(level));
+    }
+
+    /// Sets the variety level of the engine, between 0 (the default) and MAX_VARIETY. Above 0, small random
+    /// offsets are added to the scores of root moves, so that the engine does not always play the same
+    /// game when it has no book. They are largest at low depths and fade as the search goes deeper, so
+    /// that only moves of nearly equal scores are swapped. They are derived from the seed of the engine.
+    /// Takes effect at the next call to start().
+    pub fn set_variety(&mut self, level: u8) {
+        self.info.set_variety(level);
+    }
+
+    /// Sets the number of lines the engine searches for, between 1 and 255.
+    /// Searching more than one line is slower, and is only useful for analysis.
+    /// Takes effect at the next call to start().
+    pub fn set_multipv(&mut self, multipv: u8) {
+        self.info.multipv.store(multipv.max(1), Ordering::Release);
+    }
+
+    /// Restricts the search to the given moves at the root, as with the searchmoves of UCI. Moves that are
+    /// not legal are ignored, and the restriction is lifted if none of them are, or when the board is written
+    /// to. The book is not used while the restriction holds. Takes effect at the next call to start().
+    pub fn set_root_moves(&mut self, moves: &[Move]) {
+        self.info.set_root_moves(moves);
+    }
+
+    /// Sets the maximum depth searched by the engine, or removes that limit.
+    /// Takes effect at the next call to start().
+    pub fn set_depth_limit(&mut self, depth: Option<u8>) {
+        self.info.set_depth_limit(depth);
+    }
+
+    /// Sets the way the search threads share the work of searching a position: either at depths offset
+    /// from one another, or by splitting the root moves between them. Which one scales better depends
+    /// on the number of cores, see the smpbench command of the CLI. Takes effect at the next call to start().
+    pub fn set_smp_mode(&mut self, mode: SmpMode) {
+        self.info.smp_mode.store(mode as u8, Ordering::Release);
+    }
+
+    /// Sets the way the engine evaluates positions: with its network, which is the default, with a hand-crafted
+    /// evaluation, or with the material balance alone. The last two are much weaker but faster, and are meant for
+    /// machines too small for the network, for stress tests, and as a baseline to measure the network against.
+    /// Entries of the transposition table found with another mode are kept. Takes effect at the next call to start().
+    pub fn set_eval_mode(&mut self, mode: EvalMode) {
+        self.info.set_eval_mode(mode);
+    }
+
+    /// Returns the resources used by the engine.
+    pub fn get_config(&self) -> EngineConfig {
+        self.config
+    }
+
+    /// Changes the resources used by the engine, the number of threads being clamped between 1 and 128.
+    /// If they differ from the current ones, the engine is stopped, its threads are restarted and its
+    /// transposition table is reallocated, losing its entries. Other settings are kept.
+    pub fn set_config(&mut self, config: EngineConfig) {
+        let config = EngineConfig {
+            threads: config.threads.clamp(1, params::MAX_SEARCH_THREAD),
+            hash_mb: config.hash_mb.max(1),
+        };
+
+        if config == self.config {
+            return;
+        }
+
+        self.join_threads();
+        self.info = Arc::new(self.info.resized(config.threads, config.hash_mb, !self.cores.is_empty()));
+        self.config = config;
+        self.spawn_threads();
+    }
+
+    /// Pins each search thread to a physical core, or lets the operating system schedule them as it wishes,
+    /// which is the default. When pinned, the search threads first touch their share of the transposition table,
+    /// so that its pages are spread over the NUMA nodes of their cores rather than all placed on the node of the
+    /// thread allocating it. Only supported on Linux, elsewhere the threads are never pinned. If it changes, the
+    /// engine is stopped, its threads are restarted and its transposition table is reallocated, losing its entries.
+    pub fn set_affinity(&mut self, affinity: bool) {
+        let cores = if affinity {utils::physical_cores()} else {Vec::new()};
+
+        if cores == self.cores {
+            return;
+        }
+
+        self.join_threads();
+        self.cores = cores;
+        self.info = Arc::new(self.info.resized(self.config.threads, self.config.hash_mb, !self.cores.is_empty()));
+        self.spawn_threads();
+    }
+
+    /// Returns true if the search threads are pinned to cores.
+    pub fn get_affinity(&self) -> bool {
+        !self.cores.is_empty()
+    }
+
+    /// Sets the parameters of the search. Takes effect at the next call to start().
+    pub fn set_tunables(&mut self, tunables: Tunables) {
+        self.info.set_tunables(tunables);
+    }
+
+    /// Sets the seed from which all pseudo-random numbers of the engine are derived: the choice
+    /// of book moves, the noise and blunders of lower strength levels, and the jitter of draw scores.
+    /// By default, the seed is taken from the system time. Takes effect at the next call to start().
+    pub fn set_seed(&mut self, seed: u64) {
+        let seed = utils::fold_seed(seed);
+        self.seed = seed;
+        self.info.set_seed(seed);
+    }
+
+    /// Sets the deterministic mode of the engine. In that mode, a single thread searches the
+    /// position, so that the moves and scores reported don't depend on the scheduling of the threads:
+    /// with the same seed, the same calls give the same results when the search is limited by depth
+    /// or nodes rather than time. It is much slower, and is meant for debugging and regression tests.
+    /// Takes effect at the next call to start().
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.info.set_deterministic(deterministic);
+    }
+
+    /// Sets the contempt of the engine for draws, in centipawns: the engine considers a draw
+    /// to be worth -cp for itself. A positive contempt makes the engine avoid draws against
+    /// weaker opponents, while a negative one makes it seek them. Takes effect at the next call to start().
+    pub fn set_contempt(&mut self, cp: i32) {
+        self.info.set_contempt(cp);
+    }
+
+    /// Sets whether the engine avoids repetitions when it is better and seeks them when it is worse. When on,
+    /// the contempt is biased at each depth by the score of the previous one, up to half a pawn, and draw scores
+    /// are no longer slightly randomized: the engine does not bail into a repetition when it is winning, even by
+    /// little, nor does it avoid one when it is losing. Off by default. Takes effect at the next call to start().
+    pub fn set_avoid_repetitions(&mut self, avoid: bool) {
+        self.info.set_avoid_repetitions(avoid);
+    }
+
+    /// Searches the fixed bench positions to the given depth, on a single thread of its own with a fresh
+    /// transposition table and a fixed seed, and reports the nodes searched and the speed. The total number
+    /// of nodes is a signature of the search: it changes with any change of the search or of the network.
+    pub fn bench(&self, depth: u8) -> Result<BenchResult> {
+        bench::run(self.net.clone(), depth)
+    }
+
+    /// Evaluates the given position statically, without searching it,
+    /// and explains the evaluation of the network.
+    pub fn evaluate_detailed(&self, board: &Board) -> EvalBreakdown {
+        Eval::new(self.net.clone()).breakdown(board)
+    }
+
+    /// Returns the state of the analysis of the position, which may be read while the engine is thinking.
+    /// The principal variations are retrieved from the transposition table.
+    pub fn analysis(&self) -> Analysis {
+        self.info.analysis()
+    }
+
+    /// Searches the current position for a forced mate of the side to move within the given number of moves,
+    /// blocking the caller, as mate::search_mate() does. The position is searched on the caller's thread, even
+    /// if the engine is thinking, and the search of the engine is left untouched.
+    pub fn search_mate(&self, moves: u8) -> MateResult {
+        mate::search_mate(&self.info.board(), moves)
+    }
+
+    /// Returns statistics about the searches of the engine since its creation,
+    /// such as the hit rate of the evaluation caches. They may be read while the engine is thinking.
+    pub fn stats(&self) -> Stats {
+        self.info.stats()
+    }
+
+    /// Saves the state of the engine's analysis, its transposition table, to the given file, so that it
+    /// may be restored later with load_state(). The position itself is not saved. The engine may be
+    /// thinking, though some of the entries written by the search in the meantime may be missing.
+    pub fn save_state(&self, path: &Path) -> Result<()> {
+        self.info.get_table().save(path, self.info.generation())
+    }
+
+    /// Restores the state of the engine's analysis saved with save_state(), stopping the search if it is on.
+    /// The saved entries replace those of the transposition table, and are used by the searches of any
+    /// position, most usefully the one that was analysed when saving.
+    pub fn load_state(&mut self, path: &Path) -> Result<()> {
+        drop(self.write_board());
+
+        let generation = self.info.get_table().load(path)?;
+        self.info.set_generation(generation);
+
+        Ok(())
     }
 
     /// Starts the engine and begins thinking for the next best move.
-    /// May return false, meaning the engine is already thinking, or
-    /// it has found a book move. In either case, the engine must be
-    /// polled to get it's status.
+    /// May return false, meaning the engine is already thinking, it
+    /// has found a book move, or the game is over. In any case, the
+    /// engine must be polled to get it's status.
     /// May return true, meaning the engine has started thinking and
     /// will need to be stopped and polled whenever we want some results.
     pub fn start(&mut self) -> bool {
@@ -270,18 +1216,33 @@ impl Engine {
             return false;
         }
 
+        // A network set during the previous search is used from this one.
+        self.swap_net();
+
+        // If the game is over, there is no move to search, and no search would ever find one.
+        let status = self.info.read_board().status();
+        if !status.is_playing() {
+            self.status = EngineStatus::GameOver(status);
+            return false;
+        }
+
         // If a match is found in a book, return it.
         if let Some(mv) = self.lookup() {
             self.status = EngineStatus::BookMove(mv);
             return false;
         }
 
+        // The move the engine played in the position before, if any, is searched first.
+        self.seed_from_experience();
+
         // Set the engine as thinking.
         self.status = EngineStatus::Thinking;
 
         // Set the searching flag and wait at the barrier with 
         // the other threads that are already waiting.
-        self.info.searching.store(true, Ordering::Release);
+        self.info.set_searching(true);
+        self.info.running.store(self.info.num_threads, Ordering::Release);
+        *self.info.completion.lock().unwrap() = Arc::new(Completion::pending());
         self.info.wait();
 
         return true;
@@ -304,76 +1265,235 @@ impl Engine {
         self.info.searching.store(false, Ordering::Release);
         self.info.wait();
 
+        // The deadline only applies to the search it was set for.
+        self.info.set_deadline(None);
+
         self.status = EngineStatus::Preferred {
-            mv: self.info.get_best_move().unwrap(),
+            mv: self.info.final_move(&mut self.seed).unwrap(),
             depth: self.info.search_depth(),
+            seldepth: self.info.seldepth(),
         };
     }
 
     /// Stops the search if it is on and resets the search informations.
-    /// Then returns a write lock to the board.
+    /// Then returns a write lock to the board. The report of the game is cleared.
     pub fn write_board(&mut self) -> RwLockWriteGuard<'_, Board> {
+        self.report.clear();
+        self.report_stats = self.stats();
+        self.lock_board()
+    }
+
+    /// Plays the given legal move on the board, stopping the search if it is on. The transposition
+    /// table is kept across searches, and the search threads keep their move ordering heuristics
+    /// when the position they search was reached by playing moves from the previous one.
+    /// The move is added to the report of the game, with the search that chose it if the engine did.
+    pub fn advance(&mut self, mv: Move) {
+        let board = self.info.board();
+        let report = self.move_report(&board, mv);
+
+        if let (Some(experience), Some(depth), Some(score)) = (&self.experience, report.depth, report.score) {
+            if depth >= params::EXPERIENCE_MIN_DEPTH {
+                // The entry is kept in memory even if it could not be written to the file of the experience.
+                experience.record(&board, mv, score, depth).ok();
+            }
+        }
+
+        self.report.push(&board, report);
+        self.report_stats = self.stats();
+
+        self.lock_board().do_move(mv);
+    }
+
+    /// Takes back the given move, which must be the last one played on the board, stopping the search if it is on.
+    /// Unlike writing to the board, this keeps the report of the game, without the moves taken back.
+    pub fn undo_move(&mut self, mv: Move) {
+        let ply = {
+            let mut board = self.lock_board();
+            board.undo_move(mv);
+            board.get_ply()
+        };
+
+        let len = self.report.get_start().map_or(0, |start| ply.saturating_sub(start.get_ply()));
+        self.report.truncate(usize::from(len));
+        self.report_stats = self.stats();
+    }
+
+    /// Returns the report of the moves played with advance() since the board was last written to.
+    pub fn report(&self) -> &GameReport {
+        &self.report
+    }
+
+    /// Searches the current position until roughly the given number of nodes are searched,
+    /// blocking the caller, then returns the status of the engine as with poll(). Unlike start(),
+    /// the search does not resume the previous one: if the engine was thinking, it is stopped
+    /// first. If the game is over, no search is started and the status is EngineStatus::GameOver.
+    pub fn search_nodes(&mut self, nodes: u64) -> &EngineStatus {
+        self.info.set_node_limit(Some(nodes));
+        self.search_blocking(None);
+        self.info.set_node_limit(None);
+
+        &self.status
+    }
+
+    /// Searches the current position up to the given depth, blocking the caller,
+    /// then returns the status of the engine, as search_nodes() does.
+    pub fn search_depth(&mut self, depth: u8) -> &EngineStatus {
+        let depth_limit = self.info.depth_limit.load(Ordering::Acquire);
+
+        self.info.set_depth_limit(Some(depth.max(1)));
+        self.search_blocking(None);
+        self.info.depth_limit.store(depth_limit, Ordering::Release);
+
+        &self.status
+    }
+
+    /// Searches the current position for the given duration, blocking the caller,
+    /// then returns the status of the engine, as search_nodes() does.
+    pub fn search_movetime(&mut self, duration: Duration) -> &EngineStatus {
+        self.search_blocking(Some(Deadline::after(duration)));
+
+        &self.status
+    }
+
+    /// Starts a new search of the current position for the given duration, without blocking the caller,
+    /// and returns a future resolving when the search ends: when the duration has passed, when another
+    /// limit of the search is reached, or when the engine is stopped. The engine must then be stopped and
+    /// polled to get its move. As with search_movetime(), the search does not resume the previous one.
+    /// If a book move is found or the game is over, no search is started and the future is ready at once.
+    pub fn think(&mut self, duration: Duration) -> SearchDone {
+        let completion = if self.restart(Some(Deadline::after(duration))) {
+            self.info.completion.lock().unwrap().clone()
+        } else {
+            Arc::new(Completion::done())
+        };
+
+        SearchDone {completion}
+    }
+
+    /// Returns true if the engine is thinking, but its search has ended by itself,
+    /// or because another limit was reached. The engine is then waiting to be stopped.
+    pub fn is_search_done(&self) -> bool {
+        self.status.is_thinking() && self.info.running.load(Ordering::Acquire) == 0
+    }
+}
+
+// ================================ impl
+
+impl Engine {
+    /// Stops the engine if it is thinking, and starts a new search with the given deadline, which
+    /// does not resume the previous one. Returns false if no search was started, because a
+    /// book move was found or the game is over.
+    fn restart(&mut self, deadline: Option<Deadline>) -> bool {
+        self.stop();
+        self.status = EngineStatus::Idling;
+        self.info.reset_search();
+        self.info.set_deadline(deadline);
+
+        if self.start() {
+            return true;
+        }
+
+        self.info.set_deadline(None);
+        false
+    }
+
+    /// Stops the search if it is on and resets the search informations.
+    /// Then returns a write lock to the board.
+    fn lock_board(&mut self) -> RwLockWriteGuard<'_, Board> {
         // Stop if thinking.
         if self.info.is_searching() {
             self.info.searching.store(false, Ordering::Release);
             self.info.wait();
         }
 
-        // Sets the engine as idling.
+        // Sets the engine as idling, without any deadline nor restriction of the root moves.
+        self.info.set_deadline(None);
+        self.info.set_root_moves(&[]);
         self.status = EngineStatus::Idling;
 
-        self.info.search_depth.store(0, Ordering::Release);
-        self.info.search_id.store(0, Ordering::Release);
-        self.info.best_move.reset();
+        self.info.reset_search();
 
-        self.info.board.write().unwrap()
+        self.info.write_board()
     }
-}
 
-// ================================ impl
+    /// Returns the report of the given move, about to be played in the given position: if the engine chose it,
+    /// the line its search expected and the effort spent since the previous move are reported with it.
+    fn move_report(&self, board: &Board, mv: Move) -> MoveReport {
+        let mut report = MoveReport {
+            fen: board.to_string(),
+            mv,
+            san: board.to_san(mv),
+            book: false,
+            depth: None,
+            score: None,
+            pv: Vec::new(),
+            nodes: 0,
+            time: Duration::ZERO,
+        };
 
-impl Engine {
-    /// Stops the search if it is on.
-    /// Probes the book to see if any move may be applied in this situation.
-    fn lookup(&mut self) -> Option<Move> {
-        if let Some(book) = &self.book {
-            let results = book.probe(&self.info.board.read().unwrap());
-        
-            match results.len() {
-                0 => None,
-                1 => {
-                    let (mv, _) = results[0];
-                    Some(mv)
-                },
-                _ => {
-                    let total_weight: u32 = results.iter().map(|&(_, weight)| u32::from(weight)).sum();
-                    let rand = utils::xorshift32(&mut self.seed) % total_weight;
-
-                    let mut sum = 0;
-                    for &(mv, weight) in results.iter() {
-                        let next_sum = sum + u32::from(weight);
-
-                        if (sum..next_sum).contains(&rand) {
-                            return Some(mv);
-                        }
-
-                        sum = next_sum;
-                    }
-
-                    unreachable!()
-                },
-            }
-        } else {
-            None
-        }        
+        match self.status {
+            EngineStatus::BookMove(book_move) if book_move == mv => report.book = true,
+            EngineStatus::Preferred {mv: preferred, depth, ..} if preferred == mv => {
+                let stats = self.stats();
+                let line = self.analysis().lines.into_iter().find(|line| line.pv.first() == Some(&mv));
+
+                report.depth = Some(depth);
+                report.score = line.as_ref().map(|line| line.score);
+                report.pv = line.map_or_else(|| vec![mv], |line| line.pv);
+                report.nodes = stats.nodes - self.report_stats.nodes;
+                report.time = stats.search_time.saturating_sub(self.report_stats.search_time);
+            },
+            _ => (),
+        }
+
+        report
     }
-}
 
-// ================================ traits impl
+    /// Starts a new search with the given deadline and waits for the threads
+    /// to stop by themselves, when one of the limits of the search is reached.
+    fn search_blocking(&mut self, deadline: Option<Deadline>) {
+        if self.restart(deadline) {
+            let completion = self.info.completion.lock().unwrap().clone();
+            completion.wait();
 
-impl Drop for Engine {
-    /// On dropping the engine, make sure that all threads are joined.
-    fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    /// Replaces the network with the pending one, if any, restarting the search threads
+    /// with a fresh transposition table. The engine must not be searching.
+    fn swap_net(&mut self) {
+        if let Some(net) = self.pending_net.take() {
+            self.join_threads();
+            self.net = net;
+            self.info = Arc::new(self.info.resized(self.config.threads, self.config.hash_mb, !self.cores.is_empty()));
+            self.spawn_threads();
+        }
+    }
+
+    /// Starts the search threads, as many as configured.
+    fn spawn_threads(&mut self) {
+        let threads = self.config.threads;
+
+        self.handles = (0..threads).map(|id| {
+            let info = self.info.clone();
+            let net = self.net.clone();
+            let core = (!self.cores.is_empty()).then(|| self.cores[id % self.cores.len()]);
+
+            thread::spawn(move || {
+                if let Some(core) = core {
+                    utils::pin_thread(core);
+                    info.get_table().touch(id, threads);
+                }
+
+                let mut search = Search::new(id, info, net);
+                search.thread_main();
+            })
+        }).collect();
+    }
+
+    /// Stops the engine if it is thinking, then tells the search threads to return and joins them.
+    fn join_threads(&mut self) {
         if self.handles.is_empty() {
             return;
         }
@@ -387,4 +1507,81 @@ impl Drop for Engine {
             handle.join().ok();
         }
     }
+
+    /// Inserts the move the experience of the engine has for the position to search, if any, in the
+    /// transposition table, with its score as a lower bound, unless the table has an entry at least as deep.
+    fn seed_from_experience(&self) {
+        let board = self.info.board();
+        let entry = match self.experience.as_ref().and_then(|experience| experience.get(&board)) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        // A restriction of the root moves may exclude the move, which may also come from another position with the same key.
+        let root_moves = self.info.root_moves();
+        let restricted = !root_moves.is_empty() && !root_moves.contains(&entry.mv);
+        if restricted || !board.is_pseudo_legal(entry.mv) || !board.is_legal(entry.mv) {
+            return;
+        }
+
+        let table = self.info.get_table();
+        if table.get_lower_bound(board.get_zobrist(), entry.depth).is_none() {
+            table.insert(TableEntry::new(&board, self.info.generation(), entry.mv, entry.score, entry.depth, TableEntryFlag::Beta));
+        }
+    }
+
+    /// Probes the book to see if any move may be applied in this situation, unless the game went
+    /// past the maximum ply of the book or the root moves are restricted. The move chosen is remembered for book learning.
+    fn lookup(&mut self) -> Option<Move> {
+        let book = self.book.as_ref()?;
+
+        if !self.info.root_moves().is_empty() {
+            return None;
+        }
+        let board = self.info.board();
+
+        // Books are made of standard games, whose openings may be bad at other variants.
+        if board.get_variant() != Variant::Standard {
+            return None;
+        }
+
+        if self.book_options.max_ply.is_some_and(|max_ply| board.get_ply() >= max_ply) {
+            return None;
+        }
+
+        let mut mv = book.choose_with(&board, self.book_options.policy, utils::xorshift32(&mut self.seed))?;
+
+        // The experience of the engine may show that the move leads to a position bad for it,
+        // in which case the heaviest of the other moves is played instead, if any is not.
+        if let Some(experience) = &self.experience {
+            let refuted = |mv: Move| {
+                let mut child = board.clone();
+                child.do_move(mv);
+                experience.get(&child).is_some_and(|entry| entry.score >= params::EXPERIENCE_BOOK_MARGIN)
+            };
+
+            if refuted(mv) {
+                let others = book.probe(&board);
+                let others = others.iter().copied().filter(|&(other, _)| !refuted(other));
+                if let Some((other, _)) = others.rev().max_by_key(|&(_, weight)| weight) {
+                    mv = other;
+                }
+            }
+        }
+
+        // The same position may be probed several times.
+        self.book_moves.retain(|(other, _)| other.get_zobrist() != board.get_zobrist());
+        self.book_moves.push((board, mv));
+
+        Some(mv)
+    }
+}
+
+// ================================ traits impl
+
+impl Drop for Engine {
+    /// On dropping the engine, make sure that all threads are joined.
+    fn drop(&mut self) {
+        self.join_threads();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use clap::{App, Arg};
+
+use chess::epd::Epd;
+use chess::prelude::*;
+use engine::LocalEngine;
+
+/*
+ * Runs a test suite of EPD positions, such as WAC or STS, and reports how many of them the engine solved.
+ * A position is solved when the move played is one of its best moves ("bm") and none of the moves to
+ * avoid ("am"). Each position is searched for a fixed time, on a single thread, which makes the number
+ * of solved positions usable to detect regressions of the search.
+ *
+ * $ cargo build --bin epd-test --release
+ * $ target/release/epd-test engine/nets/nnue.bin wac.epd --movetime 1000
+ */
+
+/// The main function parses the programs arguments, then searches all positions of the suite.
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine EPD test suite runner")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("Searches the positions of an EPD test suite, and reports how many best moves were found.")
+        .arg(Arg::with_name("net")
+            .index(1)
+            .value_name("NET")
+            .help("The path to the network file to use for evaluation.")
+            .required(true))
+        .arg(Arg::with_name("suite")
+            .index(2)
+            .value_name("SUITE")
+            .help("The path to the EPD file containing the positions to search.")
+            .required(true))
+        .arg(Arg::with_name("movetime")
+            .short("t")
+            .long("movetime")
+            .value_name("MILLISECONDS")
+            .default_value("1000")
+            .help("The time spent searching each position, in milliseconds.")
+            .takes_value(true))
+        .get_matches();
+
+    let movetime = u64::from_str(args.value_of("movetime").unwrap()).map_err(|_| Error::msg("Unable to parse movetime."))?;
+    let movetime = Duration::from_millis(movetime);
+
+    let suite = Epd::open(Path::new(args.value_of("suite").unwrap()))?;
+    let net = fs::read(args.value_of("net").unwrap())?;
+    let mut engine = LocalEngine::new(suite.first().map_or_else(Board::default, |epd| epd.get_board().clone()), &net)?;
+
+    let mut solved = 0;
+    for (i, epd) in suite.iter().enumerate() {
+        *engine.write_board() = epd.get_board().clone();
+
+        let id = epd.get_id().map_or_else(|| format!("#{}", i + 1), str::to_string);
+        let expected = ["bm", "am"].iter()
+            .filter_map(|&opcode| epd.get_operation(opcode).map(|moves| format!("{} {}", opcode, moves.join(" "))))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        match engine.think(movetime) {
+            Some(mv) if epd.is_solution(mv) => {
+                solved += 1;
+                println!("[ok]     {}: played {} ({}).", id, mv, expected);
+            },
+            Some(mv) => println!("[failed] {}: played {} ({}).", id, mv, expected),
+            None => println!("[failed] {}: the game is over in this position.", id),
+        }
+    }
+
+    println!("Solved {}/{} positions.", solved, suite.len());
+
+    Ok(())
+}
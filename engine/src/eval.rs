@@ -1,20 +1,22 @@
-use std::alloc::{self, Layout};
-use std::fs::File;
-use std::io::Read;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
 use std::mem;
 use std::ops::Shl;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::{Error, Result};
 
+use chess::bitboard::BitBoard;
 use chess::board::Board;
 use chess::moves::Move;
 use chess::piece::Piece;
 use chess::prelude::Color;
 use chess::square::Square;
 
-use crate::utils;
+use crate::{classical, utils};
 
 /// Returns the heuristic value of a piece, in pawns.
 #[inline]
@@ -29,6 +31,169 @@ pub(crate) const fn value_of(piece: Piece) -> f32 {
     }
 }
 
+//#################################################################################################
+//
+//                                         enum EvalMode
+//
+//#################################################################################################
+
+/// The way the engine evaluates the positions it searches.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvalMode {
+    /// The neural network, updated incrementally as moves are played. This is the default.
+    #[default]
+    Nnue = 0,
+    /// A hand-crafted evaluation, with the material balance and piece-square tables. Much weaker,
+    /// but faster, and a baseline to measure what the network brings.
+    Classical = 1,
+    /// The material balance alone. The fastest, for stress tests and very small machines.
+    MaterialOnly = 2,
+}
+
+// ================================ pub(crate) impl
+
+impl EvalMode {
+    /// Returns the mode corresponding to the given discriminant.
+    #[inline]
+    pub(crate) fn from_u8(mode: u8) -> EvalMode {
+        match mode {
+            1 => EvalMode::Classical,
+            2 => EvalMode::MaterialOnly,
+            _ => EvalMode::Nnue,
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl FromStr for EvalMode {
+    type Err = Error;
+
+    /// Parses an evaluation mode: "nnue", "classical" or "material".
+    fn from_str(s: &str) -> Result<EvalMode> {
+        match s {
+            "nnue" => Ok(EvalMode::Nnue),
+            "classical" => Ok(EvalMode::Classical),
+            "material" => Ok(EvalMode::MaterialOnly),
+            _ => Err(Error::msg(format!("Invalid evaluation mode: \"{}\".", s))),
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        enum FeatureSet
+//
+//#################################################################################################
+
+/// The inputs of the first layer of a network: the pieces on the board, seen from the king of each side.
+/// The feature set of a network is told by the size of its file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureSet {
+    /// 64 king squares x 64 piece squares x 5 non-king piece types x 2 colors.
+    HalfKp,
+    /// 32 king buckets x 64 piece squares x 11 planes: the 5 non-king piece types of each color, and
+    /// the two kings sharing the last one. The board is mirrored so that the king is on the e to h files,
+    /// each of their squares being a bucket.
+    HalfKaV2,
+}
+
+// ================================ pub impl
+
+impl FeatureSet {
+    /// The feature sets a network may use.
+    pub const ALL: [FeatureSet; 2] = [FeatureSet::HalfKp, FeatureSet::HalfKaV2];
+
+    /// Returns the number of inputs of the first layer.
+    #[inline]
+    pub const fn height(self) -> usize {
+        match self {
+            FeatureSet::HalfKp => 64 * 640,
+            FeatureSet::HalfKaV2 => 32 * 704,
+        }
+    }
+}
+
+// ================================ impl
+
+impl FeatureSet {
+    /// Returns true if the kings are inputs of the network, and not only the squares they are seen from.
+    #[inline]
+    fn has_kings(self) -> bool {
+        self == FeatureSet::HalfKaV2
+    }
+
+    /// Returns the offset of the inputs seen from the given king square, oriented for its side, and the
+    /// mask mirroring the squares seen from it: the squares of the pieces are xored with it.
+    #[inline]
+    fn king_inputs(self, king_sq: usize) -> (usize, usize) {
+        match self {
+            FeatureSet::HalfKp => (640 * king_sq, 0),
+            FeatureSet::HalfKaV2 => {
+                let mirror = if king_sq & 7 < 4 {7} else {0};
+                let king_sq = king_sq ^ mirror;
+                (704 * ((king_sq >> 3) * 4 + (king_sq & 7) - 4), mirror)
+            },
+        }
+    }
+
+    /// Returns the plane of the inputs of a piece, whose color is the one of the side it
+    /// is seen from if relative is 0, and the other one if it is 1.
+    #[inline]
+    fn plane(self, piece: Piece, relative: usize) -> usize {
+        match (self, piece) {
+            (FeatureSet::HalfKaV2, Piece::King) => 10,
+            _ => (usize::from(piece) << 1) + relative,
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl fmt::Display for FeatureSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FeatureSet::HalfKp => "HalfKP",
+            FeatureSet::HalfKaV2 => "HalfKAv2",
+        })
+    }
+}
+
+//#################################################################################################
+//
+//                                      struct EvalBreakdown
+//
+//#################################################################################################
+
+/// The contribution of a piece to the evaluation.
+#[derive(Clone, Copy, Debug)]
+pub struct PieceValue {
+    pub square: Square,
+    pub color: Color,
+    pub piece: Piece,
+    /// How much the evaluation drops when the piece is removed from the board,
+    /// in pawns and from the point of view of the piece's owner.
+    pub value: f32,
+}
+
+/// An explanation of the static evaluation of a position. All scores are in pawns,
+/// from white's point of view unless stated otherwise.
+#[derive(Clone, Debug)]
+pub struct EvalBreakdown {
+    /// The evaluation of the network.
+    pub total: f32,
+    /// The material balance, with the usual values of the pieces.
+    pub material: f32,
+    /// The part of the evaluation not explained by material.
+    pub positional: f32,
+    /// The contribution of each piece, kings excepted.
+    pub pieces: Vec<PieceValue>,
+    /// The fraction of the neurons of the first layer that are active, from white's
+    /// and from black's perspective.
+    pub activations: [f32; 2],
+}
+
 //#################################################################################################
 //
 //                                         struct Net
@@ -37,8 +202,9 @@ pub(crate) const fn value_of(piece: Piece) -> f32 {
 
 /// Represents a neural network used for evaluation.
 #[derive(Debug)]
-pub(crate) struct Net {
-    w0: [[f32; Net::SIZE]; Net::HEIGHT],
+pub struct Net {
+    features: FeatureSet,
+    w0: Box<[[f32; Net::SIZE]]>,
     b0: [f32; Net::SIZE],
     w1: [[f32; 32]; 2 * Net::SIZE],
     b1: [f32; 32],
@@ -48,50 +214,115 @@ pub(crate) struct Net {
     b3: f32,
 }
 
-// ================================ pub(crate) impl
+// ================================ pub impl
 
 impl Net {
     /// Loads a neural network from a file located at the given path.
-    pub(crate) fn load(path: &Path) -> Result<Arc<Net>> {
-        let mut file = File::open(path).map_err(|_| Error::msg("Cannot open network file."))?;
+    pub fn load(path: &Path) -> Result<Arc<Net>> {
+        let bytes = fs::read(path).map_err(|_| Error::msg("Cannot open network file."))?;
+        Net::from_bytes(&bytes)
+    }
+
+    /// Loads a neural network from the raw content of a network file, its feature set being told by its size.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Arc<Net>> {
+        let features = FeatureSet::ALL.iter().copied().find(|&features| bytes.len() == 4 * Net::len(features))
+            .ok_or_else(|| Error::msg("The size of the network file matches no feature set."))?;
+        let mut floats = bytes.chunks_exact(4).map(|chunk| f32::from_be_bytes(chunk.try_into().unwrap()));
 
-        fn read_f32(file: &mut File, x: &mut f32) -> Result<()> {
-            let mut buf = [0; 4];
-            file.read(&mut buf).map_err(|_| Error::msg("Not enough bytes in network file."))?;
-            *x = f32::from_be_bytes(buf);
+        fn read_f32(floats: &mut impl Iterator<Item = f32>, x: &mut f32) -> Result<()> {
+            *x = floats.next().ok_or_else(|| Error::msg("Not enough bytes in network file."))?;
             Ok(())
         }
 
-        fn read_vec<const N: usize>(file: &mut File, vec: &mut [f32; N]) -> Result<()> {
+        fn read_vec<const N: usize>(floats: &mut impl Iterator<Item = f32>, vec: &mut [f32; N]) -> Result<()> {
             for i in 0..N {
-                read_f32(file, &mut vec[i])?;
+                read_f32(floats, &mut vec[i])?;
             }
             Ok(())
         }
 
-        fn read_mat<const N: usize, const M: usize>(file: &mut File, mat: &mut [[f32; M]; N]) -> Result<()> {
+        fn read_mat<const N: usize, const M: usize>(floats: &mut impl Iterator<Item = f32>, mat: &mut [[f32; M]; N]) -> Result<()> {
             for i in 0..N {
-                read_vec(file, &mut mat[i])?;
+                read_vec(floats, &mut mat[i])?;
             }
             Ok(())
         }
 
-        // Done with manual allocation so as not to overflow the stack with the Net struct.
-        // SAFE: Arc is specified to accept pointers allocated with std::alloc::alloc()
-        Ok(unsafe {
-            let ptr = alloc::alloc(Layout::new::<Net>()) as *mut Net;
+        let mut net = Net::zeroed(features);
 
-            read_mat(&mut file, &mut (*ptr).w0)?;
-            read_vec(&mut file, &mut (*ptr).b0)?;
-            read_mat(&mut file, &mut (*ptr).w1)?;
-            read_vec(&mut file, &mut (*ptr).b1)?;
-            read_mat(&mut file, &mut (*ptr).w2)?;
-            read_vec(&mut file, &mut (*ptr).b2)?;
-            read_vec(&mut file, &mut (*ptr).w3)?;
-            read_f32(&mut file, &mut (*ptr).b3)?;
+        for row in net.w0.iter_mut() {
+            read_vec(&mut floats, row)?;
+        }
+        read_vec(&mut floats, &mut net.b0)?;
+        read_mat(&mut floats, &mut net.w1)?;
+        read_vec(&mut floats, &mut net.b1)?;
+        read_mat(&mut floats, &mut net.w2)?;
+        read_vec(&mut floats, &mut net.b2)?;
+        read_vec(&mut floats, &mut net.w3)?;
+        read_f32(&mut floats, &mut net.b3)?;
+
+        Ok(Arc::from(net))
+    }
 
-            Arc::from_raw(ptr)
-        })
+    /// Returns the raw content of the network file of the network.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut floats = Vec::with_capacity(Net::len(self.features));
+
+        for row in self.w0.iter() {
+            floats.extend_from_slice(row);
+        }
+        floats.extend_from_slice(&self.b0);
+        for row in &self.w1 {
+            floats.extend_from_slice(row);
+        }
+        floats.extend_from_slice(&self.b1);
+        for row in &self.w2 {
+            floats.extend_from_slice(row);
+        }
+        floats.extend_from_slice(&self.b2);
+        floats.extend_from_slice(&self.w3);
+        floats.push(self.b3);
+
+        floats.iter().flat_map(|x| x.to_be_bytes()).collect()
+    }
+
+    /// Writes the network to a file located at the given path.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_bytes()).map_err(|_| Error::msg("Cannot write network file."))
+    }
+
+    /// Returns the feature set of the network.
+    #[inline]
+    pub fn features(&self) -> FeatureSet {
+        self.features
+    }
+
+    /// Converts the network to the given feature set. A HalfKP network converted to HalfKAv2 evaluates
+    /// positions as it did when the king of the side seen from is on the e to h files, and as the
+    /// mirrored position otherwise. The plane of the kings is left at zero, to be trained further.
+    pub fn convert(&self, features: FeatureSet) -> Result<Arc<Net>> {
+        let mut net = Net::zeroed(features);
+        net.b0 = self.b0;
+        net.w1 = self.w1;
+        net.b1 = self.b1;
+        net.w2 = self.w2;
+        net.b2 = self.b2;
+        net.w3 = self.w3;
+        net.b3 = self.b3;
+
+        match (self.features, features) {
+            (from, to) if from == to => net.w0.copy_from_slice(&self.w0),
+            (FeatureSet::HalfKp, FeatureSet::HalfKaV2) => {
+                for bucket in 0..32 {
+                    // The king square of the bucket, on the e to h files.
+                    let king_sq = (bucket / 4) * 8 + bucket % 4 + 4;
+                    net.w0[704 * bucket..][..640].copy_from_slice(&self.w0[640 * king_sq..][..640]);
+                }
+            },
+            (from, to) => return Err(Error::msg(format!("Cannot convert a {} network to {}.", from, to))),
+        }
+
+        Ok(Arc::from(net))
     }
 }
 
@@ -101,8 +332,25 @@ impl Net {
     /// Must be kept in sync with the constant of the same name in the training script.
     const SIZE: usize = 128;
 
-    /// 64 piece's squares x 64 king's square x 5 non-king piece types x 2 colors.
-    const HEIGHT: usize = 40960;
+    /// Creates a network of the given feature set, with all its weights and biases set to zero.
+    fn zeroed(features: FeatureSet) -> Box<Net> {
+        Box::new(Net {
+            features,
+            w0: vec![[0.0; Net::SIZE]; features.height()].into_boxed_slice(),
+            b0: [0.0; Net::SIZE],
+            w1: [[0.0; 32]; 2 * Net::SIZE],
+            b1: [0.0; 32],
+            w2: [[0.0; 32]; 32],
+            b2: [0.0; 32],
+            w3: [0.0; 32],
+            b3: 0.0,
+        })
+    }
+
+    /// Returns the number of floats of the file of a network with the given feature set.
+    const fn len(features: FeatureSet) -> usize {
+        (features.height() + 1) * Net::SIZE + (2 * Net::SIZE + 1) * 32 + 33 * 32 + 32 + 1
+    }
 }
 
 //#################################################################################################
@@ -176,22 +424,57 @@ impl Accumulator {
     }
 }
 
+//#################################################################################################
+//
+//                                      struct RefreshEntry
+//
+//#################################################################################################
+
+/// The accumulator of a side last computed with its king on a given square, and the pieces it was computed
+/// for. Refreshing the side from it, when its king comes back to the square, only takes the pieces that
+/// changed since into account, instead of all of them: these entries are also known as finny tables.
+#[derive(Clone, Debug)]
+struct RefreshEntry {
+    acc: [f32; Net::SIZE],
+    pieces: [[BitBoard; 6]; 2],
+}
+
+// ================================ impl
+
+impl RefreshEntry {
+    /// Creates the entry of an empty board.
+    #[inline]
+    fn new(net: &Net) -> RefreshEntry {
+        RefreshEntry {
+            acc: net.b0,
+            pieces: [[BitBoard::EMPTY; 6]; 2],
+        }
+    }
+}
+
 //#################################################################################################
 //
 //                                       struct GlobalInfo
 //
 //#################################################################################################
 
-/// A struct designed to handle evaluation of the board.
+/// A struct designed to handle evaluation of the board. Outside of the network mode,
+/// the first layer of the network is not updated as moves are played.
 #[derive(Debug)]
 pub(crate) struct Eval {
     king_w: usize,
     king_b: usize,
+    // The masks mirroring the squares seen from each king.
+    orient_w: usize,
+    orient_b: usize,
 
     acc: Accumulator,
     prev_acc: Vec<Accumulator>,
+    // The refresh entries of each side, for each king square seen from the side.
+    refresh: Vec<RefreshEntry>,
 
     net: Arc<Net>,
+    mode: EvalMode,
 }
 
 // ================================ pub(crate) impl
@@ -202,12 +485,28 @@ impl Eval {
         Eval {
             king_w: 0,
             king_b: 0,
+            orient_w: 0,
+            orient_b: 56,
             acc: Accumulator::new(&net),
             prev_acc: Vec::new(),
+            refresh: vec![RefreshEntry::new(&net); 2 * 64],
             net,
+            mode: EvalMode::Nnue,
         }
     }
 
+    /// Returns the way the positions are evaluated.
+    #[inline]
+    pub(crate) fn get_mode(&self) -> EvalMode {
+        self.mode
+    }
+
+    /// Changes the way the positions are evaluated, resetting the Eval struct for the given state.
+    pub(crate) fn set_mode(&mut self, mode: EvalMode, board: &Board) {
+        self.mode = mode;
+        self.reset(board);
+    }
+
     /// Resets the Eval struct for the given state.
     #[inline]
     pub(crate) fn reset(&mut self, board: &Board) {
@@ -219,7 +518,7 @@ impl Eval {
 
         for sq in board.get_occupancy().all().iter_squares() {
             let (color, piece) = board.get_piece(sq).unwrap();
-            if piece != Piece::King {
+            if piece != Piece::King || self.net.features.has_kings() {
                 self.add_piece(color, piece, sq);
             }
         }
@@ -229,6 +528,11 @@ impl Eval {
     /// last move played.
     #[inline]
     pub(crate) fn do_move(&mut self, board: &mut Board, mv: Move) {
+        if self.mode != EvalMode::Nnue {
+            board.do_move(mv);
+            return;
+        }
+
         let (from, to) = mv.squares();
         let (color, piece) = board.get_piece(from).unwrap();
 
@@ -239,6 +543,19 @@ impl Eval {
             board.do_move(mv);
             self.update_side(color, board);
 
+            // If the king is an input, move it on the other side's accumulator.
+            if self.net.features.has_kings() {
+                if color == Color::White {
+                    let (feature_1, feature_2) = (self.feature_b(color, piece, from), self.feature_b(color, piece, to));
+                    self.acc.sub_b(feature_1, &self.net);
+                    self.acc.add_b(feature_2, &self.net);
+                } else {
+                    let (feature_1, feature_2) = (self.feature_w(color, piece, from), self.feature_w(color, piece, to));
+                    self.acc.sub_w(feature_1, &self.net);
+                    self.acc.add_w(feature_2, &self.net);
+                }
+            }
+
             // If it's a king capture, remove the capturee from the other side's accumulator.
             if mv.is_capture() {
                 if color == Color::White {
@@ -261,14 +578,14 @@ impl Eval {
                 };
 
                 if color == Color::White {
-                    let feature_1 = self.feature_b(color, piece, from);
-                    let feature_2 = self.feature_b(color, piece, to);
+                    let feature_1 = self.feature_b(color, Piece::Rook, from);
+                    let feature_2 = self.feature_b(color, Piece::Rook, to);
 
                     self.acc.sub_b(feature_1, &self.net);
                     self.acc.add_b(feature_2, &self.net);
                 } else {
-                    let feature_1 = self.feature_w(color, piece, from);
-                    let feature_2 = self.feature_w(color, piece, to);
+                    let feature_1 = self.feature_w(color, Piece::Rook, from);
+                    let feature_2 = self.feature_w(color, Piece::Rook, to);
 
                     self.acc.sub_w(feature_1, &self.net);
                     self.acc.add_w(feature_2, &self.net);
@@ -304,6 +621,10 @@ impl Eval {
     pub(crate) fn undo_move(&mut self, board: &mut Board, mv: Move) {
         board.undo_move(mv);
 
+        if self.mode != EvalMode::Nnue {
+            return;
+        }
+
         let (from, to) = mv.squares();
         let (color, piece) = board.get_piece(from).unwrap();
 
@@ -371,6 +692,55 @@ impl Eval {
 
         res
     }
+
+    /// Returns the evaluation of the given position, which must be the one the Eval struct was updated for,
+    /// from the point of view of its side to move, in the current mode.
+    #[inline]
+    pub(crate) fn evaluate(&self, board: &Board) -> f32 {
+        match self.mode {
+            EvalMode::Nnue => self.get(board.get_side_to_move()),
+            EvalMode::Classical => classical::evaluate(board),
+            EvalMode::MaterialOnly => classical::material(board),
+        }
+    }
+
+    /// Evaluates the given position, explaining how the evaluation is made.
+    pub(crate) fn breakdown(&mut self, board: &Board) -> EvalBreakdown {
+        self.reset(board);
+
+        let stm = board.get_side_to_move();
+        let white_pov = |score: f32| if stm == Color::White {score} else {-score};
+        let total = white_pov(self.get(stm));
+
+        let mut material = 0.0;
+        let mut pieces = Vec::new();
+
+        for sq in board.get_occupancy().all().iter_squares() {
+            let (color, piece) = board.get_piece(sq).unwrap();
+            if piece == Piece::King {
+                continue;
+            }
+
+            let sign = if color == Color::White {1.0} else {-1.0};
+            material += sign * value_of(piece);
+
+            self.remove_piece(color, piece, sq);
+            let without = white_pov(self.get(stm));
+            self.add_piece(color, piece, sq);
+
+            pieces.push(PieceValue {square: sq, color, piece, value: sign * (total - without)});
+        }
+
+        let active = |acc: &[f32; Net::SIZE]| acc.iter().filter(|&&x| x > 0.0).count() as f32 / Net::SIZE as f32;
+
+        EvalBreakdown {
+            total,
+            material,
+            positional: total - material,
+            pieces,
+            activations: [active(&self.acc.white), active(&self.acc.black)],
+        }
+    }
 }
 
 // ================================ impl
@@ -379,13 +749,13 @@ impl Eval {
     /// Computes the feature associated with a color, piece, square triplet for white.
     #[inline]
     fn feature_w(&self, color: Color, piece: Piece, sq: Square) -> usize {
-        self.king_w + (((usize::from(piece) << 1) + usize::from(color)) << 6) + usize::from(sq)
+        self.king_w + (self.net.features.plane(piece, usize::from(color)) << 6) + (usize::from(sq) ^ self.orient_w)
     }
 
     /// Computes the feature associated with a color, piece, square triplet for black.
     #[inline]
     fn feature_b(&self, color: Color, piece: Piece, sq: Square) -> usize {
-        self.king_b + (((usize::from(piece) << 1) + 1 - usize::from(color)) << 6) + (usize::from(sq) ^ 56)
+        self.king_b + (self.net.features.plane(piece, 1 - usize::from(color)) << 6) + (usize::from(sq) ^ self.orient_b)
     }
 
     /// Takes the given piece triplet into account.
@@ -408,42 +778,212 @@ impl Eval {
         self.acc.sub_b(feature_b, &self.net);
     }
 
-    /// Updates the king square value of the specified color.
+    /// Updates the king square value of the specified color, and the way the squares are mirrored for it.
     #[inline]
     fn update_king(&mut self, color: Color, board: &Board) {
         if color == Color::White {
-            self.king_w = 640 * usize::from(board.king_sq(Color::White));
+            (self.king_w, self.orient_w) = self.net.features.king_inputs(usize::from(board.king_sq(Color::White)));
         } else {
-            self.king_b = 640 * (usize::from(board.king_sq(Color::Black)) ^ 56);
+            let (king_b, mirror) = self.net.features.king_inputs(usize::from(board.king_sq(Color::Black)) ^ 56);
+            (self.king_b, self.orient_b) = (king_b, mirror ^ 56);
         }
     }
 
+    /// Computes the accumulator of the side of the given color, after its king moved, from the
+    /// refresh entry of its new square, and updates the entry.
     #[inline]
     fn update_side(&mut self, color: Color, board: &Board) {
         self.update_king(color, board);
 
+        let idx = if color == Color::White {
+            usize::from(board.king_sq(Color::White))
+        } else {
+            64 + (usize::from(board.king_sq(Color::Black)) ^ 56)
+        };
+
+        let cached = self.refresh[idx].pieces;
         if color == Color::White {
-            self.acc.white = self.net.b0;
+            self.acc.white = self.refresh[idx].acc;
+        } else {
+            self.acc.black = self.refresh[idx].acc;
+        }
 
-            for sq in board.get_occupancy().all().iter_squares() {
-                let (color, piece) = board.get_piece(sq).unwrap();
+        let has_kings = self.net.features.has_kings();
+        let mut pieces = [[BitBoard::EMPTY; 6]; 2];
+        for piece_color in Color::iter() {
+            for piece in Piece::iter().filter(|&piece| piece != Piece::King || has_kings) {
+                let now = board.get_bitboard(piece_color, piece);
+                let before = cached[usize::from(piece_color)][usize::from(piece)];
+
+                for sq in (before & !now).iter_squares() {
+                    if color == Color::White {
+                        let feature = self.feature_w(piece_color, piece, sq);
+                        self.acc.sub_w(feature, &self.net);
+                    } else {
+                        let feature = self.feature_b(piece_color, piece, sq);
+                        self.acc.sub_b(feature, &self.net);
+                    }
+                }
 
-                if piece != Piece::King {
-                    let feature = self.feature_w(color, piece, sq);
-                    self.acc.add_w(feature, &self.net);
+                for sq in (now & !before).iter_squares() {
+                    if color == Color::White {
+                        let feature = self.feature_w(piece_color, piece, sq);
+                        self.acc.add_w(feature, &self.net);
+                    } else {
+                        let feature = self.feature_b(piece_color, piece, sq);
+                        self.acc.add_b(feature, &self.net);
+                    }
                 }
+
+                pieces[usize::from(piece_color)][usize::from(piece)] = now;
             }
-        } else {
-            self.acc.black = self.net.b0;
+        }
 
-            for sq in board.get_occupancy().all().iter_squares() {
-                let (color, piece) = board.get_piece(sq).unwrap();
+        self.refresh[idx] = RefreshEntry {
+            acc: if color == Color::White {self.acc.white} else {self.acc.black},
+            pieces,
+        };
+    }
+}
 
-                if piece != Piece::King {
-                    let feature = self.feature_b(color, piece, sq);
-                    self.acc.add_b(feature, &self.net);
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The HalfKP network shipped with the engine.
+    const NET: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/nets/nnue.bin");
+
+    /// Returns the network shipped with the engine, and the same network converted to HalfKAv2,
+    /// whose plane of the kings is filled with small weights so that the kings count.
+    fn nets() -> [Arc<Net>; 2] {
+        let halfkp = Net::load(Path::new(NET)).unwrap();
+        let mut halfkav2 = Arc::try_unwrap(halfkp.convert(FeatureSet::HalfKaV2).unwrap()).unwrap();
+
+        let mut seed = 0x2545F491u32;
+        for bucket in 0..32 {
+            for row in &mut halfkav2.w0[704 * bucket + 640..][..64] {
+                for weight in row.iter_mut() {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    *weight = (seed % 201) as f32 / 1000.0 - 0.1;
                 }
             }
         }
+
+        [halfkp, Arc::new(halfkav2)]
     }
-}
\ No newline at end of file
+
+    /// Checks that the accumulator updated incrementally is the one computed from scratch for the board.
+    fn assert_refreshed(eval: &Eval, board: &Board) {
+        let mut fresh = Eval::new(eval.net.clone());
+        fresh.reset(board);
+
+        let close = |a: &[f32; Net::SIZE], b: &[f32; Net::SIZE]| a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-3);
+        assert!(close(&eval.acc.white, &fresh.acc.white), "{} ({})", board, eval.net.features);
+        assert!(close(&eval.acc.black, &fresh.acc.black), "{} ({})", board, eval.net.features);
+    }
+
+    /// The castling moves available on the board.
+    fn castles(board: &Board) -> Vec<Move> {
+        ["e1g1", "e1c1", "e8g8", "e8c8"].iter().filter_map(|mv| board.parse_move(mv).ok()).collect()
+    }
+
+    #[test]
+    fn castling_accumulators() {
+        for net in nets() {
+            let mut eval = Eval::new(net);
+
+            for fen in [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            ] {
+                let mut board = Board::new(fen).unwrap();
+                eval.reset(&board);
+
+                // Both castles of the side to move, each answered by both castles of the opponent.
+                for mv in castles(&board) {
+                    eval.do_move(&mut board, mv);
+                    assert_refreshed(&eval, &board);
+
+                    for reply in castles(&board) {
+                        eval.do_move(&mut board, reply);
+                        assert_refreshed(&eval, &board);
+                        eval.undo_move(&mut board, reply);
+                    }
+
+                    eval.undo_move(&mut board, mv);
+                    assert_refreshed(&eval, &board);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn refresh_entries() {
+        for net in nets() {
+            let mut board = Board::new("rn2k3/pp6/8/8/8/8/PP6/RN2K3 w - - 0 1").unwrap();
+            let mut eval = Eval::new(net);
+            eval.reset(&board);
+
+            // The kings leave their squares, cross to the other half of the board and back, while
+            // the other pieces move, so that the entries of the squares they come back to are stale.
+            let mut played = Vec::new();
+            for mv in [
+                "e1d1", "b8c6", "a2a3", "c6e5", "d1e1", "e8d8", "e1d2", "a7a6",
+                "d2e2", "d8e8", "e2e1", "e5c6", "b2b3", "c6b8", "e1d1", "e8d8",
+            ] {
+                let mv = board.parse_move(mv).unwrap();
+                eval.do_move(&mut board, mv);
+                assert_refreshed(&eval, &board);
+                played.push(mv);
+            }
+
+            while let Some(mv) = played.pop() {
+                eval.undo_move(&mut board, mv);
+                assert_refreshed(&eval, &board);
+            }
+        }
+    }
+
+    #[test]
+    fn converted_net() {
+        let halfkp = Net::load(Path::new(NET)).unwrap();
+        let halfkav2 = halfkp.convert(FeatureSet::HalfKaV2).unwrap();
+        assert_eq!(halfkav2.features(), FeatureSet::HalfKaV2);
+        assert!(halfkav2.convert(FeatureSet::HalfKp).is_err());
+        assert_eq!(Net::from_bytes(&halfkav2.to_bytes()).unwrap().to_bytes(), halfkav2.to_bytes());
+
+        let evaluate = |net: &Arc<Net>, board: &Board| {
+            let mut eval = Eval::new(net.clone());
+            eval.reset(board);
+            eval.get(board.get_side_to_move())
+        };
+
+        // With both kings on the e to h files, the evaluations are the same.
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9",
+            "6k1/5ppp/8/8/3r4/8/5PPP/4R1K1 b - - 0 30",
+        ] {
+            let board = Board::new(fen).unwrap();
+            assert!((evaluate(&halfkp, &board) - evaluate(&halfkav2, &board)).abs() < 1e-4, "{}", fen);
+        }
+
+        // With both kings on the a to d files, the converted network sees the mirrored position.
+        for fen in [
+            "2kr1bnr/pppq1ppp/2n5/3pp3/3PP3/2N5/PPPQ1PPP/2KR1BNR w - - 4 8",
+            "1k6/ppp5/8/8/8/8/PPP2r2/1K1R4 w - - 0 30",
+        ] {
+            let board = Board::new(fen).unwrap();
+            let mirrored = board.flip_horizontal();
+            assert!((evaluate(&halfkp, &mirrored) - evaluate(&halfkav2, &board)).abs() < 1e-4, "{}", fen);
+        }
+    }
+}
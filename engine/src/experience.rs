@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+use anyhow::{Error, Result};
+
+use chess::board::Board;
+use chess::moves::Move;
+
+/// The first bytes of an experience file, identifying the format and its version.
+const MAGIC: &[u8; 8] = b"RUSHXP\0\x01";
+
+//#################################################################################################
+//
+//                                     struct ExperienceEntry
+//
+//#################################################################################################
+
+/// What the engine learned of a position it played a move in: the move its search chose, with its score and depth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExperienceEntry {
+    /// The move played.
+    pub mv: Move,
+    /// The score of the move, in pawns from the point of view of the side that played it.
+    pub score: f32,
+    /// The depth completed by the search that chose the move.
+    pub depth: u8,
+}
+
+// ================================ impl
+
+impl ExperienceEntry {
+    /// The size in bytes of a saved entry, with the key of its position.
+    const SIZE: usize = 17;
+
+    /// Returns true if that entry should replace the previous one of its position: the deepest
+    /// entry is kept, and the newest one when they are as deep.
+    #[inline]
+    fn replaces(self, prev: ExperienceEntry) -> bool {
+        self.depth >= prev.depth
+    }
+
+    /// Encodes the entry of the position of the given key in big endian, for saving it.
+    fn to_bytes(self, key: u64) -> [u8; ExperienceEntry::SIZE] {
+        let mut bytes = [0; ExperienceEntry::SIZE];
+        bytes[0..8].copy_from_slice(&key.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.mv.get_raw().to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.score.to_be_bytes());
+        bytes[16] = self.depth;
+        bytes
+    }
+
+    /// Decodes an entry saved with to_bytes() and the key of its position, or returns None if it is invalid.
+    fn from_bytes(bytes: &[u8; ExperienceEntry::SIZE]) -> Option<(u64, ExperienceEntry)> {
+        Some((u64::from_be_bytes(bytes[0..8].try_into().unwrap()), ExperienceEntry {
+            mv: Move::from_raw(u32::from_be_bytes(bytes[8..12].try_into().unwrap()))?,
+            score: f32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+            depth: bytes[16],
+        }))
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct Experience
+//
+//#################################################################################################
+
+/// The experience of the engine: the moves it played in the positions it searched, in past games, with their scores
+/// and depths. It is kept in a file, to which the moves are appended as they are recorded, so that it grows from game
+/// to game. An experience may be shared by several engines, playing in different threads.
+#[derive(Debug, Default)]
+pub struct Experience {
+    entries: RwLock<HashMap<u64, ExperienceEntry>>,
+    // The file the recorded entries are appended to, if any.
+    file: Mutex<Option<File>>,
+}
+
+// ================================ pub impl
+
+impl Experience {
+    /// Creates an empty experience, kept in memory only.
+    pub fn new() -> Experience {
+        Experience::default()
+    }
+
+    /// Reads the experience saved in the given file, without recording anything to it.
+    pub fn load(path: &Path) -> Result<Experience> {
+        let bytes = fs::read(path)?;
+        let experience = Experience::new();
+        experience.extend(&bytes)?;
+        Ok(experience)
+    }
+
+    /// Opens the experience saved in the given file, creating it if it does not exist.
+    /// The entries recorded afterwards are appended to it.
+    pub fn open(path: &Path) -> Result<Experience> {
+        let experience = if path.exists() {
+            Experience::load(path)?
+        } else {
+            fs::write(path, MAGIC)?;
+            Experience::new()
+        };
+
+        *experience.file.lock().unwrap() = Some(OpenOptions::new().append(true).open(path)?);
+        Ok(experience)
+    }
+
+    /// Returns the number of positions in the experience.
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    /// Returns true if the experience has no position.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the entries of the experience, in no particular order.
+    pub fn entries(&self) -> Vec<ExperienceEntry> {
+        self.entries.read().unwrap().values().copied().collect()
+    }
+
+    /// Returns the entry of the given position, if the engine played in it.
+    pub fn get(&self, board: &Board) -> Option<ExperienceEntry> {
+        self.entries.read().unwrap().get(&board.get_zobrist().get_raw()).copied()
+    }
+
+    /// Records the move played in the given position, with the score and depth of the search that chose it.
+    /// Returns false if the experience already had a deeper entry for the position, which is kept. Otherwise,
+    /// the entry is appended to the file of the experience, if any, and an error is returned if it could not be,
+    /// the entry being kept in memory nonetheless.
+    pub fn record(&self, board: &Board, mv: Move, score: f32, depth: u8) -> Result<bool> {
+        let key = board.get_zobrist().get_raw();
+        let entry = ExperienceEntry {mv, score, depth};
+
+        if !self.insert(key, entry) {
+            return Ok(false);
+        }
+
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            file.write_all(&entry.to_bytes(key))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Adds the entries of the other experience to this one, keeping the deepest entry of each position,
+    /// and those of the other experience when they are as deep. Nothing is appended to the file of this one.
+    pub fn merge(&self, other: &Experience) {
+        for (&key, &entry) in other.entries.read().unwrap().iter() {
+            self.insert(key, entry);
+        }
+    }
+
+    /// Saves the experience to the given file, with a single entry per position.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+
+        for (&key, &entry) in self.entries.read().unwrap().iter() {
+            writer.write_all(&entry.to_bytes(key))?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// ================================ impl
+
+impl Experience {
+    /// Inserts the entry of the position of the given key, returning false if the previous one is kept.
+    fn insert(&self, key: u64, entry: ExperienceEntry) -> bool {
+        let mut entries = self.entries.write().unwrap();
+
+        match entries.get(&key) {
+            Some(&prev) if !entry.replaces(prev) => false,
+            _ => {
+                entries.insert(key, entry);
+                true
+            },
+        }
+    }
+
+    /// Inserts the entries saved in the given bytes, in order, as written by save() or record().
+    fn extend(&self, bytes: &[u8]) -> Result<()> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(Error::msg("Not an experience file, or saved by another version of the engine."));
+        }
+
+        let bytes = &bytes[MAGIC.len()..];
+        if !bytes.len().is_multiple_of(ExperienceEntry::SIZE) {
+            return Err(Error::msg("Experience file has invalid size."));
+        }
+
+        for chunk in bytes.chunks_exact(ExperienceEntry::SIZE) {
+            let (key, entry) = ExperienceEntry::from_bytes(chunk.try_into().unwrap())
+                .ok_or(Error::msg("Experience file contains an invalid entry."))?;
+
+            self.insert(key, entry);
+        }
+
+        Ok(())
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::board::Board;
+    use chess::moves::Move;
+    use chess::square::Square;
+
+    use crate::experience::{Experience, ExperienceEntry, MAGIC};
+
+    #[test]
+    fn records() {
+        let board = Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let e4 = Move::quiet(Square::E2, Square::E4);
+        let d4 = Move::quiet(Square::D2, Square::D4);
+
+        // The deepest entry of a position is kept, and the newest one when they are as deep.
+        let experience = Experience::new();
+        assert!(experience.record(&board, e4, 0.3, 12).unwrap());
+        assert!(!experience.record(&board, d4, 0.2, 10).unwrap());
+        assert!(experience.record(&board, d4, 0.25, 12).unwrap());
+        assert_eq!(experience.get(&board), Some(ExperienceEntry {mv: d4, score: 0.25, depth: 12}));
+
+        // Merging keeps the deepest entries too.
+        let other = Experience::new();
+        other.record(&board, e4, 0.35, 14).unwrap();
+        experience.merge(&other);
+        assert_eq!(experience.get(&board).map(|entry| entry.mv), Some(e4));
+        assert_eq!(experience.len(), 1);
+    }
+
+    #[test]
+    fn files() {
+        let board = Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let bb5 = Move::quiet(Square::F1, Square::B5);
+        let entry = ExperienceEntry {mv: bb5, score: 0.4, depth: 16};
+
+        // Entries appended one after the other are read back in order.
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&ExperienceEntry {depth: 8, ..entry}.to_bytes(board.get_zobrist().get_raw()));
+        bytes.extend_from_slice(&entry.to_bytes(board.get_zobrist().get_raw()));
+
+        let experience = Experience::new();
+        experience.extend(&bytes).unwrap();
+        assert_eq!(experience.get(&board), Some(entry));
+
+        // Files of another format or truncated are rejected.
+        assert!(Experience::new().extend(b"RUSHTT\0\x01").is_err());
+        assert!(Experience::new().extend(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use clap::{App, Arg};
+
+use chess::prelude::*;
+use engine::Experience;
+
+/*
+ * Inspects and merges the experience files written by the engine with its --experience option. Without any
+ * option, the number of positions of the files and the depths of their entries are printed. The entry of a
+ * single position may be looked up with --fen, and the files may be merged into a new one with --merge, the
+ * deepest entry of each position being kept, and those of the last files when they are as deep.
+ *
+ * $ cargo build --bin experience --release
+ * $ target/release/experience engine.exp
+ * $ target/release/experience engine.exp --fen "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+ * $ target/release/experience first.exp second.exp --merge merged.exp
+ */
+
+/// The main function parses the programs arguments, then inspects or merges the files.
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine experience tool")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("Inspects and merges experience files.")
+        .arg(Arg::with_name("files")
+            .index(1)
+            .value_name("FILES")
+            .help("The paths to the experience files, merged in order.")
+            .multiple(true)
+            .required(true))
+        .arg(Arg::with_name("fen")
+            .long("fen")
+            .value_name("FEN")
+            .help("Prints the entry of the given position.")
+            .takes_value(true))
+        .arg(Arg::with_name("merge")
+            .long("merge")
+            .value_name("OUTPUT")
+            .help("Writes the merged files to the given path.")
+            .takes_value(true))
+        .get_matches();
+
+    let experience = Experience::new();
+    for path in args.values_of("files").unwrap() {
+        experience.merge(&Experience::load(Path::new(path)).map_err(|e| Error::msg(format!("{}: {}", path, e)))?);
+    }
+
+    if let Some(fen) = args.value_of("fen") {
+        let board = Board::from_str(fen)?;
+
+        match experience.get(&board) {
+            Some(entry) => println!("{}: {:+.2} at depth {}.", board.to_san(entry.mv), entry.score, entry.depth),
+            None => println!("No entry for this position."),
+        }
+    } else {
+        let entries = experience.entries();
+        let depths = entries.iter().map(|entry| u64::from(entry.depth));
+
+        println!("{} positions.", entries.len());
+        if let (Some(min), Some(max)) = (depths.clone().min(), depths.clone().max()) {
+            println!("Depths from {} to {}, {:.1} on average.", min, max, depths.sum::<u64>() as f64 / entries.len() as f64);
+        }
+    }
+
+    if let Some(output) = args.value_of("merge") {
+        experience.save(Path::new(output))?;
+        println!("Merged {} positions into {}.", experience.len(), output);
+    }
+
+    Ok(())
+}
@@ -1,15 +1,40 @@
 use chess::moves::Move;
+use chess::piece::Piece;
+use chess::square::Square;
 
 use crate::movepick::RatedMove;
-use crate::params::MAX_DEPTH;
+use crate::params::MAX_PLY;
+
+/// The number of plies looked back by the continuation history.
+const CONTINUATION_PLIES: usize = 2;
+
+/// The size of a continuation history table: one entry per pair of (piece, to square).
+const CONTINUATION_SIZE: usize = (Piece::PIECES.len() * 64) * (Piece::PIECES.len() * 64);
+
+/// The bound of the history scores. Updates are scaled down as scores get closer to it.
+const MAX_HISTORY: f32 = 16384.0;
+
+/// A move that was played during the search, along with the piece that was moved.
+#[derive(Clone, Copy, Debug)]
+struct PlayedMove {
+    piece: Piece,
+    mv: Move,
+}
 
 /// A struct keeping track of the various moves ordering heuristics.
 #[derive(Debug)]
 pub(crate) struct Heuristics {
-    // Two killer moves.
-    killers: [[Option<Move>; 2]; MAX_DEPTH],
+    // Two killer moves per ply. Allocated on the heap.
+    killers: Box<[[Option<Move>; 2]]>,
     // History heuristic table.
     history: [[f32; 64]; 64],
+    // The move refuting each previous move, indexed by the previous move's squares.
+    countermoves: [[Option<Move>; 64]; 64],
+    // Continuation history tables, for the moves played one and two plies ago, indexed by
+    // the (piece, to) pairs of the previous move and of the rated move. Allocated on the heap.
+    continuation: Vec<f32>,
+    // The moves played at each ply of the current line, None for null moves. Allocated on the heap.
+    played: Box<[Option<PlayedMove>]>,
 }
 
 // ================================ pub(crate) impl
@@ -27,32 +52,134 @@ impl Heuristics {
     /// Updates the history for a move that is played by the given color
     pub(crate) fn update_history(&mut self, mv: Move, depth: u8) {
         let depth = f32::from(depth);
-        self.history[usize::from(mv.from())][usize::from(mv.to())] += depth * depth;
+        gravity(&mut self.history[usize::from(mv.from())][usize::from(mv.to())], depth * depth);
+    }
+
+    #[inline]
+    /// Remembers the move played at the given depth, or None if a null move was played.
+    pub(crate) fn set_played(&mut self, depth: u8, played: Option<(Piece, Move)>) {
+        self.played[usize::from(depth)] = played.map(|(piece, mv)| PlayedMove {piece, mv});
+    }
+
+    #[inline]
+    /// Stores a quiet move that caused a cutoff at the given depth as the countermove of the
+    /// previous move, and rewards it in the continuation histories. Remaining is the depth left to search.
+    pub(crate) fn update_cutoff(&mut self, piece: Piece, mv: Move, depth: u8, remaining: u8) {
+        if let Some(prev) = self.previous(depth, 1) {
+            self.countermoves[usize::from(prev.mv.from())][usize::from(prev.mv.to())] = Some(mv);
+        }
+
+        let bonus = f32::from(remaining) * f32::from(remaining);
+        for ply in 1..=CONTINUATION_PLIES {
+            if let Some(prev) = self.previous(depth, ply) {
+                gravity(&mut self.continuation[continuation_index(ply, prev, piece, mv.to())], bonus);
+            }
+        }
+    }
+
+    #[inline]
+    /// Returns the killer moves of the given depth, the oldest first.
+    pub(crate) fn killers(&self, depth: u8) -> [Option<Move>; 2] {
+        self.killers[usize::from(depth)]
     }
 
     #[inline]
-    /// Rates a given quiet move.
-    pub(crate) fn rate(&self, mv: Move, depth: u8) -> RatedMove {
+    /// Returns the move refuting the move played just before the given depth, if any.
+    pub(crate) fn countermove(&self, depth: u8) -> Option<Move> {
+        self.previous(depth, 1).and_then(|prev| self.countermoves[usize::from(prev.mv.from())][usize::from(prev.mv.to())])
+    }
+
+    #[inline]
+    /// Rates a given quiet move, moving the given piece.
+    pub(crate) fn rate(&self, piece: Piece, mv: Move, depth: u8) -> RatedMove {
+        let prev = self.previous(depth, 1);
+
         let score = if self.killers[usize::from(depth)][0].map_or(false, |killer| killer == mv) {
             9000000.0
         } else if self.killers[usize::from(depth)][1].map_or(false, |killer| killer == mv) {
             8000000.0
+        } else if prev.and_then(|prev| self.countermoves[usize::from(prev.mv.from())][usize::from(prev.mv.to())]) == Some(mv) {
+            7000000.0
         } else {
-            self.history[usize::from(mv.from())][usize::from(mv.to())]
+            let mut score = self.history[usize::from(mv.from())][usize::from(mv.to())];
+            for ply in 1..=CONTINUATION_PLIES {
+                if let Some(prev) = self.previous(depth, ply) {
+                    score += self.continuation[continuation_index(ply, prev, piece, mv.to())];
+                }
+            }
+            score
         };
 
         RatedMove {mv, score}
     }
 }
 
+// ================================ pub(crate) impl
+
+impl Heuristics {
+    /// Updates the heuristics after the game advanced by the given number of plies: the killers
+    /// are shifted to their new depths, and the histories are aged.
+    pub(crate) fn advance(&mut self, plies: usize) {
+        for depth in 0..MAX_PLY {
+            self.killers[depth] = self.killers.get(depth + plies).copied().unwrap_or([None; 2]);
+        }
+
+        self.played.fill(None);
+        self.age();
+    }
+
+    /// Ages the heuristics between two searches: the histories are halved so that newer results prevail.
+    pub(crate) fn age(&mut self) {
+        self.history.iter_mut().flatten().chain(self.continuation.iter_mut()).for_each(|score| *score /= 2.0);
+    }
+
+    /// Forgets everything, as when a new position is searched, without reallocating the tables.
+    pub(crate) fn clear(&mut self) {
+        self.killers.fill([None; 2]);
+        self.history = [[0.0; 64]; 64];
+        self.countermoves = [[None; 64]; 64];
+        self.continuation.fill(0.0);
+        self.played.fill(None);
+    }
+}
+
+// ================================ impl
+
+impl Heuristics {
+    /// Returns the move played the given number of plies before the given depth, if any.
+    #[inline]
+    fn previous(&self, depth: u8, plies: usize) -> Option<PlayedMove> {
+        usize::from(depth).checked_sub(plies).and_then(|ply| self.played[ply])
+    }
+}
+
+/// Adds a bonus to a history score, scaled down as the score gets closer to MAX_HISTORY, so that
+/// the scores stay bounded and the moves that stopped working lose their rank quickly.
+#[inline]
+fn gravity(score: &mut f32, bonus: f32) {
+    let bonus = bonus.min(MAX_HISTORY);
+    *score += bonus - *score * bonus / MAX_HISTORY;
+}
+
+/// The index of an entry in the continuation history of the given ply.
+#[inline]
+fn continuation_index(ply: usize, prev: PlayedMove, piece: Piece, to: Square) -> usize {
+    let prev = usize::from(prev.piece) * 64 + usize::from(prev.mv.to());
+    let curr = usize::from(piece) * 64 + usize::from(to);
+    (ply - 1) * CONTINUATION_SIZE + prev * Piece::PIECES.len() * 64 + curr
+}
+
 // ================================ traits impl
 
 impl Default for Heuristics {
     /// Constructs a new Heuristics object.
     fn default() -> Heuristics {
         Heuristics {
-            killers: [[None; 2]; MAX_DEPTH],
+            killers: vec![[None; 2]; MAX_PLY].into_boxed_slice(),
             history: [[0.0; 64]; 64],
+            countermoves: [[None; 64]; 64],
+            continuation: vec![0.0; CONTINUATION_PLIES * CONTINUATION_SIZE],
+            played: vec![None; MAX_PLY].into_boxed_slice(),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use chess::board::Board;
+use chess::moves::Move;
+
+use crate::eval::{Eval, Net};
+use crate::table::{TableEntry, TableEntryFlag, TranspositionTable};
+
+//#################################################################################################
+//
+//                                        struct BenchEval
+//
+//#################################################################################################
+
+/// The evaluation of the search, updated incrementally as moves are played, exposed for the benches
+/// of the engine only. It is not part of the interface of the engine and may change at any time.
+#[derive(Debug)]
+pub struct BenchEval {
+    eval: Eval,
+}
+
+// ================================ pub impl
+
+impl BenchEval {
+    /// Creates the evaluation of the given position, with the given network.
+    pub fn new(net: Arc<Net>, board: &Board) -> BenchEval {
+        let mut eval = Eval::new(net);
+        eval.reset(board);
+        BenchEval {eval}
+    }
+
+    /// Computes the first layer of the network from scratch, for the given position.
+    #[inline]
+    pub fn reset(&mut self, board: &Board) {
+        self.eval.reset(board);
+    }
+
+    /// Plays the move on the board, updating the first layer of the network.
+    #[inline]
+    pub fn do_move(&mut self, board: &mut Board, mv: Move) {
+        self.eval.do_move(board, mv);
+    }
+
+    /// Undoes the move on the board, restoring the first layer of the network.
+    #[inline]
+    pub fn undo_move(&mut self, board: &mut Board, mv: Move) {
+        self.eval.undo_move(board, mv);
+    }
+
+    /// Runs the network forward, returning the evaluation of the position from the point of view of its side to move.
+    #[inline]
+    pub fn evaluate(&self, board: &Board) -> f32 {
+        self.eval.get(board.get_side_to_move())
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct BenchTable
+//
+//#################################################################################################
+
+/// The transposition table of the search, exposed for the benches of the engine only.
+/// It is not part of the interface of the engine and may change at any time.
+#[derive(Debug)]
+pub struct BenchTable {
+    table: TranspositionTable,
+}
+
+// ================================ pub impl
+
+impl BenchTable {
+    /// Creates a new table of roughly the given size in bytes.
+    pub fn new(size: usize) -> BenchTable {
+        BenchTable {table: TranspositionTable::new(size)}
+    }
+
+    /// Stores the exact score of the given position, searched to the given depth, and its best move.
+    #[inline]
+    pub fn insert(&self, board: &Board, mv: Move, score: f32, depth: u8) {
+        self.table.insert(TableEntry::new(board, 0, mv, score, depth, TableEntryFlag::Exact));
+    }
+
+    /// Returns the best move and the score stored for the given position, if it was searched to at least the given depth.
+    #[inline]
+    pub fn probe(&self, board: &Board, depth: u8) -> Option<(Move, f32)> {
+        self.table.probe(board.get_zobrist(), f32::NEG_INFINITY, f32::INFINITY, depth)
+    }
+}
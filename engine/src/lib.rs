@@ -1,16 +1,71 @@
 #![allow(unused)]
 
 mod params;
+mod bench;
+mod cache;
+mod classical;
 mod engine;
 mod eval;
+mod experience;
 mod heuristics;
+mod internals;
+mod local;
+mod mate;
 mod movepick;
+mod player;
+mod report;
 mod search;
+mod split;
+mod stack;
+mod strength;
 mod table;
+mod uci_client;
 mod utils;
 
 /// The version of the engine.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-// Export the Engine struct.
-pub use self::engine::Engine;
\ No newline at end of file
+// Export the Engine struct, its configuration, the options of its book, its status, the analysis it produces, its statistics, and the future awaiting its searches.
+pub use self::engine::{Analysis, BookOptions, Engine, EngineConfig, EngineStatus, Line, SearchDone, Stats};
+
+// Export the network used for evaluation, the ways positions may be evaluated, and the explanation of the evaluation.
+pub use self::eval::{EvalBreakdown, EvalMode, FeatureSet, Net, PieceValue};
+
+// Export the ways the search threads may share work.
+pub use self::split::SmpMode;
+
+// Export the results of the bench, and its positions.
+pub use self::bench::{BenchPosition, BenchResult, BENCH_DEPTH, BENCH_FENS};
+
+// Export the mate search, and its result.
+pub use self::mate::{search_mate, MateResult, MAX_MATE_MOVES};
+
+// Export the experience of the engine, learned from the moves it played.
+pub use self::experience::{Experience, ExperienceEntry};
+
+// Export the report of the moves played, and of the searches behind them.
+pub use self::report::{GameReport, MoveReport};
+
+// Export the evaluation and the transposition table to the benches, which cannot reach the internals of the engine otherwise.
+#[doc(hidden)]
+pub use self::internals::{BenchEval, BenchTable};
+
+// Export the single-threaded LocalEngine struct.
+pub use self::local::LocalEngine;
+
+// Export the players of games, the limits of their searches, and the client driving external UCI engines as players.
+pub use self::player::{KillSwitch, Player, PlayerMove, SearchLimit};
+pub use self::uci_client::UciClient;
+
+// Export the runtime search parameters, for tuning.
+pub use self::params::{Tunables, TunableSpec};
+
+// Export the starting position and the random number generator, with the random openings the tools play from,
+// and the way their games end.
+pub use self::utils::{random_opening, termination, xorshift32, STARTING_FEN};
+
+// Export the maximum strength level.
+pub use self::strength::MAX_STRENGTH;
+
+// Export the maximum variety level.
+pub use self::params::MAX_VARIETY;
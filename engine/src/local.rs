@@ -0,0 +1,237 @@
+use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use chess::board::Board;
+use chess::moves::Move;
+
+use crate::engine::{Analysis, GlobalInfo, Stats};
+use crate::eval::{Eval, EvalBreakdown, EvalMode, Net};
+use crate::params::{self, Tunables};
+use crate::search::Search;
+use crate::strength::Strength;
+use crate::utils::{self, Deadline};
+
+//#################################################################################################
+//
+//                                       struct LocalEngine
+//
+//#################################################################################################
+
+/// A single-threaded engine, searching on the caller's thread.
+/// Unlike Engine, it does not spawn any thread, which makes it usable from WebAssembly.
+#[derive(Debug)]
+pub struct LocalEngine {
+    info: Arc<GlobalInfo>,
+    search: Search,
+    net: Arc<Net>,
+    seed: u32,
+}
+
+// ================================ pub impl
+
+impl LocalEngine {
+    /// Creates a new local engine from the given board and the bytes of a network file.
+    pub fn new(board: Board, net: &[u8]) -> Result<LocalEngine> {
+        Ok(LocalEngine::with_net(board, Net::from_bytes(net)?))
+    }
+
+    /// Returns a read lock to the board.
+    pub fn read_board(&self) -> RwLockReadGuard<'_, Board> {
+        self.info.read_board()
+    }
+
+    /// Returns a write lock to the board.
+    pub fn write_board(&mut self) -> RwLockWriteGuard<'_, Board> {
+        self.info.set_root_moves(&[]);
+        self.info.reset_search();
+        self.info.write_board()
+    }
+
+    /// Plays the given legal move on the board. The transposition table and the move
+    /// ordering heuristics are kept for the next search, as with Engine::advance.
+    pub fn advance(&mut self, mv: Move) {
+        self.write_board().do_move(mv);
+    }
+
+    /// Restricts the search to the given moves at the root, as with Engine::set_root_moves.
+    /// The restriction is lifted when the board is written to.
+    pub fn set_root_moves(&mut self, moves: &[Move]) {
+        self.info.set_root_moves(moves);
+    }
+
+    /// Sets the strength level of the engine, between 0 and MAX_STRENGTH.
+    pub fn set_strength(&mut self, level: u8) {
+        self.info.set_strength(Strength::new(level));
+    }
+
+    /// Sets the variety level of the engine, between 0 and MAX_VARIETY.
+    pub fn set_variety(&mut self, level: u8) {
+        self.info.set_variety(level);
+    }
+
+    /// Evaluates the given position statically, without searching it,
+    /// and explains the evaluation of the network.
+    pub fn evaluate_detailed(&self, board: &Board) -> EvalBreakdown {
+        Eval::new(self.net.clone()).breakdown(board)
+    }
+
+    /// Sets the parameters of the search.
+    pub fn set_tunables(&mut self, tunables: Tunables) {
+        self.info.set_tunables(tunables);
+    }
+
+    /// Sets the seed from which all pseudo-random numbers of the engine are derived. With the same seed,
+    /// the same calls give the same results when the search is limited by depth or nodes rather than time.
+    pub fn set_seed(&mut self, seed: u64) {
+        let seed = utils::fold_seed(seed);
+        self.seed = seed;
+        self.info.set_seed(seed);
+    }
+
+    /// Sets the contempt of the engine for draws, in centipawns.
+    pub fn set_contempt(&mut self, cp: i32) {
+        self.info.set_contempt(cp);
+    }
+
+    /// Sets whether the engine avoids repetitions when it is better and seeks them when it is worse.
+    pub fn set_avoid_repetitions(&mut self, avoid: bool) {
+        self.info.set_avoid_repetitions(avoid);
+    }
+
+    /// Sets the way the engine evaluates positions: with its network, with a hand-crafted evaluation, or with the material balance alone.
+    pub fn set_eval_mode(&mut self, mode: EvalMode) {
+        self.info.set_eval_mode(mode);
+    }
+
+    /// Searches the current position for the given duration, blocking the caller.
+    /// Returns the best move found, or None if the game is over.
+    pub fn think(&mut self, duration: Duration) -> Option<Move> {
+        if !self.info.read_board().status().is_playing() {
+            return None;
+        }
+
+        self.info.reset_search();
+        self.info.set_deadline(Some(Deadline::after(duration)));
+        self.info.set_searching(true);
+
+        self.search.search_position();
+
+        self.info.set_searching(false);
+        self.info.set_deadline(None);
+
+        self.info.final_move(&mut self.seed)
+    }
+
+    /// Searches the current position until roughly the given number of nodes are searched,
+    /// blocking the caller. Returns the best move found and its score, from the point of
+    /// view of the side to move, or None if the game is over.
+    pub fn think_nodes(&mut self, nodes: u64) -> Option<(Move, f32)> {
+        if !self.info.read_board().status().is_playing() {
+            return None;
+        }
+
+        self.info.reset_search();
+        self.info.set_node_limit(Some(nodes));
+        self.info.set_searching(true);
+
+        self.search.search_position();
+
+        self.info.set_searching(false);
+        self.info.set_node_limit(None);
+
+        Some((self.info.final_move(&mut self.seed)?, self.info.get_best_score()?))
+    }
+
+    /// Searches the current position up to the given depth, blocking the caller.
+    /// Returns the best move found, or None if the game is over.
+    pub fn think_depth(&mut self, depth: u8) -> Option<Move> {
+        if !self.info.read_board().status().is_playing() {
+            return None;
+        }
+
+        self.info.reset_search();
+        self.info.set_depth_limit(Some(depth));
+        self.info.set_searching(true);
+
+        self.search.search_position();
+
+        self.info.set_searching(false);
+        self.info.set_depth_limit(None);
+
+        self.info.final_move(&mut self.seed)
+    }
+
+    /// Starts a new search of the current position, carried out one depth at a time by search_next_depth(),
+    /// so that the caller may do other work in between, such as handling messages. Returns false if the game is over.
+    pub fn start_search(&mut self) -> bool {
+        if !self.info.read_board().status().is_playing() {
+            return false;
+        }
+
+        self.info.reset_search();
+        true
+    }
+
+    /// Searches the next depth of the search started with start_search(), for at most the given duration,
+    /// blocking the caller. Returns false if the search is over: that depth could not be completed in time,
+    /// or the maximum depth was already reached. The results are read with analysis() and best_move().
+    pub fn search_next_depth(&mut self, duration: Duration) -> bool {
+        let depth = self.info.search_depth() + 1;
+        if depth > self.info.max_depth() {
+            return false;
+        }
+
+        self.info.set_depth_limit(Some(depth));
+        self.info.set_deadline(Some(Deadline::after(duration)));
+        self.info.set_searching(true);
+
+        self.search.search_position();
+
+        self.info.set_searching(false);
+        self.info.set_deadline(None);
+        self.info.set_depth_limit(None);
+
+        self.info.search_depth() >= depth
+    }
+
+    /// Returns the move chosen by the last search, or None if no move was found.
+    pub fn best_move(&mut self) -> Option<Move> {
+        self.info.final_move(&mut self.seed)
+    }
+
+    /// Returns the state of the analysis of the last search, as Engine::analysis() does.
+    pub fn analysis(&self) -> Analysis {
+        self.info.analysis()
+    }
+
+    /// Returns the score of the best move found by the last search, from the point of view
+    /// of the side to move, or None if no move was found.
+    pub fn score(&self) -> Option<f32> {
+        self.info.get_best_score()
+    }
+
+    /// Returns the number of nodes searched by the engine since its creation.
+    pub fn nodes(&self) -> u64 {
+        self.search.nodes()
+    }
+
+    /// Returns statistics about the searches of the engine since its creation.
+    pub fn stats(&self) -> Stats {
+        self.info.stats()
+    }
+}
+
+// ================================ pub(crate) impl
+
+impl LocalEngine {
+    /// Creates a new local engine from the given board and an already loaded network.
+    pub(crate) fn with_net(board: Board, net: Arc<Net>) -> LocalEngine {
+        let info = Arc::new(GlobalInfo::new(board, 1, params::TABLE_SIZE_MB, false));
+        let seed = utils::seed();
+        let search = Search::new(0, info.clone(), net.clone());
+
+        LocalEngine {info, search, net, seed}
+    }
+}
@@ -0,0 +1,474 @@
+use std::fs;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Error, Result};
+use clap::{App, Arg};
+
+use chess::prelude::*;
+use engine::{random_opening, termination, LocalEngine, Player, SearchLimit, Tunables, UciClient, STARTING_FEN};
+
+/*
+ * Plays matches between two engine configurations, to measure the strength difference between them.
+ * Each configuration is either this engine, with its own network and parameters, or any external engine
+ * speaking the UCI protocol. Each opening is played twice, with colors reversed.
+ * Openings are read from a file containing one FEN or EPD position per line, or are randomized.
+ * After each game, the score of the first configuration and its Elo difference with the second one,
+ * with 95% error bars, are printed. A sequential probability ratio test may be run to stop the match early.
+ *
+ * $ cargo build --bin match --release
+ * $ target/release/match --net engine/nets/nnue.bin --params2 tuned.txt --games 200 --tc 10+0.1 --sprt 0,5
+ */
+
+/// The error rates of the sequential probability ratio test.
+const SPRT_ALPHA: f64 = 0.05;
+const SPRT_BETA: f64 = 0.05;
+
+/// How long a player may think for each move.
+#[derive(Clone, Copy, Debug)]
+enum Limit {
+    // A fixed number of nodes per move, roughly.
+    Nodes(u64),
+    // A time control, with a base time and an increment added after each move.
+    Time {base: Duration, increment: Duration},
+}
+
+/// The rules used to play and adjudicate the games.
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    // How long players may think.
+    limit: Limit,
+    // The score above which a player is considered winning, in pawns.
+    win_score: f32,
+    // The number of consecutive plies a player must be winning for the game to be adjudicated.
+    win_plies: usize,
+    // The score below which the game is considered drawn, in pawns.
+    draw_score: f32,
+    // The number of consecutive plies the game must be drawn for it to be adjudicated.
+    draw_plies: usize,
+    // The number of plies after which a game is adjudicated as a draw.
+    max_plies: usize,
+}
+
+/// Reads the openings of a file, containing one FEN or EPD position per line.
+/// The operations of EPD positions are ignored, and their move counters are reset.
+fn read_openings(path: &str) -> Result<Vec<Board>> {
+    let mut openings = Vec::new();
+
+    for line in fs::read_to_string(path)?.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let fields: Vec<_> = line.split_ascii_whitespace().collect();
+
+        let fen = match fields.get(4..6) {
+            Some([halfmove, fullmove]) if halfmove.parse::<u16>().is_ok() && fullmove.parse::<u16>().is_ok() => fields[..6].join(" "),
+            _ if fields.len() >= 4 => format!("{} 0 1", fields[..4].join(" ")),
+            _ => return Err(Error::msg(format!("Invalid opening: \"{}\".", line))),
+        };
+
+        openings.push(Board::new(&fen)?);
+    }
+
+    if openings.is_empty() {
+        return Err(Error::msg("The openings file is empty."));
+    }
+
+    Ok(openings)
+}
+
+/// Creates this engine, with the network and the parameters read from the given files.
+fn local_player(net: &str, params: Option<&str>) -> Result<Box<dyn Player>> {
+    let mut engine = LocalEngine::new(Board::new(STARTING_FEN)?, &fs::read(net)?)?;
+
+    if let Some(params) = params {
+        engine.set_tunables(Tunables::from_str(&fs::read_to_string(params)?)?);
+    }
+
+    Ok(Box::new(engine))
+}
+
+/// Plays a game between the two players from the given opening, the first one playing white.
+/// Returns the result of the game from white's point of view, and the reason it ended.
+fn play_game(mut players: [&mut dyn Player; 2], opening: &Board, rules: Rules) -> Result<(i8, &'static str)> {
+    for player in players.iter_mut() {
+        player.new_game()?;
+    }
+
+    let mut board = opening.clone();
+    let mut moves = Vec::new();
+    let mut clocks = match rules.limit {
+        Limit::Time {base, ..} => [base; 2],
+        Limit::Nodes(_) => [Duration::MAX; 2],
+    };
+    let (mut winning_plies, mut drawn_plies) = (0, 0);
+
+    loop {
+        match board.status() {
+            Status::Playing => (),
+            Status::Draw => return Ok((0, termination(&board))),
+            Status::Win(Color::White) => return Ok((1, termination(&board))),
+            Status::Win(Color::Black) => return Ok((-1, termination(&board))),
+        }
+
+        if moves.len() >= rules.max_plies {
+            return Ok((0, "maximum length"));
+        }
+
+        let side = board.get_side_to_move();
+        let loss = match side {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+
+        let start = Instant::now();
+        let limit = match rules.limit {
+            Limit::Nodes(nodes) => SearchLimit::Nodes(nodes),
+            Limit::Time {increment, ..} => SearchLimit::Clock {times: clocks, increment},
+        };
+        let (mv, score) = match players[side as usize].search(opening, &moves, &board, limit) {
+            Ok(found) => (found.mv, found.score),
+            Err(_) => return Ok((loss, "engine failure")),
+        };
+        let elapsed = start.elapsed();
+
+        if let Limit::Time {increment, ..} = rules.limit {
+            let clock = &mut clocks[side as usize];
+            if elapsed > *clock {
+                return Ok((loss, "time forfeit"));
+            }
+            *clock = *clock - elapsed + increment;
+        }
+
+        if !(board.is_pseudo_legal(mv) && board.is_legal(mv)) {
+            return Ok((loss, "illegal move"));
+        }
+
+        // Adjudicates the game once a player has been winning, or the game has been drawn, for long enough.
+        if let Some(score) = score {
+            let score = if side == Color::White {score} else {-score};
+
+            if score.abs() >= rules.win_score {
+                winning_plies += 1;
+                if winning_plies >= rules.win_plies {
+                    return Ok((if score > 0.0 {1} else {-1}, "adjudication"));
+                }
+            } else {
+                winning_plies = 0;
+            }
+
+            if score.abs() <= rules.draw_score {
+                drawn_plies += 1;
+                if drawn_plies >= rules.draw_plies {
+                    return Ok((0, "adjudication"));
+                }
+            } else {
+                drawn_plies = 0;
+            }
+        }
+
+        board.do_move(mv);
+        moves.push(mv);
+    }
+}
+
+//#################################################################################################
+//
+//                                          struct Stats
+//
+//#################################################################################################
+
+/// The results of the match, from the point of view of the first player.
+#[derive(Debug, Default)]
+struct Stats {
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+// ================================ impl
+
+impl Stats {
+    /// The number of games played.
+    fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// The average score per game, between 0 and 1, and its variance.
+    fn score(&self) -> (f64, f64) {
+        let n = f64::from(self.games());
+        let score = (f64::from(self.wins) + 0.5 * f64::from(self.draws)) / n;
+        let variance = (f64::from(self.wins) * (1.0 - score).powi(2)
+            + f64::from(self.draws) * (0.5 - score).powi(2)
+            + f64::from(self.losses) * score.powi(2)) / n;
+
+        (score, variance)
+    }
+
+    /// The Elo difference corresponding to an average score, which is clamped to keep it finite.
+    fn elo(score: f64) -> f64 {
+        -400.0 * (1.0 / score.clamp(0.001, 0.999) - 1.0).log10()
+    }
+
+    /// The Elo difference between the players, and the margin of its 95% confidence interval.
+    fn elo_bounds(&self) -> (f64, f64) {
+        let (score, variance) = self.score();
+        let deviation = (variance / f64::from(self.games())).sqrt();
+
+        let (low, high) = (Stats::elo(score - 1.96 * deviation), Stats::elo(score + 1.96 * deviation));
+        (Stats::elo(score), (high - low) / 2.0)
+    }
+
+    /// The log-likelihood ratio of the hypothesis that the Elo difference is elo1, against
+    /// the hypothesis that it is elo0, using a normal approximation of the results.
+    fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let (score, variance) = self.score();
+        if variance == 0.0 {
+            return 0.0;
+        }
+
+        let expected = |elo: f64| 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+        let (s0, s1) = (expected(elo0), expected(elo1));
+
+        f64::from(self.games()) * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance)
+    }
+}
+
+/// The main function parses the programs arguments, then plays the games of the match
+/// and prints the statistics after each of them.
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine match runner")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("Plays matches between two engine configurations, and estimates their Elo difference.")
+        .arg(Arg::with_name("net")
+            .long("net")
+            .value_name("NET")
+            .help("The path to the network file used by both configurations, unless overridden.")
+            .takes_value(true))
+        .arg(Arg::with_name("net1")
+            .long("net1")
+            .value_name("NET")
+            .help("The path to the network file of the first configuration.")
+            .takes_value(true))
+        .arg(Arg::with_name("params1")
+            .long("params1")
+            .value_name("PARAMS")
+            .help("The path to a file containing the search parameters of the first configuration.")
+            .takes_value(true))
+        .arg(Arg::with_name("uci1")
+            .long("uci1")
+            .value_name("ENGINE")
+            .help("The path to an external UCI engine, used as the first configuration.")
+            .takes_value(true))
+        .arg(Arg::with_name("net2")
+            .long("net2")
+            .value_name("NET")
+            .help("The path to the network file of the second configuration.")
+            .takes_value(true))
+        .arg(Arg::with_name("params2")
+            .long("params2")
+            .value_name("PARAMS")
+            .help("The path to a file containing the search parameters of the second configuration.")
+            .takes_value(true))
+        .arg(Arg::with_name("uci2")
+            .long("uci2")
+            .value_name("ENGINE")
+            .help("The path to an external UCI engine, used as the second configuration.")
+            .takes_value(true))
+        .arg(Arg::with_name("games")
+            .short("g")
+            .long("games")
+            .value_name("GAMES")
+            .default_value("100")
+            .help("The number of games to play.")
+            .takes_value(true))
+        .arg(Arg::with_name("openings")
+            .short("o")
+            .long("openings")
+            .value_name("OPENINGS")
+            .help("The path to a file of openings, containing one FEN or EPD position per line.")
+            .takes_value(true))
+        .arg(Arg::with_name("random-plies")
+            .long("random-plies")
+            .value_name("PLIES")
+            .default_value("8")
+            .help("The number of random moves played to generate openings, when no openings file is given.")
+            .takes_value(true))
+        .arg(Arg::with_name("tc")
+            .long("tc")
+            .value_name("BASE+INC")
+            .default_value("10+0.1")
+            .help("The time control, in seconds: the base time and the increment.")
+            .takes_value(true))
+        .arg(Arg::with_name("nodes")
+            .short("n")
+            .long("nodes")
+            .value_name("NODES")
+            .help("Searches a fixed number of nodes per move instead of using a time control.")
+            .takes_value(true))
+        .arg(Arg::with_name("win-score")
+            .long("win-score")
+            .value_name("PAWNS")
+            .default_value("10")
+            .help("The score, in pawns, above which a player is considered winning.")
+            .takes_value(true))
+        .arg(Arg::with_name("win-plies")
+            .long("win-plies")
+            .value_name("PLIES")
+            .default_value("8")
+            .help("The number of consecutive plies a player must be winning for the game to be adjudicated as a win.")
+            .takes_value(true))
+        .arg(Arg::with_name("draw-score")
+            .long("draw-score")
+            .value_name("PAWNS")
+            .default_value("0.1")
+            .help("The score, in pawns, below which the game is considered drawn.")
+            .takes_value(true))
+        .arg(Arg::with_name("draw-plies")
+            .long("draw-plies")
+            .value_name("PLIES")
+            .default_value("40")
+            .help("The number of consecutive plies the game must be drawn for it to be adjudicated as a draw.")
+            .takes_value(true))
+        .arg(Arg::with_name("max-plies")
+            .long("max-plies")
+            .value_name("PLIES")
+            .default_value("400")
+            .help("The number of plies after which a game is adjudicated as a draw.")
+            .takes_value(true))
+        .arg(Arg::with_name("sprt")
+            .long("sprt")
+            .value_name("ELO0,ELO1")
+            .help("Runs a sequential probability ratio test of elo1 against elo0, stopping the match once it concludes.")
+            .takes_value(true))
+        .get_matches();
+
+    // Parses a numerical argument.
+    fn parse<T: FromStr>(args: &clap::ArgMatches, name: &str) -> Result<T> {
+        T::from_str(args.value_of(name).unwrap()).map_err(|_| Error::msg(format!("Unable to parse {}.", name)))
+    }
+
+    // Parses a pair of numbers, separated by the given character.
+    fn parse_pair(args: &clap::ArgMatches, name: &str, separator: char) -> Result<(f64, f64)> {
+        let error = || Error::msg(format!("Unable to parse {}.", name));
+        let (first, second) = args.value_of(name).unwrap().split_once(separator).ok_or_else(error)?;
+        Ok((f64::from_str(first).map_err(|_| error())?, f64::from_str(second).map_err(|_| error())?))
+    }
+
+    let limit = if args.is_present("nodes") {
+        Limit::Nodes(parse(&args, "nodes")?)
+    } else {
+        let (base, increment) = parse_pair(&args, "tc", '+')?;
+        Limit::Time {base: Duration::from_secs_f64(base), increment: Duration::from_secs_f64(increment)}
+    };
+
+    let games: u32 = parse(&args, "games")?;
+    let rules = Rules {
+        limit,
+        win_score: parse(&args, "win-score")?,
+        win_plies: parse(&args, "win-plies")?,
+        draw_score: parse(&args, "draw-score")?,
+        draw_plies: parse(&args, "draw-plies")?,
+        max_plies: parse(&args, "max-plies")?,
+    };
+    let sprt = if args.is_present("sprt") {Some(parse_pair(&args, "sprt", ',')?)} else {None};
+
+    // Creates the two players.
+    let mut players = Vec::new();
+    for n in 1..=2 {
+        players.push(if let Some(path) = args.value_of(format!("uci{}", n)) {
+            Box::new(UciClient::start(&[path.to_string()], &[])?) as Box<dyn Player>
+        } else {
+            let net = args.value_of(format!("net{}", n)).or_else(|| args.value_of("net"))
+                .ok_or_else(|| Error::msg(format!("Configuration {} needs either --net, --net{} or --uci{}.", n, n, n)))?;
+            local_player(net, args.value_of(format!("params{}", n)))?
+        });
+    }
+    let (first, second) = players.split_at_mut(1);
+    let (first, second) = (first[0].as_mut(), second[0].as_mut());
+
+    let openings = args.value_of("openings").map(read_openings).transpose()?;
+    let random_plies: usize = parse(&args, "random-plies")?;
+    let mut seed = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() | 1;
+    let mut opening = Board::new(STARTING_FEN)?;
+
+    let mut stats = Stats::default();
+
+    for game in 0..games {
+        // Each opening is played twice, with colors reversed.
+        if game % 2 == 0 {
+            opening = match &openings {
+                Some(openings) => openings[(game / 2) as usize % openings.len()].clone(),
+                None => random_opening(random_plies, &mut seed),
+            };
+        }
+
+        let (white_result, reason) = if game % 2 == 0 {
+            play_game([first, second], &opening, rules)?
+        } else {
+            play_game([second, first], &opening, rules)?
+        };
+
+        // The result from the point of view of the first player.
+        let result = if game % 2 == 0 {white_result} else {-white_result};
+
+        match result {
+            1 => stats.wins += 1,
+            -1 => stats.losses += 1,
+            _ => stats.draws += 1,
+        }
+
+        let (elo, margin) = stats.elo_bounds();
+        println!(
+            "Game {}/{}, first configuration as {}: {} ({}). Score: {} - {} - {}, Elo: {:.1} +/- {:.1}.",
+            game + 1, games, ["white", "black"][(game % 2) as usize], ["0-1", "1/2-1/2", "1-0"][(white_result + 1) as usize], reason,
+            stats.wins, stats.losses, stats.draws, elo, margin,
+        );
+
+        if let Some((elo0, elo1)) = sprt {
+            let llr = stats.llr(elo0, elo1);
+            let (lower, upper) = ((SPRT_BETA / (1.0 - SPRT_ALPHA)).ln(), ((1.0 - SPRT_BETA) / SPRT_ALPHA).ln());
+            println!("SPRT: LLR {:.2} ({:.2}, {:.2}).", llr, lower, upper);
+
+            if llr >= upper {
+                println!("H1 accepted: the first configuration is stronger by at least {} Elo.", elo1);
+                break;
+            } else if llr <= lower {
+                println!("H0 accepted: the first configuration is not stronger by {} Elo.", elo1);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        // A score of 55% over 200 games, with half of them drawn.
+        let stats = Stats {wins: 60, losses: 40, draws: 100};
+        let (score, variance) = stats.score();
+        assert!((score - 0.55).abs() < 1e-12);
+        assert!((variance - 0.1225).abs() < 1e-12);
+
+        let (elo, margin) = stats.elo_bounds();
+        assert!((elo - 34.860070).abs() < 1e-5, "{}", elo);
+        assert!((margin - 34.159901).abs() < 1e-5, "{}", margin);
+        assert!((stats.llr(0.0, 5.0) - 0.545093).abs() < 1e-5, "{}", stats.llr(0.0, 5.0));
+
+        // Even results support neither of two symmetric hypotheses, and draws alone tell nothing.
+        let stats = Stats {wins: 30, losses: 30, draws: 40};
+        assert!(stats.elo_bounds().0.abs() < 1e-9);
+        assert!(stats.llr(-5.0, 5.0).abs() < 1e-9);
+        assert_eq!(Stats {wins: 0, losses: 0, draws: 10}.llr(0.0, 5.0), 0.0);
+    }
+}
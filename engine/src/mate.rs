@@ -0,0 +1,214 @@
+use std::fmt;
+
+use chess::board::{Board, Status};
+use chess::color::Color;
+use chess::movegen::{self, MoveList};
+use chess::moves::Move;
+use chess::variant::Variant;
+
+/// The maximum number of moves a mate search looks for a mate in. Searching is exponential
+/// in the number of moves, and longer mates could not be searched in a reasonable time.
+pub const MAX_MATE_MOVES: u8 = 8;
+
+//#################################################################################################
+//
+//                                        enum MateResult
+//
+//#################################################################################################
+
+/// The result of a mate search.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MateResult {
+    /// The side to move mates in the given number of moves, whatever the defense, by playing the principal
+    /// variation. The defender plays the replies delaying the mate the longest.
+    Mate {
+        moves: u8,
+        pv: Vec<Move>,
+    },
+    /// The side to move has no forced mate within the given number of moves.
+    NoMate(u8),
+}
+
+// ================================ traits impl
+
+impl fmt::Display for MateResult {
+    /// Displays the result, with the moves of the principal variation in pure coordinate notation.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MateResult::Mate {moves, pv} => {
+                let pv = pv.iter().map(Move::to_string).collect::<Vec<_>>().join(" ");
+                write!(f, "Mate in {}: {}", moves, pv)
+            },
+            MateResult::NoMate(1) => write!(f, "No mate in 1 move."),
+            MateResult::NoMate(moves) => write!(f, "No mate within {} moves.", moves),
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct MateSearch
+//
+//#################################################################################################
+
+/// A full-width search of the forced wins of the side to move, the attacker. No position is evaluated:
+/// a line is either won by the attacker within the number of moves, or it is not. Wins are those of the
+/// variant of the board, checkmates in standard chess.
+struct MateSearch {
+    board: Board,
+    attacker: Color,
+}
+
+// ================================ impl
+
+impl MateSearch {
+    /// Returns true if the position is won by the attacker.
+    #[inline]
+    fn is_won(&self) -> bool {
+        self.board.status() == Status::Win(self.attacker)
+    }
+
+    /// Returns a move of the attacker, to move, winning within the given number of moves whatever the defense.
+    fn attack(&mut self, moves: u8) -> Option<Move> {
+        let mut legals = MoveList::new();
+        movegen::legals_into(&self.board, &mut legals);
+
+        // Checks are tried first, as they are the most likely to mate. At the last move,
+        // only checks can mate, except in variants winning in other ways.
+        let only_checks = moves == 1 && self.board.get_variant() == Variant::Standard;
+        let (checks, others): (Vec<Move>, Vec<Move>) = legals.iter().partition(|&&mv| self.board.gives_check(mv));
+        let others = if only_checks {Vec::new()} else {others};
+
+        checks.into_iter().chain(others).find(|&mv| {
+            self.board.do_move(mv);
+            let won = match self.board.status() {
+                Status::Win(color) => color == self.attacker,
+                Status::Draw => false,
+                Status::Playing => moves > 1 && !self.defend(moves - 1),
+            };
+            self.board.undo_move(mv);
+            won
+        })
+    }
+
+    /// Returns true if the defender, to move, can avoid losing within the given number of moves of the attacker.
+    fn defend(&mut self, moves: u8) -> bool {
+        let mut legals = MoveList::new();
+        movegen::legals_into(&self.board, &mut legals);
+
+        legals.iter().any(|&mv| {
+            self.board.do_move(mv);
+            let lost = match self.board.status() {
+                Status::Win(color) => color == self.attacker,
+                Status::Draw => false,
+                Status::Playing => self.attack(moves).is_some(),
+            };
+            self.board.undo_move(mv);
+            !lost
+        })
+    }
+
+    /// Returns the smallest number of moves the attacker, to move, wins in, up to the given number, and the move to play.
+    fn shortest(&mut self, moves: u8) -> Option<(u8, Move)> {
+        (1..=moves).find_map(|n| self.attack(n).map(|mv| (n, mv)))
+    }
+
+    /// Returns the principal variation of the win of the attacker, to move, in the given number of moves,
+    /// which must be the shortest: the defender plays the replies delaying it the longest.
+    fn principal_variation(&mut self, mut moves: u8, mv: Move) -> Vec<Move> {
+        let mut pv = vec![mv];
+        self.board.do_move(mv);
+
+        while !self.is_won() {
+            let mut replies = MoveList::new();
+            movegen::legals_into(&self.board, &mut replies);
+
+            // Every reply loses within moves - 1 moves: pick the one losing the slowest.
+            let (n, reply, next) = replies.iter().filter_map(|&reply| {
+                self.board.do_move(reply);
+                let shortest = self.shortest(moves - 1);
+                self.board.undo_move(reply);
+                shortest.map(|(n, next)| (n, reply, next))
+            }).max_by_key(|&(n, _, _)| n).unwrap();
+
+            pv.push(reply);
+            self.board.do_move(reply);
+            pv.push(next);
+            self.board.do_move(next);
+            moves = n;
+        }
+
+        pv
+    }
+}
+
+// ================================ pub fn
+
+/// Searches the given position for a forced win of the side to move within the given number of moves,
+/// clamped between 1 and MAX_MATE_MOVES, and returns the shortest one found, if any.
+pub fn search_mate(board: &Board, moves: u8) -> MateResult {
+    let moves = moves.clamp(1, MAX_MATE_MOVES);
+    let mut search = MateSearch {
+        board: board.clone(),
+        attacker: board.get_side_to_move(),
+    };
+
+    if !board.status().is_playing() {
+        return MateResult::NoMate(moves);
+    }
+
+    match search.shortest(moves) {
+        Some((n, mv)) => MateResult::Mate {moves: n, pv: search.principal_variation(n, mv)},
+        None => MateResult::NoMate(moves),
+    }
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Searches the position for a mate within the given number of moves, and returns the principal
+    /// variation found, in pure coordinate notation.
+    fn mate(fen: &str, moves: u8) -> Option<(u8, Vec<String>)> {
+        match search_mate(&Board::new(fen).unwrap(), moves) {
+            MateResult::Mate {moves, pv} => Some((moves, pv.iter().map(Move::to_string).collect())),
+            MateResult::NoMate(_) => None,
+        }
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let expected = Some((1, vec!["a1a8".to_string()]));
+        assert_eq!(mate("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 1), expected);
+        assert_eq!(mate("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 3), expected);
+    }
+
+    #[test]
+    fn mate_in_two() {
+        let fen = "r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1";
+        assert_eq!(mate(fen, 1), None);
+
+        let pv = ["e2e8", "a8e8", "e1e8"].iter().map(ToString::to_string).collect();
+        assert_eq!(mate(fen, 2), Some((2, pv)));
+
+        let board = Board::new(fen).unwrap();
+        assert_eq!(search_mate(&board, 2).to_string(), "Mate in 2: e2e8 a8e8 e1e8");
+    }
+
+    #[test]
+    fn no_mate() {
+        let board = Board::new("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(search_mate(&board, 2), MateResult::NoMate(2));
+        assert_eq!(search_mate(&board, 0), MateResult::NoMate(1));
+
+        // The game is already over.
+        let board = Board::new("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1").unwrap();
+        assert_eq!(search_mate(&board, 3), MateResult::NoMate(3));
+    }
+}
@@ -2,11 +2,12 @@ use std::cmp::Ordering;
 
 use chess::bitboard::BitBoard;
 use chess::board::Board;
-use chess::movegen;
+use chess::movegen::{self, Legality};
 use chess::moves::Move;
 use chess::piece::Piece;
 
 use crate::heuristics::Heuristics;
+use crate::stack::Frame;
 
 /// All under-prmotions.
 const UNDER_PROMOTES: &[Piece] = &[Piece::Rook, Piece::Bishop, Piece::Knight];
@@ -81,142 +82,196 @@ impl RatedMove {
 //#################################################################################################
 
 /// A struct used to provide a layer of abstraction over move generation and picking.
-/// Uses u16s instead of usizes to save space, since we won't go as far as 65536 moves anyway.
+/// The moves are generated by batches in the frame of the ply of the node, and only
+/// the legal ones are kept, so that the search needs not check their legality.
+/// Outside of checks, the killer moves and the countermove are tried alone before
+/// the other quiets, which need not be generated if one of them causes a cutoff.
 #[derive(Debug)]
 pub(crate) struct MovePicker {
     state: MovePickerState,
-    start: u16,
-    end: u16,
+    hash_move: Option<Move>,
+    legality: Legality,
+    // The killers and countermove tried in their own stages, removed from the quiets.
+    specials: [Option<Move>; 3],
 }
 
 // ================================ pub(crate) impl
 
 impl MovePicker {
-    /// Constructs a new move picker.
+    /// Constructs a new move picker. The move stored in the transposition table for that position,
+    /// which is the move of the principal variation in PV nodes, is tried first if it is legal.
+    /// The given frame is that of the ply of the node, its previous moves are forgotten.
     #[inline]
-    pub(crate) fn new(board: &Board, buffer: &Vec<RatedMove>) -> MovePicker {
-        let len = buffer.len() as u16;
+    pub(crate) fn new(board: &Board, frame: &mut Frame, hash_move: Option<Move>) -> MovePicker {
+        let legality = Legality::new(board);
+        let hash_move = hash_move.filter(|&mv| board.is_pseudo_legal(mv) && legality.is_legal(board, mv));
+        frame.clear();
 
         MovePicker {
-            state: MovePickerState::new(board),
-            start: len,
-            end: len,
+            state: match hash_move {
+                Some(mv) => MovePickerState::HashMove {mv},
+                None => MovePickerState::new(board),
+            },
+            hash_move,
+            legality,
+            specials: [None; 3],
         }
     }
 
-    /// Returns the next pseudo-legal move to try, or None if there is no more moves for this position.
+    /// Returns the next legal move to try, or None if there is no more moves for this position.
+    /// The frame must be the one given to new().
     #[inline]
-    pub(crate) fn next(&mut self, board: &Board, heuristics: &Heuristics, depth: u8, buffer: &mut Vec<RatedMove>) -> Option<Move> {
-        // If there were any leftovers move from a deeper node's MovePicker: forget them.
-        // SAFE: we know the buffer has at least self.end elements already.
-        unsafe {buffer.set_len(self.end as usize)};
-
-        // There are no more moves in the buffer.
-        if self.start == self.end {
-            if self.gen_next_batch(board, heuristics, depth, buffer) {
-                // A new batch was generated, sort the new moves.
-                buffer[usize::from(self.start)..].sort_unstable_by(RatedMove::pseudo_cmp);
-            } else {
-                // The new batch was empty, return None.
-                return None;
-            }
+    pub(crate) fn next(&mut self, board: &Board, heuristics: &Heuristics, depth: u8, frame: &mut Frame) -> Option<Move> {
+        // There are no more moves in the frame.
+        if frame.is_empty() && !self.gen_next_batch(board, heuristics, depth, frame) {
+            // The new batch was empty, return None.
+            return None;
         }
 
-        // Return the last element of the buffer.
-        self.end -= 1;
-        buffer.pop().map(|rated| rated.mv)
-    }
-
-    /// Needs to be called after all moves have been consumed from the movepicker.
-    #[inline]
-    pub(crate) fn truncate(&self, buffer: &mut Vec<RatedMove>) {
-        // SAFE: we know the buffer had at least self.start elements already.
-        unsafe {buffer.set_len(self.start as usize)};
+        // Return the last move of the frame, the best one.
+        frame.pop().map(|rated| rated.mv)
     }
 }
 
+// ================================ impl
+
 impl MovePicker {
-    fn gen_next_batch(&mut self, board: &Board, heuristics: &Heuristics, depth: u8, buffer: &mut Vec<RatedMove>) -> bool {
+    /// Generates the legal moves of the next non-empty batch in the empty frame, sorted.
+    /// Returns false if there is no batch left.
+    fn gen_next_batch(&mut self, board: &Board, heuristics: &Heuristics, depth: u8, frame: &mut Frame) -> bool {
         loop {
+            let hash_stage = matches!(self.state, MovePickerState::HashMove {..});
+            let quiets_stage = matches!(self.state, MovePickerState::Quiets);
+
             self.state = match self.state {
+                // The move of the transposition table, alone.
+                MovePickerState::HashMove {mv} => {
+                    frame.push(RatedMove {mv, score: 0.0});
+                    MovePickerState::new(board)
+                },
+
                 // Only queen promotions and capture promotions.
                 MovePickerState::QueenPromotes => {
-                    movegen::gen_promote_captures(board, &[Piece::Queen], |mv| buffer.push(RatedMove::promote_capture(mv)));
-                    movegen::gen_promotes(board, &[Piece::Queen], |mv| buffer.push(RatedMove::promote(mv)));
+                    movegen::gen_promote_captures(board, &[Piece::Queen], |mv| frame.push(RatedMove::promote_capture(mv)));
+                    movegen::gen_promotes(board, &[Piece::Queen], |mv| frame.push(RatedMove::promote(mv)));
                     MovePickerState::Captures
                 },
                 // All captures, including en passant ones.
                 MovePickerState::Captures => {
-                    movegen::gen_pawn_captures(board, |mv| buffer.push(RatedMove::capture(Piece::Pawn, mv)));
-                    movegen::gen_en_passant(board, |mv| buffer.push(RatedMove::capture(Piece::Pawn, mv)));
-                    movegen::gen_captures(board, |piece, mv| buffer.push(RatedMove::capture(piece, mv)));
-                    movegen::gen_king_captures(board, |mv| buffer.push(RatedMove::capture(Piece::King, mv)));
+                    movegen::gen_pawn_captures(board, |mv| frame.push(RatedMove::capture(Piece::Pawn, mv)));
+                    movegen::gen_en_passant(board, |mv| frame.push(RatedMove::capture(Piece::Pawn, mv)));
+                    movegen::gen_captures(board, |piece, mv| frame.push(RatedMove::capture(piece, mv)));
+                    movegen::gen_king_captures(board, |mv| frame.push(RatedMove::capture(Piece::King, mv)));
                     MovePickerState::Castles
                 },
                 // All castling.
                 MovePickerState::Castles => {
-                    movegen::gen_castles(board, |mv| buffer.push(RatedMove::castle(mv)));
+                    movegen::gen_castles(board, |mv| frame.push(RatedMove::castle(mv)));
                     MovePickerState::UnderPromotes
                 },
                 // All under promotions.
                 MovePickerState::UnderPromotes => {
-                    movegen::gen_promote_captures(board, UNDER_PROMOTES, |mv| buffer.push(RatedMove::promote_capture(mv)));
-                    movegen::gen_promotes(board, UNDER_PROMOTES, |mv| buffer.push(RatedMove::promote(mv)));
+                    movegen::gen_promote_captures(board, UNDER_PROMOTES, |mv| frame.push(RatedMove::promote_capture(mv)));
+                    movegen::gen_promotes(board, UNDER_PROMOTES, |mv| frame.push(RatedMove::promote(mv)));
+                    MovePickerState::Killer1
+                },
+                // The oldest killer move, alone.
+                MovePickerState::Killer1 => {
+                    self.push_special(board, heuristics.killers(depth)[0], 0, frame);
+                    MovePickerState::Killer2
+                },
+                // The newest killer move, alone.
+                MovePickerState::Killer2 => {
+                    self.push_special(board, heuristics.killers(depth)[1], 1, frame);
+                    MovePickerState::CounterMove
+                },
+                // The countermove of the previous move, alone.
+                MovePickerState::CounterMove => {
+                    self.push_special(board, heuristics.countermove(depth), 2, frame);
                     MovePickerState::Quiets
                 },
-                // All quiets, including pushes and king ones.
+                // All quiets, including pushes and king ones, except those tried already.
                 MovePickerState::Quiets => {
-                    movegen::gen_pushes(board, |mv| buffer.push(heuristics.rate(mv, depth)));
-                    movegen::gen_quiets(board, |_, mv| buffer.push(heuristics.rate(mv, depth)));
-                    movegen::gen_king_quiets(board, |mv| buffer.push(heuristics.rate(mv, depth)));
+                    movegen::gen_pushes(board, |mv| frame.push(heuristics.rate(Piece::Pawn, mv, depth)));
+                    movegen::gen_quiets(board, |piece, mv| frame.push(heuristics.rate(piece, mv, depth)));
+                    movegen::gen_king_quiets(board, |mv| frame.push(heuristics.rate(Piece::King, mv, depth)));
                     MovePickerState::Stop
                 },
 
                 // All queen promotions under single check.
                 MovePickerState::CheckQueenPromotes {mask} => {
-                    movegen::gen_promote_captures(board, &[Piece::Queen], |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::promote_capture(mv))});
-                    movegen::gen_promotes(board, &[Piece::Queen], |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::promote(mv))});
+                    movegen::gen_promote_captures(board, &[Piece::Queen], |mv| if mask.contains(mv.to()) {frame.push(RatedMove::promote_capture(mv))});
+                    movegen::gen_promotes(board, &[Piece::Queen], |mv| if mask.contains(mv.to()) {frame.push(RatedMove::promote(mv))});
                     MovePickerState::CheckCaptures {mask}
                 },
                 // All captures under single check.
                 MovePickerState::CheckCaptures {mask} => {
-                    movegen::gen_pawn_captures(board, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::capture(Piece::Pawn, mv))});
-                    movegen::gen_en_passant(board, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::capture(Piece::Pawn, mv))});
-                    movegen::gen_captures(board, |piece, mv| if mask.contains(mv.to()) {buffer.push(RatedMove::capture(piece, mv))});
-                    movegen::gen_king_captures(board, |mv| buffer.push(RatedMove::capture(Piece::King, mv)));
+                    movegen::gen_pawn_captures(board, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::capture(Piece::Pawn, mv))});
+                    movegen::gen_en_passant(board, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::capture(Piece::Pawn, mv))});
+                    movegen::gen_captures(board, |piece, mv| if mask.contains(mv.to()) {frame.push(RatedMove::capture(piece, mv))});
+                    movegen::gen_king_captures(board, |mv| frame.push(RatedMove::capture(Piece::King, mv)));
                     MovePickerState::CheckOthers {mask}
                 },
                 // All other moves under single check. 
                 MovePickerState::CheckOthers {mask} => {
                     // Under promotions.
-                    movegen::gen_promote_captures(board, UNDER_PROMOTES, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::promote_capture(mv))});
-                    movegen::gen_promotes(board, UNDER_PROMOTES, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::promote(mv))});
+                    movegen::gen_promote_captures(board, UNDER_PROMOTES, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::promote_capture(mv))});
+                    movegen::gen_promotes(board, UNDER_PROMOTES, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::promote(mv))});
                     
                     // Quiet moves.
-                    movegen::gen_pushes(board, |mv| if mask.contains(mv.to()) {buffer.push(heuristics.rate(mv, depth))});
-                    movegen::gen_quiets(board, |_, mv| if mask.contains(mv.to()) {buffer.push(heuristics.rate(mv, depth))});
-                    movegen::gen_king_quiets(board, |mv| buffer.push(heuristics.rate(mv, depth)));
+                    movegen::gen_pushes(board, |mv| if mask.contains(mv.to()) {frame.push(heuristics.rate(Piece::Pawn, mv, depth))});
+                    movegen::gen_quiets(board, |piece, mv| if mask.contains(mv.to()) {frame.push(heuristics.rate(piece, mv, depth))});
+                    movegen::gen_king_quiets(board, |mv| frame.push(heuristics.rate(Piece::King, mv, depth)));
 
                     MovePickerState::Stop
                 },
 
                 // All moves under double check (only the king may move).
                 MovePickerState::DoubleCheck => {
-                    movegen::gen_king_captures(board, |mv| buffer.push(RatedMove::capture(Piece::King, mv)));
-                    movegen::gen_king_quiets(board, |mv| buffer.push(heuristics.rate(mv, depth)));
+                    movegen::gen_king_captures(board, |mv| frame.push(RatedMove::capture(Piece::King, mv)));
+                    movegen::gen_king_quiets(board, |mv| frame.push(heuristics.rate(Piece::King, mv, depth)));
                     MovePickerState::Stop
                 },
 
                 MovePickerState::Stop => return false,
             };
 
-            let end = buffer.len() as u16;
-            if self.start != end {
-                self.end = end;
+            // The move of the table was already tried, it is removed from the batch it is generated in.
+            if let Some(mv) = self.hash_move.filter(|_| !hash_stage) {
+                if let Some(i) = frame.moves().iter().position(|rated| rated.mv == mv) {
+                    frame.swap_remove(i);
+                    self.hash_move = None;
+                }
+            }
+
+            // The illegal moves, and the quiets tried in their own stages, are removed once the batch is sorted,
+            // so that ties between the remaining moves are broken the same way whether or not there were any.
+            // The move of the table was checked to be legal already.
+            frame.moves_mut().sort_unstable_by(RatedMove::pseudo_cmp);
+            if !hash_stage {
+                let (legality, specials) = (self.legality, self.specials);
+                frame.retain(|mv| legality.is_legal(board, mv) && !(quiets_stage && specials.contains(&Some(mv))));
+            }
+
+            if !frame.is_empty() {
                 return true;
             }
         }
     }
+
+    /// Pushes the given killer or countermove alone in the frame, as the special move of the given index,
+    /// if it is a quiet move that can be played in the position and that was not tried before.
+    #[inline]
+    fn push_special(&mut self, board: &Board, mv: Option<Move>, i: usize, frame: &mut Frame) {
+        if let Some(mv) = mv {
+            let quiet = mv.is_quiet() || mv.is_double_push();
+
+            if quiet && Some(mv) != self.hash_move && !self.specials.contains(&Some(mv)) && board.is_pseudo_legal(mv) {
+                self.specials[i] = Some(mv);
+                frame.push(RatedMove {mv, score: 0.0});
+            }
+        }
+    }
 }
 
 //#################################################################################################
@@ -228,11 +283,17 @@ impl MovePicker {
 /// The MovePickerState used for standard search, generates all pseudo-legals for a given position.
 #[derive(Debug)]
 pub(crate) enum MovePickerState {
+    // The move of the transposition table, before any other.
+    HashMove {mv: Move},
+
     // No checkers.
     QueenPromotes,
     Captures,
     Castles,
     UnderPromotes,
+    Killer1,
+    Killer2,
+    CounterMove,
     Quiets,
 
     // One checker: store the mask in which pieces must move.
@@ -275,63 +336,53 @@ impl MovePickerState {
 //
 //#################################################################################################
 
-pub(crate) struct Captures {
-    start: u16,
-    end: u16,
-}
+/// The captures of a position, for the quiescence search. They are all generated
+/// at once in the frame of the ply of the node.
+pub(crate) struct Captures;
+
+// ================================ pub(crate) impl
 
 impl Captures {
+    /// Generates the legal captures of the position in the given frame, forgetting its previous moves.
     #[inline]
-    pub(crate) fn new(board: &Board, buffer: &mut Vec<RatedMove>) -> Captures {
-        let start = buffer.len() as u16;
+    pub(crate) fn new(board: &Board, frame: &mut Frame) -> Captures {
+        frame.clear();
 
         let checkers = board.get_checkers();
 
         if checkers.empty() {
             // No checkers, do all captures, including promotion, en passant, pawn and king ones.
-            movegen::gen_promote_captures(board, &Piece::PROMOTES, |mv| buffer.push(RatedMove::promote_capture(mv)));
-            movegen::gen_pawn_captures(board, |mv| buffer.push(RatedMove::capture(Piece::Pawn, mv)));
-            movegen::gen_en_passant(board, |mv| buffer.push(RatedMove::capture(Piece::Pawn, mv)));
-            movegen::gen_captures(board, |piece, mv| buffer.push(RatedMove::capture(piece, mv)));
-            movegen::gen_king_captures(board, |mv| buffer.push(RatedMove::capture(Piece::King, mv)));
+            movegen::gen_promote_captures(board, &Piece::PROMOTES, |mv| frame.push(RatedMove::promote_capture(mv)));
+            movegen::gen_pawn_captures(board, |mv| frame.push(RatedMove::capture(Piece::Pawn, mv)));
+            movegen::gen_en_passant(board, |mv| frame.push(RatedMove::capture(Piece::Pawn, mv)));
+            movegen::gen_captures(board, |piece, mv| frame.push(RatedMove::capture(piece, mv)));
+            movegen::gen_king_captures(board, |mv| frame.push(RatedMove::capture(Piece::King, mv)));
         } else if checkers.more_than_one() {
             // Two checkers, only the king may capture.
-            movegen::gen_king_captures(board, |mv| buffer.push(RatedMove::capture(Piece::King, mv)));
+            movegen::gen_king_captures(board, |mv| frame.push(RatedMove::capture(Piece::King, mv)));
         } else {
             // One checker, must check that the move is inside the computed mask.
             // SAFE: there is always a king on the board.
             let checker = unsafe {checkers.as_square_unchecked()};
             let mask = BitBoard::between(board.king_sq(board.get_side_to_move()), checker) | checkers;
 
-            movegen::gen_promote_captures(board, &Piece::PROMOTES, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::promote_capture(mv))});
-            movegen::gen_pawn_captures(board, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::capture(Piece::Pawn, mv))});
-            movegen::gen_en_passant(board, |mv| if mask.contains(mv.to()) {buffer.push(RatedMove::capture(Piece::Pawn, mv))});
-            movegen::gen_captures(board, |piece, mv| if mask.contains(mv.to()) {buffer.push(RatedMove::capture(piece, mv))});
-            movegen::gen_king_captures(board, |mv| buffer.push(RatedMove::capture(Piece::King, mv)));
+            movegen::gen_promote_captures(board, &Piece::PROMOTES, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::promote_capture(mv))});
+            movegen::gen_pawn_captures(board, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::capture(Piece::Pawn, mv))});
+            movegen::gen_en_passant(board, |mv| if mask.contains(mv.to()) {frame.push(RatedMove::capture(Piece::Pawn, mv))});
+            movegen::gen_captures(board, |piece, mv| if mask.contains(mv.to()) {frame.push(RatedMove::capture(piece, mv))});
+            movegen::gen_king_captures(board, |mv| frame.push(RatedMove::capture(Piece::King, mv)));
         }
 
-        buffer[usize::from(start)..].sort_unstable_by(RatedMove::pseudo_cmp);
-
-        Captures {
-            start,
-            end: buffer.len() as u16,
-        }
-    }
+        let legality = Legality::new(board);
+        frame.moves_mut().sort_unstable_by(RatedMove::pseudo_cmp);
+        frame.retain(|mv| legality.is_legal(board, mv));
 
-    #[inline]
-    pub(crate) fn next(&mut self, buffer: &mut Vec<RatedMove>) -> Option<Move> {
-        if self.start == self.end {
-            None
-        } else {
-            self.end -= 1;
-            buffer.pop().map(|rated| rated.mv)
-        }
+        Captures
     }
 
-    /// Needs to be called after all moves have been consumed from the movepicker.
+    /// Returns the next capture to try, or None if there is no more. The frame must be the one given to new().
     #[inline]
-    pub(crate) fn truncate(&self, buffer: &mut Vec<RatedMove>) {
-        // SAFE: we know the buffer had at least self.start elements already.
-        unsafe {buffer.set_len(self.start as usize)};
+    pub(crate) fn next(&mut self, frame: &mut Frame) -> Option<Move> {
+        frame.pop().map(|rated| rated.mv)
     }
-}
\ No newline at end of file
+}
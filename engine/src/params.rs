@@ -1,14 +1,287 @@
-/// The size of the transposition table in bytes. Not exact.
-pub(crate) const TABLE_SIZE: usize = 33554432;
+use std::fmt;
+use std::str::FromStr;
 
-/// The number of search threads used.
+use anyhow::{Error, Result};
+
+/// The default size of the transposition table in megabytes. Not exact.
+pub(crate) const TABLE_SIZE_MB: usize = 32;
+
+/// The size of the evaluation cache of each search thread in bytes. Not exact.
+pub(crate) const EVAL_CACHE_SIZE: usize = 1048576;
+
+/// The default number of search threads used.
 pub(crate) const NUM_SEARCH_THREAD: usize = 8;
 
-/// The aspiration window used by the engine.
-pub(crate) const ASPIRATION_WINDOW: &[f32] = &[0.01, 0.05, 2.5, f32::INFINITY];
+/// The maximum number of search threads.
+pub(crate) const MAX_SEARCH_THREAD: usize = 128;
+
+/// The maximum depth of the iterative deepening.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+/// The maximum number of plies searched from the root, extensions and quiescence search included.
+/// It is the size of the per-thread stacks of the search, and lines are cut there.
+pub(crate) const MAX_PLY: usize = 128;
+
+/// The number of plies the search is extended by in positions where the side to move is in check.
+pub(crate) const CHECK_EXTENSION: u8 = 1;
+
+/// The minimum remaining depth at which the move of the table is tested for singularity.
+pub(crate) const SINGULAR_MIN_DEPTH: u8 = 6;
+
+/// How much shallower than the node the entry of the table may be, for its move to be tested for singularity.
+pub(crate) const SINGULAR_TABLE_DEPTH: u8 = 3;
+
+/// The margin below the score of the move of the table, in pawns per ply of remaining depth,
+/// that all other moves must fail to reach for the move to be singular.
+pub(crate) const SINGULAR_MARGIN: f32 = 0.02;
+
+/// The number of plies the search of a singular move is extended by.
+pub(crate) const SINGULAR_EXTENSION: u8 = 1;
+
+/// With repetition avoidance, the fraction of the score of the previous iteration added to the contempt,
+/// so that the engine avoids draws when it is better and seeks them when it is worse.
+pub(crate) const REPETITION_AVOIDANCE_SCALE: f32 = 0.25;
+
+/// With repetition avoidance, the largest amount the contempt is biased by, in pawns.
+pub(crate) const REPETITION_AVOIDANCE_MAX: f32 = 0.5;
+
+/// The weight removed from a book entry played by the losing side of a game, when book learning is on.
+pub(crate) const BOOK_LEARNING_PENALTY: u16 = 4;
+
+/// The smallest depth of the searches whose moves are recorded in the experience of the engine.
+pub(crate) const EXPERIENCE_MIN_DEPTH: u8 = 8;
+
+/// The score, in pawns, from which the experience of the engine makes it avoid a book move:
+/// it does if the opponent stood that much better when the engine played in the position it leads to.
+pub(crate) const EXPERIENCE_BOOK_MARGIN: f32 = 0.5;
+
+/// The maximum variety level, at which the random offsets added to root moves are the largest.
+pub const MAX_VARIETY: u8 = 10;
+
+/// The amplitude of the random offsets added to the scores of root moves per variety level, in pawns.
+pub(crate) const VARIETY_NOISE: f32 = 0.005;
+
+/// The search depth up to which the random offsets of variety are full. Beyond it,
+/// they shrink in inverse proportion to the depth, so that deep searches are barely affected.
+pub(crate) const VARIETY_FULL_DEPTH: u8 = 4;
+
+/// The number of nodes searched between two checks of the search deadline. Must be a power of two.
+pub(crate) const NODES_PER_DEADLINE_CHECK: u64 = 2048;
+
+//#################################################################################################
+//
+//                                        struct TunableSpec
+//
+//#################################################################################################
+
+/// The description of a tunable parameter: its name, the range of its values,
+/// and the size of the steps taken when tuning it.
+#[derive(Clone, Copy, Debug)]
+pub struct TunableSpec {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+}
+
+//#################################################################################################
+//
+//                                         struct Tunables
+//
+//#################################################################################################
+
+/// The parameters of the search that may be adjusted at runtime, for instance by a tuner.
+/// Integer parameters are rounded when set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tunables {
+    /// The aspiration windows used by the engine, widened each time the search fails.
+    pub(crate) aspiration_window: [f32; 4],
+    /// Used during quiescient search for delta pruning.
+    pub(crate) delta: f32,
+    /// The minimum depth at which late move reductions are applied.
+    pub(crate) lmr_min_depth: u8,
+    /// The number of moves searched at full depth before late move reductions are applied.
+    pub(crate) lmr_min_moves: usize,
+    /// The base of the late move reductions, in plies.
+    pub(crate) lmr_base: f32,
+    /// The divisor of the logarithmic part of the late move reductions.
+    pub(crate) lmr_divisor: f32,
+    /// The maximum depth at which futility pruning is applied.
+    pub(crate) futility_depth: u8,
+    /// The futility margin per ply of remaining depth, in pawns.
+    pub(crate) futility_margin: f32,
+    /// The maximum depth at which reverse futility pruning is applied.
+    pub(crate) rfp_depth: u8,
+    /// The reverse futility margin per ply of remaining depth, in pawns.
+    pub(crate) rfp_margin: f32,
+}
+
+// ================================ pub impl
+
+impl Tunables {
+    /// The specifications of all tunable parameters.
+    pub const SPECS: &'static [TunableSpec] = &[
+        TunableSpec {name: "aspiration_window_0", min: 0.005, max: 0.5, step: 0.01},
+        TunableSpec {name: "aspiration_window_1", min: 0.01, max: 2.0, step: 0.05},
+        TunableSpec {name: "aspiration_window_2", min: 0.1, max: 10.0, step: 0.5},
+        TunableSpec {name: "delta", min: 0.0, max: 5.0, step: 0.25},
+        TunableSpec {name: "lmr_min_depth", min: 2.0, max: 8.0, step: 1.0},
+        TunableSpec {name: "lmr_min_moves", min: 1.0, max: 10.0, step: 1.0},
+        TunableSpec {name: "lmr_base", min: 0.0, max: 2.0, step: 0.1},
+        TunableSpec {name: "lmr_divisor", min: 1.0, max: 5.0, step: 0.2},
+        TunableSpec {name: "futility_depth", min: 0.0, max: 8.0, step: 1.0},
+        TunableSpec {name: "futility_margin", min: 0.1, max: 5.0, step: 0.1},
+        TunableSpec {name: "rfp_depth", min: 0.0, max: 10.0, step: 1.0},
+        TunableSpec {name: "rfp_margin", min: 0.1, max: 5.0, step: 0.1},
+    ];
+
+    /// Returns the specification of the parameter with the given name.
+    pub fn spec(name: &str) -> Result<&'static TunableSpec> {
+        Tunables::SPECS.iter().find(|spec| spec.name == name).ok_or_else(|| Error::msg(format!("Unknown parameter: \"{}\".", name)))
+    }
+
+    /// Returns the value of the parameter with the given name.
+    pub fn get(&self, name: &str) -> Result<f32> {
+        Ok(match name {
+            "aspiration_window_0" => self.aspiration_window[0],
+            "aspiration_window_1" => self.aspiration_window[1],
+            "aspiration_window_2" => self.aspiration_window[2],
+            "delta" => self.delta,
+            "lmr_min_depth" => f32::from(self.lmr_min_depth),
+            "lmr_min_moves" => self.lmr_min_moves as f32,
+            "lmr_base" => self.lmr_base,
+            "lmr_divisor" => self.lmr_divisor,
+            "futility_depth" => f32::from(self.futility_depth),
+            "futility_margin" => self.futility_margin,
+            "rfp_depth" => f32::from(self.rfp_depth),
+            "rfp_margin" => self.rfp_margin,
+            _ => return Err(Error::msg(format!("Unknown parameter: \"{}\".", name))),
+        })
+    }
+
+    /// Sets the value of the parameter with the given name, clamped to its range.
+    pub fn set(&mut self, name: &str, value: f32) -> Result<()> {
+        let spec = Tunables::spec(name)?;
+        let value = value.clamp(spec.min, spec.max);
+
+        match name {
+            "aspiration_window_0" => self.aspiration_window[0] = value,
+            "aspiration_window_1" => self.aspiration_window[1] = value,
+            "aspiration_window_2" => self.aspiration_window[2] = value,
+            "delta" => self.delta = value,
+            "lmr_min_depth" => self.lmr_min_depth = value.round() as u8,
+            "lmr_min_moves" => self.lmr_min_moves = value.round() as usize,
+            "lmr_base" => self.lmr_base = value,
+            "lmr_divisor" => self.lmr_divisor = value,
+            "futility_depth" => self.futility_depth = value.round() as u8,
+            "futility_margin" => self.futility_margin = value,
+            "rfp_depth" => self.rfp_depth = value.round() as u8,
+            "rfp_margin" => self.rfp_margin = value,
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+}
+
+// ================================ traits impl
+
+impl Default for Tunables {
+    /// Returns the default values of the parameters.
+    fn default() -> Tunables {
+        Tunables {
+            aspiration_window: [0.01, 0.05, 2.5, f32::INFINITY],
+            delta: 2.0,
+            lmr_min_depth: 3,
+            lmr_min_moves: 3,
+            lmr_base: 0.75,
+            lmr_divisor: 2.25,
+            futility_depth: 3,
+            futility_margin: 1.25,
+            rfp_depth: 6,
+            rfp_margin: 0.9,
+        }
+    }
+}
+
+impl fmt::Display for Tunables {
+    /// Formats the parameters, one "name = value" pair per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for spec in Tunables::SPECS {
+            writeln!(f, "{} = {}", spec.name, self.get(spec.name).unwrap())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Tunables {
+    type Err = Error;
+
+    /// Parses parameters from "name = value" pairs, one per line. Empty lines and lines
+    /// starting with '#' are ignored, and missing parameters keep their default values.
+    /// Unlike set(), values out of the range of their parameter are rejected.
+    fn from_str(s: &str) -> Result<Tunables> {
+        let mut tunables = Tunables::default();
+
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (name, value) = line.split_once('=').ok_or_else(|| Error::msg(format!("Invalid parameter line: \"{}\".", line)))?;
+            let value = f32::from_str(value.trim()).map_err(|_| Error::msg(format!("Invalid parameter value: \"{}\".", line)))?;
+            let spec = Tunables::spec(name.trim())?;
+            if !(spec.min..=spec.max).contains(&value) {
+                return Err(Error::msg(format!("Parameter value out of [{}, {}]: \"{}\".", spec.min, spec.max, line)));
+            }
+            tunables.set(name.trim(), value)?;
+        }
+
+        Ok(tunables)
+    }
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut tunables = Tunables::default();
+        assert_eq!(Tunables::from_str(&tunables.to_string()).unwrap(), tunables);
+
+        // Every parameter is set to the middle of its range, then read back, written and parsed again.
+        for spec in Tunables::SPECS {
+            tunables.set(spec.name, (spec.min + spec.max) / 2.0).unwrap();
+        }
+        assert_eq!(tunables.get("lmr_base").unwrap(), 1.0);
+        assert_eq!(tunables.get("lmr_min_depth").unwrap(), 5.0);
+        assert_eq!(tunables.get("lmr_min_moves").unwrap(), 6.0);
+        assert_eq!(Tunables::from_str(&tunables.to_string()).unwrap(), tunables);
+
+        // Missing parameters keep their default values, and setting clamps to the range.
+        let parsed = Tunables::from_str("# Comment.\n\ndelta = 1.5\n").unwrap();
+        assert_eq!(parsed.get("delta").unwrap(), 1.5);
+        assert_eq!(parsed.get("rfp_margin").unwrap(), Tunables::default().get("rfp_margin").unwrap());
+        tunables.set("delta", 100.0).unwrap();
+        assert_eq!(tunables.get("delta").unwrap(), 5.0);
+    }
 
-/// The maximum search depth.
-pub(crate) const MAX_DEPTH: usize = 32;
+    #[test]
+    fn rejections() {
+        let mut tunables = Tunables::default();
+        assert!(Tunables::spec("null_move_depth").is_err());
+        assert!(tunables.get("null_move_depth").is_err());
+        assert!(tunables.set("null_move_depth", 1.0).is_err());
+        assert_eq!(tunables, Tunables::default());
 
-/// Used during quiescient search for delta pruning.
-pub(crate) const DELTA: f32 = 2.0;
\ No newline at end of file
+        assert!(Tunables::from_str("null_move_depth = 1").is_err());
+        assert!(Tunables::from_str("delta = 5.5").is_err());
+        assert!(Tunables::from_str("lmr_divisor = 0").is_err());
+        assert!(Tunables::from_str("delta 1.5").is_err());
+        assert!(Tunables::from_str("delta = fast").is_err());
+    }
+}
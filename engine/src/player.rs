@@ -0,0 +1,137 @@
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+
+use chess::board::Board;
+use chess::moves::Move;
+
+use crate::local::LocalEngine;
+
+/// The fraction of its remaining time this engine spends on a move, when playing with a clock.
+const TIME_DIVISOR: u32 = 30;
+
+//#################################################################################################
+//
+//                                        enum SearchLimit
+//
+//#################################################################################################
+
+/// How long a player may search a position.
+#[derive(Clone, Copy, Debug)]
+pub enum SearchLimit {
+    /// A fixed duration.
+    Time(Duration),
+    /// A number of nodes, roughly.
+    Nodes(u64),
+    /// A fixed depth.
+    Depth(u8),
+    /// A clock: the remaining times of white and black, and the increment added after each move.
+    Clock {times: [Duration; 2], increment: Duration},
+}
+
+//#################################################################################################
+//
+//                                        struct PlayerMove
+//
+//#################################################################################################
+
+/// The move chosen by a player, with what its search found, when the player reports it.
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerMove {
+    /// The move to play.
+    pub mv: Move,
+    /// The score of the move, in pawns from the point of view of the side to move.
+    pub score: Option<f32>,
+    /// The depth completed by the search.
+    pub depth: Option<u8>,
+}
+
+//#################################################################################################
+//
+//                                        struct KillSwitch
+//
+//#################################################################################################
+
+/// A handle to the process of a player, killing it from another thread, such as when it stopped answering.
+#[derive(Clone, Debug)]
+pub struct KillSwitch(Arc<Mutex<Child>>);
+
+// ================================ pub impl
+
+impl KillSwitch {
+    /// Kills the process, which makes the player fail from then on.
+    pub fn kill(&self) {
+        let mut child = self.0.lock().unwrap();
+        child.kill().ok();
+        child.wait().ok();
+    }
+}
+
+// ================================ impl
+
+impl KillSwitch {
+    /// Creates the switch of the given process.
+    pub(crate) fn new(child: Arc<Mutex<Child>>) -> KillSwitch {
+        KillSwitch(child)
+    }
+}
+
+//#################################################################################################
+//
+//                                         trait Player
+//
+//#################################################################################################
+
+/// A chess player: this engine or an external one, which may replace each other wherever games are played,
+/// to compare them or to spar. Players are given the whole game at each move, as external engines expect it.
+pub trait Player: Send {
+    /// Returns the name of the player.
+    fn name(&self) -> String;
+
+    /// Prepares the player for a new game.
+    fn new_game(&mut self) -> Result<()>;
+
+    /// Searches the position reached by playing the given moves from the given start, which is given too,
+    /// within the given limit, and returns the move to play, which is legal in that position.
+    fn search(&mut self, start: &Board, moves: &[Move], board: &Board, limit: SearchLimit) -> Result<PlayerMove>;
+
+    /// Returns the switch killing the process of the player, if it runs in one.
+    fn kill_switch(&self) -> Option<KillSwitch> {
+        None
+    }
+}
+
+// ================================ traits impl
+
+impl Player for LocalEngine {
+    fn name(&self) -> String {
+        format!("Rush {}", crate::VERSION)
+    }
+
+    fn new_game(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Searches the position on the caller's thread. With a clock, a fraction of the remaining time is spent.
+    fn search(&mut self, _: &Board, _: &[Move], board: &Board, limit: SearchLimit) -> Result<PlayerMove> {
+        *self.write_board() = board.clone();
+
+        let mv = match limit {
+            SearchLimit::Time(duration) => self.think(duration),
+            SearchLimit::Nodes(nodes) => self.think_nodes(nodes).map(|(mv, _)| mv),
+            SearchLimit::Depth(depth) => self.think_depth(depth),
+            SearchLimit::Clock {times, increment} => {
+                let remaining = times[board.get_side_to_move() as usize];
+                self.think((remaining / TIME_DIVISOR + increment / 2).min(remaining / 2))
+            },
+        };
+
+        Ok(PlayerMove {
+            mv: mv.ok_or_else(|| Error::msg("The engine found no move."))?,
+            score: self.score(),
+            depth: Some(self.analysis().depth),
+        })
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use chess::board::Board;
+use chess::color::Color;
+use chess::moves::Move;
+use chess::pgn::Pgn;
+
+//#################################################################################################
+//
+//                                       struct MoveReport
+//
+//#################################################################################################
+
+/// The report of a move played on the board of the engine, with the search that chose it, if any.
+#[derive(Clone, Debug)]
+pub struct MoveReport {
+    /// The fen of the position the move was played in.
+    pub fen: String,
+    /// The move played.
+    pub mv: Move,
+    /// The move played, in standard algebraic notation.
+    pub san: String,
+    /// Whether the engine played the move from its book, without searching.
+    pub book: bool,
+    /// The depth completed by the search that chose the move, None if the engine did not search it.
+    pub depth: Option<u8>,
+    /// The score of the move, in pawns from the point of view of the side that played it, if it was searched.
+    pub score: Option<f32>,
+    /// The principal variation the search expected, starting with the move, if it was searched.
+    pub pv: Vec<Move>,
+    /// The number of nodes searched to choose the move, roughly.
+    pub nodes: u64,
+    /// The time spent searching to choose the move.
+    pub time: Duration,
+}
+
+// ================================ pub impl
+
+impl MoveReport {
+    /// Returns true if the move was chosen by the engine, from its book or by searching.
+    pub fn by_engine(&self) -> bool {
+        self.book || self.depth.is_some()
+    }
+
+    /// Returns the comment of the move in a PGN, if the engine chose it: its score, depth and
+    /// search time, such as "+0.35/12 1.20s", or "book".
+    pub fn comment(&self) -> Option<String> {
+        if self.book {
+            Some("book".to_string())
+        } else if let (Some(depth), Some(score)) = (self.depth, self.score) {
+            Some(format!("{:+.2}/{} {:.2}s", score, depth, self.time.as_secs_f64()))
+        } else {
+            None
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct GameReport
+//
+//#################################################################################################
+
+/// The report of the moves played on the board of the engine, with the searches behind the moves the engine
+/// chose, so that a game can be reviewed afterwards. It covers the moves played with Engine::advance(), since
+/// the board was last written to.
+#[derive(Clone, Debug, Default)]
+pub struct GameReport {
+    start: Option<Board>,
+    moves: Vec<MoveReport>,
+}
+
+// ================================ pub impl
+
+impl GameReport {
+    /// Returns the position the first move of the report was played in, None if no move was played.
+    pub fn get_start(&self) -> Option<&Board> {
+        self.start.as_ref()
+    }
+
+    /// Returns the reports of the moves played, in order.
+    pub fn get_moves(&self) -> &[MoveReport] {
+        &self.moves
+    }
+
+    /// Returns the report of the game as a json array, with an object per move.
+    /// Scores are in pawns and times in seconds.
+    pub fn to_json(&self) -> String {
+        let moves = self.moves.iter().map(|report| {
+            let pv = report.pv.iter().map(|mv| format!(r#""{}""#, mv)).collect::<Vec<_>>().join(",");
+
+            format!(
+                r#"{{"fen":"{}","move":"{}","san":"{}","book":{},"depth":{},"score":{},"pv":[{}],"nodes":{},"time":{}}}"#,
+                report.fen, report.mv, report.san, report.book,
+                report.depth.map_or("null".to_string(), |depth| depth.to_string()),
+                report.score.map_or("null".to_string(), |score| score.to_string()),
+                pv, report.nodes, report.time.as_secs_f64(),
+            )
+        }).collect::<Vec<_>>().join(",");
+
+        format!("[{}]", moves)
+    }
+
+    /// Returns the game as a PGN, the moves of the engine being commented as with MoveReport::comment().
+    /// Returns None if no move was played.
+    pub fn to_pgn(&self) -> Option<Pgn> {
+        let mut pgn = Pgn::new(self.start.clone()?);
+
+        for report in &self.moves {
+            pgn.push(report.mv);
+
+            if let Some(comment) = report.comment() {
+                pgn.set_comment(&comment);
+            }
+        }
+
+        Some(pgn)
+    }
+
+    /// Comments the moves of the given game the engine chose, as with MoveReport::comment(), and names the
+    /// engine as the player of their color. Only the moves the report covers, played in the same positions, are.
+    pub fn annotate(&self, pgn: &mut Pgn) {
+        let mut board = pgn.get_start().clone();
+        let moves = pgn.get_moves().to_vec();
+
+        for (i, mv) in moves.into_iter().enumerate() {
+            let fen = board.to_string();
+
+            if let Some(report) = self.moves.iter().find(|report| report.mv == mv && report.fen == fen && report.by_engine()) {
+                if let Some(comment) = report.comment() {
+                    pgn.set_comment_at(i, &comment);
+                }
+
+                let player = if board.get_side_to_move() == Color::White {"White"} else {"Black"};
+                pgn.set_tag(player, &format!("Rush {}", crate::VERSION));
+            }
+
+            board.do_move(mv);
+        }
+    }
+}
+
+// ================================ pub(crate) impl
+
+impl GameReport {
+    /// Adds the report of a move, played in the given position.
+    pub(crate) fn push(&mut self, board: &Board, report: MoveReport) {
+        if self.moves.is_empty() {
+            self.start = Some(board.clone());
+        }
+
+        self.moves.push(report);
+    }
+
+    /// Forgets the moves played after the first len ones.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.moves.truncate(len);
+
+        if self.moves.is_empty() {
+            self.start = None;
+        }
+    }
+
+    /// Forgets the moves played.
+    pub(crate) fn clear(&mut self) {
+        self.start = None;
+        self.moves.clear();
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::board::Board;
+
+    use crate::engine::Engine;
+
+    #[test]
+    fn undo_keeps_the_report() {
+        let board = Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut engine = Engine::new(board, None, concat!(env!("CARGO_MANIFEST_DIR"), "/nets/nnue.bin")).unwrap();
+
+        let moves: Vec<_> = ["e2e4", "e7e5", "g1f3"].iter().map(|s| {
+            let mv = engine.read_board().parse_move(s).unwrap();
+            engine.advance(mv);
+            mv
+        }).collect();
+        assert_eq!(engine.report().get_moves().len(), 3);
+
+        // Taking back a move only forgets its report.
+        engine.undo_move(moves[2]);
+        let report = engine.report();
+        assert_eq!(report.get_moves().iter().map(|report| report.mv).collect::<Vec<_>>(), &moves[..2]);
+        assert_eq!(report.to_pgn().unwrap().get_board().to_string(), engine.read_board().to_string());
+
+        engine.undo_move(moves[1]);
+        engine.undo_move(moves[0]);
+        assert!(engine.report().get_moves().is_empty());
+        assert!(engine.report().get_start().is_none());
+
+        // Writing to the board still clears the report.
+        engine.advance(moves[0]);
+        drop(engine.write_board());
+        assert!(engine.report().get_moves().is_empty());
+    }
+}
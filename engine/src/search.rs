@@ -4,26 +4,44 @@ use chess::board::Board;
 use chess::moves::Move;
 use chess::piece::Piece;
 
+use crate::cache::EvalCache;
 use crate::engine::GlobalInfo;
 use crate::eval::{Eval, Net};
 use crate::heuristics::Heuristics;
 use crate::{eval, utils};
-use crate::movepick::{Captures, MovePicker, RatedMove};
-use crate::params;
+use crate::utils::Stopwatch;
+use crate::movepick::{Captures, MovePicker};
+use crate::params::{self, Tunables};
+use crate::split::SmpMode;
+use crate::stack::SearchStack;
 use crate::table::{TableEntry, TableEntryFlag};
 
+/// The maximum number of plies the game may have advanced from the previously
+/// searched position for the move ordering heuristics to be kept.
+const MAX_ADVANCE: usize = 4;
+
 /// A struct holding all the necessary information for a search thread.
 #[derive(Debug)]
 pub(crate) struct Search {
     eval: Eval,
+    cache: EvalCache,
     board: Board,
     heuristics: Heuristics,
+    tunables: Tunables,
+    contempt: f32,
+    avoid_repetitions: bool,
 
-    buffer: Vec<RatedMove>,
+    stack: SearchStack,
     best_move: Option<Move>,
+    root_lines: Vec<(Move, f32)>,
+    root_moves: Vec<Move>,
     
     info: Arc<GlobalInfo>,
+    id: usize,
+    generation: u16,
     depth: u8,
+    seldepth: u8,
+    nodes: u64,
     seed: u32,
 }
 
@@ -31,18 +49,29 @@ pub(crate) struct Search {
 
 impl Search {
     /// Creates a new search struct, ready to bes used for searching the game tree.
-    pub(crate) fn new(seed: u32, info: Arc<GlobalInfo>, net: Arc<Net>) -> Search {
+    /// The id of the thread is used to derive its pseudo-random seed.
+    pub(crate) fn new(id: usize, info: Arc<GlobalInfo>, net: Arc<Net>) -> Search {
         Search {
             board: Board::default(),
             heuristics: Heuristics::default(),
+            tunables: Tunables::default(),
+            contempt: 0.0,
+            avoid_repetitions: false,
             eval: Eval::new(net),
+            cache: EvalCache::new(),
 
-            buffer: Vec::new(),
+            stack: SearchStack::new(),
             best_move: None,
+            root_lines: Vec::new(),
+            root_moves: Vec::new(),
             
+            seed: info.thread_seed(id),
             info,
+            id,
+            generation: 0,
             depth: 0,
-            seed,
+            seldepth: 0,
+            nodes: 0,
         }
     }
 
@@ -57,55 +86,111 @@ impl Search {
                 return;
             }
     
-            // Search the position while the flag is on.
-            self.search_position();
+            // Search the position while the flag is on. In deterministic mode, only the first thread searches.
+            if self.id == 0 || !self.info.is_deterministic() {
+                self.search_position();
+            }
+            self.info.thread_done();
     
             // The end search barrier.
             self.info.wait();
         }
     }
-}
 
-// ================================ impl
+    /// Search the position until told to stop, or until the maximum depth is reached.
+    pub(crate) fn search_position(&mut self) {
+        let start = Stopwatch::start();
 
-impl Search {
-    /// Resets what needs to be after a new position is encountered.
-    fn reset(&mut self) {
-        self.best_move = None;
-        self.heuristics = Heuristics::default();
-        self.eval.reset(&self.board);
-    }
-
-    /// Search the position until told to stop.
-    fn search_position(&mut self) {
-        // Clone global board and get search depth.
-        const MAX_IDX: usize = params::ASPIRATION_WINDOW.len() - 1;
+        // Get the search parameters.
+        self.tunables = self.info.tunables();
+        self.contempt = self.info.contempt();
+        self.avoid_repetitions = self.info.avoids_repetitions();
+        self.seed = self.info.thread_seed(self.id);
+        self.generation = self.info.generation();
+        self.root_moves = self.info.root_moves();
+        self.seldepth = 0;
         
-        { // Update the board.
-            let ply = self.board.get_ply();
+        { // Update the board. When the game merely advanced from the previous position,
+          // the move ordering heuristics are kept.
+            let (ply, zobrist) = (self.board.get_ply(), self.board.get_zobrist());
             self.board = self.info.board();
-            if self.board.get_ply() != ply {
+            let plies = usize::from(self.board.get_ply().wrapping_sub(ply));
+
+            if plies != 0 && plies <= MAX_ADVANCE && self.board.get_previous_zobrist(plies) == Some(zobrist) {
+                self.advance(plies);
+            } else if plies != 0 || self.board.get_zobrist() != zobrist {
                 self.reset();
+            } else {
+                // The same position is searched again: its histories are merely aged.
+                self.heuristics.age();
             }
         }
         
+        // The evaluations cached in another mode must not be used.
+        let eval_mode = self.info.eval_mode();
+        if eval_mode != self.eval.get_mode() {
+            self.eval.set_mode(eval_mode, &self.board);
+            self.cache.clear();
+        }
+
+        // The best move of a previous search must not be reported if it is excluded from this one.
+        self.best_move = self.best_move.filter(|&mv| self.is_root_move(mv));
+
+        match self.info.smp_mode() {
+            SmpMode::DepthOffset => self.search_depth_offset(),
+            SmpMode::RootSplit => self.search_root_split(),
+        }
+
+        self.report_stats();
+
+        // The first thread, which searches in every mode, accounts for the search.
+        if self.id == 0 {
+            self.info.add_search_stats(start.elapsed(), self.info.search_depth());
+        }
+    }
+
+    /// Returns the number of nodes searched by this thread since its creation.
+    pub(crate) fn nodes(&self) -> u64 {
+        self.nodes
+    }
+}
+
+// ================================ impl
+
+impl Search {
+    /// Iteratively deepens the search, at depths offset from those of the other threads.
+    fn search_depth_offset(&mut self) {
+        // The index of the widest aspiration window.
+        let max_idx = self.tunables.aspiration_window.len() - 1;
+
         // Compute first reference score.
         let best_score = self.quiescence(f32::NEG_INFINITY, f32::INFINITY);
         
         'main: loop {
             // Get the depth this thread needs to search to.
             let search_depth = self.info.thread_search_depth();
+
+            // Do not search deeper than the maximum depth allowed.
+            if search_depth > self.info.max_depth() {
+                break;
+            }
+
+            self.update_contempt();
             
             // Get the first values of alpha and beta in the aspiration window.
-            let mut alpha = best_score - params::ASPIRATION_WINDOW[0];
-            let mut beta = best_score + params::ASPIRATION_WINDOW[0];
+            // When searching multiple lines, the window is left open so that their scores are exact.
+            let (mut alpha, mut beta) = if self.info.multipv() > 1 {
+                (f32::NEG_INFINITY, f32::INFINITY)
+            } else {
+                (best_score - self.tunables.aspiration_window[0], best_score + self.tunables.aspiration_window[0])
+            };
             
             let (mut alpha_idx, mut beta_idx) = (0, 0);
             
             loop {
                 let best_score = self.alpha_beta(alpha, beta, true, search_depth, search_depth);
                 
-                if !self.info.is_searching() {
+                if !self.info.keep_searching() {
                     break 'main;
                 }
                 
@@ -114,43 +199,229 @@ impl Search {
                 }
                 
                 if best_score <= alpha {
-                    alpha_idx = MAX_IDX.min(alpha_idx + 1);
-                    alpha = best_score - params::ASPIRATION_WINDOW[alpha_idx];
+                    alpha_idx = max_idx.min(alpha_idx + 1);
+                    alpha = best_score - self.tunables.aspiration_window[alpha_idx];
                 } else if best_score >= beta {
-                    beta_idx = MAX_IDX.min(beta_idx + 1);
-                    beta = best_score + params::ASPIRATION_WINDOW[beta_idx];
+                    beta_idx = max_idx.min(beta_idx + 1);
+                    beta = best_score + self.tunables.aspiration_window[beta_idx];
                 } else {
                     break;
                 }
             }
             
             if let Some(mv) = self.best_move {
-                self.info.report_move(mv, search_depth);
+                self.root_lines.sort_by(|(_, score1), (_, score2)| score2.total_cmp(score1));
+                self.root_lines.truncate(self.info.multipv());
+                self.info.report_move(mv, &self.root_lines, search_depth);
+            }
+        }
+    }
+
+    /// Iteratively deepens the search, searching the root moves of each depth together with the other threads.
+    fn search_root_split(&mut self) {
+        loop {
+            // All threads search the depth following the last one completed.
+            let search_depth = self.info.search_depth() + 1;
+
+            // Do not search deeper than the maximum depth allowed.
+            if search_depth > self.info.max_depth() {
+                break;
+            }
+
+            self.update_contempt();
+
+            let table_move = self.info.get_table().get_move(self.board.get_zobrist());
+            let taken = self.info.root_queue().take(&self.board, &self.root_moves, table_move, search_depth);
+
+            // The last moves of that depth are being reported: wait for the next depth.
+            let Some((i, mv, alpha)) = taken else {
+                if !self.info.keep_searching() {
+                    break;
+                }
+                std::thread::yield_now();
+                continue;
+            };
+
+            let score = self.search_root_move(mv, alpha, search_depth);
+
+            if !self.info.keep_searching() {
+                break;
+            }
+
+            // Another thread searched the move first, and completed the depth.
+            if self.info.search_depth() >= search_depth {
+                continue;
+            }
+
+            let multipv = self.info.multipv();
+            let lines = self.info.root_queue().report(search_depth, i, score, alpha, multipv);
+
+            if let Some(mut lines) = lines {
+                lines.truncate(multipv);
+                if let Some(&(best_move, _)) = lines.first() {
+                    self.info.report_move(best_move, &lines, search_depth);
+                }
             }
         }
     }
+
+    /// Searches a single root move to the given depth, with an open window above alpha.
+    fn search_root_move(&mut self, mv: Move, alpha: f32, search_depth: u8) -> f32 {
+        self.count_node();
+
+        // Weakens or varies the engine by shifting the scores of root moves by random offsets.
+        let offset = self.info.root_noise(mv, search_depth);
+
+        let piece = self.board.get_piece_unchecked(mv.from());
+        self.heuristics.set_played(self.depth, Some((piece, mv)));
+
+        self.depth += 1;
+        self.eval.do_move(&mut self.board, mv);
+        let score = offset - self.alpha_beta(f32::NEG_INFINITY, offset - alpha, true, search_depth - 1, search_depth);
+        self.eval.undo_move(&mut self.board, mv);
+        self.depth -= 1;
+
+        score
+    }
+
+    /// Stores the result of the search of the current position in the transposition table. The results of the root
+    /// are not stored while its moves are restricted, as they would be wrong for later searches of all the moves.
+    #[inline]
+    fn store(&self, depth: u8, mv: Move, score: f32, flag: TableEntryFlag) {
+        if self.depth == 0 && !self.root_moves.is_empty() {
+            return;
+        }
+
+        self.info.get_table().insert(TableEntry::new(&self.board, self.generation, mv, score, depth, flag));
+    }
+
+    /// Returns true if the move may be searched at the root: all moves may, unless the root moves are restricted.
+    #[inline]
+    fn is_root_move(&self, mv: Move) -> bool {
+        self.root_moves.is_empty() || self.root_moves.contains(&mv)
+    }
+
+    /// Resets what needs to be after a new position is encountered.
+    fn reset(&mut self) {
+        self.best_move = None;
+        self.heuristics.clear();
+        self.eval.reset(&self.board);
+    }
+
+    /// Updates what needs to be after the game advanced by the given number of plies
+    /// from the previously searched position.
+    fn advance(&mut self, plies: usize) {
+        self.best_move = None;
+        self.heuristics.advance(plies);
+        self.eval.reset(&self.board);
+    }
+
+    /// Returns the value of a draw for the side to move. The contempt is that
+    /// of the side to move at the root, its opponent being happy with a draw.
+    #[inline]
+    fn draw_value(&mut self) -> f32 {
+        let contempt = if self.depth.is_multiple_of(2) {self.contempt} else {-self.contempt};
+
+        // With repetition avoidance, the contempt already tells draws apart from the other lines.
+        if self.avoid_repetitions {
+            -contempt
+        } else {
+            utils::draw_value(contempt, &mut self.seed)
+        }
+    }
+
+    /// With repetition avoidance, biases the contempt of the engine by the score of the best line found so far,
+    /// so that draws are worth less than the position when it is better, and more when it is worse.
+    #[inline]
+    fn update_contempt(&mut self) {
+        if self.avoid_repetitions {
+            let score = self.info.get_best_score().unwrap_or(0.0);
+            let bias = (score * params::REPETITION_AVOIDANCE_SCALE).clamp(-params::REPETITION_AVOIDANCE_MAX, params::REPETITION_AVOIDANCE_MAX);
+            self.contempt = self.info.contempt() + bias;
+        }
+    }
+
+    /// Counts a new node, periodically checking whether a limit of the search was reached.
+    #[inline]
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(self.depth);
+        if self.nodes & (params::NODES_PER_DEADLINE_CHECK - 1) == 0 {
+            self.info.check_limits(params::NODES_PER_DEADLINE_CHECK);
+            self.report_stats();
+        }
+    }
+
+    /// Reports the statistics of the evaluation cache gathered since the last report,
+    /// and the selective depth reached so far.
+    #[inline]
+    fn report_stats(&mut self) {
+        let (probes, hits) = self.cache.take_stats();
+        self.info.add_eval_stats(probes, hits);
+        self.info.report_seldepth(self.seldepth);
+    }
+
+    /// Evaluates the position for the side to move, skipping the forward pass
+    /// of the network when the position is found in the evaluation cache.
+    #[inline]
+    fn evaluate(&mut self) -> f32 {
+        let zobrist = self.board.get_zobrist();
+
+        self.cache.probe(zobrist).unwrap_or_else(|| {
+            let eval = self.eval.evaluate(&self.board);
+            self.cache.insert(zobrist, eval);
+            eval
+        })
+    }
     
     /// The alpha-beta negamax algorithm, with a few more heuristics in it.
     fn alpha_beta(&mut self, mut alpha: f32, beta: f32, do_null: bool, mut depth: u8, search_depth: u8) -> f32 {                              
         if depth == 0 {
             return self.quiescence(alpha, beta);
         }
+
+        self.count_node();
         
-        if utils::is_pseudo_draw(&self.board, alpha, self.depth == 0) {
-            alpha = utils::prng_draw_value(&mut self.seed);
-            if alpha >= beta {
-                return alpha;
+        if self.depth != 0 {
+            if utils::is_draw(&self.board, self.depth) {
+                return self.draw_value();
+            }
+
+            // The rules of the variant may end the game, the opponent having won with its last move.
+            if self.board.get_variant().winner(&self.board).is_some() {
+                return -eval::value_of(Piece::King) + self.depth as f32;
+            }
+
+            // A repetition may be forced: the position is worth at least a draw.
+            let draw_value = self.draw_value();
+            if alpha < draw_value && self.board.test_upcoming_repetition() {
+                alpha = draw_value;
+                if alpha >= beta {
+                    return alpha;
+                }
             }
         }
         
-        if self.depth == params::MAX_DEPTH as u8 {
-            return self.eval.get(self.board.get_side_to_move());
+        // The line is too long, it is cut there.
+        if usize::from(self.depth) >= params::MAX_PLY {
+            return self.evaluate();
+        }
+
+        // When searching multiple lines, all root moves must be searched.
+        let multipv = if self.depth == 0 {self.info.multipv()} else {1};
+        if self.depth == 0 {
+            self.root_lines.clear();
         }
         
-        if let Some((mv, score)) = self.info.get_table().probe(self.board.get_zobrist(), alpha, beta, depth) {
+        // At the root, the move of the table may be excluded from the search.
+        let probe = self.info.get_table().probe(self.board.get_zobrist(), alpha, beta, depth)
+            .filter(|&(mv, _)| multipv == 1 && (self.depth != 0 || self.is_root_move(mv)));
+
+        if let Some((mv, score)) = probe {
             if self.board.is_pseudo_legal(mv) && self.board.is_legal(mv) {
                 if score >= alpha && self.depth == 0 {
                     self.best_move = Some(mv);
+                    self.root_lines.push((mv, score));
                 }
                 return score;
             }
@@ -158,11 +429,31 @@ impl Search {
         
         let old_alpha = alpha;
         let in_check = self.board.get_checkers().not_empty();
+
+        // The static evaluation of the position, used for pruning at shallow depths outside of the root.
+        let static_eval = if in_check || self.depth == 0 || depth > self.tunables.rfp_depth.max(self.tunables.futility_depth) {
+            None
+        } else {
+            Some(self.evaluate())
+        };
+
+        // Reverse futility pruning: the position is so good that it will most likely fail high.
+        if let Some(static_eval) = static_eval {
+            if depth <= self.tunables.rfp_depth && !utils::is_mate_score(beta) && static_eval - self.tunables.rfp_margin * f32::from(depth) >= beta {
+                return static_eval;
+            }
+        }
+
+        // Futility pruning: the position is so bad that quiet moves are unlikely to raise alpha.
+        let futile = static_eval.is_some_and(|static_eval| {
+            depth <= self.tunables.futility_depth && !utils::is_mate_score(alpha) && static_eval + self.tunables.futility_margin * f32::from(depth) <= alpha
+        });
         
         if in_check {
-            depth += 1;
+            depth += params::CHECK_EXTENSION;
         } else if do_null && self.depth > 0 && depth >= 4 && beta.is_finite() {
             if !utils::is_endgame(&self.board) {
+                self.heuristics.set_played(self.depth, None);
                 self.depth += 1;
                 self.board.do_null();
                 let null_score = -self.alpha_beta(-beta, -beta + 0.01, false, depth - 4, search_depth);
@@ -175,26 +466,75 @@ impl Search {
             }
         }
     
+        // Singular extensions: when the move of the table is much better than all the others,
+        // the line is forced and the move is searched deeper.
+        let singular_move = if self.depth > 0 && depth >= params::SINGULAR_MIN_DEPTH {
+            self.info.get_table().get_lower_bound(self.board.get_zobrist(), depth - params::SINGULAR_TABLE_DEPTH)
+                .filter(|&(mv, score)| !utils::is_mate_score(score) && self.board.is_pseudo_legal(mv) && self.board.is_legal(mv))
+                .filter(|&(mv, score)| self.is_singular(mv, score - params::SINGULAR_MARGIN * f32::from(depth), depth / 2, search_depth))
+                .map(|(mv, _)| mv)
+        } else {
+            None
+        };
+
         let mut best_score = f32::NEG_INFINITY;
         let mut best_move = None;
-        let mut picker = MovePicker::new(&self.board, &self.buffer);
+        let hash_move = self.info.get_table().get_move(self.board.get_zobrist());
+        let mut picker = MovePicker::new(&self.board, self.stack.frame(self.depth), hash_move);
         let mut move_count = 0;
     
-        while let Some(mv) = picker.next(&self.board, &self.heuristics, self.depth, &mut self.buffer) {
-            if !self.board.is_legal(mv) {
+        while let Some(mv) = picker.next(&self.board, &self.heuristics, self.depth, self.stack.frame(self.depth)) {
+            if self.depth == 0 && !self.is_root_move(mv) {
                 continue;
             }
 
+            // Quiet moves that do not give check are the ones that may be pruned or reduced.
+            let quiet = !in_check && !mv.is_capture() && !mv.is_promote() && !self.board.gives_check(mv);
+
+            if futile && quiet && move_count > 0 {
+                continue;
+            }
+
+            // Late move reductions: quiet moves ordered late are searched at a reduced depth first.
+            let reduction = if quiet && depth >= self.tunables.lmr_min_depth && move_count >= self.tunables.lmr_min_moves {
+                let reduction = self.tunables.lmr_base + f32::from(depth).ln() * (move_count as f32).ln() / self.tunables.lmr_divisor;
+                (reduction as u8).min(depth - 2)
+            } else {
+                0
+            };
+
+            // Weakens or varies the engine by shifting the scores of root moves by random offsets.
+            // The search window is shifted accordingly to keep the bounds meaningful.
+            let offset = if self.depth == 0 {self.info.root_noise(mv, search_depth)} else {0.0};
+
+            // When searching multiple lines, alpha is not raised at the root so that all scores are exact.
+            let window_alpha = if multipv > 1 {old_alpha} else {alpha};
+
+            let extension = if singular_move == Some(mv) {params::SINGULAR_EXTENSION} else {0};
+
+            let piece = self.board.get_piece_unchecked(mv.from());
+            self.heuristics.set_played(self.depth, Some((piece, mv)));
+
             self.depth += 1;
             self.eval.do_move(&mut self.board, mv);
-            let score = -self.alpha_beta(-beta, -alpha, do_null, depth-1, search_depth);
+            let mut score = offset - self.alpha_beta(offset - beta, offset - window_alpha, do_null, depth+extension-1-reduction, search_depth);
+
+            // The reduced search failed high: search the move again at full depth.
+            if reduction > 0 && score > window_alpha {
+                score = offset - self.alpha_beta(offset - beta, offset - window_alpha, do_null, depth+extension-1, search_depth);
+            }
+
             self.eval.undo_move(&mut self.board, mv);
             self.depth -= 1;
 
-            if self.info.search_depth() >= search_depth || !self.info.is_searching() {
-                picker.truncate(&mut self.buffer);
+            if self.info.search_depth() >= search_depth || !self.info.keep_searching() {
                 return 0.0;
             }
+
+            // Remembers the root moves whose score is exact.
+            if self.depth == 0 && score > window_alpha && score < beta {
+                self.root_lines.push((mv, score));
+            }
     
             if score > best_score {
                 best_score = score;
@@ -204,17 +544,11 @@ impl Search {
                     if score >= beta {
                         if !mv.is_capture() {
                             self.heuristics.store_killer(mv, self.depth);
+                            self.heuristics.update_cutoff(piece, mv, self.depth, depth);
                         }
 
-                        self.info.get_table().insert(TableEntry::new(
-                            &self.board,
-                            mv, 
-                            beta,
-                            depth, 
-                            TableEntryFlag::Beta
-                        ));
+                        self.store(depth, mv, beta, TableEntryFlag::Beta);
                         
-                        picker.truncate(&mut self.buffer);
                         return beta;
                     }
 
@@ -228,8 +562,6 @@ impl Search {
             
             move_count += 1;
         }
-
-        picker.truncate(&mut self.buffer);
         
         if move_count == 0 {
             return if in_check {
@@ -240,42 +572,73 @@ impl Search {
         }
         
         if alpha != old_alpha {
-            self.info.get_table().insert(TableEntry::new(
-                &self.board,
-                best_move.unwrap(), 
-                best_score, 
-                depth, 
-                TableEntryFlag::Exact
-            ));
+            self.store(depth, best_move.unwrap(), best_score, TableEntryFlag::Exact);
             
             if self.depth == 0 {
                 self.best_move = best_move;
             }
         } else {
-            self.info.get_table().insert(TableEntry::new(
-                &self.board,
-                best_move.unwrap(), 
-                best_score, 
-                depth, 
-                TableEntryFlag::Alpha
-            ));
+            self.store(depth, best_move.unwrap(), best_score, TableEntryFlag::Alpha);
         }
         
         alpha
     }
 
+    /// Returns true if the given move is singular: all the other moves fail low against beta
+    /// when searched at the given reduced depth, so that it is most likely forced.
+    fn is_singular(&mut self, singular: Move, beta: f32, depth: u8, search_depth: u8) -> bool {
+        let mut picker = MovePicker::new(&self.board, self.stack.frame(self.depth), None);
+
+        while let Some(mv) = picker.next(&self.board, &self.heuristics, self.depth, self.stack.frame(self.depth)) {
+            if mv == singular {
+                continue;
+            }
+
+            let piece = self.board.get_piece_unchecked(mv.from());
+            self.heuristics.set_played(self.depth, Some((piece, mv)));
+
+            self.depth += 1;
+            self.eval.do_move(&mut self.board, mv);
+            let score = -self.alpha_beta(-beta, -beta + 0.01, true, depth - 1, search_depth);
+            self.eval.undo_move(&mut self.board, mv);
+            self.depth -= 1;
+
+            // Another move reaches beta, or the search was stopped.
+            if score >= beta || self.info.search_depth() >= search_depth || !self.info.keep_searching() {
+                return false;
+            }
+        }
+
+        true
+    }
+
     /// Return the value of the position, computed with a quiescent search (only considering captures).
     fn quiescence(&mut self, mut alpha: f32, beta: f32) -> f32 {
-        if utils::is_pseudo_draw(&self.board, alpha, self.depth == 0) {
-            alpha = utils::prng_draw_value(&mut self.seed);
-            if alpha >= beta {
-                return alpha;
+        self.count_node();
+
+        if self.depth != 0 {
+            if utils::is_draw(&self.board, self.depth) {
+                return self.draw_value();
+            }
+
+            // The rules of the variant may end the game, the opponent having won with its last move.
+            if self.board.get_variant().winner(&self.board).is_some() {
+                return -eval::value_of(Piece::King) + self.depth as f32;
+            }
+
+            // A repetition may be forced: the position is worth at least a draw.
+            let draw_value = self.draw_value();
+            if alpha < draw_value && self.board.test_upcoming_repetition() {
+                alpha = draw_value;
+                if alpha >= beta {
+                    return alpha;
+                }
             }
         }
         
-        let stand_pat = self.eval.get(self.board.get_side_to_move());
+        let stand_pat = self.evaluate();
     
-        if self.depth == params::MAX_DEPTH as u8 {
+        if usize::from(self.depth) >= params::MAX_PLY {
             return stand_pat;
         }
     
@@ -294,10 +657,10 @@ impl Search {
     
         alpha = alpha.max(stand_pat);
     
-        let mut captures = Captures::new(&self.board, &mut self.buffer);
+        let mut captures = Captures::new(&self.board, self.stack.frame(self.depth));
     
-        while let Some(mv) = captures.next(&mut self.buffer) {
-            if eval::value_of(mv.get_capture()) + params::DELTA < alpha || !self.board.is_legal(mv) {
+        while let Some(mv) = captures.next(self.stack.frame(self.depth)) {
+            if eval::value_of(mv.get_capture()) + self.tunables.delta < alpha {
                 continue;
             }
     
@@ -307,21 +670,17 @@ impl Search {
             self.eval.undo_move(&mut self.board, mv);
             self.depth -= 1;
     
-            if !self.info.is_searching() {
-                captures.truncate(&mut self.buffer);
+            if !self.info.keep_searching() {
                 return 0.0;
             }
     
             if score > alpha {
                 if score >= beta {
-                    captures.truncate(&mut self.buffer);
                     return beta;
                 }
                 alpha = score;
             }
         }
-
-        captures.truncate(&mut self.buffer);
         
         alpha
     }
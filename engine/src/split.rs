@@ -0,0 +1,144 @@
+use chess::board::Board;
+use chess::movegen;
+use chess::moves::Move;
+
+//#################################################################################################
+//
+//                                         enum SmpMode
+//
+//#################################################################################################
+
+/// The way the search threads of the engine share the work of searching a position.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmpMode {
+    /// Every thread searches the whole tree, at depths offset from one thread to another,
+    /// sharing their results through the transposition table. This is the default.
+    #[default]
+    DepthOffset = 0,
+    /// The threads search the same depth together, each taking root moves from a shared queue
+    /// until all of them are searched. Idle threads help with the moves still being searched.
+    RootSplit = 1,
+}
+
+// ================================ pub(crate) impl
+
+impl SmpMode {
+    /// Returns the mode corresponding to the given discriminant.
+    #[inline]
+    pub(crate) fn from_u8(mode: u8) -> SmpMode {
+        match mode {
+            1 => SmpMode::RootSplit,
+            _ => SmpMode::DepthOffset,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct RootQueue
+//
+//#################################################################################################
+
+/// The root moves of the depth being searched in the root splitting mode, shared between the
+/// search threads. Moves are handed out in order, the best ones of the previous depth first.
+#[derive(Debug, Default)]
+pub(crate) struct RootQueue {
+    depth: u8,
+    moves: Vec<Move>,
+    scores: Vec<Option<(f32, bool)>>,
+    next: usize,
+    alpha: f32,
+}
+
+// ================================ pub(crate) impl
+
+impl RootQueue {
+    /// Empties the queue, before searching a new position.
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.depth = 0;
+        self.moves.clear();
+        self.scores.clear();
+        self.next = 0;
+    }
+
+    /// Takes a root move to search at the given depth, filling the queue for that depth first if needed,
+    /// with the given root moves or all legal moves if there are none.
+    /// Returns the index of the move, the move, and the best exact score found at that depth so far.
+    /// Once all the moves were handed out, returns the first move still being searched, if any.
+    pub(crate) fn take(&mut self, board: &Board, root_moves: &[Move], table_move: Option<Move>, depth: u8) -> Option<(usize, Move, f32)> {
+        if self.depth != depth {
+            self.fill(board, root_moves, table_move, depth);
+        }
+
+        let i = if self.next < self.moves.len() {
+            self.next += 1;
+            self.next - 1
+        } else {
+            self.scores.iter().position(Option::is_none)?
+        };
+
+        Some((i, self.moves[i], self.alpha))
+    }
+
+    /// Reports the score of the i-th root move searched at the given depth with the given alpha, where
+    /// the score is exact if it is above alpha. When it was the last move left, returns the root moves
+    /// whose score is exact, best first. Scores of moves searched by several threads are reported once.
+    pub(crate) fn report(&mut self, depth: u8, i: usize, score: f32, alpha: f32, multipv: usize) -> Option<Vec<(Move, f32)>> {
+        if self.depth != depth || self.scores[i].is_some() {
+            return None;
+        }
+
+        let exact = score > alpha;
+        self.scores[i] = Some((score, exact));
+
+        // When searching multiple lines, alpha is not raised so that all scores are exact.
+        if exact && multipv == 1 {
+            self.alpha = self.alpha.max(score);
+        }
+
+        if self.scores.iter().any(Option::is_none) {
+            return None;
+        }
+
+        let mut lines: Vec<_> = self.moves.iter().zip(&self.scores).filter_map(|(&mv, score)| {
+            score.filter(|&(_, exact)| exact).map(|(score, _)| (mv, score))
+        }).collect();
+        lines.sort_by(|(_, score1), (_, score2)| score2.total_cmp(score1));
+
+        Some(lines)
+    }
+}
+
+// ================================ impl
+
+impl RootQueue {
+    /// Fills the queue with the root moves to search at the given depth. When following the previous
+    /// depth, they are ordered by their previous scores, otherwise the move of the table comes first.
+    fn fill(&mut self, board: &Board, root_moves: &[Move], table_move: Option<Move>, depth: u8) {
+        let complete = !self.moves.is_empty() && self.scores.iter().all(Option::is_some);
+
+        if complete && depth == self.depth + 1 {
+            let mut moves: Vec<_> = self.moves.iter().copied().zip(self.scores.iter().map(|score| score.unwrap())).collect();
+            moves.sort_by(|(_, (score1, exact1)), (_, (score2, exact2))| exact2.cmp(exact1).then(score2.total_cmp(score1)));
+            self.moves = moves.into_iter().map(|(mv, _)| mv).collect();
+        } else {
+            self.moves.clear();
+            if root_moves.is_empty() {
+                movegen::legals(board, &mut self.moves);
+            } else {
+                self.moves.extend_from_slice(root_moves);
+            }
+
+            if let Some(i) = table_move.and_then(|mv| self.moves.iter().position(|&other| other == mv)) {
+                self.moves[..=i].rotate_right(1);
+            }
+        }
+
+        self.depth = depth;
+        self.scores = vec![None; self.moves.len()];
+        self.next = 0;
+        self.alpha = f32::NEG_INFINITY;
+    }
+}
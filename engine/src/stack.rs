@@ -0,0 +1,120 @@
+use chess::moves::Move;
+use chess::square::Square;
+
+use crate::movepick::RatedMove;
+use crate::params;
+
+/// The number of moves a frame can hold, more than the number of pseudo-legal moves of any position.
+pub(crate) const FRAME_SIZE: usize = 256;
+
+//#################################################################################################
+//
+//                                          struct Frame
+//
+//#################################################################################################
+
+/// The moves generated at a ply of the search, waiting to be tried.
+#[derive(Clone, Debug)]
+pub(crate) struct Frame {
+    moves: [RatedMove; FRAME_SIZE],
+    len: usize,
+}
+
+// ================================ pub(crate) impl
+
+impl Frame {
+    /// Forgets all the moves of the frame.
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns true if the frame holds no move.
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a move to the frame.
+    #[inline]
+    pub(crate) fn push(&mut self, rated: RatedMove) {
+        self.moves[self.len] = rated;
+        self.len += 1;
+    }
+
+    /// Removes the last move of the frame and returns it.
+    #[inline]
+    pub(crate) fn pop(&mut self) -> Option<RatedMove> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.moves[self.len])
+    }
+
+    /// Removes the move at the given index, replacing it by the last one.
+    #[inline]
+    pub(crate) fn swap_remove(&mut self, i: usize) {
+        self.len -= 1;
+        self.moves[i] = self.moves[self.len];
+    }
+
+    /// Keeps only the moves of the frame for which the given predicate is true, in order.
+    #[inline]
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(Move) -> bool) {
+        let len = self.len;
+        self.len = 0;
+
+        for i in 0..len {
+            let rated = self.moves[i];
+            if keep(rated.mv) {
+                self.push(rated);
+            }
+        }
+    }
+
+    /// Returns the moves of the frame.
+    #[inline]
+    pub(crate) fn moves(&self) -> &[RatedMove] {
+        &self.moves[..self.len]
+    }
+
+    /// Returns the moves of the frame, mutably.
+    #[inline]
+    pub(crate) fn moves_mut(&mut self) -> &mut [RatedMove] {
+        &mut self.moves[..self.len]
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct SearchStack
+//
+//#################################################################################################
+
+/// The frames of a search thread, one per ply from the root. Each node generates its moves in the
+/// frame of its ply, which the nodes below it never touch. Allocated on the heap.
+#[derive(Debug)]
+pub(crate) struct SearchStack {
+    frames: Box<[Frame]>,
+}
+
+// ================================ pub(crate) impl
+
+impl SearchStack {
+    /// Allocates a new stack, with a frame for each of the plies of the search.
+    pub(crate) fn new() -> SearchStack {
+        let empty = Frame {
+            // The moves of an empty frame are never read.
+            moves: [RatedMove {mv: Move::quiet(Square::A1, Square::A2), score: 0.0}; FRAME_SIZE],
+            len: 0,
+        };
+
+        SearchStack {
+            frames: vec![empty; params::MAX_PLY].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the frame of the given ply.
+    #[inline]
+    pub(crate) fn frame(&mut self, ply: u8) -> &mut Frame {
+        &mut self.frames[usize::from(ply)]
+    }
+}
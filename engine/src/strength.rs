@@ -0,0 +1,56 @@
+use crate::params;
+
+/// The maximum strength level, at which the engine plays at full strength.
+pub const MAX_STRENGTH: u8 = 20;
+
+//#################################################################################################
+//
+//                                        struct Strength
+//
+//#################################################################################################
+
+/// The limitations applied to the engine to weaken it, derived from a strength level.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Strength {
+    /// The maximum depth searched.
+    pub(crate) max_depth: u8,
+    /// The maximum number of nodes searched, roughly.
+    pub(crate) max_nodes: u64,
+    /// The amplitude of the random noise added to the scores of root moves, in pawns.
+    pub(crate) noise: f32,
+    /// The probability, in percents, of playing a random move instead of the best one.
+    pub(crate) blunder_chance: u32,
+}
+
+// ================================ pub(crate) impl
+
+impl Strength {
+    /// Returns the limitations associated with a strength level, between 0 and MAX_STRENGTH.
+    /// Greater levels are treated as MAX_STRENGTH.
+    pub(crate) fn new(level: u8) -> Strength {
+        if level >= MAX_STRENGTH {
+            return Strength::default();
+        }
+
+        Strength {
+            max_depth: 1 + level / 2,
+            max_nodes: params::NODES_PER_DEADLINE_CHECK << level,
+            noise: 0.1 * f32::from(MAX_STRENGTH - level),
+            blunder_chance: 5 * u32::from(5u8.saturating_sub(level)),
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl Default for Strength {
+    /// Returns the full strength, without any limitation.
+    fn default() -> Strength {
+        Strength {
+            max_depth: params::MAX_DEPTH as u8,
+            max_nodes: u64::MAX,
+            noise: 0.0,
+            blunder_chance: 0,
+        }
+    }
+}
@@ -1,8 +1,23 @@
+use std::alloc::{self, Layout};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Error, Result};
+
 use chess::board::Board;
 use chess::moves::Move;
 use chess::zobrist::Zobrist;
 
-use crate::params;
+/// The weight of the age of entries in the replacement strategy, relatively to their depth:
+/// an entry of the previous search is replaced by a new one at most AGE_WEIGHT plies shallower.
+const AGE_WEIGHT: i32 = 4;
+
+/// The first bytes of a saved table, identifying the format and its version.
+const MAGIC: &[u8; 8] = b"RUSHTT\0\x01";
 
 //#################################################################################################
 //
@@ -18,6 +33,16 @@ pub(crate) enum TableEntryFlag {
     Exact = 2,
 }
 
+// ================================ pub(crate) impl
+
+impl TableEntryFlag {
+    /// Returns true if the score of an entry with that flag is a lower bound of the score of the node.
+    #[inline]
+    pub(crate) fn is_lower_bound(self) -> bool {
+        matches!(self, TableEntryFlag::Beta | TableEntryFlag::Exact)
+    }
+}
+
 /// A struct representing an entry of the table.
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct TableEntry {
@@ -32,12 +57,13 @@ pub(crate) struct TableEntry {
 // ================================ pub(crate) impl
 
 impl TableEntry {
-    /// Creates a new table entry based with the given values.
+    /// Creates a new table entry based with the given values. The age is the generation
+    /// of the search that created the entry.
     #[inline]
-    pub(crate) fn new(board: &Board, mv: Move, score: f32, depth: u8, flag: TableEntryFlag) -> TableEntry {
+    pub(crate) fn new(board: &Board, age: u16, mv: Move, score: f32, depth: u8, flag: TableEntryFlag) -> TableEntry {
         TableEntry {
             zobrist: board.get_zobrist(), 
-            age: board.get_ply(), 
+            age, 
             mv, 
             score, 
             depth,
@@ -46,94 +72,411 @@ impl TableEntry {
     }
 }
 
+// ================================ impl
+
+impl TableEntry {
+    /// The size in bytes of a saved entry.
+    const SIZE: usize = 20;
+
+    /// Encodes the entry in big endian, for saving it.
+    fn to_bytes(self) -> [u8; TableEntry::SIZE] {
+        let mut bytes = [0; TableEntry::SIZE];
+        bytes[0..8].copy_from_slice(&self.zobrist.get_raw().to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.age.to_be_bytes());
+        bytes[10..14].copy_from_slice(&self.mv.get_raw().to_be_bytes());
+        bytes[14..18].copy_from_slice(&self.score.to_be_bytes());
+        bytes[18] = self.depth;
+        bytes[19] = self.flag as u8;
+        bytes
+    }
+
+    /// Decodes an entry saved with to_bytes(), or returns None if it is invalid.
+    fn from_bytes(bytes: &[u8; TableEntry::SIZE]) -> Option<TableEntry> {
+        Some(TableEntry {
+            zobrist: Zobrist::from_raw(u64::from_be_bytes(bytes[0..8].try_into().unwrap())),
+            age: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            mv: Move::from_raw(u32::from_be_bytes(bytes[10..14].try_into().unwrap()))?,
+            score: f32::from_be_bytes(bytes[14..18].try_into().unwrap()),
+            depth: bytes[18],
+            flag: match bytes[19] {
+                0 => TableEntryFlag::Alpha,
+                1 => TableEntryFlag::Beta,
+                2 => TableEntryFlag::Exact,
+                _ => return None,
+            },
+        })
+    }
+
+    /// Encodes the entry in the two data words of a slot.
+    #[inline]
+    fn to_words(self) -> [u64; 2] {
+        [
+            u64::from(self.mv.get_raw()) | u64::from(self.score.to_bits()) << 32,
+            u64::from(self.age) | u64::from(self.depth) << 16 | (self.flag as u64) << 24,
+        ]
+    }
+
+    /// Decodes an entry from the data words of a slot and the key it was stored with,
+    /// or returns None if the slot is empty.
+    #[inline]
+    fn from_words(zobrist: Zobrist, words: [u64; 2]) -> Option<TableEntry> {
+        Some(TableEntry {
+            zobrist,
+            age: words[1] as u16,
+            mv: Move::from_raw(words[0] as u32)?,
+            score: f32::from_bits((words[0] >> 32) as u32),
+            depth: (words[1] >> 16) as u8,
+            flag: match words[1] >> 24 {
+                0 => TableEntryFlag::Alpha,
+                1 => TableEntryFlag::Beta,
+                2 => TableEntryFlag::Exact,
+                _ => return None,
+            },
+        })
+    }
+
+    /// Returns true if that entry should replace the previous one in a slot where depth is preferred:
+    /// deeper, newer and more exact entries are preferred.
+    #[inline]
+    fn replaces(self, prev: TableEntry) -> bool {
+        let replace_score =
+            self.depth as i32 - prev.depth as i32 +
+            AGE_WEIGHT * i32::from(self.age.wrapping_sub(prev.age) as i16) +
+            self.flag  as i32 - prev.flag  as i32;
+
+        replace_score >= 0
+    }
+}
+
+//#################################################################################################
+//
+//                                         struct Slot
+//
+//#################################################################################################
+
+/// A slot of the table, holding one entry as two data words, and a check word which is the xor of the
+/// zobrist key with the data words. The words are written and read one by one without locking: when two
+/// threads write the same slot at once, the words may come from different entries, but the check word
+/// then no longer matches the key, and the torn entry is rejected instead of being returned.
+#[derive(Debug, Default)]
+struct Slot {
+    check: AtomicU64,
+    data: [AtomicU64; 2],
+}
+
+// ================================ impl
+
+impl Slot {
+    /// Returns the entry stored in the slot, if it is not empty and its words are consistent.
+    #[inline]
+    fn load(&self) -> Option<TableEntry> {
+        let words = [self.data[0].load(Ordering::Relaxed), self.data[1].load(Ordering::Relaxed)];
+        let zobrist = Zobrist::from_raw(self.check.load(Ordering::Relaxed) ^ words[0] ^ words[1]);
+        TableEntry::from_words(zobrist, words)
+    }
+
+    /// Returns the entry stored in the slot, if it is the entry of that position.
+    #[inline]
+    fn probe(&self, zobrist: Zobrist) -> Option<TableEntry> {
+        self.load().filter(|entry| entry.zobrist == zobrist)
+    }
+
+    /// Stores the entry in the slot.
+    #[inline]
+    fn store(&self, entry: TableEntry) {
+        let words = entry.to_words();
+        self.check.store(entry.zobrist.get_raw() ^ words[0] ^ words[1], Ordering::Relaxed);
+        self.data[0].store(words[0], Ordering::Relaxed);
+        self.data[1].store(words[1], Ordering::Relaxed);
+    }
+
+    /// Empties the slot.
+    #[inline]
+    fn clear(&self) {
+        self.check.store(0, Ordering::Relaxed);
+        self.data[0].store(0, Ordering::Relaxed);
+        self.data[1].store(0, Ordering::Relaxed);
+    }
+}
+
 //#################################################################################################
 //
 //                                     struct TranspositionTable
 //
 //#################################################################################################
 
-/// The type of a bucket in the map.
-type Bucket = Option<TableEntry>;
+/// A bucket of the table, with two slots: the first keeps the most valuable entry according to the
+/// replacement strategy, and the second always receives the entries the first one rejected, so that
+/// recent shallow entries are not lost to old deep ones.
+#[derive(Debug, Default)]
+struct Bucket {
+    deep: Slot,
+    recent: Slot,
+}
+
+// ================================ impl
 
-/// The size in buckets of the table. It is a power of two for
-/// faster indexing.
-const NUM_BUCKETS: usize = (params::TABLE_SIZE / std::mem::size_of::<Bucket>()).next_power_of_two();
+impl Bucket {
+    /// Returns the entry of that position in the bucket, preferring the deepest one if both slots hold it.
+    #[inline]
+    fn probe(&self, zobrist: Zobrist) -> Option<TableEntry> {
+        match (self.deep.probe(zobrist), self.recent.probe(zobrist)) {
+            (Some(deep), Some(recent)) if recent.depth > deep.depth => Some(recent),
+            (Some(deep), _) => Some(deep),
+            (None, recent) => recent,
+        }
+    }
+}
 
 /// The struct representing an access to a transposition table.
-/// A transposition table is a lock-less memory-efficient concurrent hashmap.
-/// It's only default is that it is lossy and may rarely corrupt some of it's data.
-#[repr(transparent)]
-#[derive(Clone, Debug)]
-pub(crate) struct TranspositionTable(*mut Bucket);
+/// A transposition table is a lock-less memory-efficient concurrent hashmap. It is lossy,
+/// but entries torn by concurrent writes are detected and ignored.
+#[derive(Debug)]
+pub(crate) struct TranspositionTable {
+    buckets: Box<[Bucket]>,
+}
 
 // ================================ pub(crate) impl
 
 impl TranspositionTable {
-    /// Creates a new transposition table, from leaking a vector.
-    pub(crate) fn new() -> TranspositionTable {
-        let mut vec = vec![None; NUM_BUCKETS];
-        let ptr = vec.as_mut_ptr();
-        vec.leak();
+    /// Creates a new transposition table of roughly the given size in bytes.
+    /// Its size in buckets is a power of two for faster indexing.
+    pub(crate) fn new(size: usize) -> TranspositionTable {
+        let num_buckets = (size / mem::size_of::<Bucket>()).next_power_of_two();
 
-        TranspositionTable(ptr)
+        TranspositionTable {
+            buckets: (0..num_buckets).map(|_| Bucket::default()).collect(),
+        }
+    }
+
+    /// Creates a new empty transposition table as with new(), without touching its memory: the operating system
+    /// places each of its pages on the NUMA node of the thread writing to it first, see TranspositionTable::touch().
+    pub(crate) fn untouched(size: usize) -> TranspositionTable {
+        let num_buckets = (size / mem::size_of::<Bucket>()).next_power_of_two();
+        let layout = Layout::array::<Bucket>(num_buckets).expect("Transposition table too large.");
+
+        // SAFE: an all-zeros bucket is an empty one, its words being atomic integers, and the memory
+        // is allocated with the layout of the slice, so that the box deallocates it correctly.
+        let buckets = unsafe {
+            let ptr = alloc::alloc_zeroed(layout) as *mut Bucket;
+            if ptr.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
+            Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, num_buckets))
+        };
+
+        TranspositionTable {buckets}
+    }
+
+    /// Touches the memory of the given part of the table, out of parts parts of equal size, without changing its
+    /// entries. Called by each of the search threads pinned to their core, with their id, so that the pages of a table
+    /// created with untouched() are spread over the NUMA nodes of the cores instead of hammering a single one.
+    pub(crate) fn touch(&self, part: usize, parts: usize) {
+        let len = self.buckets.len().div_ceil(parts);
+        let step = (4096 / mem::size_of::<Bucket>()).max(1);
+
+        for bucket in self.buckets.iter().skip(part * len).take(len).step_by(step) {
+            // Writing a page places it, adding zero leaves the entry as it is even if it is being written.
+            bucket.deep.check.fetch_add(0, Ordering::Relaxed);
+        }
     }
     
-    /// Inserts into the hashtable, or not depending on the replacement strategy.
+    /// Inserts into the hashtable: the entry replaces the one in the first slot of its bucket if the
+    /// replacement strategy prefers it, or goes to the second slot otherwise.
     #[inline]
     pub(crate) fn insert(&self, entry: TableEntry) {
-        let i = entry.zobrist.idx::<NUM_BUCKETS>();
-
-        // SAFE: not inherently unsafe, at worst we risk getting a currupted entry.
-        if let Some(prev) = unsafe {*self.0.offset(i)} {
-            let replace_score = 
-                entry.depth as i32 - prev.depth as i32 + 
-                entry.age   as i32 - prev.age   as i32 +
-                entry.flag  as i32 - prev.flag  as i32;
+        let bucket = self.bucket(entry.zobrist);
 
-            if replace_score < 0 {
-                return;
-            }
+        match bucket.deep.load() {
+            Some(prev) if !entry.replaces(prev) => bucket.recent.store(entry),
+            _ => bucket.deep.store(entry),
         }
+    }
 
-        // SAFE: not inherently unsafe, at worst we risk corrupting an entry.
-        unsafe {*self.0.offset(i) = Some(entry)};
+    /// Returns the move stored for that position, if any.
+    #[inline]
+    pub(crate) fn get_move(&self, zobrist: Zobrist) -> Option<Move> {
+        self.bucket(zobrist).probe(zobrist).map(|entry| entry.mv)
+    }
+
+    /// Returns the move and the score stored for that position, if the entry is at least as deep as
+    /// the given depth and its score is a lower bound: the move is known to be at least that good.
+    #[inline]
+    pub(crate) fn get_lower_bound(&self, zobrist: Zobrist, depth: u8) -> Option<(Move, f32)> {
+        match self.bucket(zobrist).probe(zobrist) {
+            Some(entry) if entry.depth >= depth && entry.flag.is_lower_bound() => Some((entry.mv, entry.score)),
+            _ => None,
+        }
     }
 
     /// Probes the hashmap and gets any pertinent information available.
     #[inline]
     pub(crate) fn probe(&self, zobrist: Zobrist, alpha: f32, beta: f32, depth: u8) -> Option<(Move, f32)> {
-        let i = zobrist.idx::<NUM_BUCKETS>();
-        
-        // SAFE: not inherently unsafe, at worst we risk getting a currupted entry.
-        if let Some(entry) = unsafe {*self.0.offset(i)} {
-            if entry.zobrist == zobrist && entry.depth >= depth {
+        match self.bucket(zobrist).probe(zobrist) {
+            Some(entry) if entry.depth >= depth => {
                 let mv = entry.mv;
                 let score = entry.score;
 
-                return match entry.flag {
+                match entry.flag {
                     TableEntryFlag::Exact => Some((mv, score)),
                     TableEntryFlag::Alpha if score <= alpha => Some((mv, alpha)),
                     TableEntryFlag::Beta if score >= beta => Some((mv, beta)),
                     _ => None,
-                };
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Saves the entries of the table to the given file, along with the generation of the current search,
+    /// which is the age of the newest entries. The file is written as the table is read, so that entries
+    /// written by a search running at the same time may be lost.
+    pub(crate) fn save(&self, path: &Path, generation: u16) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&generation.to_be_bytes())?;
+
+        for bucket in self.buckets.iter() {
+            for entry in bucket.deep.load().into_iter().chain(bucket.recent.load()) {
+                writer.write_all(&entry.to_bytes())?;
             }
         }
 
-        None
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Replaces the entries of the table with those saved in the given file, and returns the generation
+    /// of the search they were saved at. Must not be called while searching. If the file is invalid, the table
+    /// is left untouched or empty.
+    pub(crate) fn load(&self, path: &Path) -> Result<u16> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut header = [0; MAGIC.len() + 2];
+        reader.read_exact(&mut header).map_err(|_| Error::msg("Table file is too short."))?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(Error::msg("Not a table file, or saved by another version of the engine."));
+        }
+        let generation = u16::from_be_bytes(header[MAGIC.len()..].try_into().unwrap());
+
+        self.clear();
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if !bytes.len().is_multiple_of(TableEntry::SIZE) {
+            return Err(Error::msg("Table file has invalid size."));
+        }
+
+        for chunk in bytes.chunks_exact(TableEntry::SIZE) {
+            let entry = TableEntry::from_bytes(chunk.try_into().unwrap()).ok_or_else(|| {
+                self.clear();
+                Error::msg("Table file contains an invalid entry.")
+            })?;
+
+            self.insert(entry);
+        }
+
+        Ok(generation)
     }
 }
 
-// ================================ traits impl
+// ================================ impl
 
-impl Drop for TranspositionTable {
-    /// TranspositionTable needs to be manually dropped.
-    fn drop(&mut self) {
-        // SAFE: the pointer is dropped only once
-        unsafe {Box::from_raw(self.0)};
+impl TranspositionTable {
+    /// Returns the bucket of the given position.
+    #[inline]
+    fn bucket(&self, zobrist: Zobrist) -> &Bucket {
+        &self.buckets[(zobrist.get_raw() & (self.buckets.len() as u64 - 1)) as usize]
+    }
+
+    /// Removes all the entries of the table.
+    fn clear(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.deep.clear();
+            bucket.recent.clear();
+        }
     }
 }
 
-// rustc correctly assesses that our TranspositionTable is not thread-safe.
-// Let us turn a blind eye to that.
-unsafe impl Send for TranspositionTable {}
-unsafe impl Sync for TranspositionTable {}
\ No newline at end of file
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use chess::moves::Move;
+    use chess::square::Square;
+    use chess::zobrist::Zobrist;
+
+    use crate::table::{TableEntry, TableEntryFlag, TranspositionTable};
+
+    // Returns an entry whose move, score and depth are all derived from its key, so that
+    // an entry mixing the words of several others can be told apart.
+    fn entry(key: u64, age: u16) -> TableEntry {
+        let from = (key % 63) as usize;
+        TableEntry {
+            zobrist: Zobrist::from_raw(key),
+            age,
+            mv: Move::quiet(Square::SQUARES[from], Square::SQUARES[from + 1]),
+            score: key as f32,
+            depth: (key % 32) as u8,
+            flag: TableEntryFlag::Exact,
+        }
+    }
+
+    #[test]
+    fn replacement() {
+        let table = TranspositionTable::new(1024);
+        let num_buckets = table.buckets.len() as u64;
+
+        // A shallower entry does not replace a deeper one, but is kept in the other slot.
+        let deep = TableEntry {depth: 20, ..entry(1, 0)};
+        let shallow = TableEntry {depth: 2, ..entry(1 + num_buckets, 0)};
+        table.insert(deep);
+        table.insert(shallow);
+        assert_eq!(table.get_move(deep.zobrist), Some(deep.mv));
+        assert_eq!(table.get_move(shallow.zobrist), Some(shallow.mv));
+
+        // The entries of a newer search replace old ones, even deeper.
+        let newer = TableEntry {depth: 18, ..entry(1 + 2 * num_buckets, 1)};
+        table.insert(newer);
+        assert_eq!(table.get_move(newer.zobrist), Some(newer.mv));
+        assert_eq!(table.get_move(deep.zobrist), None);
+    }
+
+    #[test]
+    fn concurrent_writes() {
+        // A tiny table, so that the threads keep writing the same slots at once.
+        let table = Arc::new(TranspositionTable::new(256));
+
+        let threads: Vec<_> = (0..8u64).map(|t| {
+            let table = table.clone();
+            thread::spawn(move || {
+                let mut keys = [t; 16];
+                for i in 0..500_000usize {
+                    let key = keys[i % 16].wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    keys[(i + 1) % 16] = key;
+                    table.insert(entry(key, (i / 1000) as u16));
+
+                    // Any entry found must be one that was inserted, never a mix of several.
+                    for &key in &keys {
+                        let zobrist = Zobrist::from_raw(key);
+                        if let Some(found) = table.bucket(zobrist).probe(zobrist) {
+                            let expected = entry(key, found.age);
+                            assert_eq!((found.mv, found.score, found.depth), (expected.mv, expected.score, expected.depth));
+                        }
+                    }
+                }
+            })
+        }).collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
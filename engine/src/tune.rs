@@ -0,0 +1,270 @@
+use std::fs;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Error, Result};
+use clap::{App, Arg};
+
+use chess::prelude::*;
+use engine::{random_opening, xorshift32, LocalEngine, Tunables, STARTING_FEN};
+
+/*
+ * Tunes the parameters of the search with the SPSA algorithm (simultaneous perturbation
+ * stochastic approximation). At each iteration, all tuned parameters are perturbed at once
+ * in random directions, and the two resulting configurations play a few pairs of games against
+ * each other. The parameters are then moved towards the configuration that scored best.
+ * The optimized values are written to the output file after each iteration, one "name = value"
+ * pair per line, which is also the format of the file given with --params.
+ *
+ * $ cargo build --bin tune --release
+ * $ target/release/tune engine/nets/nnue.bin tuned.txt --iterations 200 --tune lmr_base,lmr_divisor
+ */
+
+/// The rules used to play and adjudicate the games.
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    // The number of nodes searched per move.
+    nodes: u64,
+    // The number of random moves played at the start of each game.
+    random_plies: usize,
+    // The score above which a player is considered winning, in pawns.
+    win_score: f32,
+    // The number of consecutive plies a player must be winning for the game to be adjudicated.
+    win_plies: usize,
+    // The number of plies after which a game is adjudicated as a draw.
+    max_plies: usize,
+}
+
+/// Plays a game between the two engines from the given opening, the first one playing white.
+/// Returns the result of the game, from white's point of view.
+fn play_game(engines: [&mut LocalEngine; 2], opening: &Board, rules: Rules) -> i8 {
+    let [white, black] = engines;
+    *white.write_board() = opening.clone();
+    *black.write_board() = opening.clone();
+
+    let mut winning_plies = 0;
+
+    for _ in 0..rules.max_plies {
+        let side = white.read_board().get_side_to_move();
+        let engine = match side {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+
+        let (mv, score) = match engine.think_nodes(rules.nodes) {
+            Some(found) => found,
+            // The game is over on the board.
+            None => return match engine.read_board().status() {
+                Status::Win(Color::White) => 1,
+                Status::Win(Color::Black) => -1,
+                _ => 0,
+            },
+        };
+
+        let score = match side {
+            Color::White => score,
+            Color::Black => -score,
+        };
+
+        // Adjudicates the game once a player has been winning for long enough.
+        if score.abs() >= rules.win_score {
+            winning_plies += 1;
+            if winning_plies >= rules.win_plies {
+                return if score > 0.0 {1} else {-1};
+            }
+        } else {
+            winning_plies = 0;
+        }
+
+        white.advance(mv);
+        black.advance(mv);
+    }
+
+    0
+}
+
+/// Builds the parameters obtained by applying the given values to the tuned parameters.
+fn with_values(base: &Tunables, tuned: &[&str], values: &[f32]) -> Result<Tunables> {
+    let mut tunables = *base;
+    for (name, &value) in tuned.iter().zip(values) {
+        tunables.set(name, value)?;
+    }
+
+    Ok(tunables)
+}
+
+/// The main function parses the programs arguments, then runs the SPSA iterations,
+/// writing the parameters to the output file after each of them.
+fn main() -> Result<()> {
+    // Get the args to the program.
+    let args = App::new("Rush chess engine parameter tuner")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("Tunes the parameters of the search with SPSA, by playing the engine against itself.")
+        .arg(Arg::with_name("net")
+            .index(1)
+            .value_name("NET")
+            .help("The path to the network file to use for evaluation.")
+            .required(true))
+        .arg(Arg::with_name("output")
+            .index(2)
+            .value_name("OUTPUT")
+            .help("The path to the file the optimized parameters are written to.")
+            .required(true))
+        .arg(Arg::with_name("params")
+            .short("p")
+            .long("params")
+            .value_name("PARAMS")
+            .help("The path to a file containing the starting values of the parameters.")
+            .takes_value(true))
+        .arg(Arg::with_name("tune")
+            .long("tune")
+            .value_name("NAMES")
+            .help("The comma separated names of the parameters to tune. All parameters are tuned by default.")
+            .takes_value(true))
+        .arg(Arg::with_name("iterations")
+            .short("i")
+            .long("iterations")
+            .value_name("ITERATIONS")
+            .default_value("100")
+            .help("The number of SPSA iterations.")
+            .takes_value(true))
+        .arg(Arg::with_name("games")
+            .short("g")
+            .long("games")
+            .value_name("GAMES")
+            .default_value("4")
+            .help("The number of pairs of games played at each iteration.")
+            .takes_value(true))
+        .arg(Arg::with_name("nodes")
+            .short("n")
+            .long("nodes")
+            .value_name("NODES")
+            .default_value("5000")
+            .help("The number of nodes searched per move, roughly.")
+            .takes_value(true))
+        .arg(Arg::with_name("rate")
+            .long("rate")
+            .value_name("RATE")
+            .default_value("4")
+            .help("The learning rate, in steps of the parameters.")
+            .takes_value(true))
+        .arg(Arg::with_name("perturbation")
+            .long("perturbation")
+            .value_name("STEPS")
+            .default_value("1")
+            .help("The size of the perturbations, in steps of the parameters.")
+            .takes_value(true))
+        .arg(Arg::with_name("random-plies")
+            .long("random-plies")
+            .value_name("PLIES")
+            .default_value("8")
+            .help("The number of random moves played at the start of each game.")
+            .takes_value(true))
+        .arg(Arg::with_name("win-score")
+            .long("win-score")
+            .value_name("PAWNS")
+            .default_value("10")
+            .help("The score, in pawns, above which a player is considered winning.")
+            .takes_value(true))
+        .arg(Arg::with_name("win-plies")
+            .long("win-plies")
+            .value_name("PLIES")
+            .default_value("8")
+            .help("The number of consecutive plies a player must be winning for the game to be adjudicated as a win.")
+            .takes_value(true))
+        .arg(Arg::with_name("max-plies")
+            .long("max-plies")
+            .value_name("PLIES")
+            .default_value("300")
+            .help("The number of plies after which a game is adjudicated as a draw.")
+            .takes_value(true))
+        .get_matches();
+
+    // Parses a numerical argument.
+    fn parse<T: FromStr>(args: &clap::ArgMatches, name: &str) -> Result<T> {
+        T::from_str(args.value_of(name).unwrap()).map_err(|_| Error::msg(format!("Unable to parse {}.", name)))
+    }
+
+    let iterations: usize = parse(&args, "iterations")?;
+    let games: usize = parse(&args, "games")?;
+    let rate: f32 = parse(&args, "rate")?;
+    let perturbation: f32 = parse(&args, "perturbation")?;
+    let rules = Rules {
+        nodes: parse(&args, "nodes")?,
+        random_plies: parse(&args, "random-plies")?,
+        win_score: parse(&args, "win-score")?,
+        win_plies: parse(&args, "win-plies")?,
+        max_plies: parse(&args, "max-plies")?,
+    };
+
+    if games == 0 {
+        return Err(Error::msg("Invalid number of games, there must be at least one pair."));
+    }
+
+    // The starting parameters, and the names of the ones to tune.
+    let base = match args.value_of("params") {
+        Some(path) => Tunables::from_str(&fs::read_to_string(path)?)?,
+        None => Tunables::default(),
+    };
+    let tuned: Vec<&str> = match args.value_of("tune") {
+        Some(names) => names.split(',').map(str::trim).collect(),
+        None => Tunables::SPECS.iter().map(|spec| spec.name).collect(),
+    };
+    let specs = tuned.iter().map(|name| Tunables::spec(name)).collect::<Result<Vec<_>>>()?;
+
+    // The current values of the tuned parameters. They are kept unrounded, so that
+    // integer parameters may move by less than a step per iteration.
+    let mut values = tuned.iter().map(|name| base.get(name)).collect::<Result<Vec<_>>>()?;
+
+    let net = fs::read(args.value_of("net").unwrap())?;
+    let mut engines = [
+        LocalEngine::new(Board::new(STARTING_FEN)?, &net)?,
+        LocalEngine::new(Board::new(STARTING_FEN)?, &net)?,
+    ];
+
+    let mut seed = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() | 1;
+
+    // The stability constant of the learning rate sequence, as recommended for SPSA.
+    let stability = iterations as f32 / 10.0;
+
+    for k in 0..iterations {
+        let a_k = rate / (k as f32 + 1.0 + stability).powf(0.602);
+        let c_k = perturbation / (k as f32 + 1.0).powf(0.101);
+
+        // Perturbs all parameters at once, in random directions.
+        let directions: Vec<f32> = specs.iter().map(|_| if xorshift32(&mut seed) & 1 == 0 {1.0} else {-1.0}).collect();
+        let perturbed = |sign: f32| -> Vec<f32> {
+            values.iter().zip(&specs).zip(&directions).map(|((value, spec), direction)| value + sign * c_k * spec.step * direction).collect()
+        };
+
+        let [plus, minus] = &mut engines;
+        plus.set_tunables(with_values(&base, &tuned, &perturbed(1.0))?);
+        minus.set_tunables(with_values(&base, &tuned, &perturbed(-1.0))?);
+
+        // Plays pairs of games from the same openings, with colors reversed.
+        let mut result = 0;
+        for _ in 0..games {
+            let opening = random_opening(rules.random_plies, &mut seed);
+
+            result += i32::from(play_game([&mut *plus, &mut *minus], &opening, rules));
+            result -= i32::from(play_game([&mut *minus, &mut *plus], &opening, rules));
+        }
+
+        // The score of the positive perturbation, between -1 and 1, and the gradient step.
+        let score = result as f32 / (2 * games) as f32;
+        for ((value, spec), direction) in values.iter_mut().zip(&specs).zip(&directions) {
+            *value = (*value + a_k * score * spec.step / (2.0 * c_k * direction)).clamp(spec.min, spec.max);
+        }
+
+        let tunables = with_values(&base, &tuned, &values)?;
+        fs::write(args.value_of("output").unwrap(), tunables.to_string())?;
+
+        println!("Iteration {}/{}: score {:+.2}.", k + 1, iterations, score);
+        for (name, value) in tuned.iter().zip(&values) {
+            println!("  {} = {}", name, value);
+        }
+    }
+
+    Ok(())
+}
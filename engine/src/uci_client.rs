@@ -0,0 +1,252 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Error, Result};
+
+use chess::board::Board;
+use chess::moves::Move;
+
+use crate::player::{KillSwitch, Player, PlayerMove, SearchLimit};
+
+/// The score given to the mates reported by UCI engines, in pawns.
+const MATE_SCORE: f32 = 1000.0;
+
+/// How long an engine is given to exit after being asked to quit, before it is killed.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+//#################################################################################################
+//
+//                                         enum UciLine
+//
+//#################################################################################################
+
+/// A line sent by a UCI engine while it searches, with what matters to a player.
+#[derive(Debug, PartialEq)]
+enum UciLine<'a> {
+    /// An info line, with the score and the depth it reports, if any.
+    Info {score: Option<f32>, depth: Option<u8>},
+    /// The best move found, if the engine gave one.
+    BestMove(Option<&'a str>),
+    /// Any other line.
+    Other,
+}
+
+// ================================ impl
+
+impl<'a> UciLine<'a> {
+    /// Parses a line sent by the engine. Scores are in pawns, from the point of view of the side to move,
+    /// mates being worth MATE_SCORE: "mate 0" means the side to move is mated.
+    fn parse(line: &'a str) -> UciLine<'a> {
+        let mut tokens = line.split_ascii_whitespace();
+
+        match tokens.next() {
+            Some("info") => {
+                let (mut score, mut depth) = (None, None);
+
+                while let Some(token) = tokens.next() {
+                    match token {
+                        "depth" => depth = tokens.next().and_then(|value| u8::from_str(value).ok()).or(depth),
+                        "score" => score = match (tokens.next(), tokens.next().and_then(|value| i32::from_str(value).ok())) {
+                            (Some("cp"), Some(cp)) => Some(cp as f32 / 100.0),
+                            (Some("mate"), Some(mate)) => Some(if mate > 0 {MATE_SCORE} else {-MATE_SCORE}),
+                            _ => score,
+                        },
+                        _ => (),
+                    }
+                }
+
+                UciLine::Info {score, depth}
+            },
+            Some("bestmove") => UciLine::BestMove(tokens.next()),
+            _ => UciLine::Other,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct UciClient
+//
+//#################################################################################################
+
+/// An external engine, such as Stockfish, talking the UCI protocol through the standard input and output of
+/// its process. The process is started from a command and its arguments, so that a remote engine may be
+/// reached through a program forwarding its streams, such as ssh.
+#[derive(Debug)]
+pub struct UciClient {
+    name: String,
+    child: Arc<Mutex<Child>>,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+// ================================ pub impl
+
+impl UciClient {
+    /// Starts the engine with the given command, the program first and its arguments after,
+    /// initializes it and sets the given options, by name.
+    pub fn start(command: &[String], options: &[(String, String)]) -> Result<UciClient> {
+        let (program, args) = command.split_first().ok_or_else(|| Error::msg("The command of a UCI engine is empty."))?;
+        let mut child = Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()
+            .map_err(|e| Error::msg(format!("Cannot start UCI engine {}: {}.", program, e)))?;
+
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+
+        let mut client = UciClient {name: program.clone(), child: Arc::new(Mutex::new(child)), input, output};
+        client.send("uci")?;
+        loop {
+            let line = client.read_line()?;
+
+            match line.trim().strip_prefix("id name ") {
+                Some(name) => client.name = name.to_string(),
+                None if line.trim() == "uciok" => break,
+                None => (),
+            }
+        }
+
+        for (name, value) in options {
+            client.send(&format!("setoption name {} value {}", name, value))?;
+        }
+
+        Ok(client)
+    }
+}
+
+// ================================ impl
+
+impl UciClient {
+    /// Sends a command to the engine.
+    fn send(&mut self, command: &str) -> Result<()> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()?;
+        Ok(())
+    }
+
+    /// Reads a line from the engine.
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+
+        if self.output.read_line(&mut line)? == 0 {
+            return Err(Error::msg("The UCI engine exited."));
+        }
+
+        Ok(line)
+    }
+
+    /// Reads the lines of the engine until the given one.
+    fn wait_for(&mut self, expected: &str) -> Result<()> {
+        while self.read_line()?.trim() != expected {}
+        Ok(())
+    }
+}
+
+// ================================ traits impl
+
+impl Player for UciClient {
+    /// Returns the name the engine gave, or its program if it gave none.
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<()> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.wait_for("readyok")
+    }
+
+    /// Sends the game to the engine and waits for its best move, keeping the last score and depth it reported.
+    fn search(&mut self, start: &Board, moves: &[Move], board: &Board, limit: SearchLimit) -> Result<PlayerMove> {
+        let moves = moves.iter().map(Move::to_string).collect::<Vec<_>>().join(" ");
+        self.send(&format!("position fen {} moves {}", start, moves))?;
+
+        self.send(&match limit {
+            SearchLimit::Time(duration) => format!("go movetime {}", duration.as_millis()),
+            SearchLimit::Nodes(nodes) => format!("go nodes {}", nodes),
+            SearchLimit::Depth(depth) => format!("go depth {}", depth),
+            SearchLimit::Clock {times, increment} => format!(
+                "go wtime {} btime {} winc {} binc {}",
+                times[0].as_millis(), times[1].as_millis(), increment.as_millis(), increment.as_millis(),
+            ),
+        })?;
+
+        let (mut score, mut depth) = (None, None);
+        loop {
+            let line = self.read_line()?;
+
+            match UciLine::parse(&line) {
+                UciLine::Info {score: new_score, depth: new_depth} => {
+                    score = new_score.or(score);
+                    depth = new_depth.or(depth);
+                },
+                UciLine::BestMove(mv) => {
+                    let mv = mv.ok_or_else(|| Error::msg("The UCI engine sent no best move."))?;
+                    return Ok(PlayerMove {mv: board.parse_move(mv)?, score, depth});
+                },
+                UciLine::Other => (),
+            }
+        }
+    }
+
+    fn kill_switch(&self) -> Option<KillSwitch> {
+        Some(KillSwitch::new(self.child.clone()))
+    }
+}
+
+impl Drop for UciClient {
+    /// Asks the engine to quit, and waits for it to do so, killing it if it does not exit in time.
+    fn drop(&mut self) {
+        self.send("quit").ok();
+
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        loop {
+            // The lock is released before the process may be killed.
+            let exited = self.child.lock().unwrap().try_wait();
+            if !matches!(exited, Ok(None)) {
+                return;
+            }
+
+            if Instant::now() >= deadline {
+                KillSwitch::new(self.child.clone()).kill();
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::{UciLine, MATE_SCORE};
+
+    #[test]
+    fn info() {
+        assert_eq!(
+            UciLine::parse("info depth 12 seldepth 18 multipv 1 score cp -35 nodes 120000 pv e2e4 e7e5\n"),
+            UciLine::Info {score: Some(-0.35), depth: Some(12)},
+        );
+        assert_eq!(UciLine::parse("info depth 3 score mate 2 pv d1h5"), UciLine::Info {score: Some(MATE_SCORE), depth: Some(3)});
+        assert_eq!(UciLine::parse("info depth 3 score mate -1 pv g2g4"), UciLine::Info {score: Some(-MATE_SCORE), depth: Some(3)});
+        assert_eq!(UciLine::parse("info depth 0 score mate 0"), UciLine::Info {score: Some(-MATE_SCORE), depth: Some(0)});
+        assert_eq!(UciLine::parse("info string NNUE enabled"), UciLine::Info {score: None, depth: None});
+        assert_eq!(UciLine::parse("info score lowerbound"), UciLine::Info {score: None, depth: None});
+    }
+
+    #[test]
+    fn bestmove() {
+        assert_eq!(UciLine::parse("bestmove e2e4 ponder e7e5\n"), UciLine::BestMove(Some("e2e4")));
+        assert_eq!(UciLine::parse("bestmove"), UciLine::BestMove(None));
+        assert_eq!(UciLine::parse("readyok"), UciLine::Other);
+        assert_eq!(UciLine::parse(""), UciLine::Other);
+    }
+}
@@ -1,31 +1,223 @@
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::SystemTime;
 
 use chess::bitboard::BitBoard;
-use chess::board::Board;
+use chess::board::{Board, DrawReason};
 use chess::color::Color;
+use chess::movegen;
 use chess::piece::Piece;
 use chess::square::Square;
+use chess::variant::Variant;
+
+use crate::{eval, params};
+
+/// The fen of the starting position.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
 /// Returns a random seed based on the current time.
+#[cfg(not(target_arch = "wasm32"))]
 #[inline]
 pub(crate) fn seed() -> u32 {
     (SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Cannot get system time.").as_nanos() & 0xFFFFFFFF) as u32
 }
 
+/// Returns a random seed, using javascript's random number generator
+/// since the system time is not available in WebAssembly.
+#[cfg(target_arch = "wasm32")]
+#[inline]
+pub(crate) fn seed() -> u32 {
+    (js_sys::Math::random() * f64::from(u32::MAX)) as u32
+}
+
+/// Returns the logical cpus the search threads may be pinned to, one per physical core the process may run on,
+/// in the order of the kernel. Hardware threads sharing a core are skipped, as pinning two search threads to them
+/// would make them compete for it.
+#[cfg(target_os = "linux")]
+pub(crate) fn physical_cores() -> Vec<usize> {
+    // SAFE: cpu_set_t is a plain bitmask, for which all-zeros is valid, and its size is the one given.
+    let allowed = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Vec::new();
+        }
+        set
+    };
+
+    let mut cores = std::collections::HashSet::new();
+    (0..libc::CPU_SETSIZE as usize).filter(|&cpu| {
+        // SAFE: cpu is below CPU_SETSIZE.
+        unsafe {libc::CPU_ISSET(cpu, &allowed)}
+    }).filter(|&cpu| {
+        // The hardware threads of a core share the list of their siblings, the cpu being its own core without it.
+        let siblings = std::fs::read_to_string(format!("/sys/devices/system/cpu/cpu{}/topology/thread_siblings_list", cpu));
+        cores.insert(siblings.map_or(cpu.to_string(), |list| list.trim().to_string()))
+    }).collect()
+}
+
+/// Returns the logical cpus the search threads may be pinned to: none, as threads cannot be pinned on this platform.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn physical_cores() -> Vec<usize> {
+    Vec::new()
+}
+
+/// Pins the calling thread to the given logical cpu, returning false if it could not be.
+#[cfg(target_os = "linux")]
+pub(crate) fn pin_thread(cpu: usize) -> bool {
+    // SAFE: cpu_set_t is a plain bitmask, for which all-zeros is valid, and its size is the one given.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+/// Pins the calling thread to the given logical cpu: it cannot be on this platform.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin_thread(_cpu: usize) -> bool {
+    false
+}
+
+/// Folds a 64 bits seed into a valid seed of xorshift32, which must not be zero.
+#[inline]
+pub(crate) fn fold_seed(seed: u64) -> u32 {
+    ((seed ^ seed >> 32) as u32).max(1)
+}
+
+/// Returns the current time, in milliseconds since the unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+fn now_millis() -> f64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Cannot get system time.").as_secs_f64() * 1000.0
+}
+
+/// Returns the current time, in milliseconds since the unix epoch.
+/// Uses javascript's Date since the system time is not available in WebAssembly.
+#[cfg(target_arch = "wasm32")]
+#[inline]
+fn now_millis() -> f64 {
+    js_sys::Date::now()
+}
+
+/// A point in time after which a search should be stopped.
+/// Works in WebAssembly too, unlike std::time::Instant.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline(f64);
+
+// ================================ pub(crate) impl
+
+impl Deadline {
+    /// Creates a deadline expiring after the given duration.
+    #[inline]
+    pub(crate) fn after(duration: Duration) -> Deadline {
+        Deadline(now_millis() + duration.as_secs_f64() * 1000.0)
+    }
+
+    /// Returns true if the deadline has passed.
+    #[inline]
+    pub(crate) fn has_passed(self) -> bool {
+        now_millis() >= self.0
+    }
+}
+
+/// A point in time from which a duration is measured.
+/// Works in WebAssembly too, unlike std::time::Instant.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Stopwatch(f64);
+
+// ================================ pub(crate) impl
+
+impl Stopwatch {
+    /// Starts measuring a duration from now.
+    #[inline]
+    pub(crate) fn start() -> Stopwatch {
+        Stopwatch(now_millis())
+    }
+
+    /// Returns the duration elapsed since the stopwatch was started.
+    #[inline]
+    pub(crate) fn elapsed(self) -> Duration {
+        Duration::from_secs_f64((now_millis() - self.0).max(0.0) / 1000.0)
+    }
+}
+
 /// The xorshift32 algorithm, producing 32 bits non-crypographic numbers.
 #[inline]
-pub(crate) fn xorshift32(seed: &mut u32) -> u32 {
+pub fn xorshift32(seed: &mut u32) -> u32 {
     *seed ^= seed.wrapping_shl(13);
     *seed ^= seed.wrapping_shr(17);
     *seed ^= seed.wrapping_shl(5);
     *seed
 }
 
-/// Returns true if the board is in pseudo-draw because of either the
-/// 50 move rule or an incoming threefold repetition.
+/// Plays the given number of random moves from the starting position, drawing them with xorshift32,
+/// until it reaches a position where the game is not over.
+pub fn random_opening(plies: usize, seed: &mut u32) -> Board {
+    let mut legals = movegen::MoveList::new();
+
+    loop {
+        let mut board = Board::new(STARTING_FEN).unwrap();
+
+        for _ in 0..plies {
+            legals.clear();
+            movegen::legals_into(&board, &mut legals);
+
+            if legals.is_empty() {
+                break;
+            }
+
+            board.do_move(legals[xorshift32(seed) as usize % legals.len()]);
+        }
+
+        if board.status().is_playing() {
+            return board;
+        }
+    }
+}
+
+/// Returns the way a game ended in the given position, which must be over, as reported by the tools playing games.
+pub fn termination(board: &Board) -> &'static str {
+    match board.draw_reason() {
+        Some(DrawReason::Stalemate) => "stalemate",
+        Some(DrawReason::FiftyMoves) => "fifty moves",
+        Some(DrawReason::Repetition) => "repetition",
+        Some(DrawReason::InsufficientMaterial) => "insufficient material",
+        None if board.get_variant().winner(board).is_none() => "checkmate",
+        None => match board.get_variant() {
+            Variant::KingOfTheHill => "king of the hill",
+            Variant::ThreeCheck => "three checks",
+            Variant::Standard => unreachable!(),
+        },
+    }
+}
+
+/// Hashes the key into a pseudo-random number, uniformly distributed between -amplitude and amplitude.
+/// The same key always gives the same number.
+#[inline]
+pub(crate) fn noise(key: u32, amplitude: f32) -> f32 {
+    if amplitude == 0.0 {
+        return 0.0;
+    }
+
+    let mut hash = key.wrapping_mul(0x9E3779B9) | 1;
+    amplitude * (2.0 * xorshift32(&mut hash) as f32 / u32::MAX as f32 - 1.0)
+}
+
+/// Returns true if the board is a draw because of either the 50 move rule or a repetition,
+/// the last plies moves having been played during the search. A checkmate takes
+/// precedence over the 50 move rule.
+#[inline]
+pub(crate) fn is_draw(board: &Board, plies: u8) -> bool {
+    let fifty_moves = board.get_halfmove() >= 100
+        && (board.get_checkers().empty() || board.has_legal_move());
+
+    fifty_moves || board.is_repetition(usize::from(plies))
+}
+
+/// Returns true if the score is a mate score, or is infinite.
 #[inline]
-pub(crate) fn is_pseudo_draw(board: &Board, alpha: f32, root: bool) -> bool {
-    board.get_halfmove() >= 100 || (!root && alpha < 0.0 && board.test_upcoming_repetition())
+pub(crate) fn is_mate_score(score: f32) -> bool {
+    score.abs() >= eval::value_of(Piece::King) - params::MAX_PLY as f32
 }
 
 /// Returns true if the board can be considered in endgame.
@@ -33,10 +225,9 @@ pub(crate) fn is_pseudo_draw(board: &Board, alpha: f32, root: bool) -> bool {
 pub(crate) fn is_endgame(board: &Board) -> bool {
     Color::COLORS.iter().all(|&color| {
         let queens = board.get_bitboard(color, Piece::Queen);
-        let rooks = board.get_bitboard(color, Piece::Rook);
-        let occ = board.get_occupancy().colored(color);
 
-        queens.empty() || (queens.is_one() && rooks.empty() && occ.count() < 3)
+        // A lone queen is all the material of its side.
+        queens.empty() || (queens.is_one() && board.material(color) == Piece::Queen.material())
     })
 }
 
@@ -54,8 +245,9 @@ pub(crate) fn may_promote(board: &Board) -> bool {
     (board.get_bitboard(us, Piece::Pawn) & BitBoard::promote_rank(us)).not_empty()
 }
 
-/// Returns a pseudo-random draw value, to avoid threefold repetitions.
+/// Returns the value of a draw for a side having the given contempt for draws, in pawns,
+/// slightly randomized to avoid blindly going for or avoiding repetitions.
 #[inline]
-pub(crate) fn prng_draw_value(seed: &mut u32) -> f32 {
-    f32::from(2 * (xorshift32(seed) & 1) as i8 - 1)
+pub(crate) fn draw_value(contempt: f32, seed: &mut u32) -> f32 {
+    -contempt + 0.01 * f32::from(2 * (xorshift32(seed) & 1) as i8 - 1)
 }
\ No newline at end of file
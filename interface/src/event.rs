@@ -0,0 +1,39 @@
+use macroquad::input::{is_key_pressed, is_mouse_button_pressed, is_mouse_button_released, mouse_position, KeyCode, MouseButton};
+
+//#################################################################################################
+//
+//                                          enum Event
+//
+//#################################################################################################
+
+/// An input of the user during a frame.
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    /// The user pressed the mouse button at the given position of the window.
+    Press((f32, f32)),
+    /// The user released the mouse button at the given position of the window.
+    Release((f32, f32)),
+    /// The user asked for a new game.
+    NewGame,
+    /// The user asked to turn the board around.
+    Flip,
+    /// The user did nothing relevant.
+    None,
+}
+
+// ================================ pub impl
+
+/// Polls the inputs of the user for the current frame.
+pub fn poll() -> Event {
+    if is_key_pressed(KeyCode::N) {
+        Event::NewGame
+    } else if is_key_pressed(KeyCode::F) {
+        Event::Flip
+    } else if is_mouse_button_pressed(MouseButton::Left) {
+        Event::Press(mouse_position())
+    } else if is_mouse_button_released(MouseButton::Left) {
+        Event::Release(mouse_position())
+    } else {
+        Event::None
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use clap::{App, Arg};
+use macroquad::window::{next_frame, Conf};
+
+use chess::book::Book;
+use chess::prelude::*;
+use engine::Engine;
+
+mod event;
+mod render;
+mod state;
+
+use crate::state::State;
+
+/// The fen used for the default position.
+const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The default time the engine thinks for on each of its moves, in seconds.
+const DEFAULT_MOVETIME: &str = "2";
+
+/// The configuration of the window.
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "Rush".to_string(),
+        window_width: render::WINDOW_WIDTH as i32,
+        window_height: render::WINDOW_HEIGHT as i32,
+        window_resizable: false,
+        ..Default::default()
+    }
+}
+
+/// Parses the arguments of the program, and creates the state of the game from them.
+fn parse_args() -> Result<State> {
+    let args = App::new("Rush chess engine interface")
+        .version(engine::VERSION)
+        .author("Benjamin Lefebvre")
+        .about("A graphical interface for playing against the Rush chess engine locally.")
+        .arg(Arg::with_name("net")
+            .index(1)
+            .value_name("NET")
+            .help("The path to the network file to use for evaluation.")
+            .required(true))
+        .arg(Arg::with_name("fen")
+            .short("f")
+            .long("fen")
+            .value_name("FEN")
+            .default_value(DEFAULT_FEN)
+            .help("Sets the fen string to use as the starting position, use double quotes to give everything in a single argument.")
+            .takes_value(true))
+        .arg(Arg::with_name("book")
+            .short("b")
+            .long("book")
+            .value_name("BOOK")
+            .help("Gives the path to a polyglot book (.bin), that the engine will use whenever it can, instead of the built-in one.")
+            .takes_value(true))
+        .arg(Arg::with_name("no_book")
+            .long("no-book")
+            .help("Disables the opening book, including the built-in one.")
+            .conflicts_with("book"))
+        .arg(Arg::with_name("color")
+            .short("c")
+            .long("color")
+            .value_name("COLOR")
+            .possible_values(&["white", "black"])
+            .default_value("white")
+            .help("Sets the color played by the user.")
+            .takes_value(true))
+        .arg(Arg::with_name("movetime")
+            .short("t")
+            .long("movetime")
+            .value_name("SECONDS")
+            .default_value(DEFAULT_MOVETIME)
+            .help("Sets the time the engine thinks for on each of its moves, in seconds.")
+            .takes_value(true))
+        .get_matches();
+
+    let fen = args.value_of("fen").unwrap();
+    let human = match args.value_of("color").unwrap() {
+        "white" => Color::White,
+        _ => Color::Black,
+    };
+
+    let movetime = f64::from_str(args.value_of("movetime").unwrap())?;
+    if !movetime.is_finite() || movetime <= 0.0 {
+        return Err(Error::msg("The movetime must be a positive number of seconds."));
+    }
+
+    // The book that may be used to lookup moves.
+    let book = if args.is_present("no_book") {
+        None
+    } else if let Some(book_path) = args.value_of("book") {
+        Some(Book::open(Path::new(book_path))?)
+    } else {
+        Some(Book::builtin())
+    };
+
+    let engine = Engine::new(Board::from_str(fen)?, book, args.value_of("net").unwrap())?;
+
+    Ok(State::new(engine, fen, human, Duration::from_secs_f64(movetime)))
+}
+
+/// The main function parses the arguments, initializes the engine,
+/// and then runs the game loop until the window is closed.
+#[macroquad::main(window_conf)]
+async fn main() {
+    let mut state = match parse_args() {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+
+    loop {
+        state.handle(event::poll());
+        state.update();
+
+        render::draw(&state);
+        next_frame().await;
+    }
+}
@@ -0,0 +1,176 @@
+use macroquad::color::Color as Rgba;
+use macroquad::input::mouse_position;
+use macroquad::shapes::{draw_circle, draw_circle_lines, draw_rectangle};
+use macroquad::text::{draw_text, measure_text};
+use macroquad::window::clear_background;
+
+use chess::prelude::*;
+use chess::piece::Piece;
+use chess::square::Square;
+
+use crate::state::State;
+
+/// The size of a square of the board, in pixels.
+pub const SQUARE_SIZE: f32 = 72.0;
+
+/// The height of the status bar below the board, in pixels.
+pub const STATUS_HEIGHT: f32 = 40.0;
+
+/// The size of the window, in pixels.
+pub const WINDOW_WIDTH: f32 = 8.0 * SQUARE_SIZE;
+pub const WINDOW_HEIGHT: f32 = 8.0 * SQUARE_SIZE + STATUS_HEIGHT;
+
+/// The pieces offered by the promotion selector, from the promotion square towards the center of the board.
+const PROMOTIONS: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+/// The colors used to draw the board.
+const LIGHT_SQUARE: Rgba = Rgba::new(0.93, 0.85, 0.71, 1.0);
+const DARK_SQUARE: Rgba = Rgba::new(0.71, 0.53, 0.39, 1.0);
+const SELECTED: Rgba = Rgba::new(0.95, 0.90, 0.30, 0.6);
+const LAST_MOVE: Rgba = Rgba::new(0.60, 0.80, 0.30, 0.45);
+const CHECK: Rgba = Rgba::new(0.90, 0.15, 0.15, 0.7);
+const DESTINATION: Rgba = Rgba::new(0.10, 0.10, 0.10, 0.3);
+const OVERLAY: Rgba = Rgba::new(0.0, 0.0, 0.0, 0.5);
+const SELECTOR: Rgba = Rgba::new(0.85, 0.85, 0.85, 1.0);
+const STATUS_BACKGROUND: Rgba = Rgba::new(0.15, 0.15, 0.15, 1.0);
+const STATUS_TEXT: Rgba = Rgba::new(0.95, 0.95, 0.95, 1.0);
+
+/// The size of the font of the coordinates drawn on the edges of the board.
+const LABEL_SIZE: f32 = 16.0;
+
+/// The colors used to draw the pieces of each side: the body and the letter of the piece.
+const WHITE_PIECE: (Rgba, Rgba) = (Rgba::new(0.98, 0.98, 0.98, 1.0), Rgba::new(0.10, 0.10, 0.10, 1.0));
+const BLACK_PIECE: (Rgba, Rgba) = (Rgba::new(0.10, 0.10, 0.10, 1.0), Rgba::new(0.98, 0.98, 0.98, 1.0));
+
+// ================================ pub impl
+
+/// Returns the square under the given position of the window, if any.
+/// The board is seen from black's side when flipped.
+pub fn square_at((x, y): (f32, f32), flipped: bool) -> Option<Square> {
+    if !(0.0..8.0 * SQUARE_SIZE).contains(&x) || !(0.0..8.0 * SQUARE_SIZE).contains(&y) {
+        return None;
+    }
+
+    let (file, rank) = ((x / SQUARE_SIZE) as i8, 7 - (y / SQUARE_SIZE) as i8);
+    Some(if flipped {Square::from((7 - file, 7 - rank))} else {Square::from((file, rank))})
+}
+
+/// Returns the position of the top-left corner of the given square in the window.
+pub fn square_origin(sq: Square, flipped: bool) -> (f32, f32) {
+    let (file, rank) = if flipped {(7 - sq.x(), sq.y())} else {(sq.x(), 7 - sq.y())};
+    (f32::from(file) * SQUARE_SIZE, f32::from(rank) * SQUARE_SIZE)
+}
+
+/// Returns the piece of the promotion selector shown for a pawn promoting on the given square
+/// that is under the given position of the window, if any.
+pub fn promotion_at(pos: (f32, f32), to: Square, flipped: bool) -> Option<Piece> {
+    let sq = square_at(pos, flipped)?;
+    PROMOTIONS.iter().zip(selector_squares(to)).find(|&(_, selector)| selector == sq).map(|(&piece, _)| piece)
+}
+
+/// Draws the piece of the given color at the given position, which is the center of the piece.
+pub fn draw_piece(color: Color, piece: Piece, (x, y): (f32, f32)) {
+    let (body, letter) = match color {
+        Color::White => WHITE_PIECE,
+        Color::Black => BLACK_PIECE,
+    };
+
+    let radius = SQUARE_SIZE * 0.38;
+    draw_circle(x, y, radius, body);
+    draw_circle_lines(x, y, radius, 2.0, letter);
+
+    let text = piece.as_char(Color::White).to_string();
+    let font_size = (SQUARE_SIZE * 0.6) as u16;
+    let dims = measure_text(&text, None, font_size, 1.0);
+    draw_text(&text, x - dims.width / 2.0, y + dims.offset_y / 2.0, f32::from(font_size), letter);
+}
+
+/// Draws the game: the board, its pieces, the hints given to the user and the status bar.
+pub fn draw(state: &State) {
+    clear_background(STATUS_BACKGROUND);
+
+    let flipped = state.flipped();
+    let center = |sq| {
+        let (x, y) = square_origin(sq, flipped);
+        (x + SQUARE_SIZE / 2.0, y + SQUARE_SIZE / 2.0)
+    };
+    let fill = |sq, color| {
+        let (x, y) = square_origin(sq, flipped);
+        draw_rectangle(x, y, SQUARE_SIZE, SQUARE_SIZE, color);
+    };
+
+    // The board is released before the status is computed, which reads it again.
+    let board = state.board();
+    let checked_king = Some(board.king_sq(board.get_side_to_move())).filter(|_| board.get_checkers().not_empty());
+    let dragged = state.selected().filter(|_| state.dragging());
+
+    for sq in Square::iter() {
+        let dark = (sq.x() + sq.y()) % 2 == 0;
+        fill(sq, if dark {DARK_SQUARE} else {LIGHT_SQUARE});
+        draw_labels(sq, flipped, if dark {LIGHT_SQUARE} else {DARK_SQUARE});
+
+        if state.last_move().is_some_and(|mv| mv.from() == sq || mv.to() == sq) {
+            fill(sq, LAST_MOVE);
+        }
+        if state.selected() == Some(sq) {
+            fill(sq, SELECTED);
+        }
+        if checked_king == Some(sq) {
+            fill(sq, CHECK);
+        }
+
+        if let Some((color, piece)) = board.get_piece(sq).filter(|_| dragged != Some(sq)) {
+            draw_piece(color, piece, center(sq));
+        }
+    }
+
+    // Dots on the squares the selected piece may go to.
+    for sq in state.destinations() {
+        let (x, y) = center(sq);
+        draw_circle(x, y, SQUARE_SIZE * 0.15, DESTINATION);
+    }
+
+    // The dragged piece follows the mouse.
+    if let Some((color, piece)) = dragged.and_then(|sq| board.get_piece(sq)) {
+        draw_piece(color, piece, mouse_position());
+    }
+
+    // The promotion selector, over the rest of the board.
+    if let Some(to) = state.promotion() {
+        draw_rectangle(0.0, 0.0, 8.0 * SQUARE_SIZE, 8.0 * SQUARE_SIZE, OVERLAY);
+
+        let color = board.get_side_to_move();
+        for (&piece, sq) in PROMOTIONS.iter().zip(selector_squares(to)) {
+            fill(sq, SELECTOR);
+            draw_piece(color, piece, center(sq));
+        }
+    }
+    drop(board);
+
+    draw_text(&state.status_text(), 12.0, 8.0 * SQUARE_SIZE + STATUS_HEIGHT * 0.65, 24.0, STATUS_TEXT);
+}
+
+// ================================ impl
+
+/// Draws the coordinates of the square if it is on the edges of the board: its file when it is on the bottom row,
+/// and its rank when it is on the left column, so that they follow the orientation of the board.
+fn draw_labels(sq: Square, flipped: bool, color: Rgba) {
+    let (x, y) = square_origin(sq, flipped);
+
+    if y == 7.0 * SQUARE_SIZE {
+        let file = char::from(b'a' + sq.x() as u8).to_string();
+        let dims = measure_text(&file, None, LABEL_SIZE as u16, 1.0);
+        draw_text(&file, x + SQUARE_SIZE - dims.width - 3.0, y + SQUARE_SIZE - 4.0, LABEL_SIZE, color);
+    }
+    if x == 0.0 {
+        let rank = (sq.y() + 1).to_string();
+        draw_text(&rank, x + 3.0, y + LABEL_SIZE, LABEL_SIZE, color);
+    }
+}
+
+/// Returns the squares of the promotion selector shown for a pawn promoting on the given square:
+/// the promotion square and the three next ones towards the center of the board.
+fn selector_squares(to: Square) -> impl Iterator<Item = Square> {
+    let dy = if to.y() == 7 {-1} else {1};
+    (0..4).map(move |i| Square::from((to.x(), to.y() + i * dy)))
+}
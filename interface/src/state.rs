@@ -0,0 +1,262 @@
+use std::sync::RwLockReadGuard;
+use std::time::{Duration, Instant};
+
+use chess::prelude::*;
+use chess::square::Square;
+use engine::Engine;
+
+use crate::event::Event;
+use crate::render;
+
+//#################################################################################################
+//
+//                                         struct State
+//
+//#################################################################################################
+
+/// The state of a game between the user and the engine.
+#[derive(Debug)]
+pub struct State {
+    // The engine, which holds the board of the game.
+    engine: Engine,
+    // The fen of the position new games start from.
+    fen: String,
+    // The color played by the user.
+    human: Color,
+    // True if the board is seen from black's side.
+    flipped: bool,
+    // The time the engine thinks for on each of its moves.
+    movetime: Duration,
+    // The legal moves of the user, empty when it is not their turn.
+    legals: Vec<Move>,
+    // The last move played, if any.
+    last_move: Option<Move>,
+    // The square of the piece the user selected, if any.
+    selected: Option<Square>,
+    // True if the selected piece is being dragged.
+    dragging: bool,
+    // The squares of the pawn move waiting for the user to pick a promotion, if any.
+    promotion: Option<(Square, Square)>,
+    // When the engine started to think, if it is thinking.
+    thinking_since: Option<Instant>,
+}
+
+// ================================ pub impl
+
+impl State {
+    /// Creates the state of a new game, where the engine plays against the user.
+    pub fn new(engine: Engine, fen: &str, human: Color, movetime: Duration) -> State {
+        let mut state = State {
+            engine,
+            fen: fen.to_string(),
+            human,
+            flipped: human == Color::Black,
+            movetime,
+            legals: Vec::new(),
+            last_move: None,
+            selected: None,
+            dragging: false,
+            promotion: None,
+            thinking_since: None,
+        };
+
+        state.refresh_legals();
+        state
+    }
+
+    /// Returns a read lock to the board of the game.
+    pub fn board(&self) -> RwLockReadGuard<'_, Board> {
+        self.engine.read_board()
+    }
+
+    /// Returns true if the board is seen from black's side, which it is at first when the user plays black.
+    pub fn flipped(&self) -> bool {
+        self.flipped
+    }
+
+    /// Returns the square of the piece selected by the user, if any.
+    pub fn selected(&self) -> Option<Square> {
+        self.selected
+    }
+
+    /// Returns true if the selected piece is being dragged.
+    pub fn dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Returns the squares the selected piece may move to.
+    pub fn destinations(&self) -> Vec<Square> {
+        let mut destinations = Vec::new();
+
+        // Promotions give the same destination several times.
+        for mv in self.legals.iter().filter(|mv| Some(mv.from()) == self.selected) {
+            if !destinations.contains(&mv.to()) {
+                destinations.push(mv.to());
+            }
+        }
+
+        destinations
+    }
+
+    /// Returns the last move played, if any.
+    pub fn last_move(&self) -> Option<Move> {
+        self.last_move
+    }
+
+    /// Returns the destination of the pawn move waiting for a promotion to be picked, if any.
+    pub fn promotion(&self) -> Option<Square> {
+        self.promotion.map(|(_, to)| to)
+    }
+
+    /// Returns the text describing the state of the game, shown below the board.
+    pub fn status_text(&self) -> String {
+        match self.board().status() {
+            Status::Playing if self.thinking_since.is_some() => format!("The engine is thinking... depth {}", self.engine.analysis().depth),
+            Status::Playing if self.promotion.is_some() => "Pick the piece to promote to.".to_string(),
+            Status::Playing => "Your move.".to_string(),
+            Status::Draw => "The game is drawn. Press N for a new game.".to_string(),
+            Status::Win(color) if color == self.human => "You won! Press N for a new game.".to_string(),
+            Status::Win(_) => "The engine won. Press N for a new game.".to_string(),
+        }
+    }
+
+    /// Handles an input of the user.
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            Event::Press(pos) if self.promotion.is_some() => self.pick_promotion(pos),
+            Event::Press(pos) => {
+                if let Some(sq) = render::square_at(pos, self.flipped()) {
+                    self.press(sq);
+                }
+            },
+            Event::Release(pos) => self.release(render::square_at(pos, self.flipped())),
+            Event::NewGame => self.new_game(),
+            Event::Flip => self.flipped = !self.flipped,
+            Event::None => (),
+        }
+    }
+
+    /// Lets the engine play when it is its turn: starts it, and plays its move once it thought for long enough.
+    pub fn update(&mut self) {
+        let engine_turn = {
+            let board = self.board();
+            board.status().is_playing() && board.get_side_to_move() != self.human
+        };
+
+        if !engine_turn {
+            return;
+        }
+
+        match self.thinking_since {
+            None => {
+                if self.engine.start() {
+                    self.thinking_since = Some(Instant::now());
+                } else if let Some(mv) = self.engine.poll().get_move() {
+                    // A move was found in the book.
+                    self.play(mv);
+                }
+            },
+            Some(since) if since.elapsed() >= self.movetime => {
+                self.engine.stop();
+                self.thinking_since = None;
+
+                if let Some(mv) = self.engine.poll().get_move() {
+                    self.play(mv);
+                }
+            },
+            Some(_) => (),
+        }
+    }
+}
+
+// ================================ impl
+
+impl State {
+    /// Moves the selected piece to the pressed square if it can go there,
+    /// or else picks up the piece of the user standing on it.
+    fn press(&mut self, sq: Square) {
+        if self.selected.is_some() && self.destinations().contains(&sq) {
+            self.try_move(sq);
+            return;
+        }
+
+        if self.legals.iter().any(|mv| mv.from() == sq) {
+            self.selected = Some(sq);
+            self.dragging = true;
+        } else {
+            self.selected = None;
+        }
+    }
+
+    /// Drops the dragged piece on the given square. When it is dropped outside of the board,
+    /// or on a square it cannot go to, it goes back to its square and stays selected.
+    fn release(&mut self, sq: Option<Square>) {
+        if !self.dragging {
+            return;
+        }
+        self.dragging = false;
+
+        if let Some(sq) = sq.filter(|sq| self.destinations().contains(sq)) {
+            self.try_move(sq);
+        }
+    }
+
+    /// Moves the selected piece to the given square, which must be one of its destinations.
+    /// For promotions, the move is played once the user picks the piece to promote to.
+    fn try_move(&mut self, to: Square) {
+        let from = self.selected.unwrap();
+        let mv = self.legals.iter().copied().find(|mv| mv.from() == from && mv.to() == to).unwrap();
+
+        if mv.is_promote() {
+            self.promotion = Some((from, to));
+            self.selected = None;
+            self.dragging = false;
+        } else {
+            self.play(mv);
+        }
+    }
+
+    /// Promotes to the piece of the selector under the given position, or cancels the promotion
+    /// if there is none.
+    fn pick_promotion(&mut self, pos: (f32, f32)) {
+        let (from, to) = self.promotion.take().unwrap();
+
+        if let Some(piece) = render::promotion_at(pos, to, self.flipped()) {
+            let mv = self.legals.iter().copied().find(|mv| mv.from() == from && mv.to() == to && mv.get_promote() == piece);
+            self.play(mv.unwrap());
+        }
+    }
+
+    /// Plays a move on the board.
+    fn play(&mut self, mv: Move) {
+        self.selected = None;
+        self.dragging = false;
+        self.last_move = Some(mv);
+        self.engine.write_board().do_move(mv);
+        self.refresh_legals();
+    }
+
+    /// Computes the legal moves of the user, if it is their turn and the game is not over.
+    fn refresh_legals(&mut self) {
+        let mut legals = Vec::new();
+
+        let board = self.engine.read_board();
+        if board.status().is_playing() && board.get_side_to_move() == self.human {
+            movegen::legals(&board, &mut legals);
+        }
+        drop(board);
+
+        self.legals = legals;
+    }
+
+    /// Starts a new game from the starting position.
+    fn new_game(&mut self) {
+        self.selected = None;
+        self.dragging = false;
+        self.promotion = None;
+        self.last_move = None;
+        self.thinking_since = None;
+        *self.engine.write_board() = Board::new(&self.fen).unwrap();
+        self.refresh_legals();
+    }
+}
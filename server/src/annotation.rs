@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use chess::square::Square;
+
+/// The maximum number of arrows, and of highlighted squares, of an annotation.
+const MAX_MARKS: usize = 64;
+
+/// The maximum length of the comment of an annotation, in characters.
+const MAX_COMMENT_LEN: usize = 1024;
+
+/// Parses a square from the attribute of the given name of a json object, returning it in its canonical form, such as "e4".
+fn parse_square(obj: &Map<String, Value>, name: &str) -> Result<String> {
+    let sq = obj.get(name).ok_or_else(|| Error::msg(format!("No attribute {} in json value.", name)))?
+        .as_str().ok_or_else(|| Error::msg(format!("{} attribute is not a string.", name)))?;
+
+    Ok(Square::from_str(sq).map_err(|_| Error::msg(format!("{} attribute is not a valid square.", name)))?.to_string())
+}
+
+/// Parses the marks of the attribute of the given name of a json object, an array of objects, each parsed by parse_mark.
+/// Without the attribute, there are no marks.
+fn parse_marks<T>(obj: &Map<String, Value>, name: &str, parse_mark: impl Fn(&Map<String, Value>) -> Result<T>) -> Result<Vec<T>> {
+    let marks = match obj.get(name) {
+        Some(marks) => marks.as_array().ok_or_else(|| Error::msg(format!("{} attribute is not an array.", name)))?,
+        None => return Ok(Vec::new()),
+    };
+
+    if marks.len() > MAX_MARKS {
+        return Err(Error::msg(format!("Too many {}.", name)));
+    }
+
+    marks.iter().map(|mark| {
+        parse_mark(mark.as_object().ok_or_else(|| Error::msg(format!("{} attribute contains a value that is not an object.", name)))?)
+    }).collect()
+}
+
+//#################################################################################################
+//
+//                                        enum MarkColor
+//
+//#################################################################################################
+
+/// The color an arrow or a highlighted square is drawn with.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkColor {
+    #[default]
+    Green,
+    Red,
+    Blue,
+    Yellow,
+}
+
+// ================================ impl
+
+impl MarkColor {
+    /// Parses the color of a mark from its json object, green being the default.
+    fn from_json(obj: &Map<String, Value>) -> Result<Self> {
+        Ok(match obj.get("color").map(Value::as_str) {
+            None => Self::default(),
+            Some(Some("green")) => Self::Green,
+            Some(Some("red")) => Self::Red,
+            Some(Some("blue")) => Self::Blue,
+            Some(Some("yellow")) => Self::Yellow,
+            Some(_) => return Err(Error::msg("Invalid color.")),
+        })
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct Annotation
+//
+//#################################################################################################
+
+/// An arrow drawn from a square to another.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Arrow {
+    pub from: String,
+    pub to: String,
+    pub color: MarkColor,
+}
+
+/// A highlighted square.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Highlight {
+    pub square: String,
+    pub color: MarkColor,
+}
+
+/// The annotation of a position of the game, shared by all the clients of the room:
+/// arrows and highlighted squares drawn on the board, and a text comment.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Annotation {
+    #[serde(default)]
+    pub arrows: Vec<Arrow>,
+    #[serde(default)]
+    pub highlights: Vec<Highlight>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+// ================================ pub impl
+
+impl Annotation {
+    /// Parses an annotation from the json object of a message. Missing attributes are empty,
+    /// so that an annotate message without any of them clears the annotation of the position.
+    pub fn from_json(obj: &Map<String, Value>) -> Result<Self> {
+        let arrows = parse_marks(obj, "arrows", |arrow| Ok(Arrow {
+            from: parse_square(arrow, "from")?,
+            to: parse_square(arrow, "to")?,
+            color: MarkColor::from_json(arrow)?,
+        }))?;
+
+        let highlights = parse_marks(obj, "highlights", |highlight| Ok(Highlight {
+            square: parse_square(highlight, "square")?,
+            color: MarkColor::from_json(highlight)?,
+        }))?;
+
+        let comment = match obj.get("comment") {
+            Some(comment) => Some(comment.as_str().ok_or(Error::msg("comment attribute is not a string."))?.trim().to_string()),
+            None => None,
+        }.filter(|comment| !comment.is_empty());
+
+        if comment.as_ref().is_some_and(|comment| comment.chars().count() > MAX_COMMENT_LEN) {
+            return Err(Error::msg("The comment is too long."));
+        }
+
+        Ok(Self {arrows, highlights, comment})
+    }
+
+    /// Returns true if the annotation has neither marks nor a comment.
+    pub fn is_empty(&self) -> bool {
+        self.arrows.is_empty() && self.highlights.is_empty() && self.comment.is_none()
+    }
+}
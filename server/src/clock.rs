@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use chess::color::Color;
+use chess::game::TimeControl;
+
+//#################################################################################################
+//
+//                                         struct Clock
+//
+//#################################################################################################
+
+/// The clocks of both players, ticking on the server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Clock {
+    // The remaining time of each player, as of the last time their clock was stopped.
+    remaining: [Duration; 2],
+    // The time added after each move.
+    increment: Duration,
+    // The color whose clock is ticking, and since when. Clocks are paused when saved.
+    #[serde(skip)]
+    running: Option<(Color, Instant)>,
+}
+
+// ================================ pub impl
+
+impl Clock {
+    /// Creates a new paused clock with the given time control.
+    pub fn new(time_control: TimeControl) -> Self {
+        Self {
+            remaining: [time_control.base; 2],
+            increment: time_control.increment,
+            running: None,
+        }
+    }
+
+    /// Returns the remaining time of the player of the given color.
+    pub fn remaining(&self, color: Color) -> Duration {
+        match self.running {
+            Some((running, since)) if running == color => {
+                self.remaining[color as usize].saturating_sub(since.elapsed())
+            },
+            _ => self.remaining[color as usize],
+        }
+    }
+
+    /// Returns the color of the player that ran out of time, if any.
+    pub fn flagged(&self) -> Option<Color> {
+        Color::COLORS.iter().copied().find(|&color| self.remaining(color) == Duration::ZERO)
+    }
+
+    /// Returns the time left before the ticking clock runs out, if a clock is ticking.
+    pub fn time_to_flag(&self) -> Option<Duration> {
+        self.running.map(|(color, _)| self.remaining(color))
+    }
+
+    /// To be called after a player made a move: stops their clock,
+    /// adds the increment to it, and starts the opponent's clock.
+    pub fn switch(&mut self, mover: Color) {
+        self.pause();
+        self.remaining[mover as usize] = self.remaining[mover as usize].saturating_add(self.increment);
+        self.running = Some((mover.invert(), Instant::now()));
+    }
+
+    /// Stops the ticking clock, if any.
+    pub fn pause(&mut self) {
+        if let Some((color, _)) = self.running {
+            self.remaining[color as usize] = self.remaining(color);
+            self.running = None;
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl From<&Clock> for Value {
+    /// Converts the clock into it's json representation: the remaining
+    /// milliseconds of both players, and the color whose clock is ticking.
+    fn from(clock: &Clock) -> Self {
+        serde_json::json!({
+            "white": clock.remaining(Color::White).as_millis() as u64,
+            "black": clock.remaining(Color::Black).as_millis() as u64,
+            "running": clock.running.map_or(Value::Null, |(color, _)| color.to_string().into()),
+        })
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn switch() {
+        let mut clock = Clock::new(TimeControl {base: Duration::from_secs(60), increment: Duration::from_secs(2)});
+        assert_eq!(clock.time_to_flag(), None);
+        assert_eq!(Value::from(&clock)["running"], Value::Null);
+
+        // White moved: its clock gets the increment, and black's starts ticking.
+        clock.switch(Color::White);
+        assert_eq!(clock.remaining(Color::White), Duration::from_secs(62));
+        thread::sleep(Duration::from_millis(20));
+        assert!(clock.remaining(Color::Black) < Duration::from_secs(60));
+        assert!(clock.time_to_flag().unwrap() < Duration::from_secs(60));
+        assert_eq!(Value::from(&clock)["running"], Color::Black.to_string());
+
+        // A paused clock does not tick.
+        clock.pause();
+        let black = clock.remaining(Color::Black);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.remaining(Color::Black), black);
+        assert_eq!(clock.time_to_flag(), None);
+
+        // Black moved: the increment is added to what was left, and white's clock starts ticking.
+        clock.switch(Color::Black);
+        assert_eq!(clock.remaining(Color::Black), black + Duration::from_secs(2));
+        assert!(clock.remaining(Color::White) <= Duration::from_secs(62));
+        assert_eq!(Value::from(&clock)["running"], Color::White.to_string());
+    }
+
+    #[test]
+    fn flag() {
+        let mut clock = Clock::new(TimeControl {base: Duration::from_millis(20), increment: Duration::ZERO});
+        assert_eq!(clock.flagged(), None);
+
+        clock.switch(Color::White);
+        thread::sleep(Duration::from_millis(40));
+        assert_eq!(clock.flagged(), Some(Color::Black));
+        assert_eq!(clock.time_to_flag(), Some(Duration::ZERO));
+
+        // The flag stays down once the clock is paused.
+        clock.pause();
+        assert_eq!(clock.remaining(Color::Black), Duration::ZERO);
+        assert_eq!(clock.flagged(), Some(Color::Black));
+    }
+}
@@ -1,97 +1,255 @@
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use anyhow::{Error, Result};
-use log;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::Instrument;
 use warp::ws::Message;
 
+use chess::book::Book;
+use chess::game::Outcome;
+use chess::pgn::Pgn;
 use chess::prelude::*;
-use engine::Engine;
+use chess::variant::Variant;
+use engine::{Analysis, Engine, EngineConfig, EvalBreakdown, EvalMode, Experience, MoveReport, SearchDone, Stats};
 
-use crate::messages::{Command, Response};
+use crate::clock::Clock;
+use crate::annotation::Annotation;
+use crate::gamelog::{GameLog, MoveRecord};
+use crate::messages::{self, Client, Command, ErrorCode, ExportFormat, LimitsUpdate, Order, Response};
+use crate::metrics::Metrics;
+use crate::store::{SavedGame, Store};
 
 //#################################################################################################
 //
-//                                       struct History
+//                                       enum Termination
 //
 //#################################################################################################
 
-/// A struct keeping a history of played and/or undoed moves, as well
-/// as their textual representations.
-#[derive(Debug)]
-struct History {
-    moves: Vec<Move>,
-    strings: Vec<String>,
-    cursor: usize,
+/// The ways players can end a game, other than on the board or on time, as they are saved.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Termination {
+    WhiteResigned,
+    BlackResigned,
+    DrawAgreed,
 }
 
-// ================================ impl
+// ================================ pub impl
 
-impl History {
-    /// Creates a new empty move history.
-    fn new() -> Self {
-        Self {
-            moves: Vec::new(),
-            strings: Vec::new(),
-            cursor: 0,
+impl Termination {
+    /// Returns the way the players ended the game with the given outcome, if they did.
+    pub fn from_outcome(outcome: Outcome) -> Option<Self> {
+        match (outcome.termination, outcome.winner) {
+            (chess::game::Termination::Resignation, Some(Color::White)) => Some(Self::BlackResigned),
+            (chess::game::Termination::Resignation, Some(Color::Black)) => Some(Self::WhiteResigned),
+            (chess::game::Termination::Agreement, _) => Some(Self::DrawAgreed),
+            _ => None,
         }
     }
 
-    /// Pushes a new move to the history, losing all undoed moves.
-    fn push(&mut self, mv: Move) {
-        // If we are not at the end of the timeline.
-        if self.cursor != self.moves.len() {
-            // Turns out the move has already been done in the past future, redo it.
-            if mv == self.moves[self.cursor] {
-                self.redo().ok();
-                return;
-            }
-
-            // Throw out all future moves, we are changing timeline.
-            self.moves.truncate(self.cursor);
-            self.strings.truncate(self.cursor);
+    /// Ends the given game the way the players did.
+    pub fn apply(self, game: &mut chess::game::Game) -> Result<()> {
+        match self {
+            Self::WhiteResigned => game.resign(Color::White)?,
+            Self::BlackResigned => game.resign(Color::Black)?,
+            Self::DrawAgreed => {
+                let color = game.get_board().get_side_to_move();
+                game.offer_draw(color)?;
+                game.accept_draw(color.invert())?;
+            },
         }
 
-        // Push a new move.
-        self.moves.push(mv);
-        self.strings.push(mv.to_string());
-        self.cursor += 1;
+        Ok(())
     }
+}
+
+/// The time between two analysis messages sent to the client requesting an analysis.
+const ANALYSIS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The number of moves broadcast as deltas after which the full state is broadcast again,
+/// so that clients that missed a message eventually get back in sync.
+const RESYNC_PERIOD: usize = 16;
+
+//#################################################################################################
+//
+//                                       struct GameConfig
+//
+//#################################################################################################
+
+/// The configuration used to create the game of every room.
+#[derive(Debug)]
+pub struct GameConfig {
+    /// The fen of the starting position.
+    pub fen: String,
+    /// The variant the games are played with.
+    pub variant: Variant,
+    /// The path to the polyglot book used by the engines, the built-in book being used if none is given.
+    pub book_path: Option<String>,
+    /// Whether the engines play without any opening book.
+    pub no_book: bool,
+    /// The path to the network file used by the engines.
+    pub net_path: String,
+    /// The way the engines evaluate positions.
+    pub eval_mode: EvalMode,
+    /// Whether the search threads of the engines are pinned to physical cores.
+    pub affinity: bool,
+    /// The experience shared by the engines of the games, if any.
+    pub experience: Option<Arc<Experience>>,
+}
+
+// ================================ impl
 
-    /// Undo a move.
-    fn undo(&mut self) -> Result<Move> {
-        // Check there is something to undo.
-        if self.cursor == 0 {
-            return Err(Error::msg("There is no move to undo"));
+impl GameConfig {
+    /// Opens the book used by the engines, if any.
+    pub fn open_book(&self) -> Result<Option<Book>> {
+        if self.no_book {
+            return Ok(None);
         }
 
-        // Decrement the cursor and return that move.
-        self.cursor -= 1;
-        Ok(self.moves[self.cursor])
+        Ok(Some(match &self.book_path {
+            Some(path) => Book::open(Path::new(path))?,
+            None => Book::builtin(),
+        }))
     }
+}
 
-    /// Redo a move.
-    fn redo(&mut self) -> Result<Move> {
-        // Check that we are not at the end of the timeline.
-        if self.cursor == self.moves.len() {
-            return Err(Error::msg("There is no move to redo"));
-        }
+//#################################################################################################
+//
+//                                         struct Limits
+//
+//#################################################################################################
+
+/// The resources the engines of the rooms may use, and the durations they may think for.
+/// They may be changed at runtime by the operator of the server.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The resources of the engine of each room, and of each analysis.
+    pub engine: EngineConfig,
+    /// The duration the engine thinks for when a client does not give one.
+    pub movetime_default: Duration,
+    /// The maximum duration a client may make the engine think for.
+    pub max_think: Duration,
+}
+
+// ================================ pub impl
 
-        // Get the move to redo and increment the cursor.
-        let mv = self.moves[self.cursor];
-        self.cursor += 1;
-        Ok(mv)
+impl Limits {
+    /// Applies the changes requested by the operator.
+    pub fn apply(&mut self, update: &LimitsUpdate) {
+        self.engine.threads = update.threads.unwrap_or(self.engine.threads);
+        self.engine.hash_mb = update.hash_mb.unwrap_or(self.engine.hash_mb);
+        self.movetime_default = update.movetime_default.unwrap_or(self.movetime_default);
+        self.max_think = update.max_think.unwrap_or(self.max_think);
+    }
+
+    /// Returns the duration the engine thinks for, when asked to think for the given one, if any.
+    pub fn think_duration(&self, duration: Option<Duration>) -> Duration {
+        duration.unwrap_or(self.movetime_default).min(self.max_think)
     }
 }
 
 // ================================ traits impl
 
-impl From<&History> for Value {
-    /// Converts the history into it's json representation: an array of the 
-    /// moves currently played.
-    fn from(history: &History) -> Self {
-        Self::from(&history.strings[..history.cursor])
+impl From<&Limits> for Value {
+    /// Converts the limits to their json representation.
+    fn from(limits: &Limits) -> Self {
+        serde_json::json!({
+            "threads": limits.engine.threads,
+            "hashMb": limits.engine.hash_mb,
+            "movetimeDefault": limits.movetime_default.as_secs_f64(),
+            "maxThinkSeconds": if limits.max_think == Duration::MAX {Value::Null} else {limits.max_think.as_secs_f64().into()},
+        })
+    }
+}
+
+/// Gets the warp message describing the book entries of a position: their moves and weights,
+/// as well as the percentage of the time the engine would play them, best first.
+fn book_msg(board: &Board, entries: &[(Move, u16)]) -> Message {
+    let total: u32 = entries.iter().map(|&(_, weight)| u32::from(weight)).sum();
+
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|&(_, weight)| std::cmp::Reverse(weight));
+
+    Message::text(serde_json::json!({
+        "book": {
+            "fen": board.to_string(),
+            "entries": entries.iter().map(|&(mv, weight)| serde_json::json!({
+                "move": mv.to_string(),
+                "san": board.to_san(mv),
+                "weight": weight,
+                "percentage": if total == 0 {0.0} else {100.0 * f64::from(weight) / f64::from(total)},
+            })).collect::<Vec<_>>(),
+        },
+    }).to_string())
+}
+
+/// Gets the warp message describing the static evaluation of a position by the network, without searching it,
+/// along with its material balance. Scores are in pawns, from white's point of view, as shown by the eval bar.
+fn eval_msg(board: &Board, breakdown: &EvalBreakdown) -> Message {
+    Message::text(serde_json::json!({
+        "eval": {
+            "fen": board.to_string(),
+            "total": breakdown.total,
+            "material": breakdown.material,
+            "positional": breakdown.positional,
+        },
+    }).to_string())
+}
+
+//#################################################################################################
+//
+//                                        struct Analyser
+//
+//#################################################################################################
+
+/// An analysis of an arbitrary position, run by an engine of its own
+/// and streamed to the client that requested it.
+#[derive(Debug)]
+struct Analyser {
+    engine: Engine,
+    dest: usize,
+    depth: Option<u8>,
+    running: Arc<AtomicBool>,
+    // The statistics of the engine, as of the last update of the metrics.
+    stats: Stats,
+}
+
+// ================================ impl
+
+impl Analyser {
+    /// Stops the analysis, if it is running.
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        self.engine.stop();
+    }
+
+    /// Gets the warp message describing the current state of the analysis,
+    /// stopping it first if it has reached its depth limit.
+    fn get_msg(&mut self) -> Message {
+        if self.depth.is_some_and(|limit| self.engine.analysis().depth >= limit) {
+            self.stop();
+        }
+
+        let Analysis {depth, seldepth, nodes, lines} = self.engine.analysis();
+
+        Message::text(serde_json::json!({
+            "analysis": {
+                "fen": self.engine.read_board().to_string(),
+                "depth": depth,
+                "seldepth": seldepth,
+                "nodes": nodes,
+                "lines": lines.iter().map(|line| serde_json::json!({
+                    "score": line.score,
+                    "pv": line.pv.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+                "done": !self.running.load(Ordering::Acquire),
+            },
+        }).to_string())
     }
 }
 
@@ -101,50 +259,134 @@ impl From<&History> for Value {
 //
 //#################################################################################################
 
-/// Manages the state of the game.
+/// Manages the state of the game of a room.
 #[derive(Debug)]
 pub struct Game {
+    room: String,
     engine: Engine,
-    history: History,
-    tx: UnboundedSender<Command>,
+    game: chess::game::Game,
+    clock: Option<Clock>,
+    strength: u8,
+    limits: Limits,
+    start_fen: String,
+    variant: Variant,
+    store: Option<Arc<Store>>,
+    seats: [Option<String>; 2],
+    // The annotations of the positions of the history, keyed by their number of moves played.
+    annotations: BTreeMap<usize, Annotation>,
+    // The color requesting to take back its last move, if any.
+    takeback: Option<Color>,
+    net_path: String,
+    eval_mode: EvalMode,
+    affinity: bool,
+    analyser: Option<Analyser>,
+    deltas: usize,
+    tx: UnboundedSender<Order>,
+    metrics: Arc<Metrics>,
+    game_log: Option<Arc<GameLog>>,
+    // The time the last move was played, or the game was opened.
+    last_move: Instant,
+    // Whether the game is counted as in progress by the metrics.
+    in_progress: bool,
+    // The statistics of the engine, as of the last update of the metrics.
+    stats: Stats,
 }
 
 // ================================ pub impl
 
 impl Game {
-    /// Creates a new game with the default position.
+    /// Creates the game of a room with the configured position and its own engine, using the given
+    /// resources, or restores the one saved in the store if there is one.
     /// Returns a channel used to pass messages to the game state.
     /// Takes a channel in argument, used by the game state to respond
-    /// to incoming messages.
-    pub fn new(engine: Engine, tx: UnboundedSender<Result<Response>>) -> UnboundedSender<Command> {
+    /// to incoming messages. The game and its engines are accounted for in the given metrics,
+    /// and its moves are recorded in the given game log, if any.
+    pub fn new(room: String, config: &GameConfig, limits: Limits, store: Option<Arc<Store>>, metrics: Arc<Metrics>, game_log: Option<Arc<GameLog>>, tx: UnboundedSender<Result<Response>>) -> Result<UnboundedSender<Order>> {
+        let board = Board::with_variant(&config.fen, config.variant)?;
+        let variant = config.variant;
+        let mut engine = Engine::with_config(board.clone(), config.open_book()?, &config.net_path, limits.engine)?;
+        engine.set_eval_mode(config.eval_mode);
+        engine.set_affinity(config.affinity);
+        engine.set_experience(config.experience.clone());
+        let (net_path, eval_mode, affinity) = (config.net_path.clone(), config.eval_mode, config.affinity);
+
         // Creates the communication channels used to send messages to the game state.
         let (game_tx, mut game_rx) = mpsc::unbounded_channel();
         let self_tx = game_tx.clone();
 
         // Spawn a new task, reacting to incoming client messages.
+        let span = tracing::info_span!("game", room = %room);
         tokio::spawn(async move {
             // The game state itself.
+            let start_fen = engine.read_board().to_string();
             let mut game = Self {
+                room,
+                game: chess::game::Game::new(board),
                 engine,
-                history: History::new(),
+                clock: None,
+                strength: engine::MAX_STRENGTH,
+                limits,
+                start_fen,
+                variant,
+                seats: [None, None],
+                annotations: BTreeMap::new(),
+                takeback: None,
+                net_path,
+                eval_mode,
+                affinity,
+                analyser: None,
+                deltas: 0,
+                store,
                 tx: self_tx,
+                metrics,
+                game_log,
+                last_move: Instant::now(),
+                in_progress: false,
+                stats: Stats::default(),
             };
 
+            // Restores the saved game, if any.
+            if let Err(e) = game.restore() {
+                tracing::error!(room = %game.room, "Could not restore the saved game: {}", e);
+            }
+            game.update_metrics();
+
             // While there are incoming messages, process them and respond
             // through the given tx channel.
-            while let Some(command) = game_rx.recv().await {
-                if let Err(e) = tx.send(game.react(command)) {
-                    log::error!("Game could not respond to engine: {}", e);
+            while let Some(Order {client, command}) = game_rx.recv().await {
+                // The room was closed, drop the game and its engine.
+                if let Command::Close = command {
+                    break;
+                }
+
+                // Rejected commands from clients are answered with the reason of the rejection.
+                let response = match (game.react(client.as_ref(), command), client) {
+                    (Err(e), Some(client)) => Ok(Response::Error {
+                        dest: client.uid,
+                        code: ErrorCode::of(&e),
+                        message: e.to_string(),
+                    }),
+                    (response, _) => response,
+                };
+                game.update_metrics();
+
+                if let Err(e) = tx.send(response) {
+                    tracing::error!(room = %game.room, "Game could not respond to engine: {}", e);
                     break;
                 }
             }
-        });
+        }.instrument(span));
 
-        game_tx
+        Ok(game_tx)
     }
 
-    /// Reacts to a given command and returns the response.
-    pub fn react(&mut self, command: Command) -> Result<Response> {
+    /// Reacts to a given command, issued by the given client or by the server
+    /// itself, and returns the response.
+    pub fn react(&mut self, client: Option<&Client>, command: Command) -> Result<Response> {
+        if let Some(client) = client {
+            self.authorize(client, &command)?;
+        }
+
         match command {
             // On welcoming a new connection, send him the welcome message.
             Command::Welcome(dest) => {
@@ -156,43 +398,38 @@ impl Game {
             // Request to play a move.
             Command::Play(s) => {
                 // Parses and performs the move.
-                let mv = self.engine.read_board().parse_move(s.as_str()).map_err(|_| Error::msg("Unable to parse move."))?;
-                self.engine.write_board().do_move(mv);
-                self.history.push(mv);
+                let mv = self.engine.read_board().parse_move(s.as_str()).map_err(|_| messages::reject(ErrorCode::IllegalMove, "Unable to parse move."))?;
+                return self.play(mv, false);
             },
-            // Request to start the engine for a given amount of seconds.
-            Command::Think(seconds) => {
+            // Request to start the engine for a given amount of seconds, or for the default duration.
+            Command::Think(duration) => {
                 // Starts the engine.
-                if self.engine.poll().is_thinking() {
+                if self.is_over() {
+                    return Err(messages::reject(ErrorCode::GameOver, "The game is over."));
+                } else if self.engine.poll().is_thinking() {
                     return Err(Error::msg("Engine is already thinking."));
                 }
 
-                // Start the engine.
-                if self.engine.start() {
-                    // Starts a task that will stop the engine later.
-                    let tx = self.tx.clone();
-                    tokio::spawn(async move {
-                        tokio::time::sleep(Duration::from_secs_f64(seconds)).await;
-                        tx.send(Command::Stop).ok();
-                    });
+                // Start the engine, and stop it when the search ends.
+                let done = self.engine.think(self.limits.think_duration(duration));
+                if self.engine.poll().is_thinking() {
+                    self.notify_search_done(done, false);
                 }
             },
-            Command::ThinkDo(seconds) => {
+            Command::ThinkDo(duration) => {
                 // Starts the engine.
-                if self.engine.poll().is_thinking() {
+                if self.is_over() {
+                    return Err(messages::reject(ErrorCode::GameOver, "The game is over."));
+                } else if self.engine.poll().is_thinking() {
                     return Err(Error::msg("Engine is already thinking."));
                 }
 
-                // Start the engine.
-                if self.engine.start() {
-                    // Starts a task that will play the engine's move later the engine later.
-                    let tx = self.tx.clone();
-                    tokio::spawn(async move {
-                        tokio::time::sleep(Duration::from_secs_f64(seconds)).await;
-                        tx.send(Command::Do).ok();
-                    });
+                // Start the engine, and play its move when the search ends.
+                let done = self.engine.think(self.limits.think_duration(duration));
+                if self.engine.poll().is_thinking() {
+                    self.notify_search_done(done, true);
                 } else {
-                    self.tx.send(Command::Do).ok();
+                    self.tx.send(Command::Do.into()).ok();
                     return Ok(Response::None)
                 }
             },
@@ -208,36 +445,844 @@ impl Game {
             Command::Do => {
                 self.engine.stop();
                 let mv = self.engine.poll().get_move().ok_or(Error::msg("Engine has no preferred move."))?;
-                self.engine.write_board().do_move(mv);
-                self.history.push(mv);
+                return self.play(mv, true);
             },
-            // Request to undo move.
+            // Request to undo move, which also takes back the end of the game, if any.
             Command::Undo => {
-                let mv = self.history.undo()?;
-                self.engine.write_board().undo_move(mv);
+                let mv = self.game.undo()?;
+                self.takeback = None;
+                self.engine.undo_move(mv);
+                self.pause_clock();
+                self.save();
             },
             // Request to redo the last undoed move.
             Command::Redo => {
-                let mv = self.history.redo()?;
-                self.engine.write_board().do_move(mv);
+                let mv = self.game.redo()?;
+                self.takeback = None;
+                self.engine.advance(mv);
+                self.pause_clock();
+                self.save();
+            },
+            // Request to set up a position, starting a new game from it without a clock.
+            Command::SetPosition(fen) => {
+                let board = Board::with_variant(&fen, self.variant)?;
+
+                *self.engine.write_board() = board.clone();
+                self.game = chess::game::Game::new(board);
+                self.start_fen = fen;
+                self.clock = None;
+                self.annotations.clear();
+                self.takeback = None;
+                self.last_move = Instant::now();
+                self.save();
+
+                tracing::info!(room = %self.room, fen = %self.start_fen, "A new position was set up.");
+            },
+            // Request to annotate the current position, replacing its annotation, or clearing it if the new one is empty.
+            Command::Annotate(annotation) => {
+                let ply = self.game.get_moves().len();
+                if annotation.is_empty() {
+                    self.annotations.remove(&ply);
+                } else {
+                    self.annotations.insert(ply, annotation);
+                }
+                self.save();
+            },
+            // Request to export the game, sent back to the client only.
+            Command::Export(format) => {
+                let dest = client.ok_or(Error::msg("Only clients can export the game."))?.uid;
+                return Ok(Response::Send {
+                    dest,
+                    msg: messages::exported(format, &self.export(format)),
+                });
+            },
+            // The game is downloaded through the save route, the export is sent back to it.
+            Command::Save {format, reply} => {
+                reply.send(self.export(format)).ok();
+                return Ok(Response::None);
+            },
+            // Request to set the time control, or to remove the clock.
+            Command::Clock(time_control) => {
+                self.clock = time_control.map(Clock::new);
+                self.save();
+            },
+            // The operator changed the resource limits, which stops the engine if it is thinking.
+            Command::Limits(limits) => {
+                self.engine.set_config(limits.engine);
+                self.limits = limits;
+            },
+            // The operator reloaded the network and the book, they are used from the next searches.
+            Command::Reload {book, net} => {
+                self.engine.set_book(book);
+                self.engine.set_net(net.clone());
+
+                if let Some(analyser) = self.analyser.as_mut() {
+                    analyser.engine.set_net(net);
+                }
+                return Ok(Response::None);
+            },
+            // Request to change the strength of the engine.
+            Command::Strength(level) => {
+                self.engine.set_strength(level);
+                self.strength = level;
+            },
+            // Request to take a seat, or to leave all seats to spectate.
+            Command::Sit(color) => {
+                let token = client.ok_or(Error::msg("Only clients can take seats."))?.token.clone();
+
+                if let Some(color) = color {
+                    match &self.seats[color as usize] {
+                        Some(seated) if *seated != token => return Err(Error::msg("This seat is already taken.")),
+                        _ => self.seats[color as usize] = Some(token),
+                    }
+                } else {
+                    for seat in self.seats.iter_mut().filter(|seat| seat.as_ref() == Some(&token)) {
+                        *seat = None;
+                    }
+                }
+
+                self.save();
+            },
+            // Request to offer a draw to the opponent.
+            Command::OfferDraw => {
+                if self.is_over() {
+                    return Err(messages::reject(ErrorCode::GameOver, "The game is over."));
+                }
+
+                let color = self.player_color(client);
+                self.game.offer_draw(color)?;
+            },
+            // Request to accept the draw offered by the opponent, ending the game.
+            Command::AcceptDraw => {
+                let color = self.answering_color(client)?;
+                self.game.accept_draw(color)?;
+                self.terminate();
+            },
+            // Request to decline the draw offered by the opponent.
+            Command::DeclineDraw => {
+                let color = self.answering_color(client)?;
+                self.game.decline_draw(color)?;
+            },
+            // Request to take back the last move of the client's color, or the last move if it is not seated,
+            // granted at once when no one is seated at the other color, as the engine always agrees.
+            Command::RequestTakeback => {
+                let color = self.seat_of(client).unwrap_or_else(|| self.engine.read_board().get_side_to_move().invert());
+                self.takeback_plies(color)?;
+
+                if self.seats[color.invert() as usize].is_some() {
+                    self.takeback = Some(color);
+                } else {
+                    self.take_back(color)?;
+                }
             },
+            // Request to accept the takeback requested by the opponent, undoing its last move.
+            Command::AcceptTakeback => {
+                let requester = self.answering_takeback(client)?;
+                self.take_back(requester)?;
+            },
+            // Request to decline the takeback requested by the opponent.
+            Command::DeclineTakeback => {
+                self.answering_takeback(client)?;
+                self.takeback = None;
+            },
+            // Request to resign, ending the game.
+            Command::Resign => {
+                if self.is_over() {
+                    return Err(messages::reject(ErrorCode::GameOver, "The game is over."));
+                }
+
+                let color = self.player_color(client);
+                self.game.resign(color)?;
+                self.terminate();
+            },
+            // Request to analyse a position, the current one by default.
+            Command::Analyse {fen, multipv, depth} => {
+                let dest = client.ok_or(Error::msg("Only clients can request analyses."))?.uid;
+                let board = match fen {
+                    Some(fen) => Board::with_variant(&fen, self.variant)?,
+                    None => self.engine.read_board().clone(),
+                };
+
+                if !board.status().is_playing() {
+                    return Err(Error::msg("Cannot analyse a finished game."));
+                }
+
+                // Reuses the engine of the previous analysis, if any.
+                let (mut engine, stats) = match self.analyser.take() {
+                    Some(mut analyser) => {
+                        analyser.stop();
+                        analyser.engine.set_config(self.limits.engine);
+                        *analyser.engine.write_board() = board;
+                        (analyser.engine, analyser.stats)
+                    },
+                    None => {
+                        let mut engine = Engine::with_config(board, None, &self.net_path, self.limits.engine)?;
+                        engine.set_eval_mode(self.eval_mode);
+                        engine.set_affinity(self.affinity);
+                        (engine, Stats::default())
+                    },
+                };
+
+                engine.set_multipv(multipv);
+                engine.set_depth_limit(depth);
+                engine.start();
+
+                // Starts a task periodically requesting analysis messages, until the analysis is stopped.
+                let running = Arc::new(AtomicBool::new(true));
+                let (tx, task_running) = (self.tx.clone(), running.clone());
+                tokio::spawn(async move {
+                    loop {
+                        tokio::time::sleep(ANALYSIS_INTERVAL).await;
+                        if !task_running.load(Ordering::Acquire) || tx.send(Command::AnalysisTick.into()).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                self.analyser = Some(Analyser {engine, dest, depth, running, stats});
+                return Ok(Response::None);
+            },
+            // Request to stop the analysis, sending its final state.
+            Command::StopAnalysis => {
+                let analyser = self.analyser.as_mut().filter(|analyser| analyser.running.load(Ordering::Acquire))
+                    .ok_or(Error::msg("There is no analysis running."))?;
+
+                if client.is_some_and(|client| client.uid != analyser.dest) {
+                    return Err(Error::msg("Only the client that requested the analysis can stop it."));
+                }
+
+                analyser.stop();
+                return Ok(Response::Analysis {
+                    dest: analyser.dest,
+                    msg: analyser.get_msg(),
+                });
+            },
+            // Time to send the state of the analysis.
+            Command::AnalysisTick => {
+                return Ok(match &mut self.analyser {
+                    Some(analyser) if analyser.running.load(Ordering::Acquire) => Response::Analysis {
+                        dest: analyser.dest,
+                        msg: analyser.get_msg(),
+                    },
+                    _ => Response::None,
+                });
+            },
+            // Request to probe the opening book in a position, the current one by default.
+            Command::BookProbe(fen) => {
+                let dest = client.ok_or(Error::msg("Only clients can probe the book."))?.uid;
+                let board = match fen {
+                    Some(fen) => Board::with_variant(&fen, self.variant)?,
+                    None => self.engine.read_board().clone(),
+                };
+
+                let book = self.engine.get_book().ok_or(Error::msg("No opening book is loaded."))?;
+                return Ok(Response::Book {
+                    dest,
+                    msg: book_msg(&board, &book.probe(&board)),
+                });
+            },
+            // Request to evaluate a position without searching it, the current one by default.
+            Command::Eval(fen) => {
+                let dest = client.ok_or(Error::msg("Only clients can request an evaluation."))?.uid;
+                let board = match fen {
+                    Some(fen) => Board::with_variant(&fen, self.variant)?,
+                    None => self.engine.read_board().clone(),
+                };
+
+                return Ok(Response::Send {
+                    dest,
+                    msg: eval_msg(&board, &self.engine.evaluate_detailed(&board)),
+                });
+            },
+            // A client left the room, stop the analysis it requested.
+            Command::Leave(uid) => {
+                if let Some(analyser) = self.analyser.as_mut().filter(|analyser| analyser.dest == uid) {
+                    analyser.stop();
+                }
+                return Ok(Response::None);
+            },
+            // A player's time may have run out.
+            Command::CheckFlag => {
+                match self.clock.as_mut() {
+                    Some(clock) if clock.flagged().is_some() => clock.pause(),
+                    // The check is outdated, nothing changed.
+                    _ => return Ok(Response::None),
+                }
+
+                tracing::info!(room = %self.room, "A player ran out of time.");
+                self.save();
+            },
+            // The engine's search may have ended, its move is played if it was asked to.
+            Command::SearchDone(play) => {
+                // The notification is outdated, the search was stopped.
+                if !self.engine.is_search_done() {
+                    return Ok(Response::None);
+                }
+
+                self.engine.stop();
+                if play {
+                    let mv = self.engine.poll().get_move().ok_or(Error::msg("Engine has no preferred move."))?;
+                    return self.play(mv, true);
+                }
+            },
+            // Closing is handled by the game's task.
+            Command::Close => return Ok(Response::None),
         }
 
-        // Broadcast the new message.
+        // Broadcast the new state.
+        self.deltas = 0;
         Ok(Response::Broadcast(self.get_msg()))
     }
 }
 
 impl Game {
+    /// Checks that the client is allowed to issue the command. When a seat is
+    /// taken, only the seated player can move that color, seated players can only
+    /// play the moves of their own color, and when any seat is taken, only seated
+    /// players can change the course of the game.
+    fn authorize(&self, client: &Client, command: &Command) -> Result<()> {
+        let is_seated = |color: Color| self.seats[color as usize].as_ref() == Some(&client.token);
+        let to_move = self.engine.read_board().get_side_to_move();
+
+        match command {
+            Command::Play(_) if Color::COLORS.iter().any(|&color| is_seated(color)) && !is_seated(to_move) => {
+                return Err(messages::reject(ErrorCode::Unauthorized, "It is not your turn to move."));
+            },
+            Command::Play(_) | Command::Do | Command::ThinkDo(_) if self.seats[to_move as usize].is_some() && !is_seated(to_move) => {
+                return Err(messages::reject(ErrorCode::Unauthorized, "Only the seated player can move this color."));
+            },
+            Command::Undo | Command::Redo | Command::Clock(_) | Command::Strength(_) | Command::SetPosition(_) |
+            Command::OfferDraw | Command::AcceptDraw | Command::DeclineDraw | Command::Resign |
+            Command::RequestTakeback | Command::AcceptTakeback | Command::DeclineTakeback => {
+                let any_seated = self.seats.iter().any(Option::is_some);
+                if any_seated && !Color::COLORS.iter().any(|&color| is_seated(color)) {
+                    return Err(messages::reject(ErrorCode::Unauthorized, "Only seated players can do this."));
+                }
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Plays a move, chosen by the engine or by a player, if the game is not over, and updates the clocks.
+    /// Returns the delta to broadcast, or the full state when more than the move changed, or periodically.
+    fn play(&mut self, mv: Move, by_engine: bool) -> Result<Response> {
+        if self.is_over() {
+            return Err(messages::reject(ErrorCode::GameOver, "The game is over."));
+        }
+
+        let mover = self.engine.read_board().get_side_to_move();
+        let draw_offer = self.game.get_draw_offer();
+        let replaces_undone = self.game.get_undone().first().is_some_and(|&undone| undone != mv);
+        self.game.play(mv)?;
+        let takeback = self.takeback.take();
+        self.engine.advance(mv);
+        self.record(by_engine);
+
+        // The annotations of the undone moves the move replaced are forgotten with them.
+        if replaces_undone {
+            self.annotations.split_off(&self.game.get_moves().len());
+        }
+
+        if self.game.status().is_playing() {
+            if let Some(clock) = self.clock.as_mut() {
+                clock.switch(mover);
+            }
+            self.schedule_flag_check();
+        } else {
+            self.pause_clock();
+        }
+
+        self.save();
+
+        if draw_offer.is_some() || takeback.is_some() || self.is_over() || self.deltas >= RESYNC_PERIOD {
+            self.deltas = 0;
+            return Ok(Response::Broadcast(self.get_msg()));
+        }
+
+        self.deltas += 1;
+        Ok(Response::MovePlayed {
+            mv,
+            san: self.game.get_sans().last().cloned().unwrap_or_default(),
+            fen_after: self.engine.read_board().to_string(),
+            clock: self.clock.as_ref().map_or(Value::Null, Value::from),
+        })
+    }
+
+    /// Returns true if the game ended, either on the board, because a player ran out of time,
+    /// or because the players ended it.
+    fn is_over(&self) -> bool {
+        self.game.is_over() || self.clock.as_ref().and_then(Clock::flagged).is_some()
+    }
+
+    /// Returns the color the client plays: the one of its seat, or the side to move
+    /// if the client is not seated, or if the command was issued by the server.
+    fn player_color(&self, client: Option<&Client>) -> Color {
+        self.seat_of(client).unwrap_or_else(|| self.engine.read_board().get_side_to_move())
+    }
+
+    /// Returns the color of the seat of the client, if it is seated.
+    fn seat_of(&self, client: Option<&Client>) -> Option<Color> {
+        client.and_then(|client| {
+            Color::COLORS.iter().copied().find(|&color| self.seats[color as usize].as_ref() == Some(&client.token))
+        })
+    }
+
+    /// Checks that there is a draw offer the client can answer, and returns the color answering it.
+    fn answering_color(&self, client: Option<&Client>) -> Result<Color> {
+        let offerer = self.game.get_draw_offer().ok_or_else(|| Error::msg("There is no draw offer."))?;
+
+        // Seated players cannot answer their own offers.
+        if client.is_some() && self.seats[offerer as usize].is_some() && self.player_color(client) == offerer {
+            return Err(Error::msg("Cannot answer your own draw offer."));
+        }
+
+        Ok(offerer.invert())
+    }
+
+    /// Returns the number of moves to undo to take back the last move of the given color: one if it
+    /// played the last move, two if its opponent answered it.
+    fn takeback_plies(&self, color: Color) -> Result<usize> {
+        let plies = if self.engine.read_board().get_side_to_move() == color {2} else {1};
+
+        if self.game.get_moves().len() < plies {
+            return Err(Error::msg("There is no move to take back."));
+        }
+        Ok(plies)
+    }
+
+    /// Checks that there is a takeback request the client can answer, and returns the color that requested it.
+    fn answering_takeback(&self, client: Option<&Client>) -> Result<Color> {
+        let requester = self.takeback.ok_or_else(|| Error::msg("There is no takeback request."))?;
+
+        // Seated players cannot answer their own requests.
+        if client.is_some() && self.player_color(client) == requester {
+            return Err(Error::msg("Cannot answer your own takeback request."));
+        }
+
+        Ok(requester)
+    }
+
+    /// Takes back the last move of the given color, and the answer of its opponent if there is one. Rewinding
+    /// the board of the engine with the game cancels its search, if any, and the clocks are paused.
+    fn take_back(&mut self, color: Color) -> Result<()> {
+        let plies = self.takeback_plies(color)?;
+
+        for _ in 0..plies {
+            let mv = self.game.undo()?;
+            self.engine.undo_move(mv);
+        }
+
+        self.takeback = None;
+        self.pause_clock();
+        self.save();
+
+        tracing::info!(room = %self.room, plies, "A move was taken back.");
+        Ok(())
+    }
+
+    /// Ends the game after the players ended it, stopping the engine and the clocks.
+    fn terminate(&mut self) {
+        if self.engine.poll().is_thinking() {
+            self.engine.stop();
+        }
+        self.pause_clock();
+        self.save();
+
+        tracing::info!(room = %self.room, "The game was ended by the players.");
+    }
+
+    /// Returns the json representation of the result of the game, if it is over:
+    /// the color of the winner, if any, and the reason the game ended.
+    fn result(&self) -> Value {
+        let (winner, reason) = match self.game.outcome() {
+            Some(Outcome {winner, termination}) => (winner, match termination {
+                chess::game::Termination::Checkmate => "checkmate",
+                chess::game::Termination::DrawnPosition => "draw",
+                chess::game::Termination::Resignation => "resignation",
+                chess::game::Termination::Agreement => "agreement",
+                chess::game::Termination::Time => "time",
+                chess::game::Termination::VariantWin => "variant",
+            }),
+            None => match self.clock.as_ref().and_then(Clock::flagged) {
+                Some(color) => (Some(color.invert()), "time"),
+                None => return Value::Null,
+            },
+        };
+
+        serde_json::json!({
+            "winner": winner.map_or(Value::Null, |color| color.to_string().into()),
+            "reason": reason,
+        })
+    }
+
+    /// Returns the game in the given format: the current position as a fen, or the whole game as a PGN,
+    /// with the players, the date, the result and the comments of the engine's moves and of the annotations.
+    fn export(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Pgn => self.to_pgn().to_string(),
+            ExportFormat::Fen => self.engine.read_board().to_string(),
+        }
+    }
+
+    /// Returns the game as a PGN. Players are unknown, unless the engine chose moves for them. The moves of the
+    /// engine are commented with its searches, when it still has their reports, followed by the comments of
+    /// the annotations of the positions they lead to.
+    fn to_pgn(&self) -> Pgn {
+        let mut pgn = self.game.to_pgn();
+        pgn.set_tag("Event", "Rush server game");
+        pgn.set_tag("Site", &self.room);
+        pgn.set_date_today();
+        if self.variant != Variant::Standard {
+            pgn.set_tag("Variant", &self.variant.to_string());
+        }
+
+        self.engine.report().annotate(&mut pgn);
+
+        let moves = self.game.get_moves().len();
+        for (&ply, annotation) in self.annotations.range(1..=moves) {
+            if let Some(comment) = &annotation.comment {
+                let comment = match pgn.get_comment(ply - 1) {
+                    Some(engine) => format!("{} {}", engine, comment),
+                    None => comment.clone(),
+                };
+                pgn.set_comment_at(ply - 1, &comment);
+            }
+        }
+
+        if let Some(color) = self.clock.as_ref().and_then(Clock::flagged).filter(|_| self.game.outcome().is_none()) {
+            pgn.set_result(Status::Win(color.invert()));
+        }
+
+        pgn
+    }
+
+    /// Pauses the clock, if the game is timed.
+    fn pause_clock(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.pause();
+        }
+    }
+
+    /// Starts a task that will notify the game when the engine's search ends.
+    fn notify_search_done(&self, done: SearchDone, play: bool) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            done.await;
+            tx.send(Command::SearchDone(play).into()).ok();
+        });
+    }
+
+    /// Starts a task that will check for the ticking clock running out.
+    fn schedule_flag_check(&self) {
+        if let Some(duration) = self.clock.as_ref().and_then(Clock::time_to_flag) {
+            let tx = self.tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                tx.send(Command::CheckFlag.into()).ok();
+            });
+        }
+    }
+
+    /// Restores the game saved in the store, if there is one.
+    fn restore(&mut self) -> Result<()> {
+        let saved = match self.store.as_ref().map(|store| store.load(&self.room)).transpose()?.flatten() {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+
+        // Replays the whole history, then undoes the moves past the cursor.
+        let mut game = chess::game::Game::new(Board::with_variant(&saved.fen, self.variant)?);
+
+        for s in saved.moves.iter() {
+            let mv = game.get_board().parse_move(s).map_err(|_| Error::msg("Unable to parse saved move."))?;
+            game.play(mv)?;
+        }
+
+        while game.get_moves().len() > saved.cursor {
+            game.undo()?;
+        }
+
+        if let Some(termination) = saved.termination {
+            termination.apply(&mut game)?;
+        }
+
+        *self.engine.write_board() = game.get_board().clone();
+        self.game = game;
+        self.clock = saved.clock;
+        self.seats = saved.seats;
+        self.annotations = saved.annotations;
+        self.start_fen = saved.fen;
+
+        tracing::info!(room = %self.room, "Restored saved game.");
+        Ok(())
+    }
+
+    /// Saves the game to the store, if there is one.
+    fn save(&self) {
+        if let Some(store) = &self.store {
+            let saved = SavedGame {
+                fen: self.start_fen.clone(),
+                moves: self.game.get_moves().iter().chain(self.game.get_undone()).map(Move::to_string).collect(),
+                cursor: self.game.get_moves().len(),
+                clock: self.clock.clone(),
+                seats: self.seats.clone(),
+                termination: self.game.outcome().and_then(Termination::from_outcome),
+                annotations: self.annotations.clone(),
+            };
+
+            if let Err(e) = store.save(&self.room, &saved) {
+                tracing::error!(room = %self.room, "Could not save the game: {}", e);
+            }
+        }
+    }
+
+    /// Records the move just played in the game log, if there is one, with the search of the engine if it chose it.
+    fn record(&mut self, by_engine: bool) {
+        let now = Instant::now();
+        let time = now.duration_since(std::mem::replace(&mut self.last_move, now)).as_secs_f64();
+
+        let (Some(game_log), Some(report)) = (self.game_log.as_ref(), self.engine.report().get_moves().last()) else {
+            return;
+        };
+
+        let report = if by_engine {report.clone()} else {
+            MoveReport {book: false, depth: None, score: None, pv: Vec::new(), nodes: 0, ..report.clone()}
+        };
+
+        game_log.record(&self.room, &MoveRecord {
+            fen: report.fen,
+            mv: report.mv.to_string(),
+            san: report.san,
+            engine: by_engine,
+            book: report.book,
+            score: report.score,
+            depth: report.depth,
+            pv: report.pv.iter().map(Move::to_string).collect(),
+            nodes: report.nodes,
+            time,
+        });
+
+        if by_engine {
+            tracing::info!(room = %self.room, mv = %report.mv, score = ?report.score, depth = ?report.depth, book = report.book, "The engine played a move.");
+        }
+    }
+
+    /// Updates the metrics of the server with the progress of the game, and the searches of its engines.
+    fn update_metrics(&mut self) {
+        let in_progress = !self.is_over();
+        if in_progress != self.in_progress {
+            self.metrics.set_game_in_progress(in_progress);
+            self.in_progress = in_progress;
+        }
+
+        let stats = self.engine.stats();
+        self.metrics.add_searches(&self.stats, &stats);
+        self.stats = stats;
+
+        if let Some(analyser) = self.analyser.as_mut() {
+            let stats = analyser.engine.stats();
+            self.metrics.add_searches(&analyser.stats, &stats);
+            analyser.stats = stats;
+        }
+    }
+
     /// Gets the warp message to send to a client to completely describe the current state of the game.
     fn get_msg(&self) -> Message {
         Message::text(serde_json::json!({
+            "room": self.room,
             "fen": self.engine.read_board().to_string(),
-            "history": Value::from(&self.history),
-            "end": !matches!(self.engine.read_board().status(), Status::Playing),
+            "variant": self.variant.to_string(),
+            "history": self.game.get_moves().iter().map(Move::to_string).collect::<Vec<_>>(),
+            "annotation": self.annotations.get(&self.game.get_moves().len()),
+            "end": self.is_over(),
+            "result": self.result(),
+            "drawOffer": self.game.get_draw_offer().map_or(Value::Null, |color| color.to_string().into()),
+            "takeback": self.takeback.map_or(Value::Null, |color| color.to_string().into()),
+            "clock": self.clock.as_ref().map_or(Value::Null, Value::from),
+            "seats": {
+                "white": self.seats[Color::White as usize].is_some(),
+                "black": self.seats[Color::Black as usize].is_some(),
+            },
+            "flagged": self.clock.as_ref().and_then(Clock::flagged).map_or(Value::Null, |color| color.to_string().into()),
             "thinking": self.engine.poll().is_thinking(),
+            "strength": self.strength,
             "engineMove": self.engine.poll().get_move().map_or(Value::Null, |mv| mv.to_string().into()),
             "engineStatus": self.engine.poll().to_string(),
         }).to_string())
     }
-}
\ No newline at end of file
+}
+
+// ================================ traits impl
+
+impl Drop for Game {
+    /// Accounts for the last searches of the engines, and stops counting the game as in progress.
+    fn drop(&mut self) {
+        self.update_metrics();
+        if self.in_progress {
+            self.metrics.set_game_in_progress(false);
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                             tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use chess::game::TimeControl;
+
+    use super::*;
+
+    /// The network the engines of the tests evaluate positions with.
+    const NET: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../engine/nets/nnue.bin");
+
+    /// Creates the game of a room from the default position, without a store nor a clock.
+    fn game() -> Game {
+        let board = Board::new(engine::STARTING_FEN).unwrap();
+        let (tx, _) = mpsc::unbounded_channel();
+
+        Game {
+            room: "test".to_string(),
+            engine: Engine::new(board.clone(), None, NET).unwrap(),
+            game: chess::game::Game::new(board.clone()),
+            clock: None,
+            strength: engine::MAX_STRENGTH,
+            limits: Limits {
+                engine: EngineConfig::default(),
+                movetime_default: Duration::from_secs(1),
+                max_think: Duration::MAX,
+            },
+            start_fen: board.to_string(),
+            variant: Variant::Standard,
+            store: None,
+            seats: [None, None],
+            annotations: BTreeMap::new(),
+            takeback: None,
+            net_path: NET.to_string(),
+            eval_mode: EvalMode::default(),
+            affinity: false,
+            analyser: None,
+            deltas: 0,
+            tx,
+            metrics: Arc::new(Metrics::default()),
+            game_log: None,
+            last_move: Instant::now(),
+            in_progress: false,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Returns the client with the given connection id, identified by a token made of that digit.
+    fn client(uid: usize) -> Client {
+        Client {uid, token: uid.to_string().repeat(messages::TOKEN_LEN)}
+    }
+
+    /// Returns the code of the error the command was rejected with, if it was.
+    fn rejection(game: &mut Game, client: &Client, command: Command) -> Option<ErrorCode> {
+        game.react(Some(client), command).err().map(|e| ErrorCode::of(&e))
+    }
+
+    #[tokio::test]
+    async fn authorize() {
+        let mut game = game();
+        let (white, black, spectator) = (client(1), client(2), client(3));
+
+        // Anyone may play while no seat is taken.
+        assert_eq!(rejection(&mut game, &spectator, Command::Play("e2e4".to_string())), None);
+        assert_eq!(rejection(&mut game, &spectator, Command::Undo), None);
+
+        // A taken seat cannot be taken again by another client, but may be taken again by its player.
+        assert_eq!(rejection(&mut game, &white, Command::Sit(Some(Color::White))), None);
+        assert!(rejection(&mut game, &black, Command::Sit(Some(Color::White))).is_some());
+        assert_eq!(rejection(&mut game, &white, Command::Sit(Some(Color::White))), None);
+        assert_eq!(game.seats[Color::White as usize], Some(white.token.clone()));
+        assert_eq!(rejection(&mut game, &black, Command::Sit(Some(Color::Black))), None);
+
+        // Only the seated player moves its color, and only on its turn.
+        assert_eq!(rejection(&mut game, &spectator, Command::Play("e2e4".to_string())), Some(ErrorCode::Unauthorized));
+        assert_eq!(rejection(&mut game, &black, Command::Play("e2e4".to_string())), Some(ErrorCode::Unauthorized));
+        assert_eq!(rejection(&mut game, &white, Command::Play("e2e4".to_string())), None);
+        assert_eq!(rejection(&mut game, &white, Command::Play("e7e5".to_string())), Some(ErrorCode::Unauthorized));
+
+        // Clients with other tokens cannot change the course of the game.
+        assert_eq!(rejection(&mut game, &spectator, Command::Undo), Some(ErrorCode::Unauthorized));
+        assert_eq!(rejection(&mut game, &spectator, Command::Resign), Some(ErrorCode::Unauthorized));
+        assert_eq!(game.game.get_moves().len(), 1);
+        assert!(game.game.status().is_playing());
+    }
+
+    #[tokio::test]
+    async fn draw_offers() {
+        let mut game = game();
+        let (white, black) = (client(1), client(2));
+        game.react(Some(&white), Command::Sit(Some(Color::White))).unwrap();
+        game.react(Some(&black), Command::Sit(Some(Color::Black))).unwrap();
+
+        // There is nothing to answer before a draw is offered.
+        assert!(rejection(&mut game, &black, Command::AcceptDraw).is_some());
+
+        // Seated players cannot answer their own offers, their opponents decline them.
+        assert_eq!(rejection(&mut game, &white, Command::OfferDraw), None);
+        assert_eq!(game.game.get_draw_offer(), Some(Color::White));
+        assert!(rejection(&mut game, &white, Command::AcceptDraw).is_some());
+        assert!(rejection(&mut game, &white, Command::DeclineDraw).is_some());
+        assert_eq!(rejection(&mut game, &black, Command::DeclineDraw), None);
+        assert_eq!(game.game.get_draw_offer(), None);
+
+        // A move answers the offer of the opponent.
+        assert_eq!(rejection(&mut game, &black, Command::OfferDraw), None);
+        assert_eq!(rejection(&mut game, &white, Command::Play("e2e4".to_string())), None);
+        assert_eq!(game.game.get_draw_offer(), None);
+
+        // Accepting the offer ends the game, which cannot go on.
+        assert_eq!(rejection(&mut game, &white, Command::OfferDraw), None);
+        assert_eq!(rejection(&mut game, &black, Command::AcceptDraw), None);
+        assert_eq!(game.game.outcome(), Some(Outcome {winner: None, termination: chess::game::Termination::Agreement}));
+        assert_eq!(rejection(&mut game, &black, Command::Play("e7e5".to_string())), Some(ErrorCode::GameOver));
+        assert_eq!(rejection(&mut game, &white, Command::OfferDraw), Some(ErrorCode::GameOver));
+    }
+
+    #[tokio::test]
+    async fn takebacks() {
+        let mut game = game();
+        let (white, black) = (client(1), client(2));
+        game.react(Some(&white), Command::Sit(Some(Color::White))).unwrap();
+        game.react(Some(&black), Command::Sit(Some(Color::Black))).unwrap();
+        game.react(Some(&white), Command::Clock(Some(TimeControl {base: Duration::from_secs(60), increment: Duration::from_secs(1)}))).unwrap();
+
+        for mv in ["e2e4", "e7e5", "g1f3"] {
+            let player = if game.engine.read_board().get_side_to_move() == Color::White {&white} else {&black};
+            game.react(Some(player), Command::Play(mv.to_string())).unwrap();
+        }
+        assert!(game.clock.as_ref().unwrap().time_to_flag().is_some());
+
+        // Taking back a move needs the opponent's consent, and a declined request changes nothing.
+        assert_eq!(rejection(&mut game, &white, Command::RequestTakeback), None);
+        assert_eq!(game.takeback, Some(Color::White));
+        assert!(rejection(&mut game, &white, Command::AcceptTakeback).is_some());
+        assert_eq!(rejection(&mut game, &black, Command::DeclineTakeback), None);
+        assert_eq!(game.takeback, None);
+        assert_eq!(game.game.get_moves().len(), 3);
+
+        // Black to move takes back its own move and white's answer to it.
+        assert_eq!(rejection(&mut game, &black, Command::RequestTakeback), None);
+        assert_eq!(rejection(&mut game, &white, Command::AcceptTakeback), None);
+        assert_eq!(game.takeback, None);
+        assert_eq!(game.game.get_moves().len(), 1);
+
+        // The board of the engine and its report are rewound with the game, and the clocks are paused.
+        let board = game.engine.read_board().clone();
+        assert_eq!(board.to_string(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(game.engine.report().get_moves().len(), 1);
+        assert_eq!(game.clock.as_ref().unwrap().time_to_flag(), None);
+
+        // Black plays again from there.
+        assert_eq!(rejection(&mut game, &black, Command::Play("c7c5".to_string())), None);
+        assert_eq!(game.game.get_moves().len(), 2);
+    }
+}
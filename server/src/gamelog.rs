@@ -0,0 +1,91 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use anyhow::{Error, Result};
+use serde::Serialize;
+
+use crate::store;
+
+//#################################################################################################
+//
+//                                       struct MoveRecord
+//
+//#################################################################################################
+
+/// The record of a move played in a game, as it is written to the log of the game.
+#[derive(Debug, Serialize)]
+pub struct MoveRecord {
+    /// The fen of the position the move was played in.
+    pub fen: String,
+    /// The move played, such as "e2e4".
+    #[serde(rename = "move")]
+    pub mv: String,
+    /// The move played in standard algebraic notation, such as "e4".
+    pub san: String,
+    /// Whether the move was chosen by the engine, rather than by a player.
+    pub engine: bool,
+    /// Whether the engine played the move from its book, without searching.
+    pub book: bool,
+    /// The score of the move, in pawns from the point of view of the engine, if it searched it.
+    pub score: Option<f32>,
+    /// The depth the engine searched the move to, if it searched it.
+    pub depth: Option<u8>,
+    /// The principal variation the engine expected, starting with the move, if it searched it.
+    pub pv: Vec<String>,
+    /// The number of nodes the engine searched to choose the move, 0 if it did not search it.
+    pub nodes: u64,
+    /// The number of seconds elapsed since the previous move, or since the game was opened.
+    pub time: f64,
+}
+
+//#################################################################################################
+//
+//                                        struct GameLog
+//
+//#################################################################################################
+
+/// A log of the moves of the game of each room, written as a json object per line
+/// to a file per room, so that games can be audited and rebuilt afterwards. The files
+/// are written by a thread of their own, so that the games never wait for the disk.
+#[derive(Debug)]
+pub struct GameLog {
+    tx: Sender<(String, String)>,
+}
+
+// ================================ pub impl
+
+impl GameLog {
+    /// Creates a new game log, writing to files in the directory at the given path,
+    /// which is created if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| Error::msg(format!("Cannot create game log directory: {}.", e)))?;
+
+        let (tx, rx) = mpsc::channel::<(String, String)>();
+        thread::spawn(move || {
+            for (room, line) in rx {
+                let written = OpenOptions::new().create(true).append(true).open(store::room_path(&dir, &room, "jsonl"))
+                    .and_then(|mut file| writeln!(file, "{}", line));
+
+                if let Err(e) = written {
+                    tracing::error!(room = %room, "Could not write to the game log: {}", e);
+                }
+            }
+        });
+
+        Ok(Self {tx})
+    }
+
+    /// Appends the record of a move to the log of the game of a room.
+    pub fn record(&self, room: &str, record: &MoveRecord) {
+        match serde_json::to_string(record) {
+            Ok(line) => {
+                self.tx.send((room.to_string(), line)).ok();
+            },
+            Err(e) => tracing::error!(room = %room, "Could not serialize a move record: {}", e),
+        }
+    }
+}
@@ -1,21 +1,33 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Error, Result};
-use clap::{Arg, App};
-use log::LevelFilter;
-use simple_logger::SimpleLogger;
+use clap::{Arg, ArgMatches, App};
+use engine::{EngineConfig, EvalMode, Experience};
+use tracing_subscriber::filter::LevelFilter;
 use warp::Filter;
 
-use chess::board::Board;
-use engine::Engine;
-
+mod annotation;
+mod clock;
 mod game;
+mod gamelog;
 mod messages;
+mod metrics;
 mod sockets;
+mod store;
+mod tournament;
 
+use crate::game::{GameConfig, Limits};
+use crate::gamelog::GameLog;
+use crate::messages::ExportFormat;
+use crate::metrics::Metrics;
 use crate::sockets::Sockets;
+use crate::store::Store;
+use crate::tournament::{Tournament, TournamentConfig};
 
 /// The default address the server listens on.
 const DEFAULT_ADDRESS: &str = "127.0.0.1:5050";
@@ -23,6 +35,24 @@ const DEFAULT_ADDRESS: &str = "127.0.0.1:5050";
 /// The fen used for the default position.
 const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
+/// The default number of seconds the engine thinks for, when a client does not specify it.
+const DEFAULT_MOVETIME: &str = "5";
+
+/// Parses the value of an argument, if it was given.
+fn parse_arg<T: FromStr>(args: &ArgMatches, name: &str) -> Result<Option<T>> {
+    args.value_of(name).map(|value| {
+        T::from_str(value).map_err(|_| Error::msg(format!("Failed to parse {}: {}.", name, value)))
+    }).transpose()
+}
+
+/// Parses the value of an argument giving a strictly positive number of seconds, if it was given.
+fn parse_seconds(args: &ArgMatches, name: &str) -> Result<Option<Duration>> {
+    parse_arg::<f64>(args, name)?.map(|seconds| {
+        Duration::try_from_secs_f64(seconds).ok().filter(|duration| !duration.is_zero())
+            .ok_or_else(|| Error::msg(format!("Invalid number of seconds for {}: {}.", name, seconds)))
+    }).transpose()
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     // Gets the arguments.
@@ -48,10 +78,75 @@ async fn main() -> Result<()> {
             .default_value(DEFAULT_FEN)
             .help("Sets the fen string to use as the starting position, use double quotes to give everything in a single argument.")
             .takes_value(true))
+        .arg(Arg::with_name("variant")
+            .long("variant")
+            .value_name("VARIANT")
+            .default_value("standard")
+            .help("Sets the variant the games are played with: standard, kingofthehill (koth) or threecheck (3check).")
+            .takes_value(true))
         .arg(Arg::with_name("book")
             .long("book")
             .value_name("BOOK")
-            .help("Gives the path to a polyglot book (.bin), that the engine will use whenever it can.")
+            .help("Gives the path to a polyglot book (.bin), that the engine will use whenever it can, instead of the built-in one.")
+            .takes_value(true))
+        .arg(Arg::with_name("no_book")
+            .long("no-book")
+            .help("Disables the opening book, including the built-in one.")
+            .conflicts_with("book"))
+        .arg(Arg::with_name("eval")
+            .long("eval")
+            .value_name("MODE")
+            .help("Sets the way the engines evaluate positions: with their network, with a hand-crafted evaluation, or with the material balance alone, for machines too small for the network.")
+            .possible_values(&["nnue", "classical", "material"])
+            .default_value("nnue"))
+        .arg(Arg::with_name("store")
+            .long("store")
+            .value_name("STORE")
+            .help("Gives the path to a directory where the game of each room is saved after every move, and restored from when the room is opened.")
+            .takes_value(true))
+        .arg(Arg::with_name("game_log")
+            .long("game-log")
+            .value_name("GAME_LOG")
+            .help("Gives the path to a directory where the moves of the game of each room are logged, one json object per line, with the score and depth of the engine's moves.")
+            .takes_value(true))
+        .arg(Arg::with_name("threads")
+            .long("threads")
+            .value_name("THREADS")
+            .help("Sets the number of search threads of each engine, 8 by default.")
+            .takes_value(true))
+        .arg(Arg::with_name("hash_mb")
+            .long("hash-mb")
+            .value_name("MEGABYTES")
+            .help("Sets the size in megabytes of the transposition table of each engine, rounded up to a power of two, 32 by default.")
+            .takes_value(true))
+        .arg(Arg::with_name("experience")
+            .long("experience")
+            .value_name("EXPERIENCE")
+            .help("Gives the path to an experience file, created if it does not exist, shared by the engines of all rooms: they record the moves they play in it, and learn from it in later games.")
+            .takes_value(true))
+        .arg(Arg::with_name("affinity")
+            .long("affinity")
+            .help("Pins the search threads of the engines to physical cores, spreading their transposition tables over the memory of their NUMA nodes. Only supported on Linux."))
+        .arg(Arg::with_name("tournament")
+            .long("tournament")
+            .value_name("TOURNAMENT")
+            .help("Gives the path to a json file configuring a round-robin tournament between engine configurations or external UCI engines, played in the background, whose standings and games are served on the /tournament routes.")
+            .takes_value(true))
+        .arg(Arg::with_name("movetime_default")
+            .long("movetime-default")
+            .value_name("SECONDS")
+            .default_value(DEFAULT_MOVETIME)
+            .help("Sets the number of seconds the engine thinks for when a client does not specify it.")
+            .takes_value(true))
+        .arg(Arg::with_name("max_think_seconds")
+            .long("max-think-seconds")
+            .value_name("SECONDS")
+            .help("Sets the maximum number of seconds a client may make the engine think for, unlimited by default.")
+            .takes_value(true))
+        .arg(Arg::with_name("password")
+            .long("password")
+            .value_name("PASSWORD")
+            .help("Allows clients giving this password to change the limits above at runtime, with a \"limits\" message, and to reload the book and the network from their files, with a \"reload\" message.")
             .takes_value(true))
         .arg(Arg::with_name("log_level")
             .long("log-level")
@@ -63,15 +158,16 @@ async fn main() -> Result<()> {
 
     { // Setups the logger.
         let log_level = match args.value_of("log_level").unwrap() {
-            "off" => LevelFilter::Off,
-            "error" => LevelFilter::Error,
-            "warn" => LevelFilter::Warn,
-            "info" => LevelFilter::Info,
-            "debug" => LevelFilter::Debug,
+            "off" => LevelFilter::OFF,
+            "error" => LevelFilter::ERROR,
+            "warn" => LevelFilter::WARN,
+            "info" => LevelFilter::INFO,
+            "debug" => LevelFilter::DEBUG,
             _ => unreachable!()
         };
     
-        SimpleLogger::new().with_level(log_level).init().unwrap();
+        // Also collects the records of the crates using log, like warp.
+        tracing_subscriber::fmt().with_max_level(log_level).init();
     }
 
     // Parses the socket address.
@@ -81,21 +177,51 @@ async fn main() -> Result<()> {
         Err(_) => return Err(Error::msg(format!("Failed to parse address: {}.", addr_str))),
     };
 
+    // The metrics of the server, shared by the sockets and the /metrics route.
+    let metrics = Arc::new(Metrics::default());
+
+    // The tournament played in the background, if any.
+    let mut tournament = None;
+
     // Creates our state object and converts it into a warp filter.
     let sockets = {
-        // The book that may be used to lookup moves.
-        let book_path = args.value_of("book");
+        // The configuration of the games of every room.
+        let config = GameConfig {
+            fen: args.value_of("fen").unwrap().to_string(),
+            variant: parse_arg(&args, "variant")?.unwrap(),
+            // The book that may be used to lookup moves.
+            book_path: args.value_of("book").map(str::to_string),
+            no_book: args.is_present("no_book"),
+            // The neural network used for evaluation.
+            net_path: args.value_of("net").unwrap().to_string(),
+            eval_mode: parse_arg::<EvalMode>(&args, "eval")?.unwrap(),
+            affinity: args.is_present("affinity"),
+            experience: args.value_of("experience").map(|path| Experience::open(Path::new(path)).map(Arc::new)).transpose()?,
+        };
+
+        // The resources the engines may use, and the durations they may think for.
+        let default = EngineConfig::default();
+        let limits = Limits {
+            engine: EngineConfig {
+                threads: parse_arg(&args, "threads")?.unwrap_or(default.threads),
+                hash_mb: parse_arg(&args, "hash_mb")?.unwrap_or(default.hash_mb),
+            },
+            movetime_default: parse_seconds(&args, "movetime_default")?.unwrap(),
+            max_think: parse_seconds(&args, "max_think_seconds")?.unwrap_or(Duration::MAX),
+        };
 
-        // The neural network used for evaluation.
-        let net_path = args.value_of("net").unwrap();
+        // The store used to persist the games, if any.
+        let store = args.value_of("store").map(Store::new).transpose()?;
 
-        // Initializes the chess library.
-        chess::init();
+        // The log of the moves of the games, if any.
+        let game_log = args.value_of("game_log").map(GameLog::new).transpose()?;
 
-        let board = Board::new(args.value_of("fen").unwrap())?;
-        let engine = Engine::new(board, book_path, net_path)?;
+        if let Some(path) = args.value_of("tournament") {
+            tournament = Some(Tournament::start(TournamentConfig::open(path)?, &config)?);
+        }
 
-        let sockets = Sockets::new(engine);
+        let password = args.value_of("password").map(str::to_string);
+        let sockets = Sockets::new(config, limits, password, store, metrics.clone(), game_log).await?;
         warp::any().map(move || sockets.clone())
     };
 
@@ -104,19 +230,76 @@ async fn main() -> Result<()> {
         // For getting the websocket resource.
         let ws = warp::path("ws")
             .and(warp::ws())
-            .and(sockets)
+            .and(sockets.clone())
             .map(|ws: warp::ws::Ws, state: Arc<Sockets>| {
                 ws.on_upgrade(move |socket| {
                     state.handle_connection(socket)
                 })
             });
 
+        // For downloading the game of an open room, as a PGN or, with "?format=fen", as the fen of its position.
+        let save = warp::path!("save" / String)
+            .and(warp::get())
+            .and(warp::query::<HashMap<String, String>>())
+            .and(sockets)
+            .and_then(|room: String, query: HashMap<String, String>, state: Arc<Sockets>| async move {
+                let format = match query.get("format") {
+                    Some(format) => ExportFormat::from_str(format).map_err(|_| warp::reject())?,
+                    None => ExportFormat::default(),
+                };
+
+                let data = state.export(&room, format).await.ok_or_else(warp::reject::not_found)?;
+                let disposition = format!("attachment; filename=\"{}.{}\"", room, format.extension());
+                Ok::<_, warp::Rejection>(warp::reply::with_header(
+                    warp::reply::with_header(data, "content-type", format.content_type()),
+                    "content-disposition",
+                    disposition,
+                ))
+            });
+
+        // For the metrics, in the text format of Prometheus.
+        let metrics = warp::path("metrics")
+            .and(warp::path::end())
+            .and(warp::get())
+            .map(move || {
+                warp::reply::with_header(metrics.render(), "content-type", "text/plain; version=0.0.4")
+            });
+
+        // For the standings, crosstable and games of the tournament, as json, and for each of its games, as a PGN.
+        let standings = {
+            let tournament = tournament.clone();
+            warp::path("tournament")
+                .and(warp::path::end())
+                .and(warp::get())
+                .and_then(move || {
+                    let tournament = tournament.clone();
+                    async move {
+                        let tournament = tournament.ok_or_else(warp::reject::not_found)?;
+                        Ok::<_, warp::Rejection>(warp::reply::with_header(tournament.to_json(), "content-type", "application/json"))
+                    }
+                })
+        };
+        let tournament_game = warp::path!("tournament" / usize)
+            .and(warp::get())
+            .and_then(move |index: usize| {
+                let pgn = tournament.as_ref().and_then(|tournament| tournament.pgn(index));
+                async move {
+                    let pgn = pgn.ok_or_else(warp::reject::not_found)?;
+                    let disposition = format!("attachment; filename=\"tournament-{}.pgn\"", index + 1);
+                    Ok::<_, warp::Rejection>(warp::reply::with_header(
+                        warp::reply::with_header(pgn, "content-type", ExportFormat::Pgn.content_type()),
+                        "content-disposition",
+                        disposition,
+                    ))
+                }
+            });
+
         // For files.
         let public = warp::get()
             .and(warp::fs::dir("www/public"))
             .with(warp::compression::gzip());
 
-        public.or(ws)
+        metrics.or(save).or(standings).or(tournament_game).or(public).or(ws)
     };
 
     // Launches the server, printing the used port.
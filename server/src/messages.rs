@@ -1,7 +1,407 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::{Error, Result};
-use serde_json::Value;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::sync::oneshot;
 use warp::ws::Message;
 
+use chess::book::Book;
+use chess::color::Color;
+use chess::game::TimeControl;
+use chess::moves::Move;
+use engine::Net;
+
+use crate::annotation::Annotation;
+use crate::game::Limits;
+
+/// The maximum length of a room id.
+const MAX_ROOM_ID_LEN: usize = 32;
+
+/// The longest base time or increment of a clock: a week.
+const MAX_CLOCK: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// The length of an identity token.
+pub const TOKEN_LEN: usize = 32;
+
+/// Constructs the message sent to a client upon connection, giving it its identity token.
+pub fn identity(token: &str) -> Message {
+    Message::text(serde_json::json!({
+        "token": token,
+    }).to_string())
+}
+
+/// Constructs the message sent to a client whose request was rejected, with the code of the error.
+pub fn rejection(reason: &Error) -> Message {
+    error(ErrorCode::of(reason), &reason.to_string())
+}
+
+/// Creates the error rejecting a request with the given code and message.
+pub fn reject(code: ErrorCode, message: impl Into<String>) -> Error {
+    Error::new(Rejection {code, message: message.into()})
+}
+
+/// Constructs the message sent to a client to report an error, with its code and its message.
+pub fn error(code: ErrorCode, message: &str) -> Message {
+    Message::text(serde_json::json!({
+        "error": {
+            "code": code,
+            "message": message,
+        },
+    }).to_string())
+}
+
+/// Constructs the message sent to the operator after changing the resource limits, describing the new ones.
+pub fn limits(limits: &Limits) -> Message {
+    Message::text(serde_json::json!({
+        "limits": Value::from(limits),
+    }).to_string())
+}
+
+/// Constructs the message sent to the operator after reloading the network and the book,
+/// telling whether the engines play with a book.
+pub fn reloaded(book: bool) -> Message {
+    Message::text(serde_json::json!({
+        "reloaded": {
+            "book": book,
+            "net": true,
+        },
+    }).to_string())
+}
+
+/// Constructs the message broadcast when a move is played, describing only what changed
+/// since the previous state: the move, its san, the position reached and the clock.
+pub fn move_played(mv: Move, san: &str, fen_after: &str, clock: &Value) -> Message {
+    Message::text(serde_json::json!({
+        "played": {
+            "move": mv.to_string(),
+            "san": san,
+            "fen": fen_after,
+            "clock": clock,
+        },
+    }).to_string())
+}
+
+/// Constructs the message sent to a client that requested the export of the game, in the given format.
+pub fn exported(format: ExportFormat, data: &str) -> Message {
+    Message::text(serde_json::json!({
+        "export": {
+            "format": format.to_string(),
+            "data": data,
+        },
+    }).to_string())
+}
+
+//#################################################################################################
+//
+//                                         enum Request
+//
+//#################################################################################################
+
+/// A struct representing a parsed message from a client, either directed
+/// at the lobby or at the game of the client's room.
+#[derive(Debug)]
+pub enum Request {
+    Identify(String),
+    Create,
+    Join(String),
+    Limits {
+        password: String,
+        update: LimitsUpdate,
+    },
+    Reload {
+        password: String,
+    },
+    Game(Command),
+}
+
+// ================================ pub impl
+
+impl Request {
+    /// Tries to parse a request from a warp message.
+    pub fn from_msg(msg: Message) -> Result<Self> {
+        let data = msg.to_str().map_err(|_| Error::msg("Incoming message is not text."))?;
+
+        let json: Value = serde_json::from_str(data)?;
+        let obj = json.as_object().ok_or(Error::msg("Json value is not an object."))?;
+
+        let kind = obj.get("kind").ok_or(Error::msg("No attribute kind in json value."))?
+            .as_str().ok_or(Error::msg("kind attribute is not a string."))?;
+
+        // Messages of the operator are not logged, as they contain the password.
+        if kind != "limits" && kind != "reload" {
+            tracing::info!("Received message: {}", data);
+        }
+
+        Ok(match kind {
+            "identify" => {
+                let token = obj.get("token").ok_or(Error::msg("No attribute token in json value."))?
+                    .as_str().ok_or(Error::msg("token attribute is not a string."))?;
+                Self::Identify(parse_token(token)?)
+            },
+            "create" => Self::Create,
+            "join" => {
+                let room = obj.get("room").ok_or(Error::msg("No attribute room in json value."))?
+                    .as_str().ok_or(Error::msg("room attribute is not a string."))?;
+                Self::Join(parse_room_id(room)?)
+            },
+            "limits" => {
+                let password = obj.get("password").ok_or(Error::msg("No attribute password in json value."))?
+                    .as_str().ok_or(Error::msg("password attribute is not a string."))?.to_string();
+                Self::Limits {
+                    password,
+                    update: LimitsUpdate::from_json(obj)?,
+                }
+            },
+            "reload" => {
+                let password = obj.get("password").ok_or(Error::msg("No attribute password in json value."))?
+                    .as_str().ok_or(Error::msg("password attribute is not a string."))?.to_string();
+                Self::Reload {password}
+            },
+            _ => Self::Game(Command::from_json(kind, obj)?),
+        })
+    }
+}
+
+/// Checks that a room id is valid: it must be non-empty, not too long, and only
+/// contain ascii alphanumeric characters, dashes and underscores.
+fn parse_room_id(room: &str) -> Result<String> {
+    if room.is_empty() || room.len() > MAX_ROOM_ID_LEN {
+        return Err(Error::msg("Invalid room id length."));
+    }
+
+    if !room.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::msg("Invalid character in room id."));
+    }
+
+    Ok(room.to_string())
+}
+
+/// Checks that an identity token is valid: it must be made of TOKEN_LEN hexadecimal digits.
+fn parse_token(token: &str) -> Result<String> {
+    if token.len() != TOKEN_LEN || !token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::msg("Invalid token."));
+    }
+
+    Ok(token.to_ascii_lowercase())
+}
+
+//#################################################################################################
+//
+//                                       struct LimitsUpdate
+//
+//#################################################################################################
+
+/// Changes to the resource limits of the engines, requested by the operator of the server.
+/// The limits that are not given are left unchanged.
+#[derive(Debug, Default)]
+pub struct LimitsUpdate {
+    pub threads: Option<usize>,
+    pub hash_mb: Option<usize>,
+    pub movetime_default: Option<Duration>,
+    pub max_think: Option<Duration>,
+}
+
+// ================================ impl
+
+impl LimitsUpdate {
+    /// Parses the changes from the json object of a message.
+    fn from_json(obj: &Map<String, Value>) -> Result<Self> {
+        let count = |name: &str| -> Result<Option<usize>> {
+            match obj.get(name) {
+                Some(value) => match value.as_u64() {
+                    Some(count) if count > 0 => Ok(Some(count as usize)),
+                    _ => Err(Error::msg(format!("{} attribute is not a strictly positive integer.", name))),
+                },
+                None => Ok(None),
+            }
+        };
+
+        let seconds = |name: &str| -> Result<Option<Duration>> {
+            match obj.get(name) {
+                Some(value) => Ok(Some(parse_seconds(value).ok_or_else(|| Error::msg(format!("{} attribute is not a positive number.", name)))?)),
+                None => Ok(None),
+            }
+        };
+
+        Ok(Self {
+            threads: count("threads")?,
+            hash_mb: count("hashMb")?,
+            movetime_default: seconds("movetimeDefault")?,
+            max_think: seconds("maxThinkSeconds")?,
+        })
+    }
+}
+
+/// Parses a strictly positive number of seconds.
+fn parse_seconds(value: &Value) -> Option<Duration> {
+    value.as_f64().filter(|&seconds| seconds > 0.0).and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+}
+
+//#################################################################################################
+//
+//                                         struct Client
+//
+//#################################################################################################
+
+/// The identity of a connected client.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The id of the connection.
+    pub uid: usize,
+    /// The token identifying the client, kept across connections.
+    pub token: String,
+}
+
+//#################################################################################################
+//
+//                                         struct Order
+//
+//#################################################################################################
+
+/// A command sent to a game, along with the client that issued it.
+/// Orders issued by the server itself have no client.
+#[derive(Debug)]
+pub struct Order {
+    pub client: Option<Client>,
+    pub command: Command,
+}
+
+// ================================ traits impl
+
+impl From<Command> for Order {
+    /// Creates an order issued by the server itself.
+    fn from(command: Command) -> Self {
+        Self {
+            client: None,
+            command,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        enum ErrorCode
+//
+//#################################################################################################
+
+/// The codes of the errors reported to clients, so that they may react to them without reading the messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The message could not be parsed, or can't be done in the current state of the game.
+    InvalidRequest,
+    /// The client is not allowed to do this, such as moving a color another player is seated at.
+    Unauthorized,
+    /// The move could not be parsed, or is illegal.
+    IllegalMove,
+    /// The fen could not be parsed, or gives an invalid position.
+    InvalidFen,
+    /// The game is over.
+    GameOver,
+}
+
+// ================================ pub impl
+
+impl ErrorCode {
+    /// Returns the code of an error: the one it was rejected with, or the one matching the error of
+    /// the chess lib it comes from. Other errors are invalid requests.
+    pub fn of(error: &Error) -> ErrorCode {
+        if let Some(rejection) = error.downcast_ref::<Rejection>() {
+            return rejection.code;
+        }
+
+        match error.downcast_ref::<chess::Error>() {
+            Some(chess::Error::InvalidFen {..} | chess::Error::InvalidPosition {..}) => ErrorCode::InvalidFen,
+            Some(chess::Error::ParseMove {..} | chess::Error::IllegalMove | chess::Error::AmbiguousMove) => ErrorCode::IllegalMove,
+            Some(chess::Error::GameOver | chess::Error::OutOfTime) => ErrorCode::GameOver,
+            _ => ErrorCode::InvalidRequest,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                        struct Rejection
+//
+//#################################################################################################
+
+/// An error rejecting the request of a client, with the code it is reported with.
+#[derive(Debug)]
+struct Rejection {
+    code: ErrorCode,
+    message: String,
+}
+
+// ================================ traits impl
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+//#################################################################################################
+//
+//                                       enum ExportFormat
+//
+//#################################################################################################
+
+/// The formats a game can be exported in.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// The whole game, in portable game notation.
+    #[default]
+    Pgn,
+    /// The current position only.
+    Fen,
+}
+
+// ================================ pub impl
+
+impl ExportFormat {
+    /// Returns the extension of the files of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Pgn => "pgn",
+            Self::Fen => "fen",
+        }
+    }
+
+    /// Returns the content type of the files of the format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Pgn => "application/x-chess-pgn",
+            Self::Fen => "text/plain",
+        }
+    }
+}
+
+// ================================ traits impl
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pgn" => Ok(Self::Pgn),
+            "fen" => Ok(Self::Fen),
+            _ => Err(Error::msg("Invalid export format.")),
+        }
+    }
+}
+
 //#################################################################################################
 //
 //                                         enum Command
@@ -13,48 +413,170 @@ use warp::ws::Message;
 pub enum Command {
     Welcome(usize),
     Play(String),
-    Think(f64),
-    ThinkDo(f64),
+    Think(Option<Duration>),
+    ThinkDo(Option<Duration>),
     Stop,
     Do,
     Undo,
     Redo,
+    Clock(Option<TimeControl>),
+    Limits(Limits),
+    Reload {
+        book: Option<Book>,
+        net: Arc<Net>,
+    },
+    Strength(u8),
+    Sit(Option<Color>),
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+    Resign,
+    Analyse {
+        fen: Option<String>,
+        multipv: u8,
+        depth: Option<u8>,
+    },
+    StopAnalysis,
+    AnalysisTick,
+    BookProbe(Option<String>),
+    Eval(Option<String>),
+    SetPosition(String),
+    Annotate(Annotation),
+    Export(ExportFormat),
+    Save {
+        format: ExportFormat,
+        reply: oneshot::Sender<String>,
+    },
+    CheckFlag,
+    SearchDone(bool),
+    Leave(usize),
+    Close,
 }
 
 // ================================ pub impl
 
 impl Command {
-    /// Tries to parse a command from a warp message.
-    pub fn from_msg(msg: Message) -> Result<Self> {
-        let data = msg.to_str().map_err(|_| Error::msg("Incoming message is not text."))?;
-        log::info!("Received message: {}", data);
-
-        let json: Value = serde_json::from_str(data)?;
-        let obj = json.as_object().ok_or(Error::msg("Json value is not an object."))?;
-
-        let kind = obj.get("kind").ok_or(Error::msg("No attribute kind in json value."))?
-            .as_str().ok_or(Error::msg("kind attribute is not a string."))?;
-
+    /// Tries to parse a command from the kind and the json object of a message.
+    pub fn from_json(kind: &str, obj: &Map<String, Value>) -> Result<Self> {
         Ok(match kind {
             "play" => {
                 let mv = obj.get("move").ok_or(Error::msg("No attribute move in json value."))?
                     .as_str().ok_or(Error::msg("move attribute is not a string."))?.to_string();
                 Self::Play(mv)
             },
-            "think" => {
-                let seconds = obj.get("seconds").ok_or(Error::msg("No attribute move in json value."))?
-                    .as_f64().ok_or(Error::msg("seconds attribute is not a string."))?;
-                Self::Think(seconds)
-            },
-            "thinkdo" => {
-                let seconds = obj.get("seconds").ok_or(Error::msg("No attribute move in json value."))?
-                    .as_f64().ok_or(Error::msg("seconds attribute is not a string."))?;
-                Self::ThinkDo(seconds)
+            // Without a number of seconds, the engine thinks for the default duration.
+            "think" | "thinkdo" => {
+                let duration = match obj.get("seconds") {
+                    Some(seconds) => Some(parse_seconds(seconds).ok_or(Error::msg("seconds attribute is not a positive number."))?),
+                    None => None,
+                };
+
+                if kind == "think" {
+                    Self::Think(duration)
+                } else {
+                    Self::ThinkDo(duration)
+                }
             },
             "stop" => Self::Stop,
             "do" => Self::Do,
             "undo" => Self::Undo,
             "redo" => Self::Redo,
+            "strength" => {
+                let level = obj.get("level").ok_or(Error::msg("No attribute level in json value."))?
+                    .as_u64().ok_or(Error::msg("level attribute is not a positive integer."))?;
+                Self::Strength(level.min(u64::from(engine::MAX_STRENGTH)) as u8)
+            },
+            "analyse" => {
+                let fen = match obj.get("fen") {
+                    Some(fen) => Some(fen.as_str().ok_or(Error::msg("fen attribute is not a string."))?.to_string()),
+                    None => None,
+                };
+                let multipv = match obj.get("multipv") {
+                    Some(multipv) => multipv.as_u64().ok_or(Error::msg("multipv attribute is not a positive integer."))?,
+                    None => 1,
+                };
+                let depth = match obj.get("depth") {
+                    Some(depth) => Some(depth.as_u64().ok_or(Error::msg("depth attribute is not a positive integer."))?),
+                    None => None,
+                };
+                Self::Analyse {
+                    fen,
+                    multipv: multipv.clamp(1, u64::from(u8::MAX)) as u8,
+                    depth: depth.map(|depth| depth.min(u64::from(u8::MAX)) as u8),
+                }
+            },
+            "stopanalysis" => Self::StopAnalysis,
+            "book" => {
+                let fen = match obj.get("fen") {
+                    Some(fen) => Some(fen.as_str().ok_or(Error::msg("fen attribute is not a string."))?.to_string()),
+                    None => None,
+                };
+                Self::BookProbe(fen)
+            },
+            "eval" => {
+                let fen = match obj.get("fen") {
+                    Some(fen) => Some(fen.as_str().ok_or(Error::msg("fen attribute is not a string."))?.to_string()),
+                    None => None,
+                };
+                Self::Eval(fen)
+            },
+            "position" => {
+                let fen = obj.get("fen").ok_or(Error::msg("No attribute fen in json value."))?
+                    .as_str().ok_or(Error::msg("fen attribute is not a string."))?.to_string();
+                Self::SetPosition(fen)
+            },
+            "annotate" => Self::Annotate(Annotation::from_json(obj)?),
+            // Without a format, the game is exported as a PGN.
+            "export" => {
+                let format = match obj.get("format") {
+                    Some(format) => ExportFormat::from_str(format.as_str().ok_or(Error::msg("format attribute is not a string."))?)?,
+                    None => ExportFormat::default(),
+                };
+                Self::Export(format)
+            },
+            "offer_draw" => Self::OfferDraw,
+            "accept_draw" => Self::AcceptDraw,
+            "decline_draw" => Self::DeclineDraw,
+            "request_takeback" => Self::RequestTakeback,
+            "accept_takeback" => Self::AcceptTakeback,
+            "decline_takeback" => Self::DeclineTakeback,
+            "resign" => Self::Resign,
+            "sit" => {
+                let color = match obj.get("color").ok_or(Error::msg("No attribute color in json value."))?
+                    .as_str().ok_or(Error::msg("color attribute is not a string."))? {
+                    "white" => Color::White,
+                    "black" => Color::Black,
+                    _ => return Err(Error::msg("Invalid color.")),
+                };
+                Self::Sit(Some(color))
+            },
+            "spectate" => Self::Sit(None),
+            "clock" => {
+                // Without a base time, the clock is removed.
+                let time_control = match obj.get("base") {
+                    Some(base) => {
+                        let base = base.as_f64().ok_or(Error::msg("base attribute is not a number."))?;
+                        let increment = match obj.get("increment") {
+                            Some(increment) => increment.as_f64().ok_or(Error::msg("increment attribute is not a number."))?,
+                            None => 0.0,
+                        };
+
+                        if !(base > 0.0 && increment >= 0.0) {
+                            return Err(Error::msg("Invalid time control."));
+                        }
+
+                        match (Duration::try_from_secs_f64(base), Duration::try_from_secs_f64(increment)) {
+                            (Ok(base), Ok(increment)) if base.max(increment) <= MAX_CLOCK => Some(TimeControl {base, increment}),
+                            _ => return Err(Error::msg("Invalid time control.")),
+                        }
+                    },
+                    None => None,
+                };
+                Self::Clock(time_control)
+            },
             _ => return Err(Error::msg("Invalid message kind")),
         })
     }
@@ -71,8 +593,50 @@ impl Command {
 pub enum Response {
     None,
     Broadcast(Message),
+    MovePlayed {
+        mv: Move,
+        san: String,
+        fen_after: String,
+        clock: Value,
+    },
     Send {
         dest: usize,
         msg: Message,
+    },
+    Analysis {
+        dest: usize,
+        msg: Message,
+    },
+    Book {
+        dest: usize,
+        msg: Message,
+    },
+    Error {
+        dest: usize,
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock() {
+        let parse = |json: Value| Command::from_json("clock", json.as_object().unwrap());
+
+        assert!(matches!(parse(serde_json::json!({"base": 300, "increment": 2})), Ok(Command::Clock(Some(_)))));
+        assert!(matches!(parse(serde_json::json!({})), Ok(Command::Clock(None))));
+        assert!(parse(serde_json::json!({"base": 0})).is_err());
+        assert!(parse(serde_json::json!({"base": 1e300})).is_err());
+        assert!(parse(serde_json::json!({"base": 300, "increment": 1e300})).is_err());
+        assert!(parse(serde_json::json!({"base": 300, "increment": -1})).is_err());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,103 @@
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use engine::Stats;
+
+/// Writes a metric in the text format of Prometheus, with its help and type lines.
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+    writeln!(out, "{} {}", name, value).unwrap();
+}
+
+//#################################################################################################
+//
+//                                        struct Metrics
+//
+//#################################################################################################
+
+/// The counters and gauges describing the activity of the server, shared by the sockets and the games
+/// of all rooms, and exported on the /metrics route. Rates, such as the number of websocket messages per
+/// second, are left to Prometheus to compute from the counters.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // The number of clients connected.
+    clients: AtomicU64,
+    // The number of games open and not over.
+    games: AtomicU64,
+    // The number of websocket messages received from clients and sent to them.
+    messages_received: AtomicU64,
+    messages_sent: AtomicU64,
+    // The statistics of the searches of all the engines, past and present.
+    searches: AtomicU64,
+    nodes: AtomicU64,
+    search_micros: AtomicU64,
+    total_depth: AtomicU64,
+}
+
+// ================================ pub impl
+
+impl Metrics {
+    /// Counts a newly connected client.
+    pub fn client_connected(&self) {
+        self.clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a client disconnecting.
+    pub fn client_disconnected(&self) {
+        self.clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Counts a game starting or stopping to be in progress.
+    pub fn set_game_in_progress(&self, in_progress: bool) {
+        if in_progress {
+            self.games.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.games.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a message received from a client.
+    pub fn message_received(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts the given number of messages sent to clients.
+    pub fn messages_sent(&self, count: usize) {
+        self.messages_sent.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Adds the searches an engine made between the two given readings of its statistics.
+    pub fn add_searches(&self, before: &Stats, after: &Stats) {
+        let search_time = after.search_time.saturating_sub(before.search_time);
+
+        self.searches.fetch_add(after.searches - before.searches, Ordering::Relaxed);
+        self.nodes.fetch_add(after.nodes - before.nodes, Ordering::Relaxed);
+        self.search_micros.fetch_add(search_time.as_micros() as u64, Ordering::Relaxed);
+        self.total_depth.fetch_add(after.total_depth - before.total_depth, Ordering::Relaxed);
+    }
+
+    /// Renders the metrics in the text format of Prometheus.
+    pub fn render(&self) -> String {
+        let stats = Stats {
+            searches: self.searches.load(Ordering::Relaxed),
+            nodes: self.nodes.load(Ordering::Relaxed),
+            search_time: Duration::from_micros(self.search_micros.load(Ordering::Relaxed)),
+            total_depth: self.total_depth.load(Ordering::Relaxed),
+            ..Stats::default()
+        };
+
+        let mut out = String::new();
+        write_metric(&mut out, "rush_clients_connected", "gauge", "The number of clients connected.", self.clients.load(Ordering::Relaxed));
+        write_metric(&mut out, "rush_games_in_progress", "gauge", "The number of games open and not over.", self.games.load(Ordering::Relaxed));
+        write_metric(&mut out, "rush_websocket_messages_received_total", "counter", "The number of websocket messages received from clients.", self.messages_received.load(Ordering::Relaxed));
+        write_metric(&mut out, "rush_websocket_messages_sent_total", "counter", "The number of websocket messages sent to clients.", self.messages_sent.load(Ordering::Relaxed));
+        write_metric(&mut out, "rush_engine_searches_total", "counter", "The number of searches started by the engines.", stats.searches);
+        write_metric(&mut out, "rush_engine_nodes_total", "counter", "The number of nodes searched by the engines.", stats.nodes);
+        write_metric(&mut out, "rush_engine_search_seconds_total", "counter", "The time spent searching by the engines.", stats.search_time.as_secs_f64());
+        write_metric(&mut out, "rush_engine_nodes_per_second", "gauge", "The number of nodes searched per second by the engines, on average.", stats.nodes_per_second());
+        write_metric(&mut out, "rush_engine_average_depth", "gauge", "The depth completed by the searches of the engines, on average.", stats.average_depth());
+        out
+    }
+}
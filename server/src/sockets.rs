@@ -1,18 +1,82 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use anyhow::Result;
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, mpsc, oneshot};
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::Instrument;
 use warp::ws::Message;
 
-use engine::Engine;
+use engine::Net;
 
-use crate::game::Game;
-use crate::messages::{Command, Response};
+use crate::game::{Game, GameConfig, Limits};
+use crate::gamelog::GameLog;
+use crate::messages::{self, Client, Command, ErrorCode, ExportFormat, LimitsUpdate, Order, Request, Response};
+use crate::metrics::Metrics;
+use crate::store::Store;
+
+/// The id of the room clients are put in when they connect. It is never closed.
+const DEFAULT_ROOM: &str = "main";
+
+/// Generates a new random identity token, made of 128 bits drawn from the operating system.
+fn new_token() -> String {
+    let mut bytes = [0; messages::TOKEN_LEN / 2];
+    getrandom::getrandom(&mut bytes).expect("Failed to draw a random token.");
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+//#################################################################################################
+//
+//                                         struct Room
+//
+//#################################################################################################
+
+/// A room, in which clients play and watch a game together.
+#[derive(Debug)]
+struct Room {
+    // The game of the room, which the room communicates with through this channel.
+    game_tx: UnboundedSender<Order>,
+    // The ids of the clients in the room.
+    members: HashSet<usize>,
+}
+
+//#################################################################################################
+//
+//                                         struct Lobby
+//
+//#################################################################################################
+
+/// Keeps track of the rooms, and of the room each client is in.
+#[derive(Debug, Default)]
+struct Lobby {
+    rooms: HashMap<String, Room>,
+    clients: HashMap<usize, String>,
+}
+
+// ================================ impl
+
+impl Lobby {
+    /// Removes a client from its room, closing the room if it is left empty.
+    fn leave(&mut self, uid: usize) {
+        if let Some(id) = self.clients.remove(&uid) {
+            if let Some(room) = self.rooms.get_mut(&id) {
+                room.members.remove(&uid);
+                room.game_tx.send(Command::Leave(uid).into()).ok();
+
+                if room.members.is_empty() && id != DEFAULT_ROOM {
+                    room.game_tx.send(Command::Close.into()).ok();
+                    self.rooms.remove(&id);
+                    tracing::info!(room = %id, "Closed an empty room.");
+                }
+            }
+        }
+    }
+}
 
 //#################################################################################################
 //
@@ -25,53 +89,58 @@ use crate::messages::{Command, Response};
 pub struct Sockets {
     // The atomic counter keeping trace of wich ids have been attributed already.
     next_uid: AtomicUsize,
+    // The atomic counter used to generate the ids of created rooms.
+    next_room: AtomicUsize,
     // The shared hashmap containing all of our sinks.
     senders: RwLock<HashMap<usize, UnboundedSender<Result<Message, warp::Error>>>>,
-    // The game state, which the state communcates with through this channel.
-    game_tx: UnboundedSender<Command>,
+    // The rooms and their members.
+    lobby: RwLock<Lobby>,
+    // The configuration used to create the games of new rooms.
+    config: GameConfig,
+    // The current resource limits of the engines.
+    limits: Mutex<Limits>,
+    // The password allowing to change the limits at runtime, if it is allowed.
+    password: Option<String>,
+    // The store used to persist the games, if any.
+    store: Option<Arc<Store>>,
+    // The metrics describing the activity of the server.
+    metrics: Arc<Metrics>,
+    // The log the moves of the games are recorded in, if any.
+    game_log: Option<Arc<GameLog>>,
 }
 
 // ================================ pub impl
 
 impl Sockets {
-    /// Creates a new Socket object, managing all connections.
-    pub fn new(engine: Engine) -> Arc<Self> {
-        // Create channels to communicate with the game state.
-        let (tx, mut game_rx) = mpsc::unbounded_channel();
-        let game_tx = Game::new(engine, tx);
-
-        // Construct the state object.
+    /// Creates a new Socket object, managing all connections, and opens the default room.
+    /// The limits may be changed at runtime by the clients knowing the password, if one is given.
+    /// The connections and the games of the rooms are accounted for in the given metrics,
+    /// and the moves of the games are recorded in the given game log, if any.
+    pub async fn new(config: GameConfig, limits: Limits, password: Option<String>, store: Option<Store>, metrics: Arc<Metrics>, game_log: Option<GameLog>) -> Result<Arc<Self>> {
         let state = Arc::new(Self {
             next_uid: AtomicUsize::new(0),
+            next_room: AtomicUsize::new(0),
             senders: RwLock::new(HashMap::new()),
-            game_tx,
+            lobby: RwLock::new(Lobby::default()),
+            config,
+            limits: Mutex::new(limits),
+            password,
+            store: store.map(Arc::new),
+            metrics,
+            game_log: game_log.map(Arc::new),
         });
 
-        // Copy a reference to this state object and create a task forwarding
-        // messages from the game state to the web.
-        let state_cpy = state.clone();
-        tokio::spawn(async move {
-            let state = state_cpy;
+        let room = state.open_room(DEFAULT_ROOM.to_string())?;
+        state.lobby.write().await.rooms.insert(DEFAULT_ROOM.to_string(), room);
 
-            // While receiving messages from the game state, forward them
-            // according to it's demands.
-            while let Some(res) = game_rx.recv().await {
-                match res {
-                    Ok(Response::Broadcast(msg)) => state.broadcast(msg).await,
-                    Ok(Response::Send {dest, msg}) => state.send(dest, msg).await,
-                    Ok(Response::None) => (),
-                    Err(e) => log::debug!("Wrong command in context: {}", e),
-                }
-            }
-        });
-
-        state
+        Ok(state)
     }
 
     /// Handle a new connections through it's life cycle.
     pub async fn handle_connection(self: Arc<Self>, ws: warp::ws::WebSocket) {
         // Get the next valid unique id.
         let uid = self.next_uid.fetch_add(1, Ordering::Relaxed);
+        self.metrics.client_connected();
 
         // Split the socket into it's sink and sender components.
         let (tx, mut rx) = ws.split();
@@ -85,15 +154,24 @@ impl Sockets {
             // React to a message coming from the program by
             // forwarding it through the socket.
             let mpsc_rx = UnboundedReceiverStream::new(mpsc_rx);
-            tokio::spawn(mpsc_rx.forward(tx).map(|res| {
+            tokio::spawn(mpsc_rx.forward(tx).map(move |res| {
                 if let Err(e) = res {
-                    log::warn!("WebSocket send error: {}", e);
+                    tracing::warn!(uid, "WebSocket send error: {}", e);
                 }
             }));
         }
 
-        // Request the game state to send the welcome message to the new client.
-        self.game_tx.send(Command::Welcome(uid)).ok();
+        // Gives the new client its identity token.
+        let mut client = Client {
+            uid,
+            token: new_token(),
+        };
+        self.send(uid, messages::identity(&client.token)).await;
+
+        // Puts the new client in the default room, which sends him the welcome message.
+        if let Err(e) = self.join(uid, DEFAULT_ROOM.to_string()).await {
+            tracing::error!(uid, "Could not join the default room: {}", e);
+        }
 
         // Listen for incoming messages from the web.
         while let Some(res) = rx.next().await {
@@ -104,22 +182,40 @@ impl Sockets {
                     if msg.is_close() {
                         break;
                     }
+                    self.metrics.message_received();
 
-                    // If the message was incorrect, print the error to the terminal.
-                    if let Err(e) = self.on_message(msg) {
-                        log::warn!("Erroneous order: {}", e);
+                    // If the message was incorrect, print the error to the terminal
+                    // and tell the client why it was rejected.
+                    if let Err(e) = self.on_message(&mut client, msg).await {
+                        tracing::warn!(uid, "Erroneous order: {}", e);
+                        self.send(uid, messages::rejection(&e)).await;
                     }
                 },
                 // On error, prints it and breaks out of the event loop.
                 Err(e) => {
-                    log::warn!("WebSocket receive error: {}", e);
+                    tracing::warn!(uid, "WebSocket receive error: {}", e);
                     break;
                 },
             }
         }
 
-        // On disconnection, remove the client from our list.
+        // On disconnection, remove the client from its room and from our list.
+        self.lobby.write().await.leave(uid);
         self.senders.write().await.remove(&uid);
+        self.metrics.client_disconnected();
+    }
+
+    /// Exports the game of the room with the given id in the given format, for the save route.
+    /// Returns None if the room is not open.
+    pub async fn export(&self, room: &str, format: ExportFormat) -> Option<String> {
+        let (reply, rx) = oneshot::channel();
+
+        {
+            let lobby = self.lobby.read().await;
+            lobby.rooms.get(room)?.game_tx.send(Command::Save {format, reply}.into()).ok()?;
+        }
+
+        rx.await.ok()
     }
 }
 
@@ -129,21 +225,170 @@ impl Sockets {
     /// Sends a message to a specified client, if it is still connected.
     async fn send(&self, uid: usize, msg: Message) {
         if let Some(tx) = self.senders.read().await.get(&uid) {
-            tx.send(Ok(msg)).ok();
+            if tx.send(Ok(msg)).is_ok() {
+                self.metrics.messages_sent(1);
+            }
+        }
+    }
+
+    /// Broadcasts a message to all clients of a room.
+    async fn broadcast(&self, room: &str, msg: Message) {
+        let lobby = self.lobby.read().await;
+        let senders = self.senders.read().await;
+
+        if let Some(room) = lobby.rooms.get(room) {
+            let sent = room.members.iter().filter_map(|uid| senders.get(uid))
+                .filter(|tx| tx.send(Ok(msg.clone())).is_ok())
+                .count();
+            self.metrics.messages_sent(sent);
+        }
+    }
+
+    /// Creates the game of a new room, and a task forwarding its messages to the room's clients.
+    fn open_room(self: &Arc<Self>, id: String) -> Result<Room> {
+        // Create channels to communicate with the game state.
+        let (tx, mut game_rx) = mpsc::unbounded_channel();
+        let limits = *self.limits.lock().unwrap();
+        let game_tx = Game::new(id.clone(), &self.config, limits, self.store.clone(), self.metrics.clone(), self.game_log.clone(), tx)?;
+        tracing::info!(room = %id, "Opened a new room.");
+
+        // Copy a reference to this state object and create a task forwarding
+        // messages from the game state to the web.
+        let state = self.clone();
+        let span = tracing::info_span!("room", room = %id);
+        tokio::spawn(async move {
+            // While receiving messages from the game state, forward them
+            // according to it's demands.
+            while let Some(res) = game_rx.recv().await {
+                match res {
+                    Ok(Response::Broadcast(msg)) => state.broadcast(&id, msg).await,
+                    Ok(Response::MovePlayed {mv, san, fen_after, clock}) => {
+                        state.broadcast(&id, messages::move_played(mv, &san, &fen_after, &clock)).await
+                    },
+                    Ok(Response::Send {dest, msg}) | Ok(Response::Analysis {dest, msg}) | Ok(Response::Book {dest, msg}) => state.send(dest, msg).await,
+                    Ok(Response::Error {dest, code, message}) => state.send(dest, messages::error(code, &message)).await,
+                    Ok(Response::None) => (),
+                    Err(e) => tracing::debug!("Wrong command in context: {}", e),
+                }
+            }
+        }.instrument(span));
+
+        Ok(Room {
+            game_tx,
+            members: HashSet::new(),
+        })
+    }
+
+    /// Moves a client to the room with the given id, opening it if needed.
+    async fn join(self: &Arc<Self>, uid: usize, id: String) -> Result<()> {
+        let mut lobby = self.lobby.write().await;
+
+        // Leave the current room, unless the client is already in the requested one.
+        if lobby.clients.get(&uid) != Some(&id) {
+            lobby.leave(uid);
+        }
+
+        if !lobby.rooms.contains_key(&id) {
+            let room = self.open_room(id.clone())?;
+            lobby.rooms.insert(id.clone(), room);
+        }
+
+        let room = lobby.rooms.get_mut(&id).unwrap();
+        room.members.insert(uid);
+
+        // Request the game state to send the welcome message to the new member.
+        room.game_tx.send(Command::Welcome(uid).into()).ok();
+        lobby.clients.insert(uid, id);
+
+        Ok(())
+    }
+
+    /// Checks the password of a command of the operator.
+    fn check_password(&self, password: &str) -> Result<()> {
+        match &self.password {
+            Some(expected) if expected == password => Ok(()),
+            Some(_) => Err(messages::reject(ErrorCode::Unauthorized, "Wrong password.")),
+            None => Err(messages::reject(ErrorCode::Unauthorized, "The server cannot be operated at runtime.")),
         }
     }
 
-    /// Broadcasts a message to all connected clients.
-    async fn broadcast(&self, msg: Message) {
-        for tx in self.senders.read().await.values() {
-            tx.send(Ok(msg.clone())).ok();
+    /// Changes the resource limits, if the password is correct, and applies them to the games of all rooms.
+    async fn set_limits(&self, uid: usize, password: &str, update: &LimitsUpdate) -> Result<()> {
+        self.check_password(password)?;
+
+        // Holding the lobby lock, so that no room is opened with the previous limits.
+        let lobby = self.lobby.read().await;
+        let limits = {
+            let mut limits = self.limits.lock().unwrap();
+            limits.apply(update);
+            *limits
+        };
+
+        for room in lobby.rooms.values() {
+            room.game_tx.send(Command::Limits(limits).into()).ok();
         }
+
+        tracing::info!(uid, "Changed the limits to {:?}.", limits);
+        self.send(uid, messages::limits(&limits)).await;
+        Ok(())
     }
 
-    /// Upon receiving a message from a client, parses it and forwards it to the game state.
-    fn on_message(&self, msg: Message) -> Result<()> {
-        let command = Command::from_msg(msg)?;
-        self.game_tx.send(command)?;
+    /// Reloads the network and the book from their files, if the password is correct, and gives them to
+    /// the engines of all rooms, which use them from their next search. Rooms opened later read the files too.
+    async fn reload(&self, uid: usize, password: &str) -> Result<()> {
+        self.check_password(password)?;
+
+        let lobby = self.lobby.read().await;
+        let net = Net::load(Path::new(&self.config.net_path))?;
+        // Each engine owns its book, which it may learn from.
+        let books = lobby.rooms.values().map(|_| self.config.open_book()).collect::<Result<Vec<_>>>()?;
+
+        let has_book = !self.config.no_book;
+        for (room, book) in lobby.rooms.values().zip(books) {
+            room.game_tx.send(Command::Reload {book, net: net.clone()}.into()).ok();
+        }
+
+        tracing::info!(uid, "Reloaded the network and the book.");
+        self.send(uid, messages::reloaded(has_book)).await;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Generates the id of a new room, that is not already used.
+    async fn new_room_id(&self) -> String {
+        let lobby = self.lobby.read().await;
+
+        loop {
+            let id = format!("room-{}", self.next_room.fetch_add(1, Ordering::Relaxed));
+            if !lobby.rooms.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// Upon receiving a message from a client, parses it and forwards it to the lobby
+    /// or to the game state of the client's room.
+    async fn on_message(self: &Arc<Self>, client: &mut Client, msg: Message) -> Result<()> {
+        match Request::from_msg(msg)? {
+            Request::Identify(token) => client.token = token,
+            Request::Create => {
+                let id = self.new_room_id().await;
+                self.join(client.uid, id).await?;
+            },
+            Request::Join(id) => self.join(client.uid, id).await?,
+            Request::Limits {password, update} => self.set_limits(client.uid, &password, &update).await?,
+            Request::Reload {password} => self.reload(client.uid, &password).await?,
+            Request::Game(command) => {
+                let lobby = self.lobby.read().await;
+
+                if let Some(room) = lobby.clients.get(&client.uid).and_then(|id| lobby.rooms.get(id)) {
+                    room.game_tx.send(Order {
+                        client: Some(client.clone()),
+                        command,
+                    })?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::annotation::Annotation;
+use crate::clock::Clock;
+use crate::game::Termination;
+
+/// Returns the path of the file, with the given extension, in which the data of a room is kept in the given directory.
+/// Room ids are validated when parsed, so they are safe to use as file names.
+pub fn room_path(dir: &Path, room: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{}.{}", room, extension))
+}
+
+//#################################################################################################
+//
+//                                       struct SavedGame
+//
+//#################################################################################################
+
+/// The serialized state of a game, as it is written to disk.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedGame {
+    /// The fen of the position the game started from.
+    pub fen: String,
+    /// All moves of the history, including undoed ones.
+    pub moves: Vec<String>,
+    /// The number of moves of the history that are currently played.
+    pub cursor: usize,
+    /// The clocks of the players, saved paused, if the game is timed.
+    #[serde(default)]
+    pub clock: Option<Clock>,
+    /// The tokens of the players seated at each color.
+    #[serde(default)]
+    pub seats: [Option<String>; 2],
+    /// The way the game was ended by the players, if they ended it.
+    #[serde(default)]
+    pub termination: Option<Termination>,
+    /// The annotations of the positions of the history, including undoed ones, keyed by their number of moves played.
+    #[serde(default)]
+    pub annotations: BTreeMap<usize, Annotation>,
+}
+
+//#################################################################################################
+//
+//                                         struct Store
+//
+//#################################################################################################
+
+/// A persistence layer, saving the game of each room as a json file
+/// in a directory, so that they survive server restarts.
+#[derive(Debug)]
+pub struct Store {
+    dir: PathBuf,
+}
+
+// ================================ pub impl
+
+impl Store {
+    /// Creates a new store, backed by the directory at the given path,
+    /// which is created if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| Error::msg(format!("Cannot create store directory: {}.", e)))?;
+
+        Ok(Self {dir})
+    }
+
+    /// Loads the saved game of a room, if its file exists.
+    pub fn load(&self, room: &str) -> Result<Option<SavedGame>> {
+        let data = match fs::read_to_string(self.path(room)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::msg(format!("Cannot read store file: {}.", e))),
+        };
+
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    /// Saves the game of a room to disk. The file is first written to a temporary file
+    /// and then renamed, so that a crash never leaves a half-written file.
+    pub fn save(&self, room: &str, game: &SavedGame) -> Result<()> {
+        let path = self.path(room);
+        let tmp_path = path.with_extension("tmp");
+
+        fs::write(&tmp_path, serde_json::to_string(game)?)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+}
+
+// ================================ impl
+
+impl Store {
+    /// The path of the file in which the game of a room is saved.
+    fn path(&self, room: &str) -> PathBuf {
+        room_path(&self.dir, room, "json")
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use chess::pgn::{self, Pgn};
+use chess::prelude::*;
+use chess::variant::Variant;
+use engine::{termination, EvalMode, LocalEngine, Player, SearchLimit, UciClient, MAX_STRENGTH};
+
+use crate::game::GameConfig;
+
+/// The number of games each pair of participants plays when the configuration does not specify it.
+const DEFAULT_GAMES: usize = 2;
+
+/// The number of seconds the participants think for each move when the configuration does not specify it.
+const DEFAULT_MOVETIME: f64 = 1.0;
+
+/// The number of plies after which a game is adjudicated as a draw when the configuration does not specify it.
+const DEFAULT_MAX_PLIES: usize = 400;
+
+/// The time a participant is given to answer on top of the movetime, after which it loses the game by forfeit.
+const MOVE_MARGIN: Duration = Duration::from_secs(5);
+
+/// The terminations of the games lost by a participant that did not answer in time, and of the following ones,
+/// lost as its process was killed.
+const TIME_FORFEIT: &str = "time forfeit";
+const ABANDONED: &str = "abandoned";
+
+fn default_games() -> usize {DEFAULT_GAMES}
+fn default_movetime() -> f64 {DEFAULT_MOVETIME}
+fn default_max_plies() -> usize {DEFAULT_MAX_PLIES}
+
+/// Returns the pairings of a round-robin between the given number of participants, by the indices of the ones
+/// playing white and black. Each pair meets once per round, with the colors alternating from one round to the next.
+fn schedule(count: usize, rounds: usize) -> Vec<(usize, usize)> {
+    (0..rounds).flat_map(|round| {
+        (0..count).flat_map(move |i| (i + 1..count).map(move |j| if round % 2 == 0 {(i, j)} else {(j, i)}))
+    }).collect()
+}
+
+/// Returns the points scored by white in a game with the given result, which must be over.
+fn white_points(result: Status) -> f32 {
+    match result {
+        Status::Win(Color::White) => 1.0,
+        Status::Win(Color::Black) => 0.0,
+        _ => 0.5,
+    }
+}
+
+//#################################################################################################
+//
+//                                   struct ParticipantConfig
+//
+//#################################################################################################
+
+/// The configuration of a participant of a tournament: this engine, with its own settings, or an external
+/// engine speaking the UCI protocol, started with the given command.
+#[derive(Debug, Deserialize)]
+pub struct ParticipantConfig {
+    /// The name of the participant, unique in the tournament.
+    pub name: String,
+    /// The command starting an external UCI engine, the program first and its arguments after.
+    /// Remote engines may be reached with a program forwarding their streams, such as ssh.
+    #[serde(default)]
+    pub uci: Option<Vec<String>>,
+    /// The options set on the external UCI engine, by name.
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    /// The way this engine evaluates positions, "nnue", "classical" or "material", that of the server by default.
+    #[serde(default)]
+    pub eval: Option<String>,
+    /// The strength level of this engine, the strongest by default.
+    #[serde(default)]
+    pub strength: Option<u8>,
+    /// The contempt of this engine for draws, in centipawns.
+    #[serde(default)]
+    pub contempt: Option<i32>,
+}
+
+// ================================ impl
+
+impl ParticipantConfig {
+    /// Creates the player of the configuration, with the network given by its bytes for this engine.
+    fn player(&self, game_config: &GameConfig, net: &[u8]) -> Result<Box<dyn Player>> {
+        if let Some(command) = &self.uci {
+            if self.eval.is_some() || self.strength.is_some() || self.contempt.is_some() {
+                return Err(Error::msg(format!("The UCI engine {} cannot be given the settings of this engine.", self.name)));
+            }
+
+            let options = self.options.iter().map(|(name, value)| (name.clone(), value.clone())).collect::<Vec<_>>();
+            return Ok(Box::new(UciClient::start(command, &options)?));
+        }
+
+        let mut engine = LocalEngine::new(Board::with_variant(&game_config.fen, game_config.variant)?, net)?;
+
+        engine.set_eval_mode(match &self.eval {
+            Some(eval) => eval.parse::<EvalMode>()?,
+            None => game_config.eval_mode,
+        });
+        if let Some(strength) = self.strength {
+            if strength > MAX_STRENGTH {
+                return Err(Error::msg(format!("The strength of {} must be at most {}.", self.name, MAX_STRENGTH)));
+            }
+            engine.set_strength(strength);
+        }
+        if let Some(contempt) = self.contempt {
+            engine.set_contempt(contempt);
+        }
+
+        Ok(Box::new(engine))
+    }
+}
+
+//#################################################################################################
+//
+//                                   struct TournamentConfig
+//
+//#################################################################################################
+
+/// The configuration of a tournament, read from a json file.
+#[derive(Debug, Deserialize)]
+pub struct TournamentConfig {
+    /// The participants, at least two.
+    pub participants: Vec<ParticipantConfig>,
+    /// The number of games each pair of participants plays, the colors alternating.
+    #[serde(default = "default_games")]
+    pub games: usize,
+    /// The number of seconds the participants think for each move.
+    #[serde(default = "default_movetime")]
+    pub movetime: f64,
+    /// The number of plies after which a game is adjudicated as a draw.
+    #[serde(default = "default_max_plies")]
+    pub max_plies: usize,
+    /// The fens of the openings, each played twice by each pair of participants, with colors reversed.
+    /// The starting position of the server is used if none is given.
+    #[serde(default)]
+    pub openings: Vec<String>,
+}
+
+// ================================ pub impl
+
+impl TournamentConfig {
+    /// Reads the configuration of a tournament from the json file at the given path.
+    pub fn open(path: &str) -> Result<TournamentConfig> {
+        let data = fs::read_to_string(path).map_err(|e| Error::msg(format!("Cannot read tournament file: {}.", e)))?;
+        Ok(serde_json::from_str(&data)?)
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct GameRecord
+//
+//#################################################################################################
+
+/// A game of the tournament, once it is over.
+#[derive(Debug)]
+struct GameRecord {
+    // The indices of the participants playing white and black.
+    white: usize,
+    black: usize,
+    // The game, with its result.
+    pgn: Pgn,
+    // The way the game ended.
+    termination: &'static str,
+}
+
+//#################################################################################################
+//
+//                                        struct Standings
+//
+//#################################################################################################
+
+/// The scores of the participants of a tournament, computed from the games played so far.
+#[derive(Debug)]
+struct Standings {
+    // The points scored by each participant against each other one.
+    crosstable: Vec<Vec<f32>>,
+    // The wins, draws and losses of each participant.
+    records: Vec<[usize; 3]>,
+    // The points scored by each participant.
+    points: Vec<f32>,
+    // The participants, by decreasing points, ties being kept in order.
+    ranking: Vec<usize>,
+}
+
+// ================================ impl
+
+impl Standings {
+    /// Computes the standings of the given number of participants from their games.
+    fn new(count: usize, games: &[GameRecord]) -> Standings {
+        let mut crosstable = vec![vec![0.0; count]; count];
+        let mut records = vec![[0; 3]; count];
+        for game in games {
+            let points = white_points(game.pgn.get_result());
+            crosstable[game.white][game.black] += points;
+            crosstable[game.black][game.white] += 1.0 - points;
+
+            // Wins, draws and losses.
+            let outcome = (2.0 - 2.0 * points) as usize;
+            records[game.white][outcome] += 1;
+            records[game.black][2 - outcome] += 1;
+        }
+
+        let points = crosstable.iter().map(|row| row.iter().sum::<f32>()).collect::<Vec<_>>();
+        let mut ranking = (0..count).collect::<Vec<_>>();
+        ranking.sort_by(|&a, &b| points[b].partial_cmp(&points[a]).unwrap());
+
+        Standings {crosstable, records, points, ranking}
+    }
+}
+
+//#################################################################################################
+//
+//                                       struct Tournament
+//
+//#################################################################################################
+
+/// A round-robin tournament between engine configurations, played in the background, one game at a time,
+/// while the server runs. Its standings, crosstable and games may be read at any time.
+#[derive(Debug)]
+pub struct Tournament {
+    names: Vec<String>,
+    // The pairings of the games, by the indices of the participants playing white and black, in order.
+    schedule: Vec<(usize, usize)>,
+    variant: Variant,
+    // The games played so far, in order.
+    games: Mutex<Vec<GameRecord>>,
+    // The reason the tournament stopped before its end, if it did.
+    error: Mutex<Option<String>>,
+}
+
+// ================================ pub impl
+
+impl Tournament {
+    /// Starts the tournament of the given configuration in a background thread, the games being played with the
+    /// variant of the server and the participants using its network. The external engines are started at once,
+    /// so that an invalid configuration is reported before any game is played.
+    pub fn start(config: TournamentConfig, game_config: &GameConfig) -> Result<Arc<Tournament>> {
+        if config.participants.len() < 2 {
+            return Err(Error::msg("A tournament needs at least two participants."));
+        }
+        let mut names = HashSet::new();
+        if let Some(config) = config.participants.iter().find(|config| !names.insert(config.name.as_str())) {
+            return Err(Error::msg(format!("Two participants are named {}.", config.name)));
+        }
+        if config.games == 0 || config.max_plies == 0 {
+            return Err(Error::msg("A tournament needs at least one game per pairing, and one ply per game."));
+        }
+        if game_config.variant != Variant::Standard && config.participants.iter().any(|config| config.uci.is_some()) {
+            return Err(Error::msg("UCI engines may only play standard chess."));
+        }
+
+        let movetime = Duration::try_from_secs_f64(config.movetime).ok().filter(|duration| !duration.is_zero())
+            .ok_or_else(|| Error::msg(format!("Invalid number of seconds per move: {}.", config.movetime)))?;
+
+        let openings = if config.openings.is_empty() {
+            vec![Board::with_variant(&game_config.fen, game_config.variant)?]
+        } else {
+            config.openings.iter().map(|fen| Board::with_variant(fen, game_config.variant)).collect::<Result<Vec<_>, _>>()?
+        };
+        if let Some(opening) = openings.iter().find(|opening| !opening.status().is_playing()) {
+            return Err(Error::msg(format!("The opening {} is already over.", opening)));
+        }
+
+        let net = fs::read(Path::new(&game_config.net_path))?;
+        let players = config.participants.iter()
+            .map(|participant| participant.player(game_config, &net))
+            .collect::<Result<Vec<_>>>()?;
+
+        let tournament = Arc::new(Tournament {
+            names: config.participants.into_iter().map(|participant| participant.name).collect(),
+            schedule: schedule(players.len(), config.games),
+            variant: game_config.variant,
+            games: Mutex::new(Vec::new()),
+            error: Mutex::new(None),
+        });
+
+        let background = tournament.clone();
+        let max_plies = config.max_plies;
+        thread::Builder::new().name("tournament".to_string()).spawn(move || {
+            if let Err(e) = background.run(players, &openings, movetime, max_plies) {
+                tracing::error!("The tournament stopped: {}", e);
+                *background.error.lock().unwrap() = Some(e.to_string());
+            }
+        })?;
+
+        tracing::info!("Started a tournament of {} games.", tournament.schedule.len());
+        Ok(tournament)
+    }
+
+    /// Returns the state of the tournament as a json object: its standings, sorted by points, its crosstable,
+    /// giving the points each participant scored against each other one, and the games played so far.
+    pub fn to_json(&self) -> String {
+        let games = self.games.lock().unwrap();
+        let count = self.names.len();
+        let Standings {crosstable, records, points, ranking} = Standings::new(count, &games);
+
+        // The game being played, unless the tournament stopped.
+        let error = self.error.lock().unwrap().clone();
+        let playing = match self.schedule.get(games.len()) {
+            Some(&(white, black)) if error.is_none() => serde_json::json!({
+                "white": self.names[white],
+                "black": self.names[black],
+            }),
+            _ => Value::Null,
+        };
+
+        serde_json::json!({
+            "participants": self.names,
+            "variant": self.variant.to_string(),
+            "scheduled": self.schedule.len(),
+            "played": games.len(),
+            "playing": playing,
+            "error": error,
+            "standings": ranking.iter().map(|&i| serde_json::json!({
+                "name": self.names[i],
+                "points": points[i],
+                "games": records[i].iter().sum::<usize>(),
+                "wins": records[i][0],
+                "draws": records[i][1],
+                "losses": records[i][2],
+            })).collect::<Vec<_>>(),
+            "crosstable": (0..count).map(|i| (0..count).map(|j| {
+                if i == j {Value::Null} else {crosstable[i][j].into()}
+            }).collect::<Vec<_>>()).collect::<Vec<_>>(),
+            "games": games.iter().enumerate().map(|(index, game)| serde_json::json!({
+                "index": index,
+                "white": self.names[game.white],
+                "black": self.names[game.black],
+                "result": pgn::result_str(game.pgn.get_result()),
+                "termination": game.termination,
+                "plies": game.pgn.get_moves().len(),
+            })).collect::<Vec<_>>(),
+        }).to_string()
+    }
+
+    /// Returns the game of the given index as a PGN, if it was played.
+    pub fn pgn(&self, index: usize) -> Option<String> {
+        self.games.lock().unwrap().get(index).map(|game| game.pgn.to_string())
+    }
+}
+
+// ================================ impl
+
+impl Tournament {
+    /// Plays the games of the schedule in order, the openings being played once with each color.
+    fn run(&self, mut players: Vec<Box<dyn Player>>, openings: &[Board], movetime: Duration, max_plies: usize) -> Result<()> {
+        let pairs = self.names.len() * (self.names.len() - 1) / 2;
+        // The participants whose process was killed, which lose their remaining games.
+        let mut killed = vec![false; players.len()];
+
+        for (index, &(white, black)) in self.schedule.iter().enumerate() {
+            let round = index / pairs;
+            let opening = &openings[round / 2 % openings.len()];
+
+            let (mut pgn, termination) = if killed[white] || killed[black] {
+                let mut pgn = Pgn::new(opening.clone());
+                pgn.set_result(match (killed[white], killed[black]) {
+                    (true, true) => Status::Draw,
+                    (true, false) => Status::Win(Color::Black),
+                    _ => Status::Win(Color::White),
+                });
+                (pgn, ABANDONED)
+            } else {
+                // Borrows both players mutably.
+                let (first, second) = players.split_at_mut(white.max(black));
+                let (white_player, black_player) = if white < black {
+                    (first[white].as_mut(), second[0].as_mut())
+                } else {
+                    (second[0].as_mut(), first[black].as_mut())
+                };
+
+                play_game([white_player, black_player], opening, movetime, max_plies).map_err(|e| {
+                    Error::msg(format!("Game {} between {} and {} failed: {}", index + 1, self.names[white], self.names[black], e))
+                })?
+            };
+
+            if termination == TIME_FORFEIT {
+                let loser = if pgn.get_result() == Status::Win(Color::White) {black} else {white};
+                killed[loser] = players[loser].kill_switch().is_some();
+            }
+            pgn.set_tag("Event", "Rush tournament");
+            pgn.set_tag("Round", &(round + 1).to_string());
+            pgn.set_tag("White", &self.names[white]);
+            pgn.set_tag("Black", &self.names[black]);
+            pgn.set_tag("Termination", match termination {
+                "maximum length" => "adjudication",
+                TIME_FORFEIT | ABANDONED => termination,
+                _ => "normal",
+            });
+            pgn.set_date_today();
+            if self.variant != Variant::Standard {
+                pgn.set_tag("Variant", &self.variant.to_string());
+            }
+
+            tracing::info!("Tournament game {}: {} - {}, {} by {}.", index + 1, self.names[white], self.names[black], pgn::result_str(pgn.get_result()), termination);
+            self.games.lock().unwrap().push(GameRecord {white, black, pgn, termination});
+        }
+
+        tracing::info!("The tournament is over.");
+        Ok(())
+    }
+}
+
+/// Plays a game between the two players from the given opening, the first one playing white, and returns it with the way it ended.
+fn play_game(mut players: [&mut dyn Player; 2], opening: &Board, movetime: Duration, max_plies: usize) -> Result<(Pgn, &'static str)> {
+    for player in players.iter_mut() {
+        player.new_game()?;
+    }
+
+    let mut pgn = Pgn::new(opening.clone());
+
+    loop {
+        if !pgn.get_board().status().is_playing() {
+            let termination = termination(pgn.get_board());
+            return Ok((pgn, termination));
+        }
+
+        if pgn.get_moves().len() >= max_plies {
+            pgn.set_result(Status::Draw);
+            return Ok((pgn, "maximum length"));
+        }
+
+        // The search runs on its own thread, so that a participant that stopped answering can be killed.
+        let side = pgn.get_board().get_side_to_move();
+        let player = &mut *players[side as usize];
+        let kill_switch = player.kill_switch();
+        let (start, moves, board) = (pgn.get_start(), pgn.get_moves(), pgn.get_board());
+        let found = thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel();
+            scope.spawn(move || tx.send(player.search(start, moves, board, SearchLimit::Time(movetime))).ok());
+
+            // Killing the process makes the search fail, ending its thread.
+            let found = rx.recv_timeout(movetime + MOVE_MARGIN).ok();
+            if let (None, Some(kill_switch)) = (&found, kill_switch) {
+                kill_switch.kill();
+            }
+            found
+        });
+
+        match found {
+            Some(found) => pgn.push(found?.mv),
+            None => {
+                pgn.set_result(Status::Win(side.invert()));
+                return Ok((pgn, TIME_FORFEIT));
+            },
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                            tests
+//
+//#################################################################################################
+
+#[cfg(test)]
+mod tests {
+    use chess::pgn::Pgn;
+    use chess::prelude::*;
+    use engine::STARTING_FEN;
+
+    use std::time::{Duration, Instant};
+
+    use engine::{Player, UciClient};
+
+    use super::{play_game, schedule, GameRecord, Standings, MOVE_MARGIN, TIME_FORFEIT};
+
+    /// A game between the given participants, with the given result.
+    fn record(white: usize, black: usize, result: Status) -> GameRecord {
+        let mut pgn = Pgn::new(Board::new(STARTING_FEN).unwrap());
+        pgn.set_result(result);
+        GameRecord {white, black, pgn, termination: "adjudication"}
+    }
+
+    #[test]
+    fn round_robin() {
+        assert_eq!(schedule(3, 2), vec![(0, 1), (0, 2), (1, 2), (1, 0), (2, 0), (2, 1)]);
+        assert!(schedule(4, 0).is_empty());
+
+        // Over two rounds, each participant plays each other one once with each color.
+        let games = schedule(5, 2);
+        assert_eq!(games.len(), 20);
+        for white in 0..5 {
+            for black in (0..5).filter(|&black| black != white) {
+                assert_eq!(games.iter().filter(|&&game| game == (white, black)).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn standings() {
+        let games = [
+            record(0, 1, Status::Win(Color::White)),
+            record(0, 2, Status::Draw),
+            record(1, 2, Status::Win(Color::Black)),
+            record(2, 0, Status::Win(Color::Black)),
+        ];
+        let standings = Standings::new(3, &games);
+
+        assert_eq!(standings.crosstable, vec![
+            vec![0.0, 1.0, 1.5],
+            vec![0.0, 0.0, 0.0],
+            vec![0.5, 1.0, 0.0],
+        ]);
+        assert_eq!(standings.records, vec![[2, 1, 0], [0, 0, 2], [1, 1, 1]]);
+        assert_eq!(standings.points, vec![2.5, 0.0, 1.5]);
+        assert_eq!(standings.ranking, vec![0, 2, 1]);
+
+        // Ties keep the order of the participants.
+        let standings = Standings::new(3, &[record(2, 1, Status::Draw)]);
+        assert_eq!(standings.points, vec![0.0, 0.5, 0.5]);
+        assert_eq!(standings.ranking, vec![1, 2, 0]);
+        assert!(Standings::new(2, &[]).crosstable.iter().flatten().all(|&points| points == 0.0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn time_forfeit() {
+        // An engine that never answers the go command.
+        let command = ["sh", "-c", "while read line; do case $line in uci) echo uciok;; isready) echo readyok;; esac; done"];
+        let start = || UciClient::start(&command.map(str::to_string), &[]).unwrap();
+        let (mut white, mut black) = (start(), start());
+
+        let since = Instant::now();
+        let (pgn, termination) = play_game([&mut white, &mut black], &Board::new(STARTING_FEN).unwrap(), Duration::from_millis(10), 10).unwrap();
+        assert!(since.elapsed() < MOVE_MARGIN * 2);
+        assert_eq!(termination, TIME_FORFEIT);
+        assert_eq!(pgn.get_result(), Status::Win(Color::Black));
+        assert!(pgn.get_moves().is_empty());
+
+        // The process was killed.
+        assert!(white.new_game().is_err());
+        assert!(black.new_game().is_ok());
+    }
+}
@@ -5,7 +5,7 @@ import css from 'rollup-plugin-css-only';
 import svelte from 'rollup-plugin-svelte';
 import {terser} from 'rollup-plugin-terser';
 
-export default {
+export default [{
     // Name of the main file.
     input: 'src/main.js',
     // Output parameters.
@@ -40,4 +40,19 @@ export default {
     onwarn: (warning, warn) => {
         if (warning.id.indexOf(__dirname + '/node_modules/') !== 0) warn(warning)
     },
-};
+}, {
+    // The engine worker, loaded with new Worker("/build/worker.js").
+    input: 'src/worker.js',
+    output: {
+        name: 'chess_engine_worker',
+        format: 'iife',
+        sourcemap: true,
+        file: 'public/build/worker.js',
+    },
+    plugins: [
+        rust({
+            serverPath: "/build/",
+        }),
+        terser(),
+    ],
+}];
@@ -1,16 +1,24 @@
-use js_sys::Error as JsError;
+use js_sys::{Array, Error as JsError, Math, Object, Reflect};
 use wasm_bindgen::prelude::*;
 use wee_alloc::WeeAlloc;
 
 use std::str::FromStr;
+use std::time::Duration;
 
-use chess::board::Board;
+use chess::board::{Board, DrawReason, Status};
+use chess::book::Book;
 use chess::color::Color;
 use chess::piece::Piece;
 use chess::movegen;
 use chess::moves::Move;
 use chess::square::Square;
 
+use engine::{LocalEngine, MateResult};
+
+// The engine protocol of the Web Worker.
+mod worker;
+pub use worker::EngineWorker;
+
 // Use the wee_alloc allocator instead of the std one to save space.
 #[global_allocator]
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
@@ -28,6 +36,26 @@ fn parse_square(sq: &str) -> Result<Square, JsValue> {
     Square::from_str(sq).map_err(|_| js_error("Invalid square literal."))
 }
 
+// Tries to parse a promotion piece from a String, such as "q".
+fn parse_promotion(promotion: &str) -> Result<Piece, JsValue> {
+    let mut chars = promotion.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => match Piece::from_char(c.to_ascii_lowercase()) {
+            Ok((_, piece)) if Piece::PROMOTES.contains(&piece) => Ok(piece),
+            _ => Err(js_error("Invalid promotion literal.")),
+        },
+        _ => Err(js_error("Invalid promotion literal.")),
+    }
+}
+
+/// Returns the square at the same place on the board once it is flipped, such as "h8" for "a1",
+/// so that the front-end can translate coordinates when the board is seen from black's side.
+#[wasm_bindgen(js_name = flipSquare)]
+pub fn flip_square(sq: &str) -> Result<String, JsValue> {
+    Ok(parse_square(sq)?.rotate().to_string())
+}
+
 /// The WasmChess struct, simply named "Chess" in JS is a class
 /// representing a chess board, and wrapping some of it's functionnalities.
 #[wasm_bindgen(js_name = Chess)]
@@ -35,6 +63,8 @@ fn parse_square(sq: &str) -> Result<Square, JsValue> {
 pub struct WasmChess {
     board: Board,
     legals: Vec<Move>,
+    engine: Option<LocalEngine>,
+    book: Book,
 }
 
 #[wasm_bindgen(js_class = Chess)]
@@ -42,29 +72,112 @@ impl WasmChess {
     /// Constructs a new WasmChess object, from it's fen representation.
     #[wasm_bindgen(constructor)]
     pub fn new() -> WasmChess {
-        // Initialize the chess lib, if not done already.
-        chess::init();
+        let mut chess = WasmChess {
+            board: Board::new(DEFAULT_FEN).unwrap(),
+            legals: Vec::new(),
+            engine: None,
+            book: Book::builtin(),
+        };
+        chess.refresh_legals();
 
-        let board = Board::new(DEFAULT_FEN).unwrap();
-        let mut legals = Vec::new();
-        movegen::legals(&board, &mut legals);
-
-        WasmChess {board, legals}
+        chess
     }
 
     /// A setter for the current position, given by a fen string.
     #[wasm_bindgen(method, js_name = setPosition)]
-    pub fn set_position(&mut self, fen: &str, end: bool) -> Result<(), JsValue> {
+    pub fn set_position(&mut self, fen: &str) -> Result<(), JsValue> {
         self.board = Board::new(fen).map_err(|_| js_error("Invalid fen literal."))?;
+        self.refresh_legals();
 
-        self.legals.clear();
-        if !end {
-            movegen::legals(&self.board, &mut self.legals);
-        }
+        Ok(())
+    }
+
+    /// Plays the given move, if it is legal, and updates the list of legal moves.
+    /// The promotion is optional, and must be given as a single character ("q", "r", "b" or "n").
+    #[wasm_bindgen(method, js_name = playMove)]
+    pub fn play_move(&mut self, from: String, to: String, promotion: Option<String>) -> Result<(), JsValue> {
+        let from = parse_square(&from)?;
+        let to = parse_square(&to)?;
+        let promotion = promotion.as_deref().map(parse_promotion).transpose()?;
+
+        // Only the legal moves are played, and there are none once the game is over.
+        let mv = self.legals.iter().copied()
+            .find(|mv| mv.from() == from && mv.to() == to && mv.promotion() == promotion)
+            .ok_or_else(|| js_error("Illegal move."))?;
+        self.board.do_move(mv);
+        self.refresh_legals();
+
+        Ok(())
+    }
+
+    /// Returns the fen representation of the current position.
+    #[wasm_bindgen(method, js_name = getFen)]
+    pub fn get_fen(&self) -> String {
+        self.board.to_string()
+    }
+
+    /// Loads the engine's neural network from the bytes of a network file,
+    /// so that the engine can be used with think.
+    #[wasm_bindgen(method, js_name = loadNet)]
+    pub fn load_net(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let engine = LocalEngine::new(self.board.clone(), bytes).map_err(|_| js_error("Invalid network file."))?;
+        self.engine = Some(engine);
 
         Ok(())
     }
 
+    /// Searches the current position for the given number of milliseconds, and returns
+    /// the best move found, such as "e2e4". The network must have been loaded with loadNet.
+    /// In the opening, a move of the built-in book is played instantly instead.
+    #[wasm_bindgen(method)]
+    pub fn think(&mut self, ms: f64) -> Result<String, JsValue> {
+        let engine = self.engine.as_mut().ok_or_else(|| js_error("The network is not loaded."))?;
+
+        let rand = (Math::random() * f64::from(u32::MAX)) as u32;
+        if let Some(mv) = self.book.choose(&self.board, rand) {
+            return Ok(mv.to_string());
+        }
+
+        *engine.write_board() = self.board.clone();
+
+        engine.think(Duration::from_secs_f64(ms.max(0.0) / 1000.0))
+            .map(|mv| mv.to_string())
+            .ok_or_else(|| js_error("The game is over."))
+    }
+
+    /// Evaluates the current position without searching it, and returns the explanation of
+    /// the evaluation as a json string. Scores are in pawns, from white's point of view, except
+    /// for the values of the pieces which are from their owner's. The network must have been loaded with loadNet.
+    #[wasm_bindgen(method)]
+    pub fn evaluate(&self) -> Result<String, JsValue> {
+        let engine = self.engine.as_ref().ok_or_else(|| js_error("The network is not loaded."))?;
+        let breakdown = engine.evaluate_detailed(&self.board);
+
+        let pieces = breakdown.pieces.iter().map(|piece| format!(
+            r#"{{"square":"{}","piece":"{}","value":{}}}"#,
+            piece.square, piece.piece.as_char(piece.color), piece.value,
+        )).collect::<Vec<_>>().join(",");
+
+        Ok(format!(
+            r#"{{"total":{},"material":{},"positional":{},"pieces":[{}],"activations":[{},{}]}}"#,
+            breakdown.total, breakdown.material, breakdown.positional, pieces, breakdown.activations[0], breakdown.activations[1],
+        ))
+    }
+
+    /// Searches the current position for a forced mate of the side to move within the given number of moves,
+    /// and returns it as a json string, such as {"mate":2,"pv":["d5f6","g7f6","c4f7"]}, or {"mate":null} if
+    /// there is none. Does not need the network, so that puzzles can be verified before it is loaded.
+    #[wasm_bindgen(method, js_name = searchMate)]
+    pub fn search_mate(&self, moves: u8) -> String {
+        match engine::search_mate(&self.board, moves) {
+            MateResult::Mate {moves, pv} => {
+                let pv = pv.iter().map(|mv| format!(r#""{}""#, mv)).collect::<Vec<_>>().join(",");
+                format!(r#"{{"mate":{},"pv":[{}]}}"#, moves, pv)
+            },
+            MateResult::NoMate(_) => r#"{"mate":null}"#.to_string(),
+        }
+    }
+
     /// Returns true if the given move is legal.
     #[wasm_bindgen(method, js_name = isLegal)]
     pub fn is_legal(&self, from: String, to: String) -> Result<bool, JsValue> {
@@ -74,6 +187,22 @@ impl WasmChess {
         Ok(self.legals.iter().any(|mv| mv.from() == from && mv.to() == to))
     }
 
+    /// Returns the legal moves of the piece on the given square, as an array of objects such as
+    /// {to: "e8", promotion: "q"}, the promotion being null for moves that are not promotions.
+    /// A pawn reaching the last rank has a move for each piece it may promote to.
+    #[wasm_bindgen(method, js_name = legalMovesFrom)]
+    pub fn legal_moves_from(&self, from: String) -> Result<Array, JsValue> {
+        let from = parse_square(&from)?;
+
+        self.legals.iter().filter(|mv| mv.from() == from).map(|mv| {
+            let obj = Object::new();
+            let promotion = mv.promotion().map_or(JsValue::NULL, |piece| piece.as_char(Color::Black).to_string().into());
+            Reflect::set(&obj, &"to".into(), &mv.to().to_string().into())?;
+            Reflect::set(&obj, &"promotion".into(), &promotion)?;
+            Ok(obj)
+        }).collect()
+    }
+
     /// Returns true if the given move is a promotion. 
     #[wasm_bindgen(method, js_name = isPromotion)]
     pub fn is_promotion(&self, from: String, to: String) -> Result<bool, JsValue> {
@@ -87,18 +216,86 @@ impl WasmChess {
         } && piece == Piece::Pawn)
     }
 
+    /// Returns the status of the game as a json string, such as {"status":"checkmate","winner":"white"}, so that
+    /// the front-end can tell when the game is over and why. The status is one of "playing", "checkmate", "stalemate",
+    /// "draw-fifty", "draw-repetition", "draw-material" or "variant-win", and the winner is null unless a side won.
+    #[wasm_bindgen(method, js_name = getStatus)]
+    pub fn get_status(&self) -> String {
+        let (status, winner) = match self.board.status() {
+            Status::Playing => ("playing", None),
+            Status::Win(color) if self.board.get_variant().winner(&self.board).is_some() => ("variant-win", Some(color)),
+            Status::Win(color) => ("checkmate", Some(color)),
+            Status::Draw => (match self.board.draw_reason() {
+                Some(DrawReason::FiftyMoves) => "draw-fifty",
+                Some(DrawReason::Repetition) => "draw-repetition",
+                Some(DrawReason::InsufficientMaterial) => "draw-material",
+                Some(DrawReason::Stalemate) | None => "stalemate",
+            }, None),
+        };
+
+        let winner = match winner {
+            Some(Color::White) => r#""white""#,
+            Some(Color::Black) => r#""black""#,
+            None => "null",
+        };
+
+        format!(r#"{{"status":"{}","winner":{}}}"#, status, winner)
+    }
+
     /// Returns true if the king is in check in this position.
     #[wasm_bindgen(method, js_name = isInCheck)]
     pub fn is_in_check(&self) -> bool {
         self.board.get_checkers().not_empty()
     }
 
+    /// Returns the castling rights of the position in fen notation, such as "KQkq", or "-" if there are none.
+    #[wasm_bindgen(method, js_name = getCastleRights)]
+    pub fn get_castle_rights(&self) -> String {
+        self.board.get_castle_rights().to_string()
+    }
+
+    /// Returns the square a pawn capturing en passant would move to, such as "e3", if the last move
+    /// was a pawn pushed two squares, so that the front-end can highlight it.
+    #[wasm_bindgen(method, js_name = getEpTarget)]
+    pub fn get_ep_target(&self) -> Option<String> {
+        self.board.get_ep_target().map(|sq| sq.to_string())
+    }
+
     /// Returns true if the side to move is white.
     #[wasm_bindgen(method, js_name = isWhiteToMove)]
     pub fn is_white_to_move(&self) -> bool {
         self.board.get_side_to_move() == Color::White
     }
 
+    /// Counts the leaf nodes of the game tree of the current position, to the given depth,
+    /// so that the move generator of the wasm build can be checked against known results.
+    #[wasm_bindgen(method)]
+    pub fn perft(&self, depth: u32) -> f64 {
+        movegen::perft(&mut self.board.clone(), depth as usize) as f64
+    }
+
+    /// Counts the leaf nodes of the subtree of each legal move, to the given depth which must be at
+    /// least 1. Returns a json string mapping the moves to their counts, such as {"e2e4":20,...}.
+    #[wasm_bindgen(method)]
+    pub fn divide(&self, depth: u32) -> Result<String, JsValue> {
+        if depth == 0 {
+            return Err(js_error("The depth of divide must be at least 1."));
+        }
+
+        let mut board = self.board.clone();
+        let mut legals = Vec::new();
+        movegen::legals(&board, &mut legals);
+
+        let counts = legals.into_iter().map(|mv| {
+            board.do_move(mv);
+            let count = movegen::perft(&mut board, depth as usize - 1);
+            board.undo_move(mv);
+            format!(r#""{}":{}"#, mv, count)
+        }).collect::<Vec<_>>().join(",");
+
+        Ok(format!("{{{}}}", counts))
+    }
+
     // Compile only when in debug mode to save up some bytes.
     /// Prints self, using rust debug's format.
     #[cfg(debug_assertions)]
@@ -106,4 +303,14 @@ impl WasmChess {
     pub fn to_string(&self) -> String {
         format!("{:?}", self)
     }
+}
+
+impl WasmChess {
+    // Updates the legal moves after the position changed, leaving none once the game is over.
+    fn refresh_legals(&mut self) {
+        self.legals.clear();
+        if self.board.status().is_playing() {
+            movegen::legals(&self.board, &mut self.legals);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use js_sys::{Date, Math, Reflect, JSON};
+use wasm_bindgen::prelude::*;
+
+use std::time::Duration;
+
+use chess::board::Board;
+use chess::book::Book;
+
+use engine::{Analysis, LocalEngine};
+
+use crate::{js_error, DEFAULT_FEN};
+
+// The thinking duration of a go command without a movetime, in milliseconds.
+const DEFAULT_MOVETIME: f64 = 5000.0;
+
+// Reads a field of a parsed json command, as a JsValue that may be undefined.
+fn field(cmd: &JsValue, name: &str) -> Result<JsValue, JsValue> {
+    Reflect::get(cmd, &JsValue::from_str(name))
+}
+
+// Formats the info message describing the state of the analysis, as the server does for its analysis messages.
+fn info_msg(fen: &str, analysis: &Analysis) -> String {
+    let lines = analysis.lines.iter().map(|line| format!(
+        r#"{{"score":{},"pv":[{}]}}"#,
+        line.score, line.pv.iter().map(|mv| format!(r#""{}""#, mv)).collect::<Vec<_>>().join(","),
+    )).collect::<Vec<_>>().join(",");
+
+    format!(
+        r#"{{"info":{{"fen":"{}","depth":{},"seldepth":{},"nodes":{},"lines":[{}]}}}}"#,
+        fen, analysis.depth, analysis.seldepth, analysis.nodes, lines,
+    )
+}
+
+// Formats the bestmove message, ending a search. The move is null if the game is over.
+fn bestmove_msg(mv: Option<String>, book: bool) -> String {
+    match mv {
+        Some(mv) => format!(r#"{{"bestmove":{{"move":"{}","book":{}}}}}"#, mv, book),
+        None => r#"{"bestmove":{"move":null,"book":false}}"#.to_string(),
+    }
+}
+
+/// The EngineWorker struct, named "EngineWorker" in JS, is the engine of the front-end, meant to
+/// run inside a Web Worker. It speaks a message-based protocol mirroring the one of the server,
+/// so that the same UI code can target either: commands and responses are json strings.
+///
+/// Commands are objects with a kind:
+/// - {"kind": "setPosition", "fen": "..."} sets the position, stopping the search if any.
+/// - {"kind": "go", "movetime": 1000} starts searching for the given number of milliseconds, 5000 by default.
+/// - {"kind": "stop"} stops the search, which responds with its best move.
+///
+/// Responses are {"info": {"fen", "depth", "seldepth", "nodes", "lines": [{"score", "pv"}]}}, after
+/// each depth searched, and {"bestmove": {"move", "book"}}, ending each search.
+///
+/// The search is carried out one depth at a time by step(), which the worker calls repeatedly
+/// while it is searching, handling the commands it receives in between.
+#[wasm_bindgen(js_name = EngineWorker)]
+#[derive(Debug)]
+pub struct EngineWorker {
+    engine: LocalEngine,
+    book: Book,
+    deadline: Option<f64>,
+}
+
+#[wasm_bindgen(js_class = EngineWorker)]
+impl EngineWorker {
+    /// Constructs a new EngineWorker on the starting position, from the bytes of a network file.
+    #[wasm_bindgen(constructor)]
+    pub fn new(net: &[u8]) -> Result<EngineWorker, JsValue> {
+        let board = Board::new(DEFAULT_FEN).unwrap();
+        let engine = LocalEngine::new(board, net).map_err(|_| js_error("Invalid network file."))?;
+
+        Ok(EngineWorker {engine, book: Book::builtin(), deadline: None})
+    }
+
+    /// Handles a command, given as a json string. Returns the response to send back at once, if any.
+    #[wasm_bindgen(method)]
+    pub fn handle(&mut self, msg: &str) -> Result<Option<String>, JsValue> {
+        let cmd = JSON::parse(msg).map_err(|_| js_error("Invalid json command."))?;
+        let kind = field(&cmd, "kind")?.as_string().ok_or_else(|| js_error("No kind attribute in command."))?;
+
+        match kind.as_str() {
+            "setPosition" => {
+                let fen = field(&cmd, "fen")?.as_string().ok_or_else(|| js_error("No fen attribute in command."))?;
+                let board = Board::new(&fen).map_err(|_| js_error("Invalid fen literal."))?;
+
+                self.deadline = None;
+                *self.engine.write_board() = board;
+                Ok(None)
+            },
+            "go" => {
+                let movetime = field(&cmd, "movetime")?.as_f64().unwrap_or(DEFAULT_MOVETIME).max(0.0);
+                let board = self.engine.read_board().clone();
+
+                // In the opening, a move of the built-in book is played instantly.
+                let rand = (Math::random() * f64::from(u32::MAX)) as u32;
+                if let Some(mv) = self.book.choose(&board, rand) {
+                    self.deadline = None;
+                    return Ok(Some(bestmove_msg(Some(mv.to_string()), true)));
+                }
+
+                if !self.engine.start_search() {
+                    self.deadline = None;
+                    return Ok(Some(bestmove_msg(None, false)));
+                }
+
+                self.deadline = Some(Date::now() + movetime);
+                Ok(None)
+            },
+            "stop" => Ok(self.deadline.take().map(|_| self.bestmove())),
+            _ => Err(js_error("Invalid command kind.")),
+        }
+    }
+
+    /// Returns true if a search is going on, in which case step() must be called until it ends.
+    #[wasm_bindgen(method, js_name = isSearching)]
+    pub fn is_searching(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    /// Searches the next depth, for at most the time left, and returns the response to send back:
+    /// an info message, or the bestmove message once the search is over. Returns None if no search is going on.
+    #[wasm_bindgen(method)]
+    pub fn step(&mut self) -> Option<String> {
+        let left = (self.deadline? - Date::now()).max(0.0);
+
+        // The first depth is searched even when out of time, so that there is a move to play.
+        let searching = left > 0.0 || self.engine.score().is_none();
+        if searching && self.engine.search_next_depth(Duration::from_secs_f64(left / 1000.0)) {
+            let fen = self.engine.read_board().to_string();
+            return Some(info_msg(&fen, &self.engine.analysis()));
+        }
+
+        self.deadline = None;
+        Some(self.bestmove())
+    }
+}
+
+impl EngineWorker {
+    // Gets the bestmove message of the search that just ended.
+    fn bestmove(&mut self) -> String {
+        bestmove_msg(self.engine.best_move().map(|mv| mv.to_string()), false)
+    }
+}
@@ -0,0 +1,48 @@
+// The engine of the front-end, running inside a Web Worker so that searching does not freeze the page.
+// It speaks the same json protocol as the server: commands and responses are json strings, so that
+// the UI code may target either. The first message posted to the worker must be the bytes of the network.
+import chess_wasm from "./chess-wasm/Cargo.toml";
+
+// The wasm module, that needs to be awaited to be downloaded and initialized.
+const wasm = chess_wasm();
+
+// The EngineWorker object, created once the network is received.
+let engine = null;
+
+// Sends a response to the page, if there is one.
+function respond(response) {
+    if (response !== undefined) {
+        postMessage(response);
+    }
+}
+
+// Searches one depth, then yields to the event loop so that the commands received are handled.
+function step() {
+    respond(engine.step());
+
+    if (engine.isSearching()) {
+        setTimeout(step, 0);
+    }
+}
+
+// Upon receiving a message from the page.
+onmessage = async e => {
+    const lib = await wasm;
+
+    try {
+        if (engine === null) {
+            engine = new lib.EngineWorker(new Uint8Array(e.data));
+            return;
+        }
+
+        const searching = engine.isSearching();
+        respond(engine.handle(e.data));
+
+        // A new search was started by the command.
+        if (!searching && engine.isSearching()) {
+            setTimeout(step, 0);
+        }
+    } catch (err) {
+        postMessage(JSON.stringify({error: err.message}));
+    }
+};